@@ -43,12 +43,15 @@ use buck2_execute::execute::request::CommandExecutionOutput;
 use buck2_execute::execute::request::CommandExecutionPaths;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::request::LocalResourceLimits;
 use buck2_execute::execute::request::WorkerId;
+use buck2_execute::execute::request::WorkerProtocol;
 use buck2_execute::execute::request::WorkerSpec;
 use derive_more::Display;
 use dupe::Dupe;
 use gazebo::prelude::*;
 use host_sharing::HostSharingRequirements;
+use host_sharing::ResourceRequirements;
 use host_sharing::WeightClass;
 use indexmap::indexmap;
 use indexmap::IndexSet;
@@ -161,6 +164,8 @@ pub(crate) struct UnregisteredRunAction {
     pub(crate) allow_cache_upload: bool,
     pub(crate) force_full_hybrid_if_capable: bool,
     pub(crate) unique_input_inodes: bool,
+    pub(crate) local_resource_limits: LocalResourceLimits,
+    pub(crate) resource_requirements: ResourceRequirements,
 }
 
 impl UnregisteredAction for UnregisteredRunAction {
@@ -215,6 +220,8 @@ struct UnpackedWorkerValues<'v> {
     exe: &'v dyn CommandLineArgLike,
     id: WorkerId,
     concurrency: Option<usize>,
+    protocol: WorkerProtocol,
+    supports_multiplex: bool,
 }
 
 struct UnpackedRunActionValues<'v> {
@@ -256,6 +263,8 @@ impl RunAction {
             exe: worker.exe_command_line(),
             id: WorkerId(worker.id),
             concurrency: worker.concurrency(),
+            protocol: worker.protocol(),
+            supports_multiplex: worker.supports_multiplex(),
         });
 
         Ok(UnpackedRunActionValues {
@@ -291,6 +300,8 @@ impl RunAction {
                 exe: worker_rendered,
                 id: worker.id,
                 concurrency: worker.concurrency,
+                protocol: worker.protocol,
+                supports_multiplex: worker.supports_multiplex,
             })
         } else {
             None
@@ -554,12 +565,18 @@ impl IncrementalActionExecutable for RunAction {
             .with_prefetch_lossy_stderr(true)
             .with_executor_preference(self.inner.executor_preference)
             .with_host_sharing_requirements(host_sharing_requirements)
+            .with_resource_requirements(self.inner.resource_requirements.clone())
             .with_low_pass_filter(self.inner.low_pass_filter)
             .with_outputs_cleanup(!self.inner.no_outputs_cleanup)
             .with_local_environment_inheritance(EnvironmentInheritance::local_command_exclusions())
             .with_force_full_hybrid_if_capable(self.inner.force_full_hybrid_if_capable)
             .with_custom_tmpdir(ctx.target().custom_tmpdir())
-            .with_unique_input_inodes(self.inner.unique_input_inodes);
+            .with_unique_input_inodes(self.inner.unique_input_inodes)
+            .with_local_resource_limits(
+                knobs
+                    .local_resource_limits
+                    .overridden_by(self.inner.local_resource_limits),
+            );
 
         // First prepare the action, check the action cache, check dep_files if needed, and execute the command
         let prepared_action = ctx.prepare_action(&req)?;
@@ -591,11 +608,14 @@ impl IncrementalActionExecutable for RunAction {
             }
         };
 
-        if self.inner.allow_cache_upload {
-            result.did_cache_upload = ctx
-                .cache_upload(prepared_action.action.dupe(), &result, None)
-                .await?;
-        }
+        result.did_cache_upload = ctx
+            .cache_upload(
+                prepared_action.action.dupe(),
+                &result,
+                None,
+                self.inner.allow_cache_upload,
+            )
+            .await?;
 
         let (outputs, metadata) =
             ctx.unpack_command_execution_result(&req, result, self.inner.allow_cache_upload)?;
@@ -42,6 +42,7 @@ use buck2_common::cas_digest::CasDigest;
 use buck2_core::category::Category;
 use buck2_core::execution_types::executor_config::RemoteExecutorUseCase;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_execute::execute::request::LocalResourceLimits;
 use buck2_execute::execute::request::OutputType;
 use buck2_execute::materialize::http::Checksum;
 use buck2_interpreter::starlark_promise::StarlarkPromise;
@@ -50,6 +51,8 @@ use chrono::Utc;
 use dupe::Dupe;
 use dupe::OptionDupedExt;
 use either::Either;
+use host_sharing::CustomResources;
+use host_sharing::ResourceRequirements;
 use host_sharing::WeightClass;
 use host_sharing::WeightPercentage;
 use indexmap::indexset;
@@ -124,6 +127,8 @@ enum RunActionError {
     InvalidWeight(i32),
     #[error("`weight` and `weight_percentage` cannot both be passed")]
     DuplicateWeightsSpecified,
+    #[error("`local_cpu_max` must be a positive number, got `{0}`")]
+    InvalidLocalCpuMax(f64),
     #[error("`dep_files` value with key `{}` has an invalid count of associated outputs. Expected 1, got {}.", .key, .count)]
     InvalidDepFileOutputs { key: String, count: usize },
     #[error("`dep_files` with keys `{}` and {} are using the same tag", .first, .second)]
@@ -561,6 +566,8 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
     /// * `arguments`: must be of type `cmd_args`, or a type convertible to such (such as a list of strings and artifacts) and must contain at least one `.as_output()` artifact
     /// * `category`: category and identifier - when used together, identify the action in Buck2's event stream, and must be unique for a given target
     /// * `weight`: used to note how heavy the command is and will typically be set to a higher value to indicate that less such commands should be run in parallel (if running locally)
+    /// * `local_memory_max` and `local_cpu_max`: limit the memory (in bytes) and CPU (in number of CPUs, possibly fractional) that the command can use when it runs locally. Those override the `buck2.local_action_memory_max` and `buck2.local_action_cpu_max` defaults, and are only enforced on Linux hosts where the forkserver can manage cgroups. A command that exceeds its memory limit is killed.
+    /// * `memory_required` and `custom_resources`: declare the memory (in bytes) and the amounts of custom resources (a dict from resource name to amount, e.g. `{"gpu": 1}`) the command needs when it runs locally. Buck2 only starts the command locally once those resources are available (see `buck2.local_memory_capacity` and `buck2.local_custom_resources`), and with a hybrid executor, prefers running it remotely when they are not.
     /// * `no_outputs_cleanup`: if this flag is set then Buck2 won't clean the outputs of a previous build that might be present on a disk; in which case, command from arguments should be responsible for the cleanup (that is useful, for example, when an action is supporting incremental mode and its outputs are based on result from a previous build)
     /// * `metadata_env_var` and `meadata_path` should be used together: both set or both unset
     ///     * `metadata_path`: defines a path relative to the result directory for a file with action metadata, which will be created right before the command will be run.
@@ -603,6 +610,10 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
             Either<ValueOf<'v, &'v WorkerRunInfo<'v>>, ValueOf<'v, &'v RunInfo<'v>>>,
         >,
         #[starlark(require = named, default = false)] unique_input_inodes: bool,
+        #[starlark(require = named)] local_memory_max: Option<u64>,
+        #[starlark(require = named)] local_cpu_max: Option<f64>,
+        #[starlark(require = named)] memory_required: Option<u64>,
+        #[starlark(require = named)] custom_resources: Option<SmallMap<&'v str, u64>>,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<NoneType> {
         struct RunCommandArtifactVisitor {
@@ -694,6 +705,12 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
             }
         };
 
+        let local_cpu_max_millis = match local_cpu_max {
+            None => None,
+            Some(v) if v > 0.0 && v.is_finite() => Some((v * 1000.0) as u64),
+            Some(v) => return Err(RunActionError::InvalidLocalCpuMax(v).into()),
+        };
+
         let starlark_env = match env {
             None => Value::new_none(),
             Some(env) => {
@@ -780,6 +797,20 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
             allow_cache_upload,
             force_full_hybrid_if_capable,
             unique_input_inodes,
+            local_resource_limits: LocalResourceLimits {
+                memory_max: local_memory_max,
+                cpu_max_millis: local_cpu_max_millis,
+            },
+            resource_requirements: ResourceRequirements {
+                memory: memory_required.unwrap_or(0),
+                custom: CustomResources(
+                    custom_resources
+                        .into_iter()
+                        .flatten()
+                        .map(|(name, amount)| (name.to_owned(), amount))
+                        .collect(),
+                ),
+            },
         };
         this.state().register_action(
             artifacts.inputs,
@@ -29,6 +29,8 @@ use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
 use buck2_core::unsafe_send_future::UnsafeSendFuture;
 use buck2_events::dispatch::get_dispatcher;
 use buck2_execute::digest_config::HasDigestConfig;
+use buck2_interpreter::dice::starlark_limits::StarlarkEvalKind;
+use buck2_interpreter::dice::starlark_limits::StarlarkEvalLimits;
 use buck2_interpreter::dice::starlark_provider::with_starlark_eval_provider;
 use buck2_interpreter::print_handler::EventDispatcherPrintHandler;
 use buck2_interpreter::starlark_profiler::StarlarkProfileModeOrInstrumentation;
@@ -267,9 +269,11 @@ async fn run_analysis_with_env_underlying(
         Some(profiler) => StarlarkProfilerOrInstrumentation::for_profiler(profiler),
     };
 
+    let limits = StarlarkEvalLimits::from_buckconfig(dice, StarlarkEvalKind::Analysis).await?;
     let (mut eval, ctx, list_res) = with_starlark_eval_provider(
         dice,
         &mut profiler,
+        limits,
         format!("analysis:{}", node.label()),
         |provider| {
             let mut eval = provider.make(&env)?;
@@ -10,6 +10,7 @@
 use allocative::Allocative;
 use async_trait::async_trait;
 use buck2_build_api::analysis::anon_promises_dyn::AnonPromisesDyn;
+use buck2_interpreter::dice::starlark_limits::StarlarkEvalLimits;
 use buck2_interpreter::dice::starlark_provider::with_starlark_eval_provider;
 use buck2_interpreter::starlark_profiler::StarlarkProfilerOrInstrumentation;
 use buck2_interpreter::starlark_promise::StarlarkPromise;
@@ -85,6 +86,7 @@ impl<'v> AnonPromisesDyn<'v> for AnonPromises<'v> {
         with_starlark_eval_provider(
             dice,
             &mut StarlarkProfilerOrInstrumentation::disabled(),
+            StarlarkEvalLimits::none(),
             description,
             |_provider| {
                 // But must bind the promises sequentially
@@ -58,6 +58,8 @@ use buck2_core::unsafe_send_future::UnsafeSendFuture;
 use buck2_events::dispatch::get_dispatcher;
 use buck2_events::dispatch::span_async;
 use buck2_execute::digest_config::HasDigestConfig;
+use buck2_interpreter::dice::starlark_limits::StarlarkEvalKind;
+use buck2_interpreter::dice::starlark_limits::StarlarkEvalLimits;
 use buck2_interpreter::dice::starlark_provider::with_starlark_eval_provider;
 use buck2_interpreter::print_handler::EventDispatcherPrintHandler;
 use buck2_interpreter::starlark_profiler::StarlarkProfilerOrInstrumentation;
@@ -342,6 +344,7 @@ impl AnonTargetKey {
         );
 
         let rule_impl = get_rule_impl(dice, self.0.rule_type()).await?;
+        let limits = StarlarkEvalLimits::from_buckconfig(dice, StarlarkEvalKind::Analysis).await?;
         let env = Module::new();
         let print = EventDispatcherPrintHandler(get_dispatcher());
 
@@ -354,6 +357,7 @@ impl AnonTargetKey {
                 let (mut eval, ctx, list_res) = with_starlark_eval_provider(
                     dice,
                     &mut StarlarkProfilerOrInstrumentation::disabled(),
+                    limits,
                     format!("anon_analysis:{}", self),
                     |provider| {
                         let mut eval = provider.make(&env)?;
@@ -148,6 +148,11 @@ async fn build_action_no_redirect(
         let mut buck2_build_time = None;
         let mut hostname = None;
 
+        let undeclared_inputs = command_reports
+            .last()
+            .map(|r| r.undeclared_inputs.clone())
+            .unwrap_or_default();
+
         match execute_result {
             Ok((outputs, meta)) => {
                 output_size = outputs.calc_output_count_and_bytes().bytes;
@@ -227,6 +232,7 @@ async fn build_action_no_redirect(
                 buck2_revision,
                 buck2_build_time,
                 hostname,
+                undeclared_inputs,
             }),
         )
     };
@@ -331,6 +337,12 @@ async fn command_execution_report_to_proto(
             }
             .into()
         }
+        CommandExecutionStatus::OomKilled { memory_max, .. } => {
+            buck2_data::command_execution::OomKilled {
+                memory_max: *memory_max,
+            }
+            .into()
+        }
         CommandExecutionStatus::Error { stage, error } => buck2_data::command_execution::Error {
             stage: (*stage).to_owned(),
             error: format!("{:#}", error),
@@ -483,6 +483,7 @@ impl ActionExecutionCtx for BuckActionExecutionContext<'_> {
         action_digest: ActionDigest,
         execution_result: &CommandExecutionResult,
         dep_file_entry: Option<DepFileEntry>,
+        allow_cache_upload: bool,
     ) -> anyhow::Result<bool> {
         let action = self.target();
         self.executor
@@ -492,6 +493,7 @@ impl ActionExecutionCtx for BuckActionExecutionContext<'_> {
                     target: &action as _,
                     action_digest,
                     digest_config: self.digest_config(),
+                    allow_cache_upload,
                 },
                 execution_result,
                 dep_file_entry,
@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use buck2_execute::execute::request::LocalResourceLimits;
 use dice::UserComputationData;
 use dupe::Dupe;
 
@@ -27,6 +28,9 @@ pub struct RunActionKnobs {
 
     /// Whether to enforce timeouts when running things on RE.
     pub enforce_re_timeouts: bool,
+
+    /// Default resource limits for actions running locally. Actions can override those.
+    pub local_resource_limits: LocalResourceLimits,
 }
 
 pub trait HasRunActionKnobs {
@@ -215,6 +215,7 @@ pub trait ActionExecutionCtx: Send + Sync {
         action_digest: ActionDigest,
         execution_result: &CommandExecutionResult,
         dep_file_entry: Option<DepFileEntry>,
+        allow_cache_upload: bool,
     ) -> anyhow::Result<bool>;
 
     /// Executes a command
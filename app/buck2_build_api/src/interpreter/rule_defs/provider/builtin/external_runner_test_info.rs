@@ -9,6 +9,7 @@
 
 use std::iter::empty;
 use std::iter::once;
+use std::time::Duration;
 
 use allocative::Allocative;
 use anyhow::Context as _;
@@ -97,6 +98,11 @@ pub struct ExternalRunnerTestInfoGen<V> {
     /// should be ignored when executing tests even if those are passed as required from test runner.
     #[provider(field_type = DictType<String, Option<StarlarkConfiguredProvidersLabel>>)]
     local_resources: V,
+
+    /// How long, in milliseconds, this test may run for before it is terminated. If none is
+    /// passed, the test runner decides.
+    #[provider(field_type = u64)]
+    timeout_ms: V,
 }
 
 // NOTE: All the methods here unwrap because we validate at freeze time.
@@ -151,6 +157,13 @@ impl FrozenExternalRunnerTestInfo {
         unwrap_all(iter_local_resources(self.local_resources.to_value())).collect()
     }
 
+    pub fn timeout(&self) -> Option<Duration> {
+        NoneOr::<u64>::unpack_value(self.timeout_ms.to_value())
+            .unwrap()
+            .into_option()
+            .map(Duration::from_millis)
+    }
+
     pub fn visit_artifacts(
         &self,
         visitor: &mut dyn CommandLineArtifactVisitor,
@@ -430,6 +443,8 @@ where
     NoneOr::<bool>::unpack_value(info.run_from_project_root.to_value())
         .context("`run_from_project_root` must be a bool if provided")?;
     unpack_opt_executor(info.default_executor.to_value()).context("Invalid `default_executor`")?;
+    NoneOr::<u64>::unpack_value(info.timeout_ms.to_value())
+        .context("`timeout_ms` must be a non-negative int if provided")?;
     info.test_type
         .to_value()
         .unpack_str()
@@ -451,6 +466,7 @@ fn external_runner_test_info_creator(globals: &mut GlobalsBuilder) {
         #[starlark(default = NoneType)] default_executor: Value<'v>,
         #[starlark(default = NoneType)] executor_overrides: Value<'v>,
         #[starlark(default = NoneType)] local_resources: Value<'v>,
+        #[starlark(default = NoneType)] timeout_ms: Value<'v>,
     ) -> anyhow::Result<ExternalRunnerTestInfo<'v>> {
         let res = ExternalRunnerTestInfo {
             test_type: r#type,
@@ -463,6 +479,7 @@ fn external_runner_test_info_creator(globals: &mut GlobalsBuilder) {
             default_executor,
             executor_overrides,
             local_resources,
+            timeout_ms,
         };
         validate_external_runner_test_info(&res)?;
         Ok(res)
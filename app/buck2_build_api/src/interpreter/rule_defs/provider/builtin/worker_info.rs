@@ -14,6 +14,7 @@ use std::sync::atomic::AtomicU64;
 use allocative::Allocative;
 use anyhow::Context;
 use buck2_build_api_derive::internal_provider;
+use buck2_execute::execute::request::WorkerProtocol;
 use starlark::any::ProvidesStaticType;
 use starlark::coerce::Coerce;
 use starlark::environment::GlobalsBuilder;
@@ -42,6 +43,12 @@ pub struct WorkerInfoGen<V> {
     // Maximum number of concurrent commands to execute on a worker instance without queuing
     #[provider(field_type = NoneOr<usize>)]
     pub concurrency: V,
+    // Protocol spoken by the worker: `buck2` (default), `bazel_proto` or `bazel_json`
+    #[provider(field_type = String)]
+    pub protocol: V,
+    // Whether a Bazel worker can process several requests at once (a multiplex worker)
+    #[provider(field_type = bool)]
+    pub supports_multiplex: V,
 
     pub id: u64,
 }
@@ -57,16 +64,21 @@ fn worker_info_creator(globals: &mut GlobalsBuilder) {
     fn WorkerInfo<'v>(
         #[starlark(default = AllocList::EMPTY)] exe: Value<'v>,
         #[starlark(require = named, default = NoneOr::None)] concurrency: NoneOr<usize>,
+        #[starlark(require = named, default = "buck2")] protocol: &str,
+        #[starlark(require = named, default = false)] supports_multiplex: bool,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<WorkerInfo<'v>> {
         let heap = eval.heap();
         let valid_exe = StarlarkCmdArgs::try_from_value(exe)?;
         let exe = heap.alloc(valid_exe);
+        validate_protocol(protocol, supports_multiplex)?;
         let id = next_id();
         Ok(WorkerInfo {
             exe,
             id,
             concurrency: heap.alloc(concurrency),
+            protocol: heap.alloc(protocol),
+            supports_multiplex: Value::new_bool(supports_multiplex),
         })
     }
 }
@@ -84,6 +96,32 @@ impl<'v, V: ValueLike<'v>> WorkerInfoGen<V> {
             .expect("validated at construction")
             .into_option()
     }
+
+    pub fn protocol(&self) -> WorkerProtocol {
+        self.protocol
+            .to_value()
+            .unpack_str()
+            .expect("validated at construction")
+            .parse()
+            .expect("validated at construction")
+    }
+
+    pub fn supports_multiplex(&self) -> bool {
+        self.supports_multiplex
+            .to_value()
+            .unpack_bool()
+            .expect("validated at construction")
+    }
+}
+
+fn validate_protocol(protocol: &str, supports_multiplex: bool) -> anyhow::Result<WorkerProtocol> {
+    let protocol: WorkerProtocol = protocol.parse()?;
+    if supports_multiplex && protocol == WorkerProtocol::Buck2 {
+        return Err(anyhow::anyhow!(
+            "`supports_multiplex` only applies to Bazel workers"
+        ));
+    }
+    Ok(protocol)
 }
 
 fn validate_worker_info<'v, V>(info: &WorkerInfoGen<V>) -> anyhow::Result<()>
@@ -103,5 +141,23 @@ where
         ));
     }
 
+    let protocol = info.protocol.to_value().unpack_str().with_context(|| {
+        format!(
+            "Value for `protocol` field is not a string: `{}`",
+            info.protocol
+        )
+    })?;
+    let supports_multiplex = info
+        .supports_multiplex
+        .to_value()
+        .unpack_bool()
+        .with_context(|| {
+            format!(
+                "Value for `supports_multiplex` field is not a bool: `{}`",
+                info.supports_multiplex
+            )
+        })?;
+    validate_protocol(protocol, supports_multiplex)?;
+
     Ok(())
 }
@@ -88,6 +88,7 @@ impl TestProvider for FrozenExternalRunnerTestInfo {
             contacts: self.contacts().map(|l| l.to_owned()).collect(),
             oncall: self.contacts().exactly_one().ok().map(str::to_owned),
             working_dir_cell,
+            timeout: self.timeout(),
         };
 
         async move { executor.external_runner_spec(spec).await }.boxed()
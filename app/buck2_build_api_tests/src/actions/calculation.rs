@@ -522,6 +522,7 @@ async fn test_command_details_omission() {
             stderr: "stderr".to_owned().into_bytes(),
         },
         exit_code: Some(1),
+        undeclared_inputs: Vec::new(),
     };
 
     let proto = command_details(&report, false).await;
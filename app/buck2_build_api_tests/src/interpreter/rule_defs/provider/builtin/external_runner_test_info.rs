@@ -34,6 +34,7 @@ fn test_construction() -> anyhow::Result<()> {
             ExternalRunnerTestInfo(type = "foo", labels = ("foo",))
             ExternalRunnerTestInfo(type = "foo", use_project_relative_paths = True)
             ExternalRunnerTestInfo(type = "foo", run_from_project_root = True)
+            ExternalRunnerTestInfo(type = "foo", timeout_ms = 60000)
         "#
     );
     let mut tester = tester();
@@ -184,6 +185,16 @@ fn test_validation() -> anyhow::Result<()> {
         "`executor_overrides`",
     );
 
+    tester.run_starlark_bzl_test_expecting_error(
+        indoc!(
+            r#"
+        def test():
+            ExternalRunnerTestInfo(type = "foo", timeout_ms = -1)
+        "#
+        ),
+        "`timeout_ms`",
+    );
+
     Ok(())
 }
 
@@ -24,8 +24,38 @@ fn run_display() {
         .run_starlark_bzl_test(
             r#"
 def test():
-    assert_eq('WorkerInfo(exe=cmd_args("x"), concurrency=None)', str(WorkerInfo(exe="x")))
+    assert_eq('WorkerInfo(exe=cmd_args("x"), concurrency=None, protocol="buck2", supports_multiplex=False)', str(WorkerInfo(exe="x")))
 "#,
         )
         .unwrap();
 }
+
+#[test]
+fn test_protocol() {
+    let mut tester = run_info_tester();
+    tester
+        .run_starlark_bzl_test(
+            r#"
+def test():
+    info = WorkerInfo(exe="x", protocol="bazel_json", supports_multiplex=True)
+    assert_eq("bazel_json", info.protocol)
+    assert_eq(True, info.supports_multiplex)
+"#,
+        )
+        .unwrap();
+
+    tester.run_starlark_bzl_test_expecting_error(
+        r#"
+def test():
+    WorkerInfo(exe="x", protocol="grpc")
+"#,
+        "Invalid worker protocol",
+    );
+    tester.run_starlark_bzl_test_expecting_error(
+        r#"
+def test():
+    WorkerInfo(exe="x", supports_multiplex=True)
+"#,
+        "only applies to Bazel workers",
+    );
+}
@@ -33,6 +33,7 @@ use buck2_events::dispatch::console_message;
 use buck2_events::dispatch::with_dispatcher;
 use buck2_events::dispatch::with_dispatcher_async;
 use buck2_execute::digest_config::HasDigestConfig;
+use buck2_interpreter::dice::starlark_limits::StarlarkEvalLimits;
 use buck2_interpreter::dice::starlark_provider::with_starlark_eval_provider;
 use buck2_interpreter::factory::StarlarkEvaluatorProvider;
 use buck2_interpreter::file_loader::LoadedModule;
@@ -154,6 +155,7 @@ pub(crate) async fn eval(
                     let (bxl_result, materializations) = with_starlark_eval_provider(
                         ctx,
                         &mut profiler,
+                        StarlarkEvalLimits::none(),
                         format!("bxl:{}", key),
                         move |provider| {
                             let env = Module::new();
@@ -51,6 +51,7 @@ use buck2_events::dispatch::console_message;
 use buck2_events::dispatch::with_dispatcher_async;
 use buck2_execute::digest_config::DigestConfig;
 use buck2_execute::digest_config::HasDigestConfig;
+use buck2_interpreter::dice::starlark_limits::StarlarkEvalLimits;
 use buck2_interpreter::dice::starlark_provider::with_starlark_eval_provider;
 use buck2_interpreter::print_handler::EventDispatcherPrintHandler;
 use buck2_interpreter::starlark_profiler::StarlarkProfilerOrInstrumentation;
@@ -449,6 +450,7 @@ pub(crate) async fn eval_bxl_for_dynamic_output<'v>(
                     with_starlark_eval_provider(
                         dice_ctx,
                         &mut StarlarkProfilerOrInstrumentation::disabled(),
+                        StarlarkEvalLimits::none(),
                         format!("bxl_dynamic:{}", "foo"),
                         move |provider| {
                             tokio::task::block_in_place(|| {
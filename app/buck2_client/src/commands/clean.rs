@@ -38,7 +38,8 @@ use crate::commands::kill::kill_command_impl;
 
 /// Delete generated files and caches.
 ///
-/// The command also kills the buck2 daemon.
+/// The command also kills the buck2 daemon. The local action cache is kept, unless
+/// `--include-local-action-cache` is passed.
 #[derive(Debug, clap::Parser)]
 pub struct CleanCommand {
     #[clap(flatten)]
@@ -64,6 +65,13 @@ the specified duration, without killing the daemon",
 
     #[clap(long = "tracked-only", requires = "stale")]
     tracked_only: bool,
+
+    #[clap(
+        long = "include-local-action-cache",
+        conflicts_with = "stale",
+        help = "Also delete the local action cache, which is kept otherwise."
+    )]
+    include_local_action_cache: bool,
 }
 
 impl CleanCommand {
@@ -82,9 +90,14 @@ impl CleanCommand {
             let buck_out_dir = ctx.paths()?.buck_out_path();
             let daemon_dir = ctx.paths()?.daemon_dir()?;
             let console = &self.common_opts.console_opts.final_console();
+            let keep = if self.include_local_action_cache {
+                None
+            } else {
+                Some(ctx.paths()?.local_action_cache_path())
+            };
 
             if self.dry_run {
-                return clean(buck_out_dir, daemon_dir, console, None).await;
+                return clean(buck_out_dir, keep, daemon_dir, console, None).await;
             }
 
             // Kill the daemon and make sure a new daemon does not spin up while we're performing clean up operations
@@ -98,7 +111,14 @@ impl CleanCommand {
 
             kill_command_impl(&lifecycle_lock, "`buck2 clean` was invoked").await?;
 
-            clean(buck_out_dir, daemon_dir, console, Some(&lifecycle_lock)).await
+            clean(
+                buck_out_dir,
+                keep,
+                daemon_dir,
+                console,
+                Some(&lifecycle_lock),
+            )
+            .await
         })
     }
 
@@ -109,6 +129,8 @@ impl CleanCommand {
 
 async fn clean(
     buck_out_dir: AbsNormPathBuf,
+    // A path in buck-out to keep.
+    keep: Option<AbsNormPathBuf>,
     daemon_dir: DaemonDir,
     console: &FinalConsole,
     // None means "dry run".
@@ -121,10 +143,10 @@ async fn clean(
     if let Some(paths) = try_clean_eden_buck_out(&buck_out_dir, lifecycle_lock.is_none()).await? {
         paths_to_clean = paths;
     } else if buck_out_dir.exists() {
-        paths_to_clean =
-            collect_paths_to_clean(&buck_out_dir)?.map(|path| path.display().to_string());
+        let paths = collect_paths_to_clean(&buck_out_dir, keep.as_ref())?;
+        paths_to_clean = paths.map(|path| path.display().to_string());
         if lifecycle_lock.is_some() {
-            tokio::task::spawn_blocking(move || clean_buck_out_with_retry(&buck_out_dir))
+            tokio::task::spawn_blocking(move || clean_buck_out_with_retry(&paths))
                 .await?
                 .context("Failed to spawn clean")?;
         }
@@ -143,13 +165,22 @@ async fn clean(
     Ok(())
 }
 
-fn collect_paths_to_clean(buck_out_path: &AbsNormPathBuf) -> anyhow::Result<Vec<AbsNormPathBuf>> {
+/// The entries of `dir` to delete so that everything but `keep` is gone.
+fn collect_paths_to_clean(
+    dir: &AbsNormPathBuf,
+    keep: Option<&AbsNormPathBuf>,
+) -> anyhow::Result<Vec<AbsNormPathBuf>> {
     let mut paths_to_clean = vec![];
-    let dir = fs_util::read_dir(buck_out_path)?;
-    for entry in dir {
+    for entry in fs_util::read_dir(dir)? {
         let entry = entry?;
         let path = entry.path();
-        paths_to_clean.push(path);
+        match keep {
+            Some(keep) if *keep == path => {}
+            Some(keep) if keep.starts_with(&path) && entry.file_type()?.is_dir() => {
+                paths_to_clean.extend(collect_paths_to_clean(&path, Some(keep))?);
+            }
+            _ => paths_to_clean.push(path),
+        }
     }
 
     Ok(paths_to_clean)
@@ -159,8 +190,8 @@ fn collect_paths_to_clean(buck_out_path: &AbsNormPathBuf) -> anyhow::Result<Vec<
 /// the daemon can fail with this error: `The process cannot access the
 /// file because it is being used by another process.`. To get around this,
 /// add a single retry.
-fn clean_buck_out_with_retry(path: &AbsNormPathBuf) -> anyhow::Result<()> {
-    let mut result = clean_buck_out(path);
+fn clean_buck_out_with_retry(paths: &[AbsNormPathBuf]) -> anyhow::Result<()> {
+    let mut result = clean_buck_out(paths);
     match result {
         Ok(_) => {
             return result;
@@ -170,21 +201,21 @@ fn clean_buck_out_with_retry(path: &AbsNormPathBuf) -> anyhow::Result<()> {
                 "Retrying buck-out clean, first attempted failed with: {:#}",
                 e
             );
-            result = clean_buck_out(path);
+            result = clean_buck_out(paths);
         }
     }
     result
 }
 
-fn clean_buck_out(path: &AbsNormPathBuf) -> anyhow::Result<()> {
-    let walk = WalkDir::new(path);
+fn clean_buck_out(paths: &[AbsNormPathBuf]) -> anyhow::Result<()> {
+    let walk = paths.iter().flat_map(WalkDir::new);
     let thread_pool = ThreadPool::new(num_cpus::get());
     let error = Arc::new(Mutex::new(None));
     // collect dir paths to delete them after deleting files in them
     // we need reverse order to make sure the dir is already empty when
     // we delete it, otherwise remove would fail with DirNotEmpty exception
     let mut reverse_dir_paths = Vec::new();
-    for dir_entry in walk.into_iter().flatten() {
+    for dir_entry in walk.flatten() {
         if dir_entry.file_type().is_dir() {
             // The walk gives us back absolute paths since we give it absolute paths.
             reverse_dir_paths.push(AbsPathBuf::new(dir_entry.into_path()).unwrap());
@@ -212,8 +243,7 @@ fn clean_buck_out(path: &AbsNormPathBuf) -> anyhow::Result<()> {
         return Err(e);
     }
 
-    // first entry is buck-out root dir and we don't want to remove it
-    for path in reverse_dir_paths.iter().skip(1).rev() {
+    for path in reverse_dir_paths.iter().rev() {
         fs_util::remove_dir(path)?;
     }
 
@@ -277,3 +307,49 @@ async fn try_clean_eden_buck_out(
 ) -> anyhow::Result<Option<Vec<String>>> {
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::fs::fs_util;
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+
+    use crate::commands::clean::clean_buck_out;
+    use crate::commands::clean::collect_paths_to_clean;
+
+    #[test]
+    fn test_clean_keeps_path() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let buck_out = AbsNormPathBuf::try_from(tempdir.path().to_owned())?;
+        let keep = buck_out.join_normalized("cache/local_action_cache")?;
+        fs_util::create_dir_all(keep.join_normalized("ab")?)?;
+        fs_util::write(keep.join_normalized("ab/cd")?, "x")?;
+        fs_util::create_dir_all(buck_out.join_normalized("cache/materializer_state")?)?;
+        fs_util::create_dir_all(buck_out.join_normalized("gen/foo")?)?;
+        fs_util::write(buck_out.join_normalized("gen/foo/out")?, "x")?;
+        fs_util::write(buck_out.join_normalized("log")?, "x")?;
+
+        let mut paths = collect_paths_to_clean(&buck_out, Some(&keep))?;
+        paths.sort();
+        assert_eq!(
+            vec![
+                buck_out.join_normalized("cache/materializer_state")?,
+                buck_out.join_normalized("gen")?,
+                buck_out.join_normalized("log")?,
+            ],
+            paths
+        );
+
+        clean_buck_out(&paths)?;
+        assert!(keep.join_normalized("ab/cd")?.exists());
+        assert!(!buck_out.join_normalized("gen")?.exists());
+        assert!(!buck_out.join_normalized("log")?.exists());
+
+        let paths = collect_paths_to_clean(&buck_out, None)?;
+        assert_eq!(vec![buck_out.join_normalized("cache")?], paths);
+        clean_buck_out(&paths)?;
+        assert!(buck_out.exists());
+        assert!(!buck_out.join_normalized("cache")?.exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_core::fs::fs_util;
+use buck2_execute::local_action_cache::LocalActionCache;
+
+/// Inspect or trim the local action cache (enabled with `buck2.local_action_cache`).
+#[derive(Debug, clap::Parser)]
+pub struct LocalActionCacheCommand {
+    #[clap(subcommand)]
+    action: Subcommand,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Subcommand {
+    /// Print the location and size of the cache.
+    Status,
+    /// Evict least recently used results until the cache fits in the given size.
+    Trim {
+        #[clap(long, value_name = "BYTES")]
+        max_bytes: u64,
+    },
+    /// Remove all results from the cache.
+    Clean,
+}
+
+impl LocalActionCacheCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext<'_>) -> ExitResult {
+        let path = ctx.paths()?.local_action_cache_path();
+
+        if !fs_util::try_exists(&path)? {
+            buck2_client_ctx::println!("No local action cache at `{}`", path)?;
+            return ExitResult::success();
+        }
+
+        // Only trimming writes to the cache. Either way, a running daemon may be using it, so it
+        // must not be reset the way the daemon does when opening it.
+        let writable = !matches!(self.action, Subcommand::Status);
+        let cache = LocalActionCache::open_existing(path, writable)?;
+
+        let max_bytes = match self.action {
+            Subcommand::Status => {
+                let stats = cache.stats()?;
+                buck2_client_ctx::println!("path: {}", cache.root())?;
+                buck2_client_ctx::println!("actions: {}", stats.actions)?;
+                buck2_client_ctx::println!("blobs: {}", stats.blobs)?;
+                buck2_client_ctx::println!("bytes: {}", stats.total_bytes)?;
+                return ExitResult::success();
+            }
+            Subcommand::Trim { max_bytes } => max_bytes,
+            Subcommand::Clean => 0,
+        };
+
+        let stats = cache.trim(max_bytes)?;
+        buck2_client_ctx::println!(
+            "Evicted {} actions, deleted {} blobs ({} bytes)",
+            stats.actions,
+            stats.blobs,
+            stats.bytes
+        )?;
+        ExitResult::success()
+    }
+}
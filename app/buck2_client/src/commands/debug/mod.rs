@@ -25,6 +25,7 @@ use materialize::MaterializeCommand;
 use crate::commands::debug::allocative::AllocativeCommand;
 use crate::commands::debug::daemon_dir::DaemonDirCommand;
 use crate::commands::debug::exe::ExeCommand;
+use crate::commands::debug::local_action_cache::LocalActionCacheCommand;
 use crate::commands::debug::log_perf::LogPerfCommand;
 use crate::commands::debug::persist_event_logs::PersistEventLogsCommand;
 use crate::commands::debug::segfault::SegfaultCommand;
@@ -45,6 +46,7 @@ mod file_status;
 mod flush_dep_files;
 mod heap_dump;
 mod internal_version;
+mod local_action_cache;
 mod log_perf;
 mod materialize;
 mod persist_event_logs;
@@ -97,6 +99,8 @@ pub enum DebugCommand {
     TraceIo(TraceIoCommand),
     #[doc(hidden)]
     PersistEventLogs(PersistEventLogsCommand),
+    /// Inspect or trim the local action cache.
+    LocalActionCache(LocalActionCacheCommand),
 }
 
 impl DebugCommand {
@@ -123,6 +127,7 @@ impl DebugCommand {
             DebugCommand::LogPerf(cmd) => cmd.exec(matches, ctx),
             DebugCommand::TraceIo(cmd) => cmd.exec(matches, ctx),
             DebugCommand::PersistEventLogs(cmd) => cmd.exec(matches, ctx),
+            DebugCommand::LocalActionCache(cmd) => cmd.exec(matches, ctx),
         }
     }
 
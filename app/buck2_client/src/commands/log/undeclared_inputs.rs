@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashSet;
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::stream_value::StreamValue;
+use buck2_event_observer::display;
+use buck2_event_observer::display::TargetDisplayOptions;
+use tokio_stream::StreamExt;
+
+use crate::commands::log::options::EventLogOptions;
+use crate::commands::log::LogCommandOutputFormat;
+
+/// Outputs the files that actions read without declaring them as inputs in the selected
+/// invocation.
+///
+/// This requires the invocation to have traced local actions, which is enabled by setting
+/// `buck2.trace_undeclared_inputs` to true.
+///
+/// The output is one line per action and file, in the format:
+///
+/// `<action> <file>`
+#[derive(Debug, clap::Parser)]
+pub struct UndeclaredInputsCommand {
+    #[clap(flatten)]
+    event_log: EventLogOptions,
+    #[clap(
+        long = "format",
+        help = "Which output format to use for this command",
+        default_value = "tabulated",
+        ignore_case = true,
+        arg_enum
+    )]
+    pub output: LogCommandOutputFormat,
+}
+
+fn print_undeclared_inputs(
+    format: &LogCommandOutputFormat,
+    action: &buck2_data::ActionExecutionEnd,
+) -> anyhow::Result<()> {
+    let action_str = display::display_action_identity(
+        action.key.as_ref(),
+        action.name.as_ref(),
+        TargetDisplayOptions::for_log(),
+    )?;
+
+    #[derive(serde::Serialize)]
+    struct Record<'a> {
+        action: &'a str,
+        path: &'a str,
+    }
+
+    for path in &action.undeclared_inputs {
+        let record = Record {
+            action: &action_str,
+            path,
+        };
+
+        match format {
+            LogCommandOutputFormat::Tabulated => {
+                buck2_client_ctx::println!("{}\t{}", action_str, path)?;
+            }
+            LogCommandOutputFormat::Csv => buck2_client_ctx::stdio::print_with_writer(|w| {
+                let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(w);
+                writer.serialize(&record)
+            })?,
+            LogCommandOutputFormat::Json => {
+                buck2_client_ctx::stdio::print_with_writer(|w| serde_json::to_writer(w, &record))?;
+                buck2_client_ctx::println!("")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl UndeclaredInputsCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext<'_>) -> ExitResult {
+        let Self { event_log, output } = self;
+
+        ctx.with_runtime(async move |ctx| {
+            let log_path = event_log.get(&ctx).await?;
+
+            let (invocation, mut events) = log_path.unpack_stream().await?;
+            buck2_client_ctx::eprintln!(
+                "Showing undeclared inputs from: {}",
+                invocation.display_command_line()
+            )?;
+
+            let mut actions = 0;
+            let mut paths = HashSet::new();
+            while let Some(event) = events.try_next().await? {
+                match event {
+                    StreamValue::Event(event) => match event.data {
+                        Some(buck2_data::buck_event::Data::SpanEnd(end)) => match end.data {
+                            Some(buck2_data::span_end_event::Data::ActionExecution(action))
+                                if !action.undeclared_inputs.is_empty() =>
+                            {
+                                print_undeclared_inputs(&output, &action)?;
+                                actions += 1;
+                                paths.extend(action.undeclared_inputs);
+                            }
+                            _ => {}
+                        },
+                        _ => {}
+                    },
+                    StreamValue::Result(..) | StreamValue::PartialResult(..) => {}
+                }
+            }
+            buck2_client_ctx::eprintln!(
+                "total: {} undeclared inputs in {} actions",
+                paths.len(),
+                actions
+            )?;
+
+            anyhow::Ok(())
+        })?;
+
+        ExitResult::success()
+    }
+}
@@ -9,9 +9,12 @@
 
 use anyhow::Context;
 use async_trait::async_trait;
+use buck2_cli_proto::config_override::ConfigType;
+use buck2_cli_proto::ConfigOverride;
 use buck2_cli_proto::CounterWithExamples;
 use buck2_cli_proto::TestRequest;
 use buck2_cli_proto::TestSessionOptions;
+use buck2_cli_proto::TestShard;
 use buck2_client_ctx::client_ctx::ClientCommandContext;
 use buck2_client_ctx::common::CommonBuildConfigurationOptions;
 use buck2_client_ctx::common::CommonBuildOptions;
@@ -26,9 +29,14 @@ use buck2_client_ctx::output_destination_arg::OutputDestinationArg;
 use buck2_client_ctx::path_arg::PathArg;
 use buck2_client_ctx::stdio::eprint_line;
 use buck2_client_ctx::streaming::StreamingCommand;
+use buck2_client_ctx::subscribers::subscriber::EventSubscriber;
 use buck2_client_ctx::subscribers::superconsole::test::span_from_build_failure_count;
 use buck2_client_ctx::subscribers::superconsole::test::TestCounterColumn;
+use buck2_client_ctx::subscribers::test_report::target_durations;
+use buck2_client_ctx::subscribers::test_report::TestReportArg;
+use buck2_client_ctx::subscribers::test_report::TestReportWriter;
 use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
 use buck2_core::fs::working_dir::WorkingDir;
 use gazebo::prelude::*;
 use superconsole::Line;
@@ -148,6 +156,55 @@ If include patterns are present, regardless of whether exclude patterns are pres
     #[clap(long)]
     test_executor_stderr: Option<OutputDestinationArg>,
 
+    /// Writes a report of the test results to the provided path when the command finishes
+    ///
+    /// --test-report=junit:FILEPATH will write a JUnit XML report
+    ///
+    /// --test-report=json:FILEPATH will write a JSON report
+    ///
+    /// Can be specified multiple times to write several reports
+    #[clap(long, value_name = "FORMAT:FILEPATH")]
+    test_report: Vec<TestReportArg>,
+
+    /// Only run the tests of this shard, out of `--shard-count`, numbered from 0
+    ///
+    /// Targets are assigned to shards by a hash of their label, so that every machine running a
+    /// shard agrees on it
+    #[clap(long, requires = "shard-count")]
+    shard_index: Option<u32>,
+
+    /// Number of shards to split the tests into
+    #[clap(long, requires = "shard-index")]
+    shard_count: Option<u32>,
+
+    /// JSON test report of a previous run (see `--test-report`), whose durations are used to
+    /// balance the shards
+    #[clap(long, requires = "shard-count", value_name = "FILEPATH")]
+    shard_timings: Option<PathArg>,
+
+    /// Shard the test cases of targets, rather than whole targets, when the test executor can
+    /// list them. Custom test executors must opt in with
+    /// `test.v2_test_executor_supports_sharding`, otherwise whole targets are sharded
+    #[clap(long, requires = "shard-count")]
+    shard_testcases: bool,
+
+    /// Execute tests even if a passing result of the same execution (same command, environment,
+    /// inputs and executor) is cached locally or in the remote action cache. Results of tests
+    /// that only run locally are cached only if `buck2.local_action_cache = true`
+    #[clap(long)]
+    no_test_cache: bool,
+
+    /// Build instrumented variants of the tests (by setting `test.coverage=true`), collect their
+    /// coverage, and merge it into an lcov report. A custom test executor must declare support for
+    /// it with `test.v2_test_executor_supports_coverage`
+    #[clap(long)]
+    coverage: bool,
+
+    /// Where to write the lcov report of `--coverage`, by default
+    /// `buck-out/<isolation dir>/coverage/coverage.lcov`
+    #[clap(long, requires = "coverage", value_name = "FILEPATH")]
+    coverage_report: Option<PathArg>,
+
     /// Additional arguments passed to the test executor.
     ///
     /// Test executor is expected to have `--env` flag to pass environment variables.
@@ -168,7 +225,48 @@ impl StreamingCommand for TestCommand {
         matches: &clap::ArgMatches,
         ctx: &mut ClientCommandContext<'_>,
     ) -> ExitResult {
-        let context = ctx.client_context(matches, &self)?;
+        let mut context = ctx.client_context(matches, &self)?;
+        let coverage_report = if self.coverage {
+            // Before the user's config, so that it can be overridden.
+            context.config_overrides.insert(
+                0,
+                ConfigOverride {
+                    config_override: "test.coverage=true".to_owned(),
+                    config_type: ConfigType::Value as i32,
+                },
+            );
+            let report = match &self.coverage_report {
+                Some(path) => path.resolve(&ctx.working_dir).into_path_buf(),
+                None => ctx
+                    .paths()?
+                    .buck_out_path()
+                    .join(ForwardRelativePath::unchecked_new("coverage/coverage.lcov"))
+                    .into_path_buf(),
+            };
+            Some(report.to_string_lossy().into_owned())
+        } else {
+            None
+        };
+        let shard = match (self.shard_index, self.shard_count) {
+            (Some(index), Some(count)) => {
+                let target_durations = match &self.shard_timings {
+                    Some(path) => {
+                        let path = path.resolve(&ctx.working_dir);
+                        target_durations(&fs_util::read_to_string(&path)?).with_context(|| {
+                            format!("Error reading shard timings from `{}`", path.display())
+                        })?
+                    }
+                    None => Default::default(),
+                };
+                Some(TestShard {
+                    index,
+                    count,
+                    target_durations,
+                    by_testcase: self.shard_testcases,
+                })
+            }
+            _ => None,
+        };
         let response = buckd
             .with_flushing()
             .test(
@@ -190,7 +288,10 @@ impl StreamingCommand for TestCommand {
                             || self.unstable_allow_all_tests_on_re,
                         force_use_project_relative_paths: self.unstable_allow_all_tests_on_re,
                         force_run_from_project_root: self.unstable_allow_all_tests_on_re,
+                        no_test_cache: self.no_test_cache,
                     }),
+                    shard,
+                    coverage_report,
                 },
                 ctx.stdin()
                     .console_interaction_stream(&self.common_opts.console_opts),
@@ -211,6 +312,8 @@ impl StreamingCommand for TestCommand {
         let failed = statuses.failed.as_ref().context("Missing `failed`")?;
         let fatals = statuses.fatals.as_ref().context("Missing `fatals`")?;
         let skipped = statuses.skipped.as_ref().context("Missing `skipped`")?;
+        let flaky = statuses.flaky.as_ref().context("Missing `flaky`")?;
+        let cached = statuses.cached.as_ref().context("Missing `cached`")?;
 
         let console = self.common_opts.console_opts.final_console();
         print_build_result(&console, &response.error_messages)?;
@@ -228,8 +331,13 @@ impl StreamingCommand for TestCommand {
             line.push(TestCounterColumn::LISTING_FAIL.to_span_from_test_statuses(statuses)?);
             line.push(Span::new_unstyled_lossy(". "));
         }
+        line.push(TestCounterColumn::PASS.to_span_from_test_statuses(statuses)?);
+        line.push(Span::new_unstyled_lossy(". "));
+        if cached.count > 0 {
+            line.push(TestCounterColumn::CACHED.to_span_from_test_statuses(statuses)?);
+            line.push(Span::new_unstyled_lossy(". "));
+        }
         let columns = [
-            TestCounterColumn::PASS,
             TestCounterColumn::FAIL,
             TestCounterColumn::FATAL,
             TestCounterColumn::SKIP,
@@ -238,6 +346,10 @@ impl StreamingCommand for TestCommand {
             line.push(column.to_span_from_test_statuses(statuses)?);
             line.push(Span::new_unstyled_lossy(". "));
         }
+        if flaky.count > 0 {
+            line.push(TestCounterColumn::FLAKY.to_span_from_test_statuses(statuses)?);
+            line.push(Span::new_unstyled_lossy(". "));
+        }
         line.push(span_from_build_failure_count(
             response.error_messages.len(),
         )?);
@@ -246,7 +358,9 @@ impl StreamingCommand for TestCommand {
         print_error_counter(&console, listing_failed, "LISTINGS FAILED", "⚠")?;
         print_error_counter(&console, failed, "TESTS FAILED", "✗")?;
         print_error_counter(&console, fatals, "TESTS FATALS", "⚠")?;
-        if passed.count + failed.count + fatals.count + skipped.count == 0 {
+        if passed.count + failed.count + fatals.count + skipped.count + flaky.count + cached.count
+            == 0
+        {
             console.print_warning("NO TESTS RAN")?;
         }
 
@@ -291,6 +405,18 @@ impl StreamingCommand for TestCommand {
         &self.common_opts.event_log_opts
     }
 
+    fn extra_subscribers(&self, ctx: &ClientCommandContext<'_>) -> Vec<Box<dyn EventSubscriber>> {
+        if self.test_report.is_empty() {
+            return Vec::new();
+        }
+        let reports = self
+            .test_report
+            .iter()
+            .map(|report| (report.format, report.path.resolve(&ctx.working_dir)))
+            .collect();
+        vec![Box::new(TestReportWriter::new(reports))]
+    }
+
     fn common_opts(&self) -> &CommonBuildConfigurationOptions {
         &self.common_opts.config_opts
     }
@@ -78,7 +78,7 @@ fn default_subscribers<'a, T: StreamingCommand>(
     )?;
     subscribers.push(recorder);
 
-    subscribers.extend(cmd.extra_subscribers());
+    subscribers.extend(cmd.extra_subscribers(ctx));
     Ok(subscribers)
 }
 
@@ -116,7 +116,7 @@ pub trait StreamingCommand: Sized + Send + Sync {
 
     fn common_opts(&self) -> &CommonBuildConfigurationOptions;
 
-    fn extra_subscribers(&self) -> Vec<Box<dyn EventSubscriber>> {
+    fn extra_subscribers(&self, _ctx: &ClientCommandContext<'_>) -> Vec<Box<dyn EventSubscriber>> {
         vec![]
     }
 
@@ -137,7 +137,9 @@ fn eprint_command_details(
                 remote_command.action_digest
             )?;
         }
-        Some(Command::OmittedLocalCommand(..)) | None => {
+        Some(Command::OmittedLocalCommand(..))
+        | Some(Command::LocalActionCacheCommand(..))
+        | None => {
             // Nothing to show in this case.
         }
     };
@@ -50,6 +50,12 @@ impl TestCounterColumn {
         get_from_test_state: |test_state| test_state.pass,
         get_from_test_statues: |test_statuses| &test_statuses.passed,
     };
+    pub const CACHED: TestCounterColumn = TestCounterColumn {
+        label: "Cached",
+        color: Some(Color::Green),
+        get_from_test_state: |test_state| test_state.cached,
+        get_from_test_statues: |test_statuses| &test_statuses.cached,
+    };
     pub const FAIL: TestCounterColumn = TestCounterColumn {
         label: "Fail",
         color: Some(Color::Red),
@@ -68,6 +74,12 @@ impl TestCounterColumn {
         get_from_test_state: |test_state| test_state.skipped,
         get_from_test_statues: |test_statuses| &test_statuses.skipped,
     };
+    pub const FLAKY: TestCounterColumn = TestCounterColumn {
+        label: "Flaky",
+        color: Some(Color::Yellow),
+        get_from_test_state: |test_state| test_state.flaky,
+        get_from_test_statues: |test_statuses| &test_statuses.flaky,
+    };
     const TIMEOUT: TestCounterColumn = TestCounterColumn {
         label: "Timeout",
         color: Some(Color::Yellow),
@@ -122,10 +134,18 @@ impl TestCounterComponent {
         spans.push(". ".try_into()?);
         spans.push(TestCounterColumn::PASS.to_span_from_test_state(test_state)?);
         spans.push(". ".try_into()?);
+        if test_state.cached > 0 {
+            spans.push(TestCounterColumn::CACHED.to_span_from_test_state(test_state)?);
+            spans.push(". ".try_into()?);
+        }
         spans.push(TestCounterColumn::FAIL.to_span_from_test_state(test_state)?);
         spans.push(". ".try_into()?);
         spans.push(TestCounterColumn::FATAL.to_span_from_test_state(test_state)?);
         spans.push(". ".try_into()?);
+        if test_state.flaky > 0 {
+            spans.push(TestCounterColumn::FLAKY.to_span_from_test_state(test_state)?);
+            spans.push(". ".try_into()?);
+        }
         spans.push(TestCounterColumn::SKIP.to_span_from_test_state(test_state)?);
         spans.push(". ".try_into()?);
         spans.push(TestCounterColumn::TIMEOUT.to_span_from_test_state(test_state)?);
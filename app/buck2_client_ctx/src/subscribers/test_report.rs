@@ -0,0 +1,390 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use buck2_core::fs::paths::abs_path::AbsPathBuf;
+use buck2_event_observer::test_state::TestCaseReport;
+use buck2_event_observer::test_state::TestReport;
+use buck2_events::BuckEvent;
+use buck2_test_api::data::TestStatus;
+
+use crate::path_arg::PathArg;
+use crate::subscribers::subscriber::EventSubscriber;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestReportFormat {
+    Junit,
+    Json,
+}
+
+/// A test report to write, as `<format>:<path>`.
+#[derive(Debug, Clone)]
+pub struct TestReportArg {
+    pub format: TestReportFormat,
+    pub path: PathArg,
+}
+
+impl FromStr for TestReportArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (format, path) = s
+            .split_once(':')
+            .with_context(|| format!("Expected `<format>:<path>`, got `{}`", s))?;
+        let format = match format {
+            "junit" => TestReportFormat::Junit,
+            "json" => TestReportFormat::Json,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unknown test report format `{}`, expected `junit` or `json`",
+                    format
+                ));
+            }
+        };
+        Ok(Self {
+            format,
+            path: path.parse()?,
+        })
+    }
+}
+
+/// Writes test reports from the test results of the command when it finishes.
+pub struct TestReportWriter {
+    reports: Vec<(TestReportFormat, AbsPathBuf)>,
+    report: TestReport,
+}
+
+impl TestReportWriter {
+    pub fn new(reports: Vec<(TestReportFormat, AbsPathBuf)>) -> Self {
+        Self {
+            reports,
+            report: TestReport::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for TestReportWriter {
+    async fn handle_events(&mut self, events: &[Arc<BuckEvent>]) -> anyhow::Result<()> {
+        for event in events {
+            if let buck2_data::buck_event::Data::Instant(instant) = event.data() {
+                if let Some(buck2_data::instant_event::Data::TestResult(result)) = &instant.data {
+                    self.report.update(result)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn exit(&mut self) -> anyhow::Result<()> {
+        for (format, path) in &self.reports {
+            let contents = match format {
+                TestReportFormat::Junit => junit_report(&self.report),
+                TestReportFormat::Json => json_report(&self.report)?,
+            };
+            tokio::fs::write(path, contents)
+                .await
+                .with_context(|| format!("Error writing test report to `{}`", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    tests: usize,
+    failures: usize,
+    errors: usize,
+    skipped: usize,
+    time: Duration,
+}
+
+impl Counts {
+    fn add(&mut self, test_case: &TestCaseReport) {
+        self.tests += 1;
+        match test_case.status {
+            TestStatus::FAIL => self.failures += 1,
+            TestStatus::FATAL
+            | TestStatus::TIMEOUT
+            | TestStatus::UNKNOWN
+            | TestStatus::LISTING_FAILED => self.errors += 1,
+            TestStatus::SKIP | TestStatus::OMITTED => self.skipped += 1,
+            TestStatus::PASS
+            | TestStatus::FLAKY
+            | TestStatus::LISTING_SUCCESS
+            | TestStatus::RERUN => {}
+        }
+        self.time += test_case.duration.unwrap_or_default();
+    }
+
+    fn attributes(&self) -> String {
+        format!(
+            "tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\"",
+            self.tests,
+            self.failures,
+            self.errors,
+            self.skipped,
+            self.time.as_secs_f64()
+        )
+    }
+}
+
+/// A JUnit XML report, with one test suite per target.
+fn junit_report(report: &TestReport) -> String {
+    // In the order they were first reported.
+    let mut suites: Vec<(&str, Vec<&TestCaseReport>)> = Vec::new();
+    let mut suite_indices = HashMap::new();
+    for test_case in &report.test_cases {
+        let suite = test_case.target.as_deref().unwrap_or_default();
+        let index = *suite_indices.entry(suite).or_insert_with(|| {
+            suites.push((suite, Vec::new()));
+            suites.len() - 1
+        });
+        suites[index].1.push(test_case);
+    }
+
+    let mut total = Counts::default();
+    let mut body = String::new();
+    for (suite, test_cases) in &suites {
+        let mut counts = Counts::default();
+        for test_case in test_cases {
+            counts.add(test_case);
+            total.add(test_case);
+        }
+
+        writeln!(
+            body,
+            "  <testsuite name=\"{}\" {}>",
+            escape(suite),
+            counts.attributes()
+        )
+        .unwrap();
+        if let Some(configuration) = test_cases.iter().find_map(|t| t.configuration.as_deref()) {
+            writeln!(
+                body,
+                "    <properties>\n      <property name=\"configuration\" value=\"{}\"/>\n    </properties>",
+                escape(configuration)
+            )
+            .unwrap();
+        }
+        for test_case in test_cases {
+            junit_test_case(&mut body, suite, test_case);
+        }
+        writeln!(body, "  </testsuite>").unwrap();
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites {}>\n{}</testsuites>\n",
+        total.attributes(),
+        body
+    )
+}
+
+fn junit_test_case(out: &mut String, suite: &str, test_case: &TestCaseReport) {
+    // Test cases are reported as `<target> - <test case>`, except when they are the whole target.
+    let name = test_case
+        .name
+        .strip_prefix(suite)
+        .and_then(|n| n.strip_prefix(" - "))
+        .unwrap_or(&test_case.name);
+    let msg = test_case.msg.as_deref().unwrap_or_default();
+
+    write!(
+        out,
+        "    <testcase name=\"{}\" classname=\"{}\"",
+        escape(name),
+        escape(suite)
+    )
+    .unwrap();
+    if let Some(duration) = test_case.duration {
+        write!(out, " time=\"{:.3}\"", duration.as_secs_f64()).unwrap();
+    }
+    writeln!(out, ">").unwrap();
+
+    let element = match test_case.status {
+        TestStatus::FAIL => Some("failure"),
+        TestStatus::FATAL
+        | TestStatus::TIMEOUT
+        | TestStatus::UNKNOWN
+        | TestStatus::LISTING_FAILED => Some("error"),
+        TestStatus::SKIP | TestStatus::OMITTED => Some("skipped"),
+        // The convention of Maven Surefire for tests that passed when retried.
+        TestStatus::FLAKY => Some("flakyFailure"),
+        TestStatus::PASS | TestStatus::LISTING_SUCCESS | TestStatus::RERUN => None,
+    };
+    if let Some(element) = element {
+        writeln!(
+            out,
+            "      <{} message=\"{}\" type=\"{:?}\"/>",
+            element,
+            escape(msg),
+            test_case.status
+        )
+        .unwrap();
+    }
+    if !test_case.details.is_empty() {
+        writeln!(
+            out,
+            "      <system-out>{}</system-out>",
+            escape(&test_case.details)
+        )
+        .unwrap();
+    }
+    writeln!(out, "    </testcase>").unwrap();
+}
+
+/// Escapes text for XML, dropping the characters XML can't represent.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{fffe}' || c == '\u{ffff}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn json_report(report: &TestReport) -> anyhow::Result<String> {
+    #[derive(serde::Serialize)]
+    struct Record<'a> {
+        name: &'a str,
+        target: Option<&'a str>,
+        configuration: Option<&'a str>,
+        status: String,
+        message: Option<&'a str>,
+        duration_secs: Option<f64>,
+        details: &'a str,
+    }
+
+    let records: Vec<_> = report
+        .test_cases
+        .iter()
+        .map(|t| Record {
+            name: &t.name,
+            target: t.target.as_deref(),
+            configuration: t.configuration.as_deref(),
+            status: format!("{:?}", t.status),
+            message: t.msg.as_deref(),
+            duration_secs: t.duration.map(|d| d.as_secs_f64()),
+            details: &t.details,
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&serde_json::json!({
+        "results": records
+    }))?)
+}
+
+/// Returns how long the tests of each target took in seconds, from a JSON report.
+pub fn target_durations(json_report: &str) -> anyhow::Result<HashMap<String, f64>> {
+    #[derive(serde::Deserialize)]
+    struct Report {
+        results: Vec<Record>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Record {
+        target: Option<String>,
+        duration_secs: Option<f64>,
+    }
+
+    let report: Report =
+        serde_json::from_str(json_report).context("Error parsing JSON test report")?;
+    let mut durations = HashMap::new();
+    for record in report.results {
+        if let (Some(target), Some(duration)) = (record.target, record.duration_secs) {
+            *durations.entry(target).or_default() += duration;
+        }
+    }
+    Ok(durations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_case(name: &str, status: TestStatus, details: &str) -> TestCaseReport {
+        TestCaseReport {
+            name: name.to_owned(),
+            target: Some("root//foo:bar".to_owned()),
+            configuration: Some("cfg#123".to_owned()),
+            status,
+            msg: None,
+            duration: Some(Duration::from_millis(1500)),
+            details: details.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_parse_test_report_arg() -> anyhow::Result<()> {
+        let arg: TestReportArg = "junit:out/report.xml".parse()?;
+        assert_eq!(TestReportFormat::Junit, arg.format);
+        assert_eq!("out/report.xml", arg.path.path().to_str().unwrap());
+        assert!("xml:report.xml".parse::<TestReportArg>().is_err());
+        assert!("report.xml".parse::<TestReportArg>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let report = TestReport {
+            test_cases: vec![
+                test_case("root//foo:bar - a", TestStatus::PASS, ""),
+                test_case("root//foo:bar - b", TestStatus::FAIL, "expected <1>\u{1b}"),
+            ],
+        };
+        assert_eq!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <testsuites tests=\"2\" failures=\"1\" errors=\"0\" skipped=\"0\" time=\"3.000\">\n  \
+            <testsuite name=\"root//foo:bar\" tests=\"2\" failures=\"1\" errors=\"0\" skipped=\"0\" time=\"3.000\">\n    \
+            <properties>\n      <property name=\"configuration\" value=\"cfg#123\"/>\n    </properties>\n    \
+            <testcase name=\"a\" classname=\"root//foo:bar\" time=\"1.500\">\n    \
+            </testcase>\n    \
+            <testcase name=\"b\" classname=\"root//foo:bar\" time=\"1.500\">\n      \
+            <failure message=\"\" type=\"FAIL\"/>\n      \
+            <system-out>expected &lt;1&gt;</system-out>\n    \
+            </testcase>\n  \
+            </testsuite>\n\
+            </testsuites>\n",
+            junit_report(&report)
+        );
+    }
+
+    #[test]
+    fn test_target_durations() -> anyhow::Result<()> {
+        let mut other = test_case("root//foo:baz", TestStatus::PASS, "");
+        other.target = Some("root//foo:baz".to_owned());
+        let report = TestReport {
+            test_cases: vec![
+                test_case("root//foo:bar - a", TestStatus::PASS, ""),
+                test_case("root//foo:bar - b", TestStatus::FAIL, ""),
+                other,
+            ],
+        };
+        let durations = target_durations(&json_report(&report)?)?;
+        assert_eq!(2, durations.len());
+        assert_eq!(Some(&3.0), durations.get("root//foo:bar"));
+        assert_eq!(Some(&1.5), durations.get("root//foo:baz"));
+        Ok(())
+    }
+}
@@ -225,6 +225,14 @@ impl CasDigestConfig {
         self.inner.digest256
     }
 
+    /// The enabled algorithm that produces digests of this kind, if any.
+    pub fn algorithm_for_kind(self, kind: DigestAlgorithmKind) -> Option<DigestAlgorithm> {
+        [self.inner.digest160, self.inner.digest256]
+            .into_iter()
+            .flatten()
+            .find(|algorithm| algorithm.kind() == kind)
+    }
+
     pub fn allows_sha1(self) -> bool {
         self.inner.digest160 == Some(DigestAlgorithm::Sha1)
     }
@@ -19,6 +19,7 @@ use futures::stream::BoxStream;
 use futures::stream::StreamExt;
 use futures::task::Poll;
 use futures::Stream;
+use hyper::Body;
 use hyper::Request;
 use hyper::Response;
 use pin_project::pin_project;
@@ -108,6 +109,15 @@ impl HttpClient for CountingHttpClient {
         Ok(resp)
     }
 
+    async fn request_streaming(
+        &self,
+        request: Request<Body>,
+    ) -> Result<Response<BoxStream<hyper::Result<Bytes>>>, HttpError> {
+        let resp = self.inner.request_streaming(request).await?;
+        let resp = resp.map(|body| CountingStream::new(body, self.bytes_downloaded.dupe()).boxed());
+        Ok(resp)
+    }
+
     fn supports_vpnless(&self) -> bool {
         CountingHttpClient::supports_vpnless(self)
     }
@@ -75,6 +75,41 @@ pub fn http_client(allow_vpnless: bool) -> anyhow::Result<CountingHttpClient> {
 /// Returns a client suitable for OSS usecases. Supports standard Curl-like
 /// proxy environment variables: $HTTP_PROXY, $HTTPS_PROXY.
 pub fn http_client_for_oss() -> anyhow::Result<Arc<dyn HttpClient>> {
+    http_client_for_oss_with_tls_config(tls_config_with_system_roots()?)
+}
+
+/// Like `http_client_for_oss`, but trusts the CA certificates in `ca_certs` instead of the system
+/// roots and authenticates with `client_cert` (a PEM file containing both the certificate and its
+/// private key), when set.
+pub fn http_client_for_oss_with_certs(
+    ca_certs: Option<&Path>,
+    client_cert: Option<&Path>,
+) -> anyhow::Result<Arc<dyn HttpClient>> {
+    let roots = match ca_certs {
+        Some(ca_certs) => load_root_certs(ca_certs)?,
+        None => load_system_root_certs()?,
+    };
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+    let config = match client_cert {
+        Some(client_cert) => {
+            let (cert, key) = load_cert_pair(client_cert, client_cert)?;
+            // TODO: replace with_single_cert with with_client_auth_cert
+            //       once rustls get upgraded to >0.21.4
+            #[allow(deprecated)]
+            config
+                .with_single_cert(cert, key)
+                .context("Error creating TLS config with client certificate")?
+        }
+        None => config.with_no_client_auth(),
+    };
+    http_client_for_oss_with_tls_config(config)
+}
+
+fn http_client_for_oss_with_tls_config(
+    config: ClientConfig,
+) -> anyhow::Result<Arc<dyn HttpClient>> {
     // Add standard proxy variables if defined.
     // Ignores values that cannot be turned into valid URIs.
     let mut proxies = Vec::new();
@@ -86,9 +121,10 @@ pub fn http_client_for_oss() -> anyhow::Result<Arc<dyn HttpClient>> {
     }
 
     if !proxies.is_empty() {
-        Ok(Arc::new(SecureProxiedClient::with_proxies(proxies)?))
+        Ok(Arc::new(SecureProxiedClient::with_proxies(
+            proxies, config,
+        )?))
     } else {
-        let config = tls_config_with_system_roots()?;
         Ok(Arc::new(SecureHttpClient::new(
             config,
             DEFAULT_MAX_REDIRECTS,
@@ -203,6 +239,23 @@ fn load_system_root_certs() -> anyhow::Result<RootCertStore> {
     Ok(roots)
 }
 
+/// Load the PEM-encoded CA certificates at `path` into a rustls cert store.
+fn load_root_certs(path: &Path) -> anyhow::Result<RootCertStore> {
+    let file = File::open(path)
+        .with_context(|| format!("opening CA certificates `{}`", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(&file))
+        .with_context(|| format!("reading CA certificates from `{}`", path.display()))?;
+
+    let mut roots = RootCertStore::empty();
+    let (valid, _invalid) = roots.add_parsable_certificates(certs.as_slice());
+    anyhow::ensure!(
+        valid > 0,
+        "Error loading CA certificates: no valid certificates in `{}`",
+        path.display()
+    );
+    Ok(roots)
+}
+
 /// Deserialize certificate pair at `cert` and `key` into structures that can
 /// be inserted into rustls CertStore.
 fn load_cert_pair<P: AsRef<Path>>(
@@ -354,6 +407,17 @@ pub trait HttpClient: Allocative + Send + Sync {
         request: Request<Bytes>,
     ) -> Result<Response<BoxStream<hyper::Result<Bytes>>>, HttpError>;
 
+    /// Send a request whose body is streamed, e.g. from a file. Redirects aren't followed, since
+    /// the body can't be sent again. Clients that can't stream bodies read it all first.
+    async fn request_streaming(
+        &self,
+        request: Request<Body>,
+    ) -> Result<Response<BoxStream<hyper::Result<Bytes>>>, HttpError> {
+        let (parts, body) = request.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        self.request(Request::from_parts(parts, body)).await
+    }
+
     /// Whether this client supports vpnless operation. When set, will make requests
     /// to the `vpnless_url` attribute in the `download_file` action rather than the
     /// normal `url` attribute.
@@ -369,6 +433,8 @@ pub trait HttpClient: Allocative + Send + Sync {
 /// out the concrete type without exposing implementation details to callers.
 trait RequestClient: Send + Sync {
     fn request(&self, request: Request<Bytes>) -> ResponseFuture;
+
+    fn request_streaming(&self, request: Request<Body>) -> ResponseFuture;
 }
 
 impl<C> RequestClient for hyper::Client<C>
@@ -378,6 +444,10 @@ where
     fn request(&self, request: Request<Bytes>) -> ResponseFuture {
         self.request(request.map(Body::from))
     }
+
+    fn request_streaming(&self, request: Request<Body>) -> ResponseFuture {
+        self.request(request)
+    }
 }
 
 /// Http client used for unit testing; errors on any calls to underlying http methods.
@@ -73,6 +73,19 @@ impl SecureHttpClient {
     }
 }
 
+/// Turns unsuccessful responses into errors.
+async fn check_status(
+    uri: String,
+    resp: Response<BoxStream<'_, hyper::Result<Bytes>>>,
+) -> Result<Response<BoxStream<'_, hyper::Result<Bytes>>>, HttpError> {
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = read_truncated_error_response(resp).await;
+        return Err(HttpError::Status { status, uri, text });
+    }
+    Ok(resp)
+}
+
 #[async_trait::async_trait]
 impl HttpClient for SecureHttpClient {
     async fn request(
@@ -91,13 +104,24 @@ impl HttpClient for SecureHttpClient {
             .handle_redirects(|req| self.send_request_impl(req))
             .await?;
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = read_truncated_error_response(resp).await;
-            return Err(HttpError::Status { status, uri, text });
-        }
+        check_status(uri, resp).await
+    }
+
+    async fn request_streaming(
+        &self,
+        request: Request<Body>,
+    ) -> Result<Response<BoxStream<hyper::Result<Bytes>>>, HttpError> {
+        let uri = request.uri().to_string();
+        tracing::debug!("http: streaming request: {:?}", request);
+        let resp = self
+            .inner
+            .request_streaming(request)
+            .await
+            .map_err(HttpError::SendRequest)?
+            .map(|body| body.boxed());
+        tracing::debug!("http: response: {:?}", resp.status());
 
-        Ok(resp)
+        check_status(uri, resp).await
     }
 }
 
@@ -12,15 +12,16 @@ use std::sync::Arc;
 use allocative::Allocative;
 use bytes::Bytes;
 use futures::stream::BoxStream;
+use hyper::Body;
 use hyper::Request;
 use hyper::Response;
 use hyper_proxy::Proxy;
 use hyper_proxy::ProxyConnector;
 use hyper_rustls::HttpsConnectorBuilder;
+use rustls::ClientConfig;
 use tokio_rustls::TlsConnector;
 
 use crate::http::secure_client::SecureHttpClient;
-use crate::http::tls_config_with_system_roots;
 use crate::http::HttpClient;
 use crate::http::HttpError;
 use crate::http::DEFAULT_MAX_REDIRECTS;
@@ -31,9 +32,10 @@ pub(super) struct SecureProxiedClient {
 }
 
 impl SecureProxiedClient {
-    pub(super) fn with_proxies<I: IntoIterator<Item = Proxy>>(proxies: I) -> anyhow::Result<Self> {
-        let config = tls_config_with_system_roots()?;
-
+    pub(super) fn with_proxies<I: IntoIterator<Item = Proxy>>(
+        proxies: I,
+        config: ClientConfig,
+    ) -> anyhow::Result<Self> {
         // This connector establishes a secure connection from client -> dest
         let https_connector = HttpsConnectorBuilder::new()
             .with_tls_config(config.clone())
@@ -68,6 +70,13 @@ impl HttpClient for SecureProxiedClient {
     ) -> Result<Response<BoxStream<hyper::Result<Bytes>>>, HttpError> {
         self.inner.request(request).await
     }
+
+    async fn request_streaming(
+        &self,
+        request: Request<Body>,
+    ) -> Result<Response<BoxStream<hyper::Result<Bytes>>>, HttpError> {
+        self.inner.request_streaming(request).await
+    }
 }
 
 #[cfg(test)]
@@ -80,6 +89,7 @@ mod tests {
     use hyper::Body;
 
     use super::*;
+    use crate::http::tls_config_with_system_roots;
 
     /// Barebones proxy server implementation that simply forwards requests onto
     /// the destination server.
@@ -161,10 +171,13 @@ mod tests {
         let proxy_server = ProxyServer::new().await?;
         println!("proxy_server uri: {}", proxy_server.uri()?);
 
-        let client = SecureProxiedClient::with_proxies([Proxy::new(
-            hyper_proxy::Intercept::Http,
-            proxy_server.uri()?,
-        )])?;
+        let client = SecureProxiedClient::with_proxies(
+            [Proxy::new(
+                hyper_proxy::Intercept::Http,
+                proxy_server.uri()?,
+            )],
+            tls_config_with_system_roots()?,
+        )?;
         let resp = client.get(&test_server.url_str("/foo")).await?;
         assert_eq!(200, resp.status().as_u16());
 
@@ -188,10 +201,13 @@ mod tests {
         let authority = proxy_server.uri()?.authority().unwrap().clone();
         let proxy_uri = format!("{}:{}", authority.host(), authority.port().unwrap());
         println!("proxy_uri: {}", proxy_uri);
-        let client = SecureProxiedClient::with_proxies([Proxy::new(
-            hyper_proxy::Intercept::Http,
-            crate::http::proxy::DefaultSchemeUri(proxy_uri.try_into()?).into(),
-        )])?;
+        let client = SecureProxiedClient::with_proxies(
+            [Proxy::new(
+                hyper_proxy::Intercept::Http,
+                crate::http::proxy::DefaultSchemeUri(proxy_uri.try_into()?).into(),
+            )],
+            tls_config_with_system_roots()?,
+        )?;
         let resp = client.get(&test_server.url_str("/foo")).await?;
         assert_eq!(200, resp.status().as_u16());
 
@@ -221,10 +237,13 @@ mod tests {
         let no_proxy = crate::http::proxy::NoProxy::new(http::uri::Scheme::HTTP, test_server_host);
 
         // Don't proxy connections to test_server.
-        let client = SecureProxiedClient::with_proxies([Proxy::new(
-            no_proxy.into_proxy_intercept(),
-            proxy_server.uri()?,
-        )])?;
+        let client = SecureProxiedClient::with_proxies(
+            [Proxy::new(
+                no_proxy.into_proxy_intercept(),
+                proxy_server.uri()?,
+            )],
+            tls_config_with_system_roots()?,
+        )?;
         let resp = client.get(&test_server.url_str("/foo")).await?;
         assert_eq!(200, resp.status().as_u16());
 
@@ -249,10 +268,13 @@ mod tests {
         // Don't proxy HTTPS connections to *.foobar.com
         let no_proxy = crate::http::proxy::NoProxy::new(http::uri::Scheme::HTTP, ".foobar.com");
 
-        let client = SecureProxiedClient::with_proxies([Proxy::new(
-            no_proxy.into_proxy_intercept(),
-            proxy_server.uri()?,
-        )])?;
+        let client = SecureProxiedClient::with_proxies(
+            [Proxy::new(
+                no_proxy.into_proxy_intercept(),
+                proxy_server.uri()?,
+            )],
+            tls_config_with_system_roots()?,
+        )?;
         let resp = client.get(&test_server.url_str("/foo")).await?;
         assert_eq!(200, resp.status().as_u16());
 
@@ -21,6 +21,7 @@ use hyper_proxy::Intercept;
 use hyper_proxy::Proxy;
 use hyper_proxy::ProxyConnector;
 
+use crate::http::tls_config_with_system_roots;
 use crate::http::HttpClient;
 use crate::http::HttpError;
 use crate::http::SecureHttpClient;
@@ -49,7 +50,7 @@ impl X2PAgentProxyClient {
                 .try_into()
                 .context("Error converting x2pagent proxy address into URI")?,
         );
-        let client = SecureProxiedClient::with_proxies([proxy])?;
+        let client = SecureProxiedClient::with_proxies([proxy], tls_config_with_system_roots()?)?;
         Ok(Self { inner: client })
     }
 
@@ -435,6 +435,8 @@ pub fn display_executor_stage(
                 Stage::WorkerExecute(_) => "worker_execute",
                 Stage::WorkerQueued(..) => "worker_queued",
                 Stage::WorkerWait(_) => "initialize_worker",
+                Stage::ActionCacheQuery(_) => "local_action_cache",
+                Stage::ActionCacheHit(_) => "local_action_cache_materialize",
             }
         }
     };
@@ -476,6 +478,7 @@ pub fn format_test_result(test_result: &buck2_data::TestResult) -> anyhow::Resul
         status,
         duration,
         details,
+        cached,
         ..
     } = test_result;
     let status = TestStatus::try_from(*status)?;
@@ -493,11 +496,13 @@ pub fn format_test_result(test_result: &buck2_data::TestResult) -> anyhow::Resul
         TestStatus::OMITTED => Span::new_styled("\u{20E0} Omitted".to_owned().cyan()),
         TestStatus::FATAL => Span::new_styled("⚠ Fatal".to_owned().red()),
         TestStatus::TIMEOUT => Span::new_styled("✉ Timeout".to_owned().cyan()),
+        TestStatus::PASS if *cached => Span::new_styled("✓ Pass (cached)".to_owned().green()),
         TestStatus::PASS => Span::new_styled("✓ Pass".to_owned().green()),
         TestStatus::LISTING_SUCCESS => Span::new_styled("✓ Listing success".to_owned().green()),
         TestStatus::UNKNOWN => Span::new_styled("? Unknown".to_owned().cyan()),
         TestStatus::RERUN => Span::new_styled("↻ Rerun".to_owned().cyan()),
         TestStatus::LISTING_FAILED => Span::new_styled("⚠ Listing failed".to_owned().red()),
+        TestStatus::FLAKY => Span::new_styled("~ Flaky".to_owned().yellow()),
     }?;
     let mut base = Line::from_iter([prefix, Span::new_unstyled(format!(": {}", name,))?]);
     if let Some(duration) = duration {
@@ -574,6 +579,7 @@ fn failure_reason_for_command_execution(
     use buck2_data::command_execution::Cancelled;
     use buck2_data::command_execution::Error;
     use buck2_data::command_execution::Failure;
+    use buck2_data::command_execution::OomKilled;
     use buck2_data::command_execution::Status;
     use buck2_data::command_execution::Success;
     use buck2_data::command_execution::Timeout;
@@ -589,12 +595,19 @@ fn failure_reason_for_command_execution(
         .as_ref()
         .context("CommandExecution did not include a `status`")?;
 
-    let locality = match command.command {
-        Some(Command::RemoteCommand(..)) => "Remote ",
-        Some(Command::LocalCommand(..)) | Some(Command::OmittedLocalCommand(..)) => "Local ",
-        Some(Command::WorkerInitCommand(..)) => "Local Worker Initialization ",
-        Some(Command::WorkerCommand(..)) => "Local Worker ",
-        None => "",
+    let locality = match &command.command {
+        Some(Command::RemoteCommand(..)) => Cow::Borrowed("Remote "),
+        Some(Command::LocalCommand(..)) | Some(Command::OmittedLocalCommand(..)) => {
+            Cow::Borrowed("Local ")
+        }
+        Some(Command::WorkerInitCommand(..)) => Cow::Borrowed("Local Worker Initialization "),
+        Some(Command::WorkerCommand(worker)) if worker.worker_instance != 0 => Cow::Owned(format!(
+            "Local Worker (instance {}) ",
+            worker.worker_instance
+        )),
+        Some(Command::WorkerCommand(..)) => Cow::Borrowed("Local Worker "),
+        Some(Command::LocalActionCacheCommand(..)) => Cow::Borrowed("Local Cache "),
+        None => Cow::Borrowed(""),
     };
 
     Ok(match status {
@@ -634,6 +647,13 @@ fn failure_reason_for_command_execution(
             format!("Internal error (stage: {}): {}", stage, error)
         }
         Status::Cancelled(Cancelled {}) => "Command was cancelled".to_owned(),
+        Status::OomKilled(OomKilled { memory_max }) => match memory_max {
+            Some(memory_max) => format!(
+                "{}command was killed for exceeding its memory limit of {} bytes",
+                locality, memory_max
+            ),
+            None => format!("{}command was killed for running out of memory", locality),
+        },
     })
 }
 
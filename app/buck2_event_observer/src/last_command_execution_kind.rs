@@ -39,7 +39,8 @@ pub fn get_last_command_execution_kind(
         }
         Some(Command::RemoteCommand(buck2_data::RemoteCommand {
             cache_hit: true, ..
-        })) => LastCommandExecutionKind::Cached,
+        }))
+        | Some(Command::LocalActionCacheCommand(..)) => LastCommandExecutionKind::Cached,
         Some(Command::RemoteCommand(buck2_data::RemoteCommand {
             cache_hit: false, ..
         })) => LastCommandExecutionKind::Remote,
@@ -7,8 +7,17 @@
  * of this source tree.
  */
 
+use std::time::Duration;
+
 use buck2_test_api::data::TestStatus;
 
+use crate::display::display_configured_target_label;
+use crate::display::TargetDisplayOptions;
+
+/// How much of the output of a test case we keep in a `TestReport` (the end of it, which is
+/// usually where the failure is).
+const MAX_REPORT_DETAILS_BYTES: usize = 64 * 1024;
+
 #[derive(Default)]
 pub struct TestState {
     pub discovered: u64,
@@ -22,12 +31,16 @@ pub struct TestState {
     pub unknown: u64,
     pub listing_success: u64,
     pub listing_failed: u64,
+    pub flaky: u64,
+    /// Passes served by the test cache, which aren't counted in `pass`.
+    pub cached: u64,
 }
 
 impl TestState {
     pub(crate) fn update(&mut self, result: &buck2_data::TestResult) -> anyhow::Result<()> {
         let status = TestStatus::try_from(result.status)?;
         let counter = match status {
+            TestStatus::PASS if result.cached => &mut self.cached,
             TestStatus::PASS => &mut self.pass,
             TestStatus::FAIL => &mut self.fail,
             TestStatus::FATAL => &mut self.fatal,
@@ -38,6 +51,7 @@ impl TestState {
             TestStatus::RERUN => &mut self.retry,
             TestStatus::LISTING_SUCCESS => &mut self.listing_success,
             TestStatus::LISTING_FAILED => &mut self.listing_failed,
+            TestStatus::FLAKY => &mut self.flaky,
         };
         *counter += 1;
 
@@ -48,3 +62,84 @@ impl TestState {
         self.skipped + self.omitted
     }
 }
+
+/// A test case in a `TestReport`.
+pub struct TestCaseReport {
+    pub name: String,
+    /// The target the test case belongs to, if known.
+    pub target: Option<String>,
+    pub configuration: Option<String>,
+    pub status: TestStatus,
+    pub msg: Option<String>,
+    pub duration: Option<Duration>,
+    /// The end of the output of the test case.
+    pub details: String,
+}
+
+/// The results of the tests of a command, in the order they were reported, to produce test
+/// reports.
+#[derive(Default)]
+pub struct TestReport {
+    pub test_cases: Vec<TestCaseReport>,
+}
+
+impl TestReport {
+    pub fn update(&mut self, result: &buck2_data::TestResult) -> anyhow::Result<()> {
+        let status = TestStatus::try_from(result.status)?;
+        // Attempts that were retried are superseded by the result of the last attempt.
+        if status == TestStatus::RERUN {
+            return Ok(());
+        }
+
+        let (target, configuration) = match &result.target_label {
+            Some(label) => (
+                Some(display_configured_target_label(
+                    label,
+                    TargetDisplayOptions::for_console(false),
+                )?),
+                label.configuration.as_ref().map(|c| c.full_name.clone()),
+            ),
+            None => (None, None),
+        };
+
+        self.test_cases.push(TestCaseReport {
+            name: result.name.clone(),
+            target,
+            configuration,
+            status,
+            msg: result.msg.as_ref().map(|m| m.msg.clone()),
+            duration: result
+                .duration
+                .as_ref()
+                .and_then(|d| Duration::try_from(d.clone()).ok()),
+            details: tail(&result.details, MAX_REPORT_DETAILS_BYTES).to_owned(),
+        });
+
+        Ok(())
+    }
+}
+
+/// Returns the last `max` bytes (at most) of `s`.
+fn tail(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut start = s.len() - max;
+    while !s.is_char_boundary(start) {
+        start += 1;
+    }
+    &s[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail() {
+        assert_eq!("abc", tail("abc", 5));
+        assert_eq!("bc", tail("abc", 2));
+        // Doesn't split characters.
+        assert_eq!("c", tail("\u{e9}c", 2));
+    }
+}
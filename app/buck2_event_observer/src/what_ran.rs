@@ -185,7 +185,12 @@ impl<'a> CommandReproducer<'a> {
             Self::CacheHit(..) => "cache".to_owned(),
             Self::ReExecute(execute) => executor_with_platform(execute),
             Self::LocalExecute(..) => "local".to_owned(),
-            Self::WorkerExecute(..) => "worker".to_owned(),
+            Self::WorkerExecute(execute) => match &execute.command {
+                Some(command) if command.worker_instance != 0 => {
+                    format!("worker#{}", command.worker_instance)
+                }
+                _ => "worker".to_owned(),
+            },
             Self::WorkerInit(..) => "worker_init".to_owned(),
         }
     }
@@ -20,6 +20,9 @@ pub struct CacheUploadInfo<'a> {
     pub target: &'a dyn CommandExecutionTarget,
     pub action_digest: ActionDigest,
     pub digest_config: DigestConfig,
+    /// Whether the action allows its result to be uploaded to a shared cache. Caches that are
+    /// private to this machine may store the result regardless.
+    pub allow_cache_upload: bool,
 }
 
 pub struct DepFileEntry {
@@ -55,7 +55,12 @@ pub enum CommandExecutionKind {
         command: Vec<String>,
         env: SortedVectorMap<String, String>,
         fallback_exe: Vec<String>,
+        /// The worker instance that ran the command.
+        worker_instance: u64,
     },
+    /// This action was served by the local on-disk action cache and not executed.
+    #[display(fmt = "local_action_cache")]
+    LocalActionCache { digest: ActionDigest },
 }
 
 impl CommandExecutionKind {
@@ -67,6 +72,7 @@ impl CommandExecutionKind {
             }
             Self::Remote { .. } => buck2_data::ActionExecutionKind::Remote,
             Self::ActionCache { .. } => buck2_data::ActionExecutionKind::ActionCache,
+            Self::LocalActionCache { .. } => buck2_data::ActionExecutionKind::LocalActionCache,
         }
     }
 
@@ -76,7 +82,8 @@ impl CommandExecutionKind {
             + From<buck2_data::RemoteCommand>
             + From<buck2_data::OmittedLocalCommand>
             + From<buck2_data::WorkerInitCommand>
-            + From<buck2_data::WorkerCommand>,
+            + From<buck2_data::WorkerCommand>
+            + From<buck2_data::LocalActionCacheCommand>,
     {
         match self {
             Self::Local {
@@ -137,6 +144,7 @@ impl CommandExecutionKind {
                 env,
                 digest,
                 fallback_exe,
+                worker_instance,
             } => buck2_data::WorkerCommand {
                 action_digest: digest.to_string(),
                 argv: command.to_owned(),
@@ -148,6 +156,11 @@ impl CommandExecutionKind {
                     })
                     .collect(),
                 fallback_exe: fallback_exe.to_owned(),
+                worker_instance: *worker_instance,
+            }
+            .into(),
+            Self::LocalActionCache { digest } => buck2_data::LocalActionCacheCommand {
+                action_digest: digest.to_string(),
             }
             .into(),
         }
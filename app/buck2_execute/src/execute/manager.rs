@@ -100,6 +100,7 @@ impl CommandExecutionManagerLike for CommandExecutionManager {
                 timing,
                 std_streams,
                 exit_code,
+                undeclared_inputs: Vec::new(),
             },
             rejected_execution: None,
             did_cache_upload: false,
@@ -162,6 +163,7 @@ impl CommandExecutionManagerLike for CommandExecutionManagerWithClaim {
                 timing,
                 std_streams,
                 exit_code,
+                undeclared_inputs: Vec::new(),
             },
             rejected_execution: None,
             did_cache_upload: false,
@@ -188,6 +190,15 @@ pub trait CommandExecutionManagerExt: Sized {
         timing: CommandExecutionMetadata,
     ) -> CommandExecutionResult;
 
+    fn oom_killed(
+        self,
+        execution_kind: CommandExecutionKind,
+        memory_max: Option<u64>,
+        std_streams: CommandStdStreams,
+        exit_code: Option<i32>,
+        timing: CommandExecutionMetadata,
+    ) -> CommandExecutionResult;
+
     fn error(self, stage: &'static str, error: impl Into<anyhow::Error>) -> CommandExecutionResult;
 }
 
@@ -231,6 +242,26 @@ where
         )
     }
 
+    fn oom_killed(
+        self,
+        execution_kind: CommandExecutionKind,
+        memory_max: Option<u64>,
+        std_streams: CommandStdStreams,
+        exit_code: Option<i32>,
+        timing: CommandExecutionMetadata,
+    ) -> CommandExecutionResult {
+        self.result(
+            CommandExecutionStatus::OomKilled {
+                execution_kind,
+                memory_max,
+            },
+            IndexMap::new(),
+            std_streams,
+            exit_code,
+            timing,
+        )
+    }
+
     fn error(self, stage: &'static str, error: impl Into<anyhow::Error>) -> CommandExecutionResult {
         self.result(
             CommandExecutionStatus::Error {
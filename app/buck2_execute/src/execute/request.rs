@@ -8,6 +8,7 @@
  */
 
 use std::fmt::Display;
+use std::str::FromStr;
 use std::time::Duration;
 
 use allocative::Allocative;
@@ -29,6 +30,7 @@ use derive_more::Display;
 use dupe::Dupe;
 use gazebo::variants::UnpackVariants;
 use host_sharing::host_sharing::HostSharingRequirements;
+use host_sharing::ResourceRequirements;
 use indexmap::IndexSet;
 use itertools::Itertools;
 use sorted_vector_map::SortedVectorMap;
@@ -250,11 +252,70 @@ impl CommandExecutionPaths {
 #[derive(Copy, Clone, Dupe, Debug, Display, Allocative, Hash, PartialEq, Eq)]
 pub struct WorkerId(pub u64);
 
+/// The protocol used to send commands to a worker.
+#[derive(
+    Copy, Clone, Dupe, Debug, Default, Display, Allocative, Hash, PartialEq, Eq
+)]
+pub enum WorkerProtocol {
+    /// Buck2's own gRPC protocol (`buck2_worker_proto`), over a unix domain socket.
+    #[default]
+    #[display(fmt = "buck2")]
+    Buck2,
+    /// Bazel's persistent worker protocol, with length-delimited `WorkRequest` / `WorkResponse`
+    /// protobuf messages over stdin / stdout.
+    #[display(fmt = "bazel_proto")]
+    BazelProto,
+    /// Bazel's persistent worker protocol, with newline-delimited JSON messages over stdin /
+    /// stdout.
+    #[display(fmt = "bazel_json")]
+    BazelJson,
+}
+
+impl FromStr for WorkerProtocol {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "buck2" => Ok(WorkerProtocol::Buck2),
+            "bazel_proto" => Ok(WorkerProtocol::BazelProto),
+            "bazel_json" => Ok(WorkerProtocol::BazelJson),
+            _ => Err(anyhow::anyhow!(
+                "Invalid worker protocol: `{}` (expected `buck2`, `bazel_proto` or `bazel_json`)",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct WorkerSpec {
     pub id: WorkerId,
     pub exe: Vec<String>,
     pub concurrency: Option<usize>,
+    pub protocol: WorkerProtocol,
+    /// Whether the worker accepts concurrent requests. Only used by Bazel workers, which are
+    /// otherwise sent one request at a time.
+    pub supports_multiplex: bool,
+}
+
+/// Resource limits for a command that runs locally. Those are enforced using cgroups by the
+/// forkserver, when it's able to.
+#[derive(Copy, Clone, Dupe, Debug, Default, PartialEq, Eq, Allocative)]
+pub struct LocalResourceLimits {
+    /// Maximum memory usage in bytes (cgroup `memory.max`).
+    pub memory_max: Option<u64>,
+    /// Maximum CPU usage in thousandths of a CPU (cgroup `cpu.max`).
+    pub cpu_max_millis: Option<u64>,
+}
+
+impl LocalResourceLimits {
+    /// Limits set in `other` take precedence over ours.
+    pub fn overridden_by(self, other: LocalResourceLimits) -> Self {
+        Self {
+            memory_max: other.memory_max.or(self.memory_max),
+            cpu_max_millis: other.cpu_max_millis.or(self.cpu_max_millis),
+        }
+    }
 }
 
 /// The data contains the information about the command to be executed.
@@ -266,10 +327,15 @@ pub struct CommandExecutionRequest {
     paths: CommandExecutionPaths,
     env: SortedVectorMap<String, String>,
     timeout: Option<Duration>,
+    /// When running locally, how long the command may take to exit after it's asked to on
+    /// timeout, before it's killed.
+    termination_grace_period: Option<Duration>,
     executor_preference: ExecutorPreference,
     // Run with a custom $TMPDIR, or just the standard system one
     custom_tmpdir: Option<BuckOutScratchPath>,
     host_sharing_requirements: HostSharingRequirements,
+    /// Resources this command needs when it runs locally, used for admission control.
+    resource_requirements: ResourceRequirements,
     // Used to disable the low pass filter for concurrent local actions. Enabled by default
     low_pass_filter: bool,
     /// Working directory, relative to the project root.
@@ -293,6 +359,8 @@ pub struct CommandExecutionRequest {
     /// Whether the executor should guarantee that the inodes for all inputs are unique (i.e. avoid
     /// hardlinking identical input files, for example)
     unique_input_inodes: bool,
+    /// Limits to apply when running locally.
+    local_resource_limits: LocalResourceLimits,
 }
 
 impl CommandExecutionRequest {
@@ -308,9 +376,11 @@ impl CommandExecutionRequest {
             paths,
             env,
             timeout: None,
+            termination_grace_period: None,
             executor_preference: ExecutorPreference::Default,
             custom_tmpdir: None,
             host_sharing_requirements: HostSharingRequirements::default(),
+            resource_requirements: ResourceRequirements::default(),
             low_pass_filter: true,
             working_directory: None,
             prefetch_lossy_stderr: false,
@@ -321,6 +391,7 @@ impl CommandExecutionRequest {
             required_local_resources: SortedSet::new(),
             worker: None,
             unique_input_inodes: false,
+            local_resource_limits: LocalResourceLimits::default(),
         }
     }
 
@@ -333,6 +404,11 @@ impl CommandExecutionRequest {
         self
     }
 
+    pub fn with_termination_grace_period(mut self, termination_grace_period: Duration) -> Self {
+        self.termination_grace_period = Some(termination_grace_period);
+        self
+    }
+
     pub fn with_executor_preference(mut self, executor_preference: ExecutorPreference) -> Self {
         self.executor_preference = executor_preference;
         self
@@ -355,6 +431,14 @@ impl CommandExecutionRequest {
         self
     }
 
+    pub fn with_resource_requirements(
+        mut self,
+        resource_requirements: ResourceRequirements,
+    ) -> Self {
+        self.resource_requirements = resource_requirements;
+        self
+    }
+
     pub fn with_low_pass_filter(mut self, low_pass_filter: bool) -> Self {
         self.low_pass_filter = low_pass_filter;
         self
@@ -428,6 +512,10 @@ impl CommandExecutionRequest {
         self.timeout
     }
 
+    pub fn termination_grace_period(&self) -> Option<Duration> {
+        self.termination_grace_period
+    }
+
     pub fn executor_preference(&self) -> ExecutorPreference {
         self.executor_preference
     }
@@ -436,6 +524,10 @@ impl CommandExecutionRequest {
         &self.host_sharing_requirements
     }
 
+    pub fn resource_requirements(&self) -> &ResourceRequirements {
+        &self.resource_requirements
+    }
+
     pub fn low_pass_filter(&self) -> bool {
         self.low_pass_filter
     }
@@ -500,6 +592,18 @@ impl CommandExecutionRequest {
     pub fn unique_input_inodes(&self) -> bool {
         self.unique_input_inodes
     }
+
+    pub fn with_local_resource_limits(
+        mut self,
+        local_resource_limits: LocalResourceLimits,
+    ) -> Self {
+        self.local_resource_limits = local_resource_limits;
+        self
+    }
+
+    pub fn local_resource_limits(&self) -> LocalResourceLimits {
+        self.local_resource_limits
+    }
 }
 
 /// Is an output a file or a directory
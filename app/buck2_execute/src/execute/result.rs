@@ -43,6 +43,11 @@ pub enum CommandExecutionStatus {
         execution_kind: CommandExecutionKind,
         duration: Duration,
     },
+    /// The command was killed because it exceeded its memory limit.
+    OomKilled {
+        execution_kind: CommandExecutionKind,
+        memory_max: Option<u64>,
+    },
     // TODO: We should rename this.
     Cancelled,
 }
@@ -54,6 +59,7 @@ impl CommandExecutionStatus {
             CommandExecutionStatus::Failure { execution_kind } => Some(execution_kind),
             CommandExecutionStatus::Error { .. } => None,
             CommandExecutionStatus::TimedOut { execution_kind, .. } => Some(execution_kind),
+            CommandExecutionStatus::OomKilled { execution_kind, .. } => Some(execution_kind),
             CommandExecutionStatus::Cancelled => None,
         }
     }
@@ -74,6 +80,12 @@ impl Display for CommandExecutionStatus {
             CommandExecutionStatus::TimedOut { duration, .. } => {
                 write!(f, "timed out after {:.3}s", duration.as_secs_f64())
             }
+            CommandExecutionStatus::OomKilled { memory_max, .. } => match memory_max {
+                Some(memory_max) => {
+                    write!(f, "killed for exceeding {} bytes of memory", memory_max)
+                }
+                None => write!(f, "killed for running out of memory"),
+            },
             CommandExecutionStatus::Cancelled => write!(f, "Cancelled"),
         }
     }
@@ -157,6 +169,9 @@ pub struct CommandExecutionReport {
     /// No exit_code means the command did not finish executing. Signals get mapped into this as
     /// 128 + SIGNUM, which is the convention shells follow.
     pub exit_code: Option<i32>,
+    /// Project-relative paths of files that the command read without declaring them as inputs.
+    /// Only populated when file accesses are traced.
+    pub undeclared_inputs: Vec<String>,
 }
 
 /// Implement FromResidual so that it's easier to refactor functions returning a CommandExecutionResult
@@ -17,4 +17,26 @@ pub struct ExecutorGlobalKnobs {
     /// Whether to emit action keys to execution logs (thos are pretty verbose and omitted by
     /// default).
     pub log_action_keys: bool,
+
+    /// Whether locally executed actions may be written to the action cache, for executors whose
+    /// configuration allows cache uploads. This lets e.g. developer machines opt out of writing
+    /// to a cache that CI populates.
+    pub allow_cache_uploads: bool,
+
+    /// Whether to run local actions (other than workers) in a filesystem sandbox that only
+    /// exposes their declared inputs. Only supported on Linux, via the forkserver.
+    pub sandbox_local_actions: bool,
+
+    /// Whether sandboxed local actions should also be denied network access.
+    pub sandbox_block_network: bool,
+
+    /// Whether to trace the files that local actions access, and report those that were not
+    /// declared as inputs. This is a diagnostic mode that slows down actions noticeably. Actions
+    /// that run in workers are not traced.
+    pub trace_undeclared_inputs: bool,
+
+    /// Whether to run each local action in its own cgroup, to enforce resource limits and
+    /// report resource usage. Actions with resource limits always ask for a cgroup, but the
+    /// daemon only sets up cgroups if this was set when it started.
+    pub local_action_cgroups: bool,
 }
@@ -22,6 +22,7 @@ pub mod directory;
 pub mod entry;
 pub mod execute;
 pub mod knobs;
+pub mod local_action_cache;
 pub mod materialize;
 pub mod output_size;
 pub mod path;
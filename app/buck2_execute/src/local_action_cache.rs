@@ -0,0 +1,898 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! An on-disk action cache for machines that don't have a remote cache available.
+//!
+//! The cache lives in a single directory, laid out as follows:
+//!
+//! - `db.sqlite` indexes action results by action digest, and records the entries each result
+//!   consists of.
+//! - `cas/` holds the contents of output files, named after their digest. A blob is shared by all
+//!   the action results that reference it.
+//! - `tmp/` is used to stage blobs before they are moved into `cas/`. Blobs are checked against
+//!   their digest before they are moved, and again when they are restored.
+//!
+//! Eviction is LRU on action results: once the blobs exceed the size limit, the least recently
+//! used action results are dropped along with the blobs that nothing references anymore.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::Context;
+use buck2_common::cas_digest::DigestAlgorithmKind;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileMetadata;
+use buck2_common::file_ops::TrackedFileDigest;
+use buck2_core::directory::DirectoryEntry;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use parking_lot::Mutex;
+use parking_lot::RwLock;
+use rusqlite::Connection;
+use rusqlite::OpenFlags;
+use rusqlite::OptionalExtension;
+
+use crate::digest_config::DigestConfig;
+use crate::directory::ActionDirectoryMember;
+use crate::directory::Symlink;
+use crate::execute::action_digest::ActionDigest;
+
+/// Hand-maintained schema version for the local action cache. Bump this when making a breaking
+/// change to the layout of the cache: caches with a different version are wiped on open.
+const SCHEMA_VERSION: i64 = 1;
+
+/// Number of action results evicted between two passes of blob garbage collection.
+const EVICTION_BATCH_SIZE: i64 = 64;
+
+/// Makes the names of staged blobs unique, so that concurrent inserts of the same blob don't
+/// write to the same temporary file.
+static NEXT_STAGED_BLOB: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, thiserror::Error)]
+enum LocalActionCacheError {
+    #[error("Local action cache at `{0}` has schema version {1}, expected {2}")]
+    SchemaVersion(AbsNormPathBuf, i64, i64),
+    #[error("Blob `{0}` does not match its digest (got `{1}`)")]
+    DigestMismatch(String, FileDigest),
+    #[error("Digest algorithm `{0}` is not enabled")]
+    DigestAlgorithm(DigestAlgorithmKind),
+}
+
+/// An entry in a cached action result. Directories carry no data: their contents are recorded as
+/// separate entries. External symlinks are never cached.
+pub type LocalActionCacheEntry = DirectoryEntry<(), ActionDirectoryMember>;
+
+/// The outputs and output streams of a successful action.
+pub struct LocalActionResult {
+    /// All the entries that make up the outputs of the action. This includes directories, so that
+    /// empty directories get restored as well. Parents are always listed before their children.
+    pub entries: Vec<(ProjectRelativePathBuf, LocalActionCacheEntry)>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LocalActionCacheStats {
+    pub actions: u64,
+    pub blobs: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TrimStats {
+    pub actions: u64,
+    pub blobs: u64,
+    pub bytes: u64,
+}
+
+pub struct LocalActionCache {
+    root: AbsNormPathBuf,
+    max_bytes: u64,
+    connection: Mutex<Connection>,
+    /// Held for reading while blobs are copied out of the cache, and for writing while they are
+    /// deleted, so that a trim doesn't pull blobs from under a restore.
+    blobs: RwLock<()>,
+}
+
+impl LocalActionCache {
+    /// Open (or create) the cache at `root`. Results get evicted once the cache holds more than
+    /// `max_bytes` worth of blobs.
+    pub fn open(root: AbsNormPathBuf, max_bytes: u64) -> anyhow::Result<Arc<Self>> {
+        Self::open_impl(root, max_bytes).context("Error opening local action cache")
+    }
+
+    /// Open an existing cache to inspect or trim it, e.g. from `buck2 debug local-action-cache`.
+    /// Unlike `open`, this never discards the cache or cleans up `tmp/`, since a daemon may be
+    /// using it. Unless `writable` is set, the cache is opened read-only.
+    pub fn open_existing(root: AbsNormPathBuf, writable: bool) -> anyhow::Result<Arc<Self>> {
+        Self::open_existing_impl(root, writable).context("Error opening local action cache")
+    }
+
+    fn open_existing_impl(root: AbsNormPathBuf, writable: bool) -> anyhow::Result<Arc<Self>> {
+        let db_path = Self::db_path(&root);
+        let connection = if writable {
+            Self::connect(&db_path)?
+        } else {
+            let connection = Connection::open_with_flags(
+                &db_path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )?;
+            connection.busy_timeout(std::time::Duration::from_secs(10))?;
+            connection
+        };
+
+        let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            return Err(LocalActionCacheError::SchemaVersion(root, version, SCHEMA_VERSION).into());
+        }
+
+        Ok(Arc::new(Self {
+            root,
+            // The size limit only matters for inserts, which nothing does with this cache.
+            max_bytes: u64::MAX,
+            connection: Mutex::new(connection),
+            blobs: RwLock::new(()),
+        }))
+    }
+
+    fn open_impl(root: AbsNormPathBuf, max_bytes: u64) -> anyhow::Result<Arc<Self>> {
+        fs_util::create_dir_all(&root)?;
+
+        let db_path = Self::db_path(&root);
+        let mut connection = Self::connect(&db_path)?;
+
+        let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            if version != 0 {
+                tracing::info!(
+                    "Local action cache version changed ({} -> {}), discarding it",
+                    version,
+                    SCHEMA_VERSION
+                );
+            }
+            drop(connection);
+            fs_util::remove_dir_all(&root)?;
+            fs_util::create_dir_all(&root)?;
+            connection = Self::connect(&db_path)?;
+            Self::create_tables(&connection)?;
+            connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+
+        let cache = Self {
+            root,
+            max_bytes,
+            connection: Mutex::new(connection),
+            blobs: RwLock::new(()),
+        };
+
+        // Anything left in tmp was staged by a daemon that died before it could finish.
+        let tmp = cache.tmp_dir();
+        if fs_util::try_exists(&tmp)? {
+            fs_util::remove_dir_all(&tmp)?;
+        }
+        fs_util::create_dir_all(&tmp)?;
+        fs_util::create_dir_all(cache.cas_dir())?;
+
+        Ok(Arc::new(cache))
+    }
+
+    fn db_path(root: &AbsNormPath) -> AbsNormPathBuf {
+        root.join(ForwardRelativePath::unchecked_new("db.sqlite"))
+    }
+
+    fn connect(path: &AbsNormPath) -> anyhow::Result<Connection> {
+        let connection = Connection::open(path)?;
+        // The cache can be trimmed by `buck2 debug local-action-cache` while the daemon is
+        // using it.
+        connection.busy_timeout(std::time::Duration::from_secs(10))?;
+        if cfg!(unix) {
+            connection.pragma_update(None, "journal_mode", "WAL")?;
+        }
+        // Like the materializer state, this is a cache: losing it on power loss is fine, and we'd
+        // rather not `fsync` in the middle of a build.
+        connection.pragma_update(None, "synchronous", "OFF")?;
+        Ok(connection)
+    }
+
+    fn create_tables(connection: &Connection) -> anyhow::Result<()> {
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS actions (
+                    action_digest   TEXT PRIMARY KEY NOT NULL,
+                    stdout          BLOB NOT NULL,
+                    stderr          BLOB NOT NULL,
+                    last_access     INTEGER NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS actions_last_access ON actions (last_access);
+                CREATE TABLE IF NOT EXISTS entries (
+                    action_digest   TEXT NOT NULL,
+                    path            TEXT NOT NULL,
+                    entry_type      TEXT NOT NULL,
+                    blob            TEXT NULL DEFAULT NULL,
+                    digest_hash     BLOB NULL DEFAULT NULL,
+                    digest_kind     INTEGER NULL DEFAULT NULL,
+                    digest_size     INTEGER NULL DEFAULT NULL,
+                    is_executable   INTEGER NULL DEFAULT NULL,
+                    symlink_target  TEXT NULL DEFAULT NULL,
+                    PRIMARY KEY (action_digest, path)
+                );
+                CREATE INDEX IF NOT EXISTS entries_blob ON entries (blob);
+                CREATE TABLE IF NOT EXISTS blobs (
+                    name            TEXT PRIMARY KEY NOT NULL,
+                    size            INTEGER NOT NULL
+                );",
+            )
+            .context("Error creating local action cache tables")
+    }
+
+    pub fn root(&self) -> &AbsNormPath {
+        &self.root
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    fn cas_dir(&self) -> AbsNormPathBuf {
+        self.root.join(ForwardRelativePath::unchecked_new("cas"))
+    }
+
+    fn tmp_dir(&self) -> AbsNormPathBuf {
+        self.root.join(ForwardRelativePath::unchecked_new("tmp"))
+    }
+
+    fn blob_name(digest: &FileDigest) -> String {
+        format!("{}_{}", digest.raw_digest(), digest.size())
+    }
+
+    fn blob_path(&self, name: &str) -> AbsNormPathBuf {
+        // Shard by the first two characters of the hash to keep directories small.
+        let mut path = self.cas_dir();
+        path.push(ForwardRelativePath::new(&name[..2]).unwrap());
+        path.push(ForwardRelativePath::new(name).unwrap());
+        path
+    }
+
+    /// Look up the result of an action. A hit bumps the result's last access time.
+    pub fn get(
+        &self,
+        action_digest: &ActionDigest,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<Option<LocalActionResult>> {
+        let key = action_digest.to_string();
+        let connection = self.connection.lock();
+
+        let streams = connection
+            .query_row(
+                "SELECT stdout, stderr FROM actions WHERE action_digest = ?1",
+                [&key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .context("Error reading from local action cache")?;
+
+        let (stdout, stderr) = match streams {
+            Some(streams) => streams,
+            None => return Ok(None),
+        };
+
+        let mut stmt = connection.prepare(
+            "SELECT path, entry_type, blob, digest_hash, digest_kind, digest_size, is_executable, symlink_target
+            FROM entries WHERE action_digest = ?1 ORDER BY path",
+        )?;
+
+        let mut entries = Vec::new();
+        let mut missing_blob = false;
+        let mut rows = stmt.query([&key])?;
+        while let Some(row) = rows.next()? {
+            let path: String = row.get(0)?;
+            let entry_type: String = row.get(1)?;
+
+            let entry = match entry_type.as_str() {
+                "directory" => DirectoryEntry::Dir(()),
+                "file" => {
+                    let blob: String = row.get(2)?;
+                    if !fs_util::try_exists(self.blob_path(&blob))? {
+                        missing_blob = true;
+                        break;
+                    }
+                    let hash: Vec<u8> = row.get(3)?;
+                    let kind: u8 = row.get(4)?;
+                    let size: u64 = row.get(5)?;
+                    let kind = kind
+                        .try_into()
+                        .with_context(|| format!("Invalid digest_kind: `{}`", kind))?;
+                    let digest = FileDigest::from_digest_bytes(kind, &hash, size)?;
+                    DirectoryEntry::Leaf(ActionDirectoryMember::File(FileMetadata {
+                        digest: TrackedFileDigest::new(digest, digest_config.cas_digest_config()),
+                        is_executable: row.get(6)?,
+                    }))
+                }
+                "symlink" => {
+                    let target: String = row.get(7)?;
+                    DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(Arc::new(Symlink::new(
+                        target.into(),
+                    ))))
+                }
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "Invalid entry type in local action cache: `{}`",
+                        other
+                    ));
+                }
+            };
+
+            entries.push((ProjectRelativePathBuf::try_from(path)?, entry));
+        }
+        drop(rows);
+        drop(stmt);
+
+        if missing_blob {
+            // The blob was deleted from under us, this result is unusable.
+            Self::delete_actions(&connection, &[key])?;
+            return Ok(None);
+        }
+
+        connection.execute(
+            "UPDATE actions SET last_access = ?1 WHERE action_digest = ?2",
+            rusqlite::params![now_millis(), key],
+        )?;
+
+        Ok(Some(LocalActionResult {
+            entries,
+            stdout,
+            stderr,
+        }))
+    }
+
+    /// Write the entries of a result returned by `get` to disk. Output paths are expected to have
+    /// been cleaned up already. Fails if a blob doesn't match its digest anymore, in which case the
+    /// blob is deleted so that the results referencing it get dropped.
+    pub fn restore(
+        &self,
+        result: &LocalActionResult,
+        fs: &ProjectRoot,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<()> {
+        let _guard = self.blobs.read();
+
+        for (path, entry) in &result.entries {
+            let dest = fs.resolve(path);
+            if let DirectoryEntry::Leaf(_) = entry {
+                if let Some(parent) = dest.parent() {
+                    fs_util::create_dir_all(parent)?;
+                }
+            }
+
+            match entry {
+                DirectoryEntry::Dir(()) => fs_util::create_dir_all(&dest)?,
+                DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) => {
+                    let blob_path = self.blob_path(&Self::blob_name(f.digest.data()));
+                    fs_util::copy(&blob_path, &dest)?;
+                    // Check the copy rather than the blob, so we know what we restored is right.
+                    if let Err(e) = verify_blob(&dest, f.digest.data(), digest_config) {
+                        fs_util::remove_file(&blob_path)?;
+                        return Err(e.context(format!("Error restoring `{}`", path)));
+                    }
+                    if f.is_executable {
+                        fs_util::set_executable(&dest)?;
+                    }
+                }
+                DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(s)) => {
+                    fs_util::symlink(s.target().as_str(), &dest)?;
+                }
+                DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(_)) => {
+                    return Err(anyhow::anyhow!(
+                        "External symlink in local action cache at `{}`",
+                        path
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Store the result of an action, copying its output files from `fs`. Returns false if the
+    /// result cannot be cached.
+    pub fn insert(
+        &self,
+        action_digest: &ActionDigest,
+        result: &LocalActionResult,
+        fs: &ProjectRoot,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<bool> {
+        if result.entries.iter().any(|(_, entry)| {
+            matches!(
+                entry,
+                DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(_))
+            )
+        }) {
+            return Ok(false);
+        }
+
+        // Copy the blobs first, outside of the lock. A blob that exists on disk but isn't in the
+        // db yet is harmless: it'll get recorded below, or overwritten by the next insert.
+        let mut blobs = Vec::new();
+        for (path, entry) in &result.entries {
+            if let DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) = entry {
+                let name = Self::blob_name(f.digest.data());
+                let blob_path = self.blob_path(&name);
+                if !fs_util::try_exists(&blob_path)? {
+                    self.stage_blob(
+                        &fs.resolve(path),
+                        f.digest.data(),
+                        &blob_path,
+                        digest_config,
+                    )
+                    .with_context(|| format!("Error storing output `{}`", path))?;
+                }
+                blobs.push((name, f.digest.size()));
+            }
+        }
+
+        let key = action_digest.to_string();
+        let total_bytes = {
+            let mut connection = self.connection.lock();
+            let tx = connection.transaction()?;
+
+            Self::delete_actions(&tx, &[key.clone()])?;
+            tx.execute(
+                "INSERT INTO actions (action_digest, stdout, stderr, last_access) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![key, result.stdout, result.stderr, now_millis()],
+            )?;
+
+            for (name, size) in &blobs {
+                tx.execute(
+                    "INSERT OR IGNORE INTO blobs (name, size) VALUES (?1, ?2)",
+                    rusqlite::params![name, size],
+                )?;
+            }
+
+            for (path, entry) in &result.entries {
+                let (entry_type, blob, hash, kind, size, is_executable, target) = match entry {
+                    DirectoryEntry::Dir(()) => ("directory", None, None, None, None, None, None),
+                    DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) => (
+                        "file",
+                        Some(Self::blob_name(f.digest.data())),
+                        Some(f.digest.raw_digest().as_bytes().to_vec()),
+                        Some(f.digest.raw_digest().algorithm() as u8),
+                        Some(f.digest.size()),
+                        Some(f.is_executable),
+                        None,
+                    ),
+                    DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(s)) => (
+                        "symlink",
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                        Some(s.target().as_str().to_owned()),
+                    ),
+                    DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(_)) => {
+                        unreachable!("Checked above")
+                    }
+                };
+
+                tx.execute(
+                    "INSERT INTO entries (action_digest, path, entry_type, blob, digest_hash, digest_kind, digest_size, is_executable, symlink_target)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    rusqlite::params![
+                        key,
+                        path.as_str(),
+                        entry_type,
+                        blob,
+                        hash,
+                        kind,
+                        size,
+                        is_executable,
+                        target
+                    ],
+                )?;
+            }
+
+            tx.commit()?;
+            Self::total_bytes(&connection)?
+        };
+
+        if total_bytes > self.max_bytes {
+            self.trim(self.max_bytes)?;
+        }
+
+        Ok(true)
+    }
+
+    fn stage_blob(
+        &self,
+        src: &AbsNormPath,
+        digest: &FileDigest,
+        blob_path: &AbsNormPath,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<()> {
+        let tmp = self.tmp_dir().join(
+            ForwardRelativePath::new(&format!(
+                "{}.{}.{}",
+                Self::blob_name(digest),
+                std::process::id(),
+                NEXT_STAGED_BLOB.fetch_add(1, Ordering::Relaxed)
+            ))
+            .unwrap(),
+        );
+        fs_util::copy(src, &tmp)?;
+
+        // The output may have been modified since the action ran, don't cache it under the wrong
+        // digest.
+        if let Err(e) = verify_blob(&tmp, digest, digest_config) {
+            fs_util::remove_file(&tmp)?;
+            return Err(e);
+        }
+
+        // Permissions are restored from the metadata, so don't let them vary between copies.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs_util::set_permissions(&tmp, std::fs::Permissions::from_mode(0o644))?;
+        }
+
+        if let Some(parent) = blob_path.parent() {
+            fs_util::create_dir_all(parent)?;
+        }
+        fs_util::rename(&tmp, blob_path)?;
+        Ok(())
+    }
+
+    /// Drop the result of an action, e.g. because it couldn't be restored.
+    pub fn remove(&self, action_digest: &ActionDigest) -> anyhow::Result<()> {
+        let connection = self.connection.lock();
+        Self::delete_actions(&connection, &[action_digest.to_string()])
+    }
+
+    fn delete_actions(connection: &Connection, keys: &[String]) -> anyhow::Result<()> {
+        for key in keys {
+            connection.execute("DELETE FROM entries WHERE action_digest = ?1", [key])?;
+            connection.execute("DELETE FROM actions WHERE action_digest = ?1", [key])?;
+        }
+        Ok(())
+    }
+
+    fn total_bytes(connection: &Connection) -> anyhow::Result<u64> {
+        Ok(
+            connection.query_row("SELECT COALESCE(SUM(size), 0) FROM blobs", [], |row| {
+                row.get(0)
+            })?,
+        )
+    }
+
+    /// Evict the least recently used action results until the cache holds no more than
+    /// `max_bytes`, and delete blobs that are no longer referenced.
+    pub fn trim(&self, max_bytes: u64) -> anyhow::Result<TrimStats> {
+        let _guard = self.blobs.write();
+        let connection = self.connection.lock();
+        let mut stats = TrimStats::default();
+
+        loop {
+            let mut orphans: Vec<(String, u64)> = Vec::new();
+            {
+                let mut stmt = connection.prepare(
+                    "SELECT name, size FROM blobs WHERE name NOT IN
+                    (SELECT blob FROM entries WHERE blob IS NOT NULL)",
+                )?;
+                for orphan in stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))? {
+                    orphans.push(orphan?);
+                }
+            }
+
+            for (name, size) in orphans {
+                let path = self.blob_path(&name);
+                if fs_util::try_exists(&path)? {
+                    fs_util::remove_file(&path)?;
+                }
+                connection.execute("DELETE FROM blobs WHERE name = ?1", [&name])?;
+                stats.blobs += 1;
+                stats.bytes += size;
+            }
+
+            if Self::total_bytes(&connection)? <= max_bytes {
+                break;
+            }
+
+            let mut victims: Vec<String> = Vec::new();
+            {
+                let mut stmt = connection
+                    .prepare("SELECT action_digest FROM actions ORDER BY last_access LIMIT ?1")?;
+                for victim in stmt.query_map([EVICTION_BATCH_SIZE], |row| row.get(0))? {
+                    victims.push(victim?);
+                }
+            }
+
+            if victims.is_empty() {
+                break;
+            }
+
+            Self::delete_actions(&connection, &victims)?;
+            stats.actions += victims.len() as u64;
+        }
+
+        Ok(stats)
+    }
+
+    pub fn stats(&self) -> anyhow::Result<LocalActionCacheStats> {
+        let connection = self.connection.lock();
+        let count = |table: &str| -> anyhow::Result<u64> {
+            Ok(
+                connection.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                    row.get(0)
+                })?,
+            )
+        };
+        Ok(LocalActionCacheStats {
+            actions: count("actions")?,
+            blobs: count("blobs")?,
+            total_bytes: Self::total_bytes(&connection)?,
+        })
+    }
+}
+
+fn now_millis() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// Check that the contents of the file at `path` match `digest`.
+fn verify_blob(
+    path: &AbsNormPath,
+    digest: &FileDigest,
+    digest_config: DigestConfig,
+) -> anyhow::Result<()> {
+    let kind = digest.raw_digest().algorithm();
+    let algorithm = digest_config
+        .cas_digest_config()
+        .algorithm_for_kind(kind)
+        .ok_or(LocalActionCacheError::DigestAlgorithm(kind))?;
+    let actual = FileDigest::from_reader_for_algorithm(fs_util::open_file(path)?, algorithm)?;
+    if actual != *digest {
+        return Err(LocalActionCacheError::DigestMismatch(
+            LocalActionCache::blob_name(digest),
+            actual,
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::fs::project::ProjectRootTemp;
+    use buck2_core::fs::project_rel_path::ProjectRelativePath;
+
+    use super::*;
+
+    fn file(fs: &ProjectRoot, path: &str, contents: &str) {
+        let path = fs.resolve(ProjectRelativePath::unchecked_new(path));
+        fs_util::create_dir_all(path.parent().unwrap()).unwrap();
+        fs_util::write(&path, contents).unwrap();
+    }
+
+    fn file_entry(contents: &str, digest_config: DigestConfig) -> LocalActionCacheEntry {
+        DirectoryEntry::Leaf(ActionDirectoryMember::File(FileMetadata {
+            digest: TrackedFileDigest::from_content(
+                contents.as_bytes(),
+                digest_config.cas_digest_config(),
+            ),
+            is_executable: false,
+        }))
+    }
+
+    fn result(entries: Vec<(&str, LocalActionCacheEntry)>) -> LocalActionResult {
+        LocalActionResult {
+            entries: entries
+                .into_iter()
+                .map(|(p, e)| (ProjectRelativePathBuf::unchecked_new(p.to_owned()), e))
+                .collect(),
+            stdout: b"out".to_vec(),
+            stderr: Vec::new(),
+        }
+    }
+
+    fn action(digest_config: DigestConfig, s: &str) -> ActionDigest {
+        ActionDigest::from_content(s.as_bytes(), digest_config.cas_digest_config())
+    }
+
+    #[test]
+    fn test_insert_get_restore() -> anyhow::Result<()> {
+        let digest_config = DigestConfig::testing_default();
+        let fs = ProjectRootTemp::new()?;
+        let cache_dir = ProjectRootTemp::new()?;
+        let cache = LocalActionCache::open(cache_dir.path().root().to_buf(), 1 << 20)?;
+
+        file(fs.path(), "out/a", "aaa");
+        let res = result(vec![
+            ("out", DirectoryEntry::Dir(())),
+            ("out/a", file_entry("aaa", digest_config)),
+            ("out/empty", DirectoryEntry::Dir(())),
+        ]);
+
+        let digest = action(digest_config, "action");
+        assert!(cache.get(&digest, digest_config)?.is_none());
+        assert!(cache.insert(&digest, &res, fs.path(), digest_config)?);
+
+        let hit = cache.get(&digest, digest_config)?.unwrap();
+        assert_eq!(hit.stdout, b"out");
+        assert_eq!(
+            hit.entries
+                .iter()
+                .map(|(p, _)| p.as_str())
+                .collect::<Vec<_>>(),
+            vec!["out", "out/a", "out/empty"]
+        );
+
+        let restored = ProjectRootTemp::new()?;
+        cache.restore(&hit, restored.path(), digest_config)?;
+        assert_eq!(
+            fs_util::read_to_string(
+                restored
+                    .path()
+                    .resolve(ProjectRelativePath::unchecked_new("out/a"))
+            )?,
+            "aaa"
+        );
+        assert!(fs_util::try_exists(
+            restored
+                .path()
+                .resolve(ProjectRelativePath::unchecked_new("out/empty"))
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trim() -> anyhow::Result<()> {
+        let digest_config = DigestConfig::testing_default();
+        let fs = ProjectRootTemp::new()?;
+        let cache_dir = ProjectRootTemp::new()?;
+        let cache = LocalActionCache::open(cache_dir.path().root().to_buf(), 1 << 20)?;
+
+        file(fs.path(), "a", "aaa");
+        file(fs.path(), "b", "bbbb");
+        cache.insert(
+            &action(digest_config, "a"),
+            &result(vec![("a", file_entry("aaa", digest_config))]),
+            fs.path(),
+            digest_config,
+        )?;
+        cache.insert(
+            &action(digest_config, "b"),
+            &result(vec![("b", file_entry("bbbb", digest_config))]),
+            fs.path(),
+            digest_config,
+        )?;
+
+        assert_eq!(
+            cache.stats()?,
+            LocalActionCacheStats {
+                actions: 2,
+                blobs: 2,
+                total_bytes: 7,
+            }
+        );
+
+        let stats = cache.trim(0)?;
+        assert_eq!(
+            stats,
+            TrimStats {
+                actions: 2,
+                blobs: 2,
+                bytes: 7,
+            }
+        );
+        assert_eq!(cache.stats()?, LocalActionCacheStats::default());
+        assert!(
+            cache
+                .get(&action(digest_config, "a"), digest_config)?
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_modified_output() -> anyhow::Result<()> {
+        let digest_config = DigestConfig::testing_default();
+        let fs = ProjectRootTemp::new()?;
+        let cache_dir = ProjectRootTemp::new()?;
+        let cache = LocalActionCache::open(cache_dir.path().root().to_buf(), 1 << 20)?;
+
+        file(fs.path(), "a", "modified");
+        let digest = action(digest_config, "a");
+        assert!(
+            cache
+                .insert(
+                    &digest,
+                    &result(vec![("a", file_entry("aaa", digest_config))]),
+                    fs.path(),
+                    digest_config,
+                )
+                .is_err()
+        );
+        assert!(cache.get(&digest, digest_config)?.is_none());
+        assert_eq!(fs_util::read_dir(cache.tmp_dir())?.count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_corrupt_blob() -> anyhow::Result<()> {
+        let digest_config = DigestConfig::testing_default();
+        let fs = ProjectRootTemp::new()?;
+        let cache_dir = ProjectRootTemp::new()?;
+        let cache = LocalActionCache::open(cache_dir.path().root().to_buf(), 1 << 20)?;
+
+        file(fs.path(), "a", "aaa");
+        let entry = file_entry("aaa", digest_config);
+        let digest = action(digest_config, "a");
+        cache.insert(
+            &digest,
+            &result(vec![("a", entry.clone())]),
+            fs.path(),
+            digest_config,
+        )?;
+
+        let DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) = &entry else {
+            unreachable!()
+        };
+        let blob_path = cache.blob_path(&LocalActionCache::blob_name(f.digest.data()));
+        fs_util::write(&blob_path, "corrupt")?;
+
+        let hit = cache.get(&digest, digest_config)?.unwrap();
+        let restored = ProjectRootTemp::new()?;
+        assert!(cache.restore(&hit, restored.path(), digest_config).is_err());
+        assert!(!fs_util::try_exists(&blob_path)?);
+
+        cache.remove(&digest)?;
+        assert!(cache.get(&digest, digest_config)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_existing() -> anyhow::Result<()> {
+        let digest_config = DigestConfig::testing_default();
+        let fs = ProjectRootTemp::new()?;
+        let cache_dir = ProjectRootTemp::new()?;
+        let root = cache_dir.path().root().to_buf();
+
+        assert!(LocalActionCache::open_existing(root.clone(), false).is_err());
+
+        let cache = LocalActionCache::open(root.clone(), 1 << 20)?;
+        file(fs.path(), "a", "aaa");
+        cache.insert(
+            &action(digest_config, "a"),
+            &result(vec![("a", file_entry("aaa", digest_config))]),
+            fs.path(),
+            digest_config,
+        )?;
+        let staged = cache
+            .tmp_dir()
+            .join(ForwardRelativePath::unchecked_new("staged"));
+        fs_util::write(&staged, "")?;
+
+        // Inspecting the cache must not disturb a daemon using it.
+        let existing = LocalActionCache::open_existing(root, false)?;
+        assert_eq!(existing.stats()?.actions, 1);
+        assert!(existing.trim(0).is_err());
+        assert!(fs_util::try_exists(&staged)?);
+
+        Ok(())
+    }
+}
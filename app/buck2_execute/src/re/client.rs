@@ -317,9 +317,10 @@ impl RemoteExecutionClient {
             .await
     }
 
-    pub async fn upload_blob(
+    pub async fn upload_blob_with_digest(
         &self,
         blob: Vec<u8>,
+        digest: TDigest,
         use_case: RemoteExecutorUseCase,
     ) -> anyhow::Result<TDigest> {
         self.data
@@ -327,7 +328,7 @@ impl RemoteExecutionClient {
             .op(self
                 .data
                 .client
-                .upload_blob(blob, use_case)
+                .upload_blob_with_digest(blob, digest, use_case)
                 .map_err(|e| self.decorate_error(e)))
             .await
     }
@@ -1002,12 +1003,30 @@ impl RemoteExecutionClientImpl {
             .with_context(|| format!("No digest was returned in request for {}", digest))
     }
 
-    pub async fn upload_blob(
+    /// Upload a blob whose digest is already known. This goes through a regular CAS upload so
+    /// that it works regardless of whether the client knows how to hash the blob itself.
+    pub async fn upload_blob_with_digest(
         &self,
         blob: Vec<u8>,
+        digest: TDigest,
         use_case: RemoteExecutorUseCase,
     ) -> anyhow::Result<TDigest> {
-        self.client().upload_blob(blob, use_case.metadata()).await
+        self.client()
+            .get_cas_client()
+            .upload(
+                use_case.metadata(),
+                UploadRequest {
+                    inlined_blobs_with_digest: Some(vec![InlinedBlobWithDigest {
+                        blob,
+                        digest: digest.clone(),
+                        ..Default::default()
+                    }]),
+                    upload_only_missing: true,
+                    ..Default::default()
+                },
+            )
+            .await?;
+        Ok(digest)
     }
 
     async fn materialize_files(
@@ -446,12 +446,17 @@ impl ManagedRemoteExecutionClient {
             .await
     }
 
-    pub async fn upload_blob(
+    pub async fn upload_blob_with_digest(
         &self,
         blob: Vec<u8>,
+        digest: TDigest,
         use_case: RemoteExecutorUseCase,
     ) -> anyhow::Result<TDigest> {
-        self.lock()?.get().await?.upload_blob(blob, use_case).await
+        self.lock()?
+            .get()
+            .await?
+            .upload_blob_with_digest(blob, digest, use_case)
+            .await
     }
 
     pub async fn get_digest_expirations(
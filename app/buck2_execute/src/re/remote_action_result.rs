@@ -160,6 +160,9 @@ fn convert_perf_counts(
             .map(|p| p.adjusted_count()),
         cpu_instructions_kernel: convert_perf_count(&perf_counts.kernel_events)?
             .map(|p| p.adjusted_count()),
+        memory_peak: None,
+        cpu_pressure_stall_us: None,
+        oom_killed: false,
     })
 }
 
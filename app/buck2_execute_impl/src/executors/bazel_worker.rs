@@ -0,0 +1,558 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Transport for workers speaking Bazel's persistent worker protocol: `WorkRequest`s are written
+//! to the worker's stdin and `WorkResponse`s are read from its stdout, either as length-delimited
+//! protobuf messages or as JSON objects.
+//!
+//! Workers are spawned by the forkserver like all other commands, so their stdin and stdout are
+//! FIFOs in the worker directory.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use buck2_common::liveliness_observer::LivelinessObserver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::file_name::FileName;
+use buck2_execute::execute::request::WorkerProtocol;
+use buck2_forkserver::client::ForkserverClient;
+use buck2_forkserver::run::GatherOutputStatus;
+use buck2_worker_proto::bazel::WorkRequest;
+use buck2_worker_proto::bazel::WorkResponse;
+use dupe::Dupe;
+use futures::future::Either;
+use prost::Message;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::io::AsyncBufRead;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::sync::oneshot;
+use tokio::sync::OwnedMutexGuard;
+use tokio::task::JoinError;
+
+use crate::executors::worker::spawn_via_forkserver;
+use crate::executors::worker::WorkerInitError;
+
+#[derive(Copy, Clone, Dupe, Debug, PartialEq, Eq)]
+enum Framing {
+    Proto,
+    Json,
+}
+
+/// JSON encoding of a `WorkRequest`, using the proto3 JSON field names.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonWorkRequest<'a> {
+    arguments: &'a [String],
+    request_id: i32,
+}
+
+/// JSON encoding of a `WorkResponse`. Fields that have their default value may be omitted.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct JsonWorkResponse {
+    exit_code: i32,
+    output: String,
+    request_id: i32,
+    was_cancelled: bool,
+}
+
+impl Framing {
+    fn encode(self, request: &WorkRequest) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Framing::Proto => Ok(request.encode_length_delimited_to_vec()),
+            Framing::Json => {
+                let mut data = serde_json::to_vec(&JsonWorkRequest {
+                    arguments: &request.arguments,
+                    request_id: request.request_id,
+                })?;
+                data.push(b'\n');
+                Ok(data)
+            }
+        }
+    }
+
+    /// Reads the next response, or returns `None` if the stream ended.
+    async fn decode(
+        self,
+        reader: &mut (impl AsyncBufRead + Unpin),
+    ) -> anyhow::Result<Option<WorkResponse>> {
+        match self {
+            Framing::Proto => {
+                let len = match read_varint(reader).await? {
+                    Some(len) => len,
+                    None => return Ok(None),
+                };
+                let mut data = vec![0; len as usize];
+                reader
+                    .read_exact(&mut data)
+                    .await
+                    .context("Error reading WorkResponse")?;
+                Ok(Some(
+                    WorkResponse::decode(data.as_slice()).context("Invalid WorkResponse")?,
+                ))
+            }
+            Framing::Json => {
+                // Workers usually write one response per line, but nothing requires that, so keep
+                // reading until we have a complete object.
+                let mut data = String::new();
+                loop {
+                    if reader.read_line(&mut data).await? == 0 {
+                        if data.trim().is_empty() {
+                            return Ok(None);
+                        }
+                        return Err(anyhow::anyhow!(
+                            "Stream ended in the middle of a WorkResponse: `{}`",
+                            data.trim()
+                        ));
+                    }
+                    if data.trim().is_empty() {
+                        data.clear();
+                        continue;
+                    }
+                    match serde_json::from_str::<JsonWorkResponse>(&data) {
+                        Ok(response) => {
+                            return Ok(Some(WorkResponse {
+                                exit_code: response.exit_code,
+                                output: response.output,
+                                request_id: response.request_id,
+                                was_cancelled: response.was_cancelled,
+                            }));
+                        }
+                        Err(e) if e.is_eof() => continue,
+                        Err(e) => {
+                            return Err(anyhow::Error::new(e)
+                                .context(format!("Invalid WorkResponse: `{}`", data.trim())));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn read_varint(reader: &mut (impl AsyncBufRead + Unpin)) -> anyhow::Result<Option<u64>> {
+    let mut value = 0;
+    for i in 0..10 {
+        let byte = match reader.read_u8().await {
+            Ok(byte) => byte,
+            Err(e) if i == 0 && e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(anyhow::Error::new(e).context("Error reading WorkResponse")),
+        };
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(anyhow::anyhow!("Invalid WorkResponse length"))
+}
+
+struct PendingRequest {
+    sender: oneshot::Sender<WorkResponse>,
+    /// Held until the response arrives for singleplex workers, so that a request that was
+    /// cancelled while in flight doesn't get its response delivered to the next one.
+    _permit: Option<OwnedMutexGuard<()>>,
+}
+
+#[derive(Default)]
+struct State {
+    pending: HashMap<i32, PendingRequest>,
+    /// Set once we stopped reading responses from the worker.
+    failure: Option<String>,
+    /// Bazel workers don't signal that they are ready, so one that exits before it sent its first
+    /// response failed to start.
+    responded: bool,
+    /// Set once the worker exited.
+    exit_code: Option<i32>,
+}
+
+pub(crate) struct BazelWorker {
+    framing: Framing,
+    supports_multiplex: bool,
+    stdin: tokio::sync::Mutex<tokio::fs::File>,
+    state: Arc<parking_lot::Mutex<State>>,
+    singleplex: Arc<tokio::sync::Mutex<()>>,
+    next_request_id: AtomicI32,
+    pid: Option<u32>,
+}
+
+impl BazelWorker {
+    pub(crate) async fn spawn(
+        protocol: WorkerProtocol,
+        supports_multiplex: bool,
+        exe: &[String],
+        env: Vec<(OsString, OsString)>,
+        root: &AbsNormPathBuf,
+        forkserver: ForkserverClient,
+        worker_dir: &AbsNormPathBuf,
+        stderr_path: &AbsNormPathBuf,
+        liveliness_observer: impl LivelinessObserver + 'static,
+    ) -> Result<Self, WorkerInitError> {
+        let framing = match protocol {
+            WorkerProtocol::BazelProto => Framing::Proto,
+            WorkerProtocol::BazelJson => Framing::Json,
+            WorkerProtocol::Buck2 => {
+                return Err(WorkerInitError::InternalError(
+                    anyhow::anyhow!("Not a Bazel worker protocol: `{}`", protocol).into(),
+                ));
+            }
+        };
+
+        let stdin_path = worker_dir.join(FileName::unchecked_new("stdin"));
+        let stdout_path = worker_dir.join(FileName::unchecked_new("stdout"));
+        let (stdin_placeholder, stdout) = open_fifos(&stdin_path, &stdout_path)
+            .map_err(|e| WorkerInitError::InternalError(e.into()))?;
+
+        let mut args: Vec<OsString> = exe[1..].iter().map(OsString::from).collect();
+        // This is how Bazel tells a worker to process requests instead of running a single command.
+        args.push(OsString::from("--persistent_worker"));
+
+        // Dropped to kill the worker when we stop reading its responses.
+        let (kill, killed) = oneshot::channel::<()>();
+        let (exit, pid) = spawn_via_forkserver(
+            forkserver,
+            OsString::from(&exe[0]),
+            args,
+            env,
+            root.clone(),
+            async move {
+                let alive = liveliness_observer.while_alive();
+                futures::pin_mut!(alive);
+                futures::future::select(alive, killed).await;
+            },
+            &stdout_path,
+            stderr_path,
+            Some(&stdin_path),
+        );
+        let mut exit = tokio::spawn(exit);
+
+        let pid = match futures::future::select(pid, &mut exit).await {
+            Either::Left((Ok(pid), _)) => pid,
+            Either::Left((Err(_), exit)) => return Err(spawn_error(exit.await, stderr_path)),
+            Either::Right((status, _)) => return Err(spawn_error(status, stderr_path)),
+        };
+
+        // The worker has its end of stdin open now, so we can open ours for writing only. This
+        // way, writes fail instead of blocking once the worker exits.
+        let stdin = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(&stdin_path)
+            .await
+            .with_context(|| format!("Error opening `{}`", stdin_path))
+            .map_err(|e| WorkerInitError::InternalError(e.into()))?;
+        drop(stdin_placeholder);
+
+        let state = Arc::new(parking_lot::Mutex::new(State::default()));
+
+        tokio::spawn({
+            let state = state.dupe();
+            async move {
+                let failure =
+                    read_responses(framing, tokio::fs::File::from_std(stdout), &state).await;
+                // This kills the worker if it's still running.
+                drop(kill);
+                let exit_code = match exit.await {
+                    Ok(Ok(GatherOutputStatus::Finished { exit_code, .. })) => Some(exit_code),
+                    _ => None,
+                };
+
+                for path in [&stdin_path, &stdout_path] {
+                    if let Err(e) = fs_util::remove_file(path) {
+                        tracing::warn!("Error removing worker FIFO: {:#}", e);
+                    }
+                }
+
+                let mut state = state.lock();
+                state.failure = Some(match exit_code {
+                    Some(exit_code) => format!("{:#} (exit code {})", failure, exit_code),
+                    None => format!("{:#}", failure),
+                });
+                state.exit_code = exit_code;
+                state.pending.clear();
+            }
+        });
+
+        Ok(Self {
+            framing,
+            supports_multiplex,
+            stdin: tokio::sync::Mutex::new(stdin),
+            state,
+            singleplex: Arc::new(tokio::sync::Mutex::new(())),
+            next_request_id: AtomicI32::new(1),
+            pid: Some(pid),
+        })
+    }
+
+    pub(crate) fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    pub(crate) fn has_exited(&self) -> bool {
+        self.state.lock().failure.is_some()
+    }
+
+    /// The exit code of the worker if it exited before it ever responded, which means it failed to
+    /// start.
+    pub(crate) fn startup_failure(&self) -> Option<i32> {
+        let state = self.state.lock();
+        if state.responded {
+            None
+        } else {
+            state.exit_code
+        }
+    }
+
+    /// Sends a `WorkRequest` to the worker and waits for the matching `WorkResponse`.
+    pub(crate) async fn exec(&self, args: &[String]) -> anyhow::Result<WorkResponse> {
+        // Singleplex workers process one request at a time and always use request id 0.
+        let (request_id, permit) = if self.supports_multiplex {
+            (self.next_request_id.fetch_add(1, Ordering::Relaxed), None)
+        } else {
+            (0, Some(self.singleplex.dupe().lock_owned().await))
+        };
+
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut state = self.state.lock();
+            if let Some(failure) = &state.failure {
+                return Err(anyhow::anyhow!("Worker is not running: {}", failure));
+            }
+            state.pending.insert(
+                request_id,
+                PendingRequest {
+                    sender,
+                    _permit: permit,
+                },
+            );
+        }
+
+        let request = WorkRequest {
+            arguments: args.to_vec(),
+            request_id,
+            ..Default::default()
+        };
+
+        if let Err(e) = self.send(&request).await {
+            self.state.lock().pending.remove(&request_id);
+            return Err(e);
+        }
+
+        match receiver.await {
+            Ok(response) => Ok(response),
+            Err(_) => Err(anyhow::anyhow!(
+                "Worker stopped before responding: {}",
+                self.state.lock().failure.as_deref().unwrap_or("unknown")
+            )),
+        }
+    }
+
+    async fn send(&self, request: &WorkRequest) -> anyhow::Result<()> {
+        let data = self.framing.encode(request)?;
+        let mut stdin = self.stdin.lock().await;
+        stdin
+            .write_all(&data)
+            .await
+            .context("Error sending WorkRequest")?;
+        stdin.flush().await.context("Error sending WorkRequest")?;
+        Ok(())
+    }
+}
+
+/// Reads responses until the worker's stdout is closed or invalid, and returns why we stopped.
+async fn read_responses(
+    framing: Framing,
+    stdout: impl AsyncRead + Unpin,
+    state: &parking_lot::Mutex<State>,
+) -> anyhow::Error {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let response = match framing.decode(&mut reader).await {
+            Ok(Some(response)) => response,
+            Ok(None) => return anyhow::anyhow!("Worker closed its stdout"),
+            Err(e) => return e,
+        };
+        let pending = {
+            let mut state = state.lock();
+            state.responded = true;
+            state.pending.remove(&response.request_id)
+        };
+        match pending {
+            Some(pending) => {
+                // The request might have been cancelled in the meantime.
+                let _ignored = pending.sender.send(response);
+            }
+            None => tracing::warn!(
+                "Worker sent a response for unknown request `{}`",
+                response.request_id
+            ),
+        }
+    }
+}
+
+/// Creates the FIFOs for the worker's stdin and stdout. Opening one end of a FIFO blocks until the
+/// other one is opened, so we open ours in a way that doesn't block, which in turn lets the
+/// forkserver open the worker's ends without blocking.
+#[cfg(unix)]
+fn open_fifos(
+    stdin_path: &AbsNormPathBuf,
+    stdout_path: &AbsNormPathBuf,
+) -> anyhow::Result<(std::fs::File, std::fs::File)> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    use nix::fcntl::fcntl;
+    use nix::fcntl::FcntlArg;
+    use nix::fcntl::OFlag;
+    use nix::sys::stat::Mode;
+
+    for path in [stdin_path, stdout_path] {
+        nix::unistd::mkfifo(path.as_path(), Mode::S_IRUSR | Mode::S_IWUSR)
+            .with_context(|| format!("Error creating FIFO `{}`", path))?;
+    }
+
+    // Opening a FIFO for both reading and writing doesn't block. This is replaced by a write-only
+    // file once the worker has opened its end.
+    let stdin = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(stdin_path)
+        .with_context(|| format!("Error opening `{}`", stdin_path))?;
+
+    // Opening a FIFO for reading doesn't block in non-blocking mode, but we want blocking reads.
+    let stdout = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(OFlag::O_NONBLOCK.bits())
+        .open(stdout_path)
+        .with_context(|| format!("Error opening `{}`", stdout_path))?;
+    fcntl(stdout.as_raw_fd(), FcntlArg::F_SETFL(OFlag::empty()))
+        .with_context(|| format!("Error configuring `{}`", stdout_path))?;
+
+    Ok((stdin, stdout))
+}
+
+#[cfg(not(unix))]
+fn open_fifos(
+    _stdin_path: &AbsNormPathBuf,
+    _stdout_path: &AbsNormPathBuf,
+) -> anyhow::Result<(std::fs::File, std::fs::File)> {
+    Err(anyhow::anyhow!("Bazel workers are only supported on Unix"))
+}
+
+/// The error for a worker whose command finished before it was spawned.
+fn spawn_error(
+    status: Result<anyhow::Result<GatherOutputStatus>, JoinError>,
+    stderr_path: &AbsNormPathBuf,
+) -> WorkerInitError {
+    match status {
+        Ok(Ok(GatherOutputStatus::SpawnFailed(e))) => WorkerInitError::SpawnFailed(e),
+        Ok(Ok(GatherOutputStatus::Finished { exit_code, .. })) => {
+            match fs_util::read_to_string(stderr_path) {
+                Ok(stderr) => WorkerInitError::EarlyExit {
+                    exit_code: Some(exit_code),
+                    stdout: String::new(),
+                    stderr,
+                },
+                Err(e) => WorkerInitError::InternalError(e.into()),
+            }
+        }
+        Ok(Ok(GatherOutputStatus::Cancelled | GatherOutputStatus::TimedOut(_))) => {
+            WorkerInitError::InternalError(anyhow::anyhow!("Worker cancelled by buck").into())
+        }
+        Ok(Err(e)) => WorkerInitError::InternalError(e.into()),
+        Err(e) => WorkerInitError::InternalError(anyhow::Error::new(e).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn roundtrip(framing: Framing, data: &[u8]) -> anyhow::Result<Vec<WorkResponse>> {
+        let mut reader = data;
+        let mut responses = Vec::new();
+        while let Some(response) = framing.decode(&mut reader).await? {
+            responses.push(response);
+        }
+        Ok(responses)
+    }
+
+    #[tokio::test]
+    async fn test_proto_framing() -> anyhow::Result<()> {
+        let request = WorkRequest {
+            arguments: vec!["a".to_owned(), "b".to_owned()],
+            request_id: 3,
+            ..Default::default()
+        };
+        let encoded = Framing::Proto.encode(&request)?;
+        assert_eq!(
+            request,
+            WorkRequest::decode_length_delimited(encoded.as_slice())?
+        );
+
+        let response = WorkResponse {
+            exit_code: 1,
+            output: "x".repeat(300),
+            request_id: 3,
+            was_cancelled: false,
+        };
+        let mut data = response.encode_length_delimited_to_vec();
+        data.extend(WorkResponse::default().encode_length_delimited_to_vec());
+        assert_eq!(
+            vec![response, WorkResponse::default()],
+            roundtrip(Framing::Proto, &data).await?
+        );
+
+        assert!(roundtrip(Framing::Proto, &data[..10]).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_json_framing() -> anyhow::Result<()> {
+        let request = WorkRequest {
+            arguments: vec!["a".to_owned()],
+            request_id: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            "{\"arguments\":[\"a\"],\"requestId\":3}\n",
+            String::from_utf8(Framing::Json.encode(&request)?)?
+        );
+
+        let data =
+            "{\"exitCode\":1,\"output\":\"oops\",\"requestId\":3}\n\n{\n  \"requestId\": 4\n}\n";
+        assert_eq!(
+            vec![
+                WorkResponse {
+                    exit_code: 1,
+                    output: "oops".to_owned(),
+                    request_id: 3,
+                    was_cancelled: false,
+                },
+                WorkResponse {
+                    request_id: 4,
+                    ..Default::default()
+                },
+            ],
+            roundtrip(Framing::Json, data.as_bytes()).await?
+        );
+
+        assert!(roundtrip(Framing::Json, b"{\"requestId\":").await.is_err());
+        assert!(roundtrip(Framing::Json, b"not json\n").await.is_err());
+        Ok(())
+    }
+}
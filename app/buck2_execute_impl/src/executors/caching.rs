@@ -271,7 +271,7 @@ impl CacheUploader {
                 .report
                 .std_streams
                 .clone()
-                .into_re(&self.re_client, self.re_use_case)
+                .into_re(&self.re_client, self.re_use_case, digest_config)
                 .await
                 .context("Error accessing std_streams")
         };
@@ -347,6 +347,10 @@ impl UploadCache for CacheUploader {
         res: &CommandExecutionResult,
         _dep_file_entry: Option<DepFileEntry>,
     ) -> anyhow::Result<bool> {
+        if !info.allow_cache_upload {
+            return Ok(false);
+        }
+
         let error_on_cache_upload = match ERROR_ON_CACHE_UPLOAD.get_copied() {
             Ok(r) => r.unwrap_or_default(),
             Err(e) => return Err(e).context("cache_upload"),
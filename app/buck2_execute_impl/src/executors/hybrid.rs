@@ -176,6 +176,18 @@ where
             return remote_result.await;
         }
 
+        // If the command would have to wait for local resources, we'd rather not wait at all.
+        let executor_preference = if !executor_preference.prefers_local()
+            && self
+                .local
+                .host_sharing_broker
+                .is_saturated(command.request.resource_requirements())
+        {
+            ExecutorPreference::RemotePreferred
+        } else {
+            executor_preference
+        };
+
         let jobs = HybridExecutorJobs {
             local: local_result.map(|r| (r, JobPriority(1))),
             remote: remote_result.map(|r| (r, JobPriority(0))),
@@ -227,6 +239,8 @@ where
                 CommandExecutionStatus::Success { .. } => false,
                 // Retry commands that failed (i.e. exit 1) only if we're instructed to do so.
                 CommandExecutionStatus::Failure { .. } => fallback_on_failure,
+                // The command might well fit in the memory available on the other side.
+                CommandExecutionStatus::OomKilled { .. } => fallback_on_failure,
                 // Don't retry timeouts. They are used for tests and falling back on a timeout is
                 // sort of the opposite of what's been requested.
                 CommandExecutionStatus::TimedOut { .. } => false,
@@ -25,8 +25,11 @@ use buck2_common::liveliness_observer::LivelinessObserverExt;
 use buck2_common::local_resource_state::LocalResourceHolder;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
 use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use buck2_core::fs::paths::abs_path::AbsPath;
+use buck2_core::fs::paths::file_name::FileName;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_core::tag_error;
 use buck2_core::tag_result;
@@ -55,6 +58,7 @@ use buck2_execute::execute::request::CommandExecutionOutput;
 use buck2_execute::execute::request::CommandExecutionOutputRef;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::request::LocalResourceLimits;
 use buck2_execute::execute::result::CommandExecutionMetadata;
 use buck2_execute::execute::result::CommandExecutionResult;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
@@ -81,6 +85,7 @@ use thiserror::Error;
 use tracing::info;
 
 use crate::executors::worker::WorkerHandle;
+use crate::executors::worker::WorkerLoad;
 use crate::executors::worker::WorkerPool;
 
 #[derive(Debug, Error)]
@@ -139,9 +144,13 @@ impl LocalExecutor {
         env: impl IntoIterator<Item = (impl AsRef<OsStr> + Send, impl AsRef<OsStr> + Send)> + Send + 'a,
         working_directory: Option<&'a ProjectRelativePath>,
         timeout: Option<Duration>,
+        termination_grace_period: Option<Duration>,
         env_inheritance: Option<&'a EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         disable_miniperf: bool,
+        sandbox: Option<&'a buck2_forkserver_proto::Sandbox>,
+        file_access_log: Option<&'a AbsNormPath>,
+        cgroup: Option<buck2_forkserver_proto::CgroupLimits>,
     ) -> impl futures::future::Future<
         Output = anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>,
     > + Send
@@ -163,21 +172,44 @@ impl LocalExecutor {
                             env,
                             &working_directory,
                             timeout,
+                            termination_grace_period,
                             env_inheritance,
                             liveliness_observer,
                             self.knobs.enable_miniperf && !disable_miniperf,
+                            sandbox.cloned(),
+                            file_access_log,
+                            cgroup,
                         )
                         .await
                     }
 
                     #[cfg(not(unix))]
                     {
-                        let _unused = (forkserver, disable_miniperf);
+                        let _unused = (
+                            forkserver,
+                            disable_miniperf,
+                            sandbox,
+                            file_access_log,
+                            cgroup,
+                        );
                         Err(anyhow::anyhow!("Forkserver is not supported off-UNIX"))
                     }
                 }
 
                 None => {
+                    // Resource limits are best-effort, so we don't fail if we can't apply them.
+                    let _unused = cgroup;
+
+                    if sandbox.is_some() {
+                        return Err(anyhow::anyhow!("Sandboxing requires the forkserver"));
+                    }
+
+                    if file_access_log.is_some() {
+                        return Err(anyhow::anyhow!(
+                            "Tracing file accesses requires the forkserver"
+                        ));
+                    }
+
                     let exe = maybe_absolutize_exe(exe, &working_directory)?;
                     let mut cmd = background_command(exe.as_ref());
                     cmd.current_dir(working_directory.as_path());
@@ -197,7 +229,7 @@ impl LocalExecutor {
                     let cancellation =
                         select(timeout.boxed(), alive.boxed()).map(|r| r.factor_first().0);
 
-                    gather_output(cmd, cancellation).await
+                    gather_output(cmd, cancellation, termination_grace_period).await
                 }
                 .with_context(|| format!("Failed to gather output from command: {}", exe)),
             }
@@ -375,23 +407,46 @@ impl LocalExecutor {
         };
         let liveliness_observer = manager.liveliness_observer.dupe().and(cancellation);
 
-        let (worker, manager) = self.initialize_worker(request, manager, dispatcher).await?;
+        let (worker, manager) = self
+            .initialize_worker(request, manager, dispatcher.dupe())
+            .await?;
+
+        let sandbox = match &worker {
+            None => self.sandbox(request, scratch_dir.as_deref()),
+            Some(..) => None,
+        };
+        let sandbox = &sandbox; // So it doesn't move in the block below.
+
+        let file_access_log = match &worker {
+            None => match self.file_access_log(action_digest, &dispatcher, sandbox.is_some()) {
+                Ok(file_access_log) => file_access_log,
+                Err(e) => return manager.error("prepare_file_access_log_failed", e),
+            },
+            Some(..) => None,
+        };
+        let file_access_log = &file_access_log; // So it doesn't move in the block below.
 
-        let execution_kind = match worker {
+        let cgroup = match &worker {
+            None => self.cgroup_limits(request),
+            Some(..) => None,
+        };
+
+        let mut execution_kind = match &worker {
             None => CommandExecutionKind::Local {
                 digest: action_digest.dupe(),
                 command: args.to_vec(),
                 env: request.env().clone(),
             },
-            Some(_) => CommandExecutionKind::LocalWorker {
+            Some((worker, _)) => CommandExecutionKind::LocalWorker {
                 digest: action_digest.dupe(),
                 command: request.args().to_vec(),
                 env: request.env().clone(),
                 fallback_exe: request.exe().to_vec(),
+                worker_instance: worker.instance(),
             },
         };
 
-        let (mut timing, res) = executor_stage_async(
+        let (mut timing, res, worker_instance) = executor_stage_async(
             {
                 let env = iter_env()
                     .map(|(k, v)| buck2_data::EnvironmentEntry {
@@ -399,7 +454,7 @@ impl LocalExecutor {
                         value: v.into_string_lossy(),
                     })
                     .collect();
-                let stage = match worker {
+                let stage = match &worker {
                     None => buck2_data::LocalExecute {
                         command: Some(buck2_data::LocalCommand {
                             action_digest: action_digest.to_string(),
@@ -408,12 +463,13 @@ impl LocalExecutor {
                         }),
                     }
                     .into(),
-                    Some(_) => buck2_data::WorkerExecute {
+                    Some((worker, _)) => buck2_data::WorkerExecute {
                         command: Some(buck2_data::WorkerCommand {
                             action_digest: action_digest.to_string(),
                             argv: request.args().to_vec(),
                             env,
                             fallback_exe: request.exe().to_vec(),
+                            worker_instance: worker.instance(),
                         }),
                     }
                     .into(),
@@ -425,12 +481,16 @@ impl LocalExecutor {
                 let start_time = SystemTime::now();
 
                 let env = iter_env().map(|(k, v)| (k, v.into_os_str()));
-                let r = if let Some(worker) = worker {
+                let mut worker_instance = None;
+                let r = if let Some((worker, _load)) = worker {
                     let env: Vec<(OsString, OsString)> = env
                         .into_iter()
                         .map(|(k, v)| (OsString::from(k), v.to_owned()))
                         .collect();
-                    Ok(worker.exec_cmd(request.args(), env).await)
+                    let (instance, res) =
+                        self.exec_worker_cmd(worker, request, env, dispatcher).await;
+                    worker_instance = Some(instance);
+                    Ok(res)
                 } else {
                     self.exec(
                         &args[0],
@@ -438,9 +498,13 @@ impl LocalExecutor {
                         env,
                         request.working_directory(),
                         request.timeout(),
+                        request.termination_grace_period(),
                         request.local_environment_inheritance(),
                         liveliness_observer,
                         request.disable_miniperf(),
+                        sandbox.as_ref(),
+                        file_access_log.as_deref(),
+                        cgroup,
                     )
                     .await
                 };
@@ -455,19 +519,67 @@ impl LocalExecutor {
                     input_materialization_duration,
                 };
 
-                (timing, r)
+                (timing, r, worker_instance)
             },
         )
         .await;
 
+        if let (
+            Some(instance),
+            CommandExecutionKind::LocalWorker {
+                worker_instance, ..
+            },
+        ) = (worker_instance, &mut execution_kind)
+        {
+            *worker_instance = instance;
+        }
+
         let (status, stdout, stderr) = match res {
             Ok(res) => res,
-            Err(e) => return manager.error("exec_failed", e), // TODO (torozco): Can this take CommandExecutionKind? Should this be a failure?
+            Err(e) => {
+                // The command might have started and logged some accesses before this.
+                if let Some(file_access_log) = file_access_log {
+                    drop(fs_util::remove_file(file_access_log));
+                }
+                return manager.error("exec_failed", e); // TODO (torozco): Can this take CommandExecutionKind? Should this be a failure?
+            }
+        };
+
+        let file_accesses = match file_access_log {
+            Some(file_access_log) => match self.read_file_access_log(file_access_log).await {
+                Ok(log) => log,
+                Err(e) => return manager.error("read_file_access_log_failed", e),
+            },
+            None => Vec::new(),
+        };
+
+        #[cfg(unix)]
+        let stderr = match (sandbox, &status) {
+            (Some(sandbox), GatherOutputStatus::Finished { exit_code, .. }) if *exit_code != 0 => {
+                let mut stderr = stderr;
+                for path in unix::sandbox_violations(&self.root, sandbox, &file_accesses) {
+                    stderr.extend(
+                        format!(
+                            "\nbuck2 sandbox: `{}` was accessed but is not a declared input of this action",
+                            path
+                        )
+                        .into_bytes(),
+                    );
+                }
+                stderr
+            }
+            _ => stderr,
+        };
+
+        let undeclared_inputs = if self.knobs.trace_undeclared_inputs {
+            self.undeclared_inputs(request, scratch_dir.as_deref(), &file_accesses)
+        } else {
+            Vec::new()
         };
 
         let std_streams = CommandStdStreams::Local { stdout, stderr };
 
-        match status {
+        let mut result = match status {
             GatherOutputStatus::Finished {
                 exit_code,
                 execution_stats,
@@ -484,6 +596,14 @@ impl LocalExecutor {
 
                 if exit_code == 0 {
                     manager.success(execution_kind, outputs, std_streams, timing)
+                } else if execution_stats.map_or(false, |s| s.oom_killed) {
+                    manager.oom_killed(
+                        execution_kind,
+                        request.local_resource_limits().memory_max,
+                        std_streams,
+                        Some(exit_code),
+                        timing,
+                    )
                 } else {
                     let manager = check_inputs(
                         manager,
@@ -530,6 +650,125 @@ impl LocalExecutor {
                 manager.timeout(execution_kind, duration, std_streams, timing)
             }
             GatherOutputStatus::Cancelled => manager.cancel_claim(),
+        };
+
+        result.report.undeclared_inputs = undeclared_inputs;
+        result
+    }
+
+    /// The cgroup limits to run this command with, if it should run in a cgroup. This requires the
+    /// forkserver, which ignores this if it can't manage cgroups.
+    fn cgroup_limits(
+        &self,
+        request: &CommandExecutionRequest,
+    ) -> Option<buck2_forkserver_proto::CgroupLimits> {
+        let limits = request.local_resource_limits();
+        if !self.knobs.local_action_cgroups && limits == LocalResourceLimits::default() {
+            return None;
+        }
+        Some(buck2_forkserver_proto::CgroupLimits {
+            memory_max: limits.memory_max,
+            cpu_max_millis: limits.cpu_max_millis,
+        })
+    }
+
+    /// The sandbox to run this command in, if sandboxing is enabled. This requires the forkserver,
+    /// and is only supported on Linux.
+    fn sandbox(
+        &self,
+        request: &CommandExecutionRequest,
+        scratch_dir: Option<&ProjectRelativePath>,
+    ) -> Option<buck2_forkserver_proto::Sandbox> {
+        if !self.knobs.sandbox_local_actions
+            || !cfg!(target_os = "linux")
+            || self.forkserver.is_none()
+        {
+            return None;
+        }
+
+        #[cfg(unix)]
+        {
+            Some(unix::sandbox_spec(
+                &self.root,
+                request,
+                scratch_dir,
+                self.knobs.sandbox_block_network,
+            ))
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _unused = (request, scratch_dir);
+            None
+        }
+    }
+
+    /// Where the forkserver should log the files this command accesses, if we trace them. We do to
+    /// report undeclared inputs, and to report what a sandboxed command was denied. This requires
+    /// the forkserver, and is only supported on Linux (x86_64 and aarch64). Commands sent to
+    /// workers are not traced, since the worker was started before them.
+    fn file_access_log(
+        &self,
+        action_digest: &ActionDigest,
+        dispatcher: &EventDispatcher,
+        sandboxed: bool,
+    ) -> anyhow::Result<Option<AbsNormPathBuf>> {
+        if !(self.knobs.trace_undeclared_inputs || sandboxed)
+            || !cfg!(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))
+            || self.forkserver.is_none()
+        {
+            return Ok(None);
+        }
+
+        let dir = self.artifact_fs.fs().resolve(
+            &self
+                .artifact_fs
+                .buck_out_path_resolver()
+                .root()
+                .join(ForwardRelativePath::unchecked_new("file_access")),
+        );
+        fs_util::create_dir_all(&dir)?;
+        let name = format!("{}-{}", dispatcher.trace_id(), action_digest);
+        Ok(Some(dir.join(FileName::new(&name)?)))
+    }
+
+    /// Reads (and deletes) the log of the files this command accessed. There is no log if the
+    /// command could not be spawned, in which case this is empty so that the spawn error is
+    /// what gets reported.
+    async fn read_file_access_log(&self, file_access_log: &AbsNormPath) -> anyhow::Result<Vec<u8>> {
+        let log = match tokio::fs::read(file_access_log).await {
+            Ok(log) => log,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Error reading `{}`", file_access_log));
+            }
+        };
+        tokio::fs::remove_file(file_access_log)
+            .await
+            .with_context(|| format!("Error removing `{}`", file_access_log))?;
+        Ok(log)
+    }
+
+    /// The files this command accessed, according to its file access log, that are in the project
+    /// but weren't declared as inputs.
+    fn undeclared_inputs(
+        &self,
+        request: &CommandExecutionRequest,
+        scratch_dir: Option<&ProjectRelativePath>,
+        log: &[u8],
+    ) -> Vec<String> {
+        #[cfg(unix)]
+        {
+            unix::undeclared_inputs(&self.root, request, scratch_dir, log)
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _unused = (request, scratch_dir, log);
+            Vec::new()
         }
     }
 
@@ -590,7 +829,10 @@ impl LocalExecutor {
         dispatcher: EventDispatcher,
     ) -> ControlFlow<
         CommandExecutionResult,
-        (Option<Arc<WorkerHandle>>, CommandExecutionManagerWithClaim),
+        (
+            Option<(Arc<WorkerHandle>, WorkerLoad)>,
+            CommandExecutionManagerWithClaim,
+        ),
     > {
         if let (Some(worker_spec), Some(worker_pool), Some(forkserver), true) = (
             request.worker(),
@@ -603,7 +845,7 @@ impl LocalExecutor {
                 .env()
                 .iter()
                 .map(|(k, v)| (OsString::from(k), OsString::from(v)));
-            let (new_worker, worker_fut) = worker_pool.get_or_create_worker(
+            let (new_worker, worker_fut, load) = worker_pool.get_or_create_worker(
                 worker_spec,
                 env,
                 &self.root,
@@ -612,7 +854,7 @@ impl LocalExecutor {
             );
 
             if let Some(Ok(worker)) = worker_fut.peek() {
-                return ControlFlow::Continue((Some(worker.clone()), manager));
+                return ControlFlow::Continue((Some((worker.clone(), load)), manager));
             }
 
             // Might make more sense for the stage to always be `WorkerWait` and for `WorkerInit` to be a separate, top level event
@@ -642,7 +884,7 @@ impl LocalExecutor {
             };
 
             match executor_stage_async(stage, worker_fut).await {
-                Ok(worker) => ControlFlow::Continue((Some(worker), manager)),
+                Ok(worker) => ControlFlow::Continue((Some((worker, load)), manager)),
                 Err(e) => {
                     let res = {
                         let manager = check_inputs(
@@ -662,6 +904,54 @@ impl LocalExecutor {
             ControlFlow::Continue((None, manager))
         }
     }
+
+    /// Runs a command on a worker. If the worker crashes, the command is retried once on a new
+    /// instance rather than failing the action. Returns the instance that ran the command.
+    async fn exec_worker_cmd(
+        &self,
+        worker: Arc<WorkerHandle>,
+        request: &CommandExecutionRequest,
+        env: Vec<(OsString, OsString)>,
+        dispatcher: EventDispatcher,
+    ) -> (u64, (GatherOutputStatus, Vec<u8>, Vec<u8>)) {
+        let res = worker.exec_cmd(request.args(), env.clone()).await;
+        if !matches!(res.0, GatherOutputStatus::SpawnFailed(_)) || !worker.has_exited() {
+            return (worker.instance(), res);
+        }
+
+        let (worker_spec, worker_pool, forkserver) =
+            match (request.worker(), &self.worker_pool, &self.forkserver) {
+                (Some(worker_spec), Some(worker_pool), Some(forkserver)) => {
+                    (worker_spec, worker_pool, forkserver)
+                }
+                _ => return (worker.instance(), res),
+            };
+
+        tracing::warn!(
+            "Worker instance {} crashed, retrying on a new instance",
+            worker.instance()
+        );
+        let (_new_worker, worker_fut, _load) = worker_pool.get_or_create_worker(
+            worker_spec,
+            request
+                .env()
+                .iter()
+                .map(|(k, v)| (OsString::from(k), OsString::from(v))),
+            &self.root,
+            forkserver.dupe(),
+            dispatcher,
+        );
+        match worker_fut.await {
+            Ok(new_worker) => (
+                new_worker.instance(),
+                new_worker.exec_cmd(request.args(), env).await,
+            ),
+            Err(e) => {
+                tracing::warn!("Error restarting worker: {}", e);
+                (worker.instance(), res)
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -709,8 +999,10 @@ impl PreparedCommandExecutor for LocalExecutor {
             buck2_data::LocalStage {
                 stage: Some(buck2_data::LocalQueued {}.into()),
             },
-            self.host_sharing_broker
-                .acquire(request.host_sharing_requirements()),
+            self.host_sharing_broker.acquire_with_resources(
+                request.host_sharing_requirements(),
+                request.resource_requirements(),
+            ),
         )
         .await;
 
@@ -999,8 +1291,16 @@ impl EnvironmentBuilder for Command {
 
 #[cfg(unix)]
 mod unix {
+    use std::collections::BTreeSet;
+    use std::collections::HashSet;
     use std::os::unix::ffi::OsStrExt;
+    use std::path::Component;
+    use std::path::Path;
+    use std::path::PathBuf;
 
+    use buck2_core::directory::DirectoryEntry;
+    use buck2_core::directory::DirectoryIterator;
+    use buck2_core::directory::FingerprintedDirectory;
     use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
 
     use super::*;
@@ -1012,9 +1312,13 @@ mod unix {
         env: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
         working_directory: &AbsPath,
         command_timeout: Option<Duration>,
+        termination_grace_period: Option<Duration>,
         env_inheritance: Option<&EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         enable_miniperf: bool,
+        sandbox: Option<buck2_forkserver_proto::Sandbox>,
+        file_access_log: Option<&AbsNormPath>,
+        cgroup: Option<buck2_forkserver_proto::CgroupLimits>,
     ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)> {
         let exe = exe.as_ref();
 
@@ -1031,6 +1335,10 @@ mod unix {
             timeout: command_timeout.try_map(|d| d.try_into())?,
             enable_miniperf,
             std_redirects: None,
+            sandbox,
+            file_access_log: file_access_log.map(|p| p.as_os_str().as_bytes().to_vec()),
+            cgroup,
+            termination_grace_period: termination_grace_period.try_map(|d| d.try_into())?,
         };
         apply_local_execution_environment(&mut req, working_directory, env, env_inheritance);
         forkserver
@@ -1038,6 +1346,164 @@ mod unix {
             .await
     }
 
+    /// Lists what the command may see in the sandbox: its inputs, and the directories its outputs
+    /// and scratch directory live in, which are writable.
+    pub fn sandbox_spec(
+        root: &AbsNormPath,
+        request: &CommandExecutionRequest,
+        scratch_dir: Option<&ProjectRelativePath>,
+        block_network: bool,
+    ) -> buck2_forkserver_proto::Sandbox {
+        let (inputs, dirs) = input_paths(request);
+        let to_bytes = |paths: Vec<String>| paths.into_iter().map(String::into_bytes).collect();
+
+        buck2_forkserver_proto::Sandbox {
+            project_root: root.as_os_str().as_bytes().to_vec(),
+            inputs: to_bytes(inputs),
+            dirs: to_bytes(dirs),
+            writable_dirs: writable_dirs(request, scratch_dir)
+                .into_iter()
+                .map(|dir| dir.as_str().as_bytes().to_vec())
+                .collect(),
+            block_network,
+        }
+    }
+
+    /// The paths of the inputs of this command, and of the directories that contain them.
+    fn input_paths(request: &CommandExecutionRequest) -> (Vec<String>, Vec<String>) {
+        let mut inputs = Vec::new();
+        let mut dirs = Vec::new();
+
+        let mut walk = request
+            .paths()
+            .input_directory()
+            .fingerprinted_unordered_walk();
+        while let Some((path, entry)) = walk.next() {
+            let path = path.get().as_str().to_owned();
+            match entry {
+                DirectoryEntry::Dir(..) => dirs.push(path),
+                DirectoryEntry::Leaf(..) => inputs.push(path),
+            }
+        }
+
+        (inputs, dirs)
+    }
+
+    /// The directories this command may write to: those its outputs and scratch directory are in.
+    fn writable_dirs<'a>(
+        request: &'a CommandExecutionRequest,
+        scratch_dir: Option<&'a ProjectRelativePath>,
+    ) -> BTreeSet<&'a ProjectRelativePath> {
+        request
+            .paths()
+            .output_paths()
+            .iter()
+            .filter_map(|(path, _)| path.parent())
+            .chain(scratch_dir)
+            .collect()
+    }
+
+    /// The files in the project that a command accessed, given the paths it accessed as logged by
+    /// the forkserver, except those for which `skip` returns true.
+    fn accessed_files(
+        root: &AbsNormPath,
+        log: &[u8],
+        skip: impl Fn(&ProjectRelativePath) -> bool,
+    ) -> BTreeSet<String> {
+        let mut accessed = BTreeSet::new();
+
+        for path in log.split(|c| *c == 0) {
+            if path.is_empty() {
+                continue;
+            }
+
+            // Paths are absolute but not normalized. We don't resolve symlinks here: what matters
+            // is the path the command used.
+            let mut normalized = PathBuf::new();
+            for component in Path::new(OsStr::from_bytes(path)).components() {
+                match component {
+                    Component::ParentDir => {
+                        normalized.pop();
+                    }
+                    Component::CurDir => {}
+                    c => normalized.push(c),
+                }
+            }
+
+            let rel = match normalized
+                .strip_prefix(root.as_path())
+                .ok()
+                .and_then(|rel| rel.to_str())
+                .and_then(|rel| ProjectRelativePath::new(rel).ok())
+            {
+                Some(rel) => rel,
+                None => continue,
+            };
+
+            if rel.is_empty() || accessed.contains(rel.as_str()) || skip(rel) {
+                continue;
+            }
+
+            // Commands probe for many files that don't exist, and list directories, neither of
+            // which makes them depend on anything.
+            match std::fs::symlink_metadata(&normalized) {
+                Ok(metadata) if !metadata.is_dir() => {}
+                _ => continue,
+            }
+
+            accessed.insert(rel.as_str().to_owned());
+        }
+
+        accessed
+    }
+
+    /// Given the paths a command accessed (as logged by the forkserver), returns the files in the
+    /// project that it accessed without declaring them as inputs.
+    pub fn undeclared_inputs(
+        root: &AbsNormPath,
+        request: &CommandExecutionRequest,
+        scratch_dir: Option<&ProjectRelativePath>,
+        log: &[u8],
+    ) -> Vec<String> {
+        let (inputs, dirs) = input_paths(request);
+        let declared: HashSet<String> = inputs.into_iter().chain(dirs).collect();
+        let writable_dirs = writable_dirs(request, scratch_dir);
+
+        accessed_files(root, log, |rel| {
+            declared.contains(rel.as_str()) || writable_dirs.iter().any(|dir| rel.starts_with(dir))
+        })
+        .into_iter()
+        .collect()
+    }
+
+    /// Given the paths a sandboxed command accessed (as logged by the forkserver), returns the
+    /// files in the project that it tried to access but that the sandbox hid.
+    pub fn sandbox_violations(
+        root: &AbsNormPath,
+        sandbox: &buck2_forkserver_proto::Sandbox,
+        log: &[u8],
+    ) -> Vec<String> {
+        const MAX_VIOLATIONS: usize = 10;
+
+        let visible: HashSet<&[u8]> = sandbox
+            .inputs
+            .iter()
+            .chain(&sandbox.dirs)
+            .map(|p| p.as_slice())
+            .collect();
+
+        accessed_files(root, log, |rel| {
+            visible.contains(rel.as_str().as_bytes())
+                || sandbox
+                    .writable_dirs
+                    .iter()
+                    .any(|d| Path::new(rel.as_str()).starts_with(OsStr::from_bytes(d)))
+        })
+        .into_iter()
+        .take(MAX_VIOLATIONS)
+        .collect()
+    }
+
     trait CommandRequestExt {
         fn push_env_directive<D>(&mut self, directive: D)
         where
@@ -1113,7 +1579,7 @@ mod tests {
         };
         cmd.args(["-c", "echo hello"]);
 
-        let (status, stdout, stderr) = gather_output(cmd, futures::future::pending()).await?;
+        let (status, stdout, stderr) = gather_output(cmd, futures::future::pending(), None).await?;
         assert!(matches!(status, GatherOutputStatus::Finished{ exit_code, .. } if exit_code == 0));
         assert_eq!(str::from_utf8(&stdout)?.trim(), "hello");
         assert_eq!(stderr, b"");
@@ -1142,6 +1608,7 @@ mod tests {
         let (status, stdout, stderr) = gather_output(
             cmd,
             timeout_into_cancellation(Some(Duration::from_secs(timeout))),
+            None,
         )
         .await?;
         assert!(
@@ -1170,6 +1637,7 @@ mod tests {
         let (status, stdout, stderr) = gather_output(
             cmd,
             timeout_into_cancellation(Some(Duration::from_secs(timeout))),
+            None,
         )
         .await?;
         assert!(
@@ -1233,8 +1701,12 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
                 NoopLivelinessObserver::create(),
                 false,
+                None,
+                None,
+                None,
             )
             .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
@@ -1268,9 +1740,13 @@ mod tests {
                 &HashMap::<String, String>::default(),
                 None,
                 None,
+                None,
                 Some(&EnvironmentInheritance::empty()),
                 NoopLivelinessObserver::create(),
                 false,
+                None,
+                None,
+                None,
             )
             .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
@@ -1278,4 +1754,43 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sandbox_violations() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let root = temp.path().root();
+        std::fs::create_dir_all(root.as_path().join("src"))?;
+        std::fs::create_dir_all(root.as_path().join("out"))?;
+        for file in ["src/a.h", "src/b.h", "out/c.h"] {
+            std::fs::write(root.as_path().join(file), "")?;
+        }
+
+        let sandbox = buck2_forkserver_proto::Sandbox {
+            project_root: vec![],
+            inputs: vec![b"src/a.h".to_vec()],
+            dirs: vec![b"src".to_vec()],
+            writable_dirs: vec![b"out".to_vec()],
+            block_network: false,
+        };
+        let log = [
+            "src/a.h",
+            "src/b.h",
+            "src/./b.h",
+            "out/c.h",
+            "src/missing.h",
+            "src",
+            "src/../../outside",
+        ]
+        .iter()
+        .map(|path| format!("{}/{}\0", root, path))
+        .collect::<String>();
+
+        assert_eq!(
+            vec!["src/b.h".to_owned()],
+            unix::sandbox_violations(root, &sandbox, log.as_bytes())
+        );
+
+        Ok(())
+    }
 }
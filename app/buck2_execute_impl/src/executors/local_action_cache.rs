@@ -0,0 +1,311 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_core::directory::unordered_entry_walk;
+use buck2_core::directory::DirectoryEntry;
+use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_execute::digest_config::DigestConfig;
+use buck2_execute::directory::extract_artifact_value;
+use buck2_execute::directory::ActionDirectoryMember;
+use buck2_execute::execute::blocking::BlockingExecutor;
+use buck2_execute::execute::blocking::IoRequest;
+use buck2_execute::execute::cache_uploader::CacheUploadInfo;
+use buck2_execute::execute::cache_uploader::DepFileEntry;
+use buck2_execute::execute::cache_uploader::UploadCache;
+use buck2_execute::execute::executor_stage_async;
+use buck2_execute::execute::kind::CommandExecutionKind;
+use buck2_execute::execute::manager::CommandExecutionManager;
+use buck2_execute::execute::manager::CommandExecutionManagerExt;
+use buck2_execute::execute::output::CommandStdStreams;
+use buck2_execute::execute::prepared::PreparedCommand;
+use buck2_execute::execute::prepared::PreparedCommandOptionalExecutor;
+use buck2_execute::execute::request::CommandExecutionOutput;
+use buck2_execute::execute::result::CommandExecutionMetadata;
+use buck2_execute::execute::result::CommandExecutionResult;
+use buck2_execute::execute::result::CommandExecutionStatus;
+use buck2_execute::local_action_cache::LocalActionCache;
+use buck2_execute::local_action_cache::LocalActionResult;
+use buck2_execute::materialize::materializer::Materializer;
+use dupe::Dupe;
+use indexmap::IndexMap;
+use more_futures::cancellation::CancellationContext;
+
+use crate::executors::local::create_output_dirs;
+
+/// Serves action results out of the local action cache.
+pub struct LocalActionCacheChecker {
+    pub artifact_fs: ArtifactFs,
+    pub materializer: Arc<dyn Materializer>,
+    pub blocking_executor: Arc<dyn BlockingExecutor>,
+    pub cache: Arc<LocalActionCache>,
+}
+
+#[async_trait]
+impl PreparedCommandOptionalExecutor for LocalActionCacheChecker {
+    async fn maybe_execute(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        manager: CommandExecutionManager,
+        cancellations: &CancellationContext,
+    ) -> ControlFlow<CommandExecutionResult, CommandExecutionManager> {
+        let request = command.request;
+        let action_digest = &command.prepared_action.action;
+        let digest_config = command.digest_config;
+
+        let cached = executor_stage_async(
+            buck2_data::LocalStage {
+                stage: Some(
+                    buck2_data::LocalActionCacheQuery {
+                        action_digest: action_digest.to_string(),
+                    }
+                    .into(),
+                ),
+            },
+            self.blocking_executor
+                .execute_io_inline(|| self.cache.get(action_digest, digest_config)),
+        )
+        .await;
+
+        let cached = match cached {
+            Ok(Some(cached)) => cached,
+            Ok(None) => return ControlFlow::Continue(manager),
+            Err(e) => {
+                // The cache is an optimization, don't fail the build over it.
+                tracing::warn!("Error querying local action cache: {:#}", e);
+                return ControlFlow::Continue(manager);
+            }
+        };
+
+        tracing::info!(
+            "Action result is cached locally, skipping execution of:\n```\n$ {}\n```\n for action `{}`",
+            request.all_args_str(),
+            action_digest,
+        );
+
+        // Nothing else runs this command until we return, so the outputs can be restored before
+        // claiming it. That way, if restoring fails, the command can still run as usual.
+        let res = executor_stage_async(
+            buck2_data::LocalStage {
+                stage: Some(
+                    buck2_data::LocalActionCacheHit {
+                        action_digest: action_digest.to_string(),
+                    }
+                    .into(),
+                ),
+            },
+            async {
+                create_output_dirs(
+                    &self.artifact_fs,
+                    request,
+                    self.materializer.dupe(),
+                    self.blocking_executor.dupe(),
+                    cancellations,
+                )
+                .await
+                .context("Error creating output directories")?;
+
+                // Entries are only read by the IoRequest, but they're needed again below to
+                // compute the output values.
+                let cached = Arc::new(cached);
+                self.blocking_executor
+                    .execute_io(
+                        Box::new(RestoreLocalActionResult {
+                            cache: self.cache.dupe(),
+                            result: cached.dupe(),
+                            digest_config,
+                        }),
+                        cancellations,
+                    )
+                    .await
+                    .context("Error restoring outputs from local action cache")?;
+
+                let mut builder = request.paths().input_directory().clone().into_builder();
+                for (path, entry) in &cached.entries {
+                    match entry {
+                        DirectoryEntry::Dir(()) => {
+                            builder.mkdir(path)?;
+                        }
+                        DirectoryEntry::Leaf(leaf) => {
+                            builder.insert(path, DirectoryEntry::Leaf(leaf.dupe()))?;
+                        }
+                    }
+                }
+
+                let mut to_declare = Vec::new();
+                let mut outputs = IndexMap::new();
+                for output in request.outputs() {
+                    let path = output.resolve(&self.artifact_fs).into_path();
+                    let value = match extract_artifact_value(&builder, &path, digest_config)? {
+                        Some(value) => value,
+                        None => continue,
+                    };
+                    let output = output.cloned();
+                    if let CommandExecutionOutput::BuildArtifact { .. } = &output {
+                        to_declare.push((path, value.dupe()));
+                    }
+                    outputs.insert(output, value);
+                }
+
+                anyhow::Ok((outputs, to_declare, cached))
+            },
+        )
+        .await;
+
+        let (outputs, to_declare, cached) = match res {
+            Ok(res) => res,
+            Err(e) => {
+                tracing::warn!(
+                    "Error restoring `{}` from local action cache, running it instead: {:#}",
+                    action_digest,
+                    e
+                );
+                if let Err(e) = self
+                    .blocking_executor
+                    .execute_io_inline(|| self.cache.remove(action_digest))
+                    .await
+                {
+                    tracing::warn!(
+                        "Error removing `{}` from local action cache: {:#}",
+                        action_digest,
+                        e
+                    );
+                }
+                return ControlFlow::Continue(manager);
+            }
+        };
+
+        let manager = manager.claim().await;
+
+        if let Err(e) = self.materializer.declare_existing(to_declare).await {
+            return ControlFlow::Break(manager.error("local_action_cache", e));
+        }
+
+        let std_streams = CommandStdStreams::Local {
+            stdout: cached.stdout.clone(),
+            stderr: cached.stderr.clone(),
+        };
+
+        ControlFlow::Break(manager.success(
+            CommandExecutionKind::LocalActionCache {
+                digest: action_digest.dupe(),
+            },
+            outputs,
+            std_streams,
+            CommandExecutionMetadata::default(),
+        ))
+    }
+}
+
+struct RestoreLocalActionResult {
+    cache: Arc<LocalActionCache>,
+    result: Arc<LocalActionResult>,
+    digest_config: DigestConfig,
+}
+
+impl IoRequest for RestoreLocalActionResult {
+    fn execute(self: Box<Self>, project_fs: &ProjectRoot) -> anyhow::Result<()> {
+        self.cache
+            .restore(&self.result, project_fs, self.digest_config)
+    }
+}
+
+/// Stores the results of actions that ran locally in the local action cache, then hands them to
+/// the inner uploader.
+pub struct LocalActionCacheUploader {
+    pub artifact_fs: ArtifactFs,
+    pub blocking_executor: Arc<dyn BlockingExecutor>,
+    pub cache: Arc<LocalActionCache>,
+    pub inner: Arc<dyn UploadCache>,
+}
+
+impl LocalActionCacheUploader {
+    async fn store(
+        &self,
+        info: &CacheUploadInfo<'_>,
+        result: &CommandExecutionResult,
+    ) -> anyhow::Result<()> {
+        match &result.report.status {
+            CommandExecutionStatus::Success {
+                execution_kind: CommandExecutionKind::Local { .. },
+            } => {}
+            _ => return Ok(()),
+        }
+
+        let mut entries = Vec::new();
+        for (output, value) in result.resolve_outputs(&self.artifact_fs) {
+            // The walk only yields the contents of directories, not the directory itself.
+            if let DirectoryEntry::Dir(_) = value.entry() {
+                entries.push((output.path().to_owned(), DirectoryEntry::Dir(())));
+            }
+
+            let mut walk = unordered_entry_walk(value.entry().as_ref());
+            while let Some((entry_path, entry)) = walk.next() {
+                let path = output.path().join(entry_path.get());
+                let entry = match entry {
+                    DirectoryEntry::Dir(_) => DirectoryEntry::Dir(()),
+                    DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(_)) => {
+                        // Those point outside of the repo, we can't promise they'll still be valid
+                        // when restoring.
+                        return Ok(());
+                    }
+                    DirectoryEntry::Leaf(leaf) => DirectoryEntry::Leaf(leaf.dupe()),
+                };
+                entries.push((path, entry));
+            }
+        }
+
+        let std_streams = result.report.std_streams.clone().into_bytes().await?;
+        let cached = LocalActionResult {
+            entries,
+            stdout: std_streams.stdout,
+            stderr: std_streams.stderr,
+        };
+
+        self.blocking_executor
+            .execute_io_inline(|| {
+                self.cache.insert(
+                    &info.action_digest,
+                    &cached,
+                    self.artifact_fs.fs(),
+                    info.digest_config,
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UploadCache for LocalActionCacheUploader {
+    async fn upload(
+        &self,
+        info: &CacheUploadInfo<'_>,
+        execution_result: &CommandExecutionResult,
+        dep_file_entry: Option<DepFileEntry>,
+    ) -> anyhow::Result<bool> {
+        if let Err(e) = self.store(info, execution_result).await {
+            tracing::warn!(
+                "Error storing `{}` in local action cache: {:#}",
+                info.action_digest,
+                e
+            );
+        }
+
+        self.inner
+            .upload(info, execution_result, dep_file_entry)
+            .await
+    }
+}
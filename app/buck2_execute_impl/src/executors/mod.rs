@@ -8,9 +8,11 @@
  */
 
 pub mod action_cache;
+mod bazel_worker;
 pub mod caching;
 pub mod hybrid;
 pub mod local;
+pub mod local_action_cache;
 pub mod re;
 pub mod stacked;
 pub mod worker;
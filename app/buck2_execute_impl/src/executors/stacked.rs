@@ -7,6 +7,8 @@
  * of this source tree.
  */
 
+use std::ops::ControlFlow;
+
 use async_trait::async_trait;
 use buck2_execute::execute::manager::CommandExecutionManager;
 use buck2_execute::execute::prepared::PreparedCommand;
@@ -50,3 +52,27 @@ where
             .is_local_execution_possible(executor_preference)
     }
 }
+
+/// Stacking two optional executors yields an optional executor that tries them in order.
+#[async_trait]
+impl<O, F> PreparedCommandOptionalExecutor for StackedExecutor<O, F>
+where
+    O: PreparedCommandOptionalExecutor,
+    F: PreparedCommandOptionalExecutor,
+{
+    async fn maybe_execute(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        manager: CommandExecutionManager,
+        cancellations: &CancellationContext,
+    ) -> ControlFlow<CommandExecutionResult, CommandExecutionManager> {
+        let manager = self
+            .optional
+            .maybe_execute(command, manager, cancellations)
+            .await?;
+
+        self.fallback
+            .maybe_execute(command, manager, cancellations)
+            .await
+    }
+}
@@ -9,8 +9,13 @@
 
 use std::collections::HashMap;
 use std::ffi::OsString;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 
 use buck2_common::client_utils::get_channel_uds;
 use buck2_common::client_utils::retrying;
@@ -28,6 +33,7 @@ use buck2_execute::execute::manager::CommandExecutionManagerWithClaim;
 use buck2_execute::execute::output::CommandStdStreams;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::WorkerId;
+use buck2_execute::execute::request::WorkerProtocol;
 use buck2_execute::execute::request::WorkerSpec;
 use buck2_execute::execute::result::CommandExecutionMetadata;
 use buck2_execute::execute::result::CommandExecutionResult;
@@ -37,16 +43,20 @@ use buck2_worker_proto::execute_command::EnvironmentEntry;
 use buck2_worker_proto::worker_client::WorkerClient;
 use buck2_worker_proto::ExecuteCommand;
 use buck2_worker_proto::ExecuteResponse;
+use dupe::Dupe;
 use futures::future::BoxFuture;
+use futures::future::Future;
 use futures::future::Shared;
 use futures::FutureExt;
 use host_sharing::HostSharingBroker;
 use host_sharing::HostSharingRequirements;
 use host_sharing::HostSharingStrategy;
 use indexmap::IndexMap;
-use tokio::task::JoinHandle;
+use tokio::sync::oneshot;
 use tonic::transport::Channel;
 
+use crate::executors::bazel_worker::BazelWorker;
+
 #[derive(thiserror::Error, Debug)]
 pub enum WorkerInitError {
     #[error("Worker failed to spawn: {0}")]
@@ -115,80 +125,86 @@ impl WorkerInitError {
     }
 }
 
+/// Spawns a worker, returning a future that resolves when it exits and a receiver for its pid.
 #[cfg(unix)]
-fn spawn_via_forkserver(
+pub(crate) fn spawn_via_forkserver(
     forkserver: ForkserverClient,
     exe: OsString,
     args: Vec<OsString>,
     env: Vec<(OsString, OsString)>,
     working_directory: AbsNormPathBuf,
-    liveliness_observer: impl LivelinessObserver + 'static,
+    cancel: impl Future<Output = ()> + Send + 'static,
     stdout_path: &AbsNormPathBuf,
     stderr_path: &AbsNormPathBuf,
-    socket_path: &AbsNormPathBuf,
-) -> JoinHandle<anyhow::Result<GatherOutputStatus>> {
+    stdin_path: Option<&AbsNormPathBuf>,
+) -> (
+    BoxFuture<'static, anyhow::Result<GatherOutputStatus>>,
+    oneshot::Receiver<u32>,
+) {
     use std::os::unix::ffi::OsStrExt;
 
     use crate::executors::local::apply_local_execution_environment;
 
-    let stdout_path = stdout_path.clone();
-    let stderr_path = stderr_path.clone();
+    let mut req = buck2_forkserver_proto::CommandRequest {
+        exe: exe.as_bytes().into(),
+        argv: args.into_iter().map(|s| s.as_bytes().into()).collect(),
+        cwd: Some(buck2_forkserver_proto::WorkingDirectory {
+            path: working_directory.as_path().as_os_str().as_bytes().into(),
+        }),
+        env: vec![],
+        timeout: None,
+        enable_miniperf: false,
+        std_redirects: Some(buck2_forkserver_proto::command_request::StdRedirectPaths {
+            stdout: stdout_path.as_os_str().as_bytes().into(),
+            stderr: stderr_path.as_os_str().as_bytes().into(),
+            stdin: stdin_path.map(|path| path.as_os_str().as_bytes().into()),
+        }),
+        sandbox: None,
+        file_access_log: None,
+        cgroup: None,
+        termination_grace_period: None,
+    };
+    apply_local_execution_environment(&mut req, &working_directory, env, None);
 
-    let socket_path = socket_path.clone();
-    tokio::spawn(async move {
-        let mut req = buck2_forkserver_proto::CommandRequest {
-            exe: exe.as_bytes().into(),
-            argv: args.into_iter().map(|s| s.as_bytes().into()).collect(),
-            cwd: Some(buck2_forkserver_proto::WorkingDirectory {
-                path: working_directory.as_path().as_os_str().as_bytes().into(),
-            }),
-            env: vec![],
-            timeout: None,
-            enable_miniperf: false,
-            std_redirects: Some(buck2_forkserver_proto::command_request::StdRedirectPaths {
-                stdout: stdout_path.as_os_str().as_bytes().into(),
-                stderr: stderr_path.as_os_str().as_bytes().into(),
-            }),
-        };
-        apply_local_execution_environment(&mut req, &working_directory, env, None);
-        let res = forkserver
-            .execute(req, async move { liveliness_observer.while_alive().await })
+    let (pid_sender, pid) = oneshot::channel();
+    let exit = async move {
+        forkserver
+            .execute_with_pid(req, cancel, pid_sender)
             .await
-            .map(|(status, _, _)| status);
-
-        // Socket is created by worker so won't exist if initialization fails.
-        if fs_util::try_exists(&socket_path)? {
-            // TODO(ctolliday) delete directory (after logs are moved to buck-out)
-            fs_util::remove_file(&socket_path)?;
-        }
-        res
-    })
+            .map(|(status, _, _)| status)
+    }
+    .boxed();
+    (exit, pid)
 }
 
 #[cfg(not(unix))]
-fn spawn_via_forkserver(
+pub(crate) fn spawn_via_forkserver(
     _forkserver: ForkserverClient,
     _exe: OsString,
     _args: Vec<OsString>,
     _env: Vec<(OsString, OsString)>,
     _working_directory: AbsNormPathBuf,
-    _liveliness_observer: impl LivelinessObserver + 'static,
+    _cancel: impl Future<Output = ()> + Send + 'static,
     _stdout_path: &AbsNormPathBuf,
     _stderr_path: &AbsNormPathBuf,
-    _socket_path: &AbsNormPathBuf,
-) -> JoinHandle<anyhow::Result<GatherOutputStatus>> {
+    _stdin_path: Option<&AbsNormPathBuf>,
+) -> (
+    BoxFuture<'static, anyhow::Result<GatherOutputStatus>>,
+    oneshot::Receiver<u32>,
+) {
     unreachable!("workers should not be initialized off unix")
 }
 
 async fn spawn_worker(
     worker_spec: &WorkerSpec,
+    instance: u64,
     env: impl IntoIterator<Item = (OsString, OsString)>,
     root: &AbsNormPathBuf,
     forkserver: ForkserverClient,
     dispatcher: EventDispatcher,
 ) -> Result<WorkerHandle, WorkerInitError> {
     // Use fixed length path at /tmp to avoid 108 character limit for unix domain sockets
-    let dir_name = format!("{}-{}", dispatcher.trace_id(), worker_spec.id);
+    let dir_name = format!("{}-{}-{}", dispatcher.trace_id(), worker_spec.id, instance);
     let worker_dir = AbsNormPathBuf::from("/tmp/buck2_worker".to_owned())
         .map_err(|e| WorkerInitError::InternalError(e.into()))?
         .join(FileName::unchecked_new(&dir_name));
@@ -210,34 +226,112 @@ async fn spawn_worker(
         args.join(" ")
     );
 
+    let (liveliness_observer, liveliness_guard) = LivelinessGuard::create();
+
+    let (connection, pid) = match worker_spec.protocol {
+        WorkerProtocol::Buck2 => {
+            let (client, exited, pid) = connect_buck2_worker(
+                args.clone(),
+                env,
+                root,
+                forkserver,
+                liveliness_observer,
+                &stdout_path,
+                &stderr_path,
+                &socket_path,
+            )
+            .await?;
+            (
+                WorkerConnection::Buck2 {
+                    client,
+                    stdout_path,
+                    exited,
+                },
+                pid,
+            )
+        }
+        WorkerProtocol::BazelProto | WorkerProtocol::BazelJson => {
+            let worker = BazelWorker::spawn(
+                worker_spec.protocol,
+                worker_spec.supports_multiplex,
+                &args,
+                env.into_iter().collect(),
+                root,
+                forkserver,
+                &worker_dir,
+                &stderr_path,
+                liveliness_observer,
+            )
+            .await?;
+            tracing::info!("Spawned Bazel worker: {}", worker_dir);
+            let pid = worker.pid();
+            (WorkerConnection::Bazel(worker), pid)
+        }
+    };
+
+    let worker = WorkerHandle::new(
+        worker_spec.id,
+        instance,
+        args,
+        connection,
+        stderr_path,
+        pid,
+        worker_spec.concurrency,
+        dispatcher,
+        liveliness_guard,
+    );
+    worker.report(buck2_data::WorkerLifecycleState::WorkerStarted, None);
+    Ok(worker)
+}
+
+async fn connect_buck2_worker(
+    args: Vec<String>,
+    env: impl IntoIterator<Item = (OsString, OsString)>,
+    root: &AbsNormPathBuf,
+    forkserver: ForkserverClient,
+    liveliness_observer: impl LivelinessObserver + 'static,
+    stdout_path: &AbsNormPathBuf,
+    stderr_path: &AbsNormPathBuf,
+    socket_path: &AbsNormPathBuf,
+) -> Result<(WorkerClient<Channel>, Arc<AtomicBool>, Option<u32>), WorkerInitError> {
     let worker_env = vec![("WORKER_SOCKET", socket_path.as_os_str())]
         .into_iter()
         .map(|(k, v)| (OsString::from(k), OsString::from(v)));
     let env: Vec<(OsString, OsString)> = env.into_iter().chain(worker_env).collect();
 
-    let (liveliness_observer, liveliness_guard) = LivelinessGuard::create();
-
-    let spawn_fut = spawn_via_forkserver(
+    let (spawn_fut, pid) = spawn_via_forkserver(
         forkserver,
         OsString::from(args[0].clone()),
         args[1..].iter().map(OsString::from).collect(),
         env.clone(),
         root.clone(),
-        liveliness_observer,
-        &stdout_path,
-        &stderr_path,
-        &socket_path,
+        async move { liveliness_observer.while_alive().await },
+        stdout_path,
+        stderr_path,
+        None,
     );
+    // Keep track of the worker exiting after we connected to it.
+    let exited = Arc::new(AtomicBool::new(false));
+    let spawn_fut = tokio::spawn({
+        let exited = exited.dupe();
+        let socket_path = socket_path.clone();
+        async move {
+            let res = spawn_fut.await;
+            exited.store(true, Ordering::Relaxed);
+            // Socket is created by worker so won't exist if initialization fails.
+            if fs_util::try_exists(&socket_path)? {
+                // TODO(ctolliday) delete directory (after logs are moved to buck-out)
+                fs_util::remove_file(&socket_path)?;
+            }
+            res
+        }
+    });
 
     let initial_delay = Duration::from_millis(50);
     let max_delay = Duration::from_millis(500);
     // Might want to make this configurable, and/or measure impact of worker initialization on critical path
     let timeout = Duration::from_secs(60);
     let channel = {
-        let stdout_path = &stdout_path;
-        let stderr_path = &stderr_path;
-        let socket_path = &socket_path;
-
         let connect = retrying(initial_delay, max_delay, timeout, move || {
             // TODO(ctolliday) T153604304
             // add handshake over grpc before returning a handle, to make sure the worker is responding
@@ -284,30 +378,154 @@ async fn spawn_worker(
     };
 
     tracing::info!("Connected to socket for spawned worker: {}", socket_path);
-    let client = WorkerClient::new(channel);
-    Ok(WorkerHandle::new(
-        client,
-        stdout_path,
-        stderr_path,
-        worker_spec.concurrency,
-        liveliness_guard,
-    ))
+    // The worker was spawned before it could create the socket, so this is already available.
+    let pid = pid.await.ok();
+    Ok((WorkerClient::new(channel), exited, pid))
+}
+
+#[cfg(target_os = "linux")]
+fn rss_bytes(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let line = status.lines().find_map(|l| l.strip_prefix("VmRSS:"))?;
+    let kb: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rss_bytes(_pid: u32) -> Option<u64> {
+    None
 }
 
 type WorkerFuture = Shared<BoxFuture<'static, Result<Arc<WorkerHandle>, Arc<WorkerInitError>>>>;
 
+/// Limits applied to the workers of a `WorkerPool`.
+#[derive(Clone, Debug)]
+pub struct WorkerPoolConfig {
+    /// Maximum number of instances started for each worker spec.
+    pub max_instances: usize,
+    /// Shut down instances that haven't run a command for this long.
+    pub idle_timeout: Option<Duration>,
+    /// Replace instances after they ran this many commands.
+    pub max_requests: Option<u64>,
+    /// Replace instances once their resident memory exceeds this many bytes. Only supported on
+    /// Linux.
+    pub max_rss_bytes: Option<u64>,
+}
+
+impl Default for WorkerPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_instances: 1,
+            idle_timeout: None,
+            max_requests: None,
+            max_rss_bytes: None,
+        }
+    }
+}
+
+impl WorkerPoolConfig {
+    /// Why this worker should be stopped, if it should.
+    fn retire_reason(
+        &self,
+        worker: &WorkerHandle,
+        load: usize,
+    ) -> Option<(buck2_data::WorkerLifecycleState, Option<u64>)> {
+        if worker.has_exited() {
+            return Some((buck2_data::WorkerLifecycleState::WorkerCrashed, None));
+        }
+        if let Some(max_requests) = self.max_requests {
+            if worker.requests() >= max_requests {
+                return Some((buck2_data::WorkerLifecycleState::WorkerMaxRequests, None));
+            }
+        }
+        if let Some(max_rss_bytes) = self.max_rss_bytes {
+            if let Some(rss) = worker.rss_bytes() {
+                if rss > max_rss_bytes {
+                    return Some((buck2_data::WorkerLifecycleState::WorkerMaxRss, Some(rss)));
+                }
+            }
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            if load == 0 && worker.idle_time() >= idle_timeout {
+                return Some((buck2_data::WorkerLifecycleState::WorkerIdle, None));
+            }
+        }
+        None
+    }
+}
+
+struct WorkerInstance {
+    worker: WorkerFuture,
+    /// Number of commands assigned to this instance that haven't finished yet.
+    load: Arc<AtomicUsize>,
+}
+
+impl WorkerInstance {
+    fn load(&self) -> usize {
+        self.load.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts towards the load of the worker instance it was returned with until dropped.
+pub struct WorkerLoad(Arc<AtomicUsize>);
+
+impl WorkerLoad {
+    fn new(load: &Arc<AtomicUsize>) -> Self {
+        load.fetch_add(1, Ordering::Relaxed);
+        Self(load.dupe())
+    }
+}
+
+impl Drop for WorkerLoad {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+type WorkerInstances = HashMap<WorkerId, Vec<WorkerInstance>>;
+
 pub struct WorkerPool {
-    workers: Arc<parking_lot::Mutex<HashMap<WorkerId, WorkerFuture>>>,
+    config: WorkerPoolConfig,
+    workers: Arc<parking_lot::Mutex<WorkerInstances>>,
+    next_instance: AtomicU64,
 }
 
 impl WorkerPool {
-    pub fn new() -> WorkerPool {
-        tracing::info!("Creating new WorkerPool");
+    pub fn new(config: WorkerPoolConfig) -> WorkerPool {
+        tracing::info!("Creating new WorkerPool with {:?}", config);
+        let workers = Arc::new(parking_lot::Mutex::new(HashMap::default()));
+
+        if let Some(idle_timeout) = config.idle_timeout {
+            // Idle workers are also stopped when they're picked for a command, but this catches
+            // the ones that aren't needed anymore.
+            let workers = Arc::downgrade(&workers);
+            let config = config.clone();
+            tokio::spawn(async move {
+                let mut interval =
+                    tokio::time::interval(std::cmp::max(idle_timeout, Duration::from_secs(1)));
+                loop {
+                    interval.tick().await;
+                    let workers = match workers.upgrade() {
+                        Some(workers) => workers,
+                        None => break,
+                    };
+                    let mut workers = workers.lock();
+                    for instances in workers.values_mut() {
+                        retire_workers(&config, instances);
+                    }
+                }
+            });
+        }
+
         WorkerPool {
-            workers: Arc::new(parking_lot::Mutex::new(HashMap::default())),
+            config,
+            workers,
+            next_instance: AtomicU64::new(1),
         }
     }
 
+    /// Picks the worker instance that should run a command: an idle one if possible, otherwise a
+    /// new one if we're under the configured maximum, otherwise the least loaded one.
     pub fn get_or_create_worker(
         &self,
         worker_spec: &WorkerSpec,
@@ -315,56 +533,184 @@ impl WorkerPool {
         root: &AbsNormPathBuf,
         forkserver: ForkserverClient,
         dispatcher: EventDispatcher,
-    ) -> (bool, WorkerFuture) {
+    ) -> (bool, WorkerFuture, WorkerLoad) {
         let mut workers = self.workers.lock();
-        if let Some(worker_fut) = workers.get(&worker_spec.id) {
-            (false, worker_fut.clone())
-        } else {
-            let worker_id = worker_spec.id;
-            let worker_spec = worker_spec.clone();
-            let root = root.clone();
-            let env: Vec<(OsString, OsString)> = env.into_iter().collect();
-            let fut = async move {
-                match spawn_worker(&worker_spec, env, &root, forkserver, dispatcher).await {
-                    Ok(worker) => Ok(Arc::new(worker)),
-                    Err(e) => Err(Arc::new(e)),
-                }
-            }
-            .boxed()
-            .shared();
+        let instances = workers.entry(worker_spec.id).or_default();
+        retire_workers(&self.config, instances);
+
+        if let Some((worker, load)) = pick_instance(&self.config, instances) {
+            return (false, worker, load);
+        }
 
-            workers.insert(worker_id, fut.clone());
-            (true, fut)
+        let instance = self.next_instance.fetch_add(1, Ordering::Relaxed);
+        let worker_spec = worker_spec.clone();
+        let root = root.clone();
+        let env: Vec<(OsString, OsString)> = env.into_iter().collect();
+        let fut = async move {
+            match spawn_worker(&worker_spec, instance, env, &root, forkserver, dispatcher).await {
+                Ok(worker) => Ok(Arc::new(worker)),
+                Err(e) => Err(Arc::new(e)),
+            }
         }
+        .boxed()
+        .shared();
+
+        let load = Arc::new(AtomicUsize::new(0));
+        let worker_load = WorkerLoad::new(&load);
+        instances.push(WorkerInstance {
+            worker: fut.clone(),
+            load,
+        });
+        (true, fut, worker_load)
     }
 }
 
+/// Picks the existing instance that should run a command, or returns `None` if a new one should be
+/// started.
+fn pick_instance(
+    config: &WorkerPoolConfig,
+    instances: &[WorkerInstance],
+) -> Option<(WorkerFuture, WorkerLoad)> {
+    // Instances that failed to start only report it to the commands that were waiting for them.
+    let usable: Vec<_> = instances
+        .iter()
+        .filter(|i| !matches!(i.worker.peek(), Some(Err(_))))
+        .collect();
+    let picked = usable
+        .iter()
+        .find(|i| i.load() == 0 && i.worker.peek().is_some())
+        .or_else(|| {
+            if usable.len() >= config.max_instances {
+                usable.iter().min_by_key(|i| i.load())
+            } else {
+                None
+            }
+        })?;
+    Some((picked.worker.clone(), WorkerLoad::new(&picked.load)))
+}
+
+/// Removes the instances that should be stopped from the pool. They exit once the commands still
+/// running on them are done. Instances that failed to start are removed too, so that the next
+/// command starts a new one.
+fn retire_workers(config: &WorkerPoolConfig, instances: &mut Vec<WorkerInstance>) {
+    instances.retain(|instance| {
+        let worker = match instance.worker.peek() {
+            Some(Ok(worker)) => worker,
+            Some(Err(e)) => {
+                tracing::debug!("Removing worker instance that failed to start: {}", e);
+                return false;
+            }
+            None => return true,
+        };
+        match config.retire_reason(worker, instance.load()) {
+            Some((state, rss_bytes)) => {
+                tracing::info!(
+                    "Stopping worker instance {} ({:?})",
+                    worker.instance(),
+                    state
+                );
+                worker.report(state, rss_bytes);
+                false
+            }
+            None => true,
+        }
+    });
+}
+
+enum WorkerConnection {
+    Buck2 {
+        client: WorkerClient<Channel>,
+        stdout_path: AbsNormPathBuf,
+        exited: Arc<AtomicBool>,
+    },
+    /// Bazel workers use stdout to send responses, so only stderr is logged.
+    Bazel(BazelWorker),
+}
+
 pub struct WorkerHandle {
-    client: WorkerClient<Channel>,
-    stdout_path: AbsNormPathBuf,
+    worker_id: WorkerId,
+    instance: u64,
+    argv: Vec<String>,
+    connection: WorkerConnection,
     stderr_path: AbsNormPathBuf,
+    pid: Option<u32>,
     host_sharing_broker: Option<HostSharingBroker>,
+    /// Number of commands this instance ran.
+    requests: AtomicU64,
+    last_used: parking_lot::Mutex<Instant>,
+    /// Set when we failed to talk to the worker, which we treat as the worker having crashed.
+    failed: AtomicBool,
+    dispatcher: EventDispatcher,
     _liveliness_guard: LivelinessGuard,
 }
 
 impl WorkerHandle {
     fn new(
-        client: WorkerClient<Channel>,
-        stdout_path: AbsNormPathBuf,
+        worker_id: WorkerId,
+        instance: u64,
+        argv: Vec<String>,
+        connection: WorkerConnection,
         stderr_path: AbsNormPathBuf,
+        pid: Option<u32>,
         concurrency_limit: Option<usize>,
+        dispatcher: EventDispatcher,
         liveliness_guard: LivelinessGuard,
     ) -> Self {
         let host_sharing_broker =
             concurrency_limit.map(|i| HostSharingBroker::new(HostSharingStrategy::Fifo, i));
         Self {
-            client,
-            stdout_path,
+            worker_id,
+            instance,
+            argv,
+            connection,
             stderr_path,
+            pid,
             host_sharing_broker,
+            requests: AtomicU64::new(0),
+            last_used: parking_lot::Mutex::new(Instant::now()),
+            failed: AtomicBool::new(false),
+            dispatcher,
             _liveliness_guard: liveliness_guard,
         }
     }
+
+    /// Identifies this instance amongst all the workers in the pool.
+    pub fn instance(&self) -> u64 {
+        self.instance
+    }
+
+    pub fn has_exited(&self) -> bool {
+        if self.failed.load(Ordering::Relaxed) {
+            return true;
+        }
+        match &self.connection {
+            WorkerConnection::Buck2 { exited, .. } => exited.load(Ordering::Relaxed),
+            WorkerConnection::Bazel(worker) => worker.has_exited(),
+        }
+    }
+
+    fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    fn idle_time(&self) -> Duration {
+        self.last_used.lock().elapsed()
+    }
+
+    fn rss_bytes(&self) -> Option<u64> {
+        rss_bytes(self.pid?)
+    }
+
+    fn report(&self, state: buck2_data::WorkerLifecycleState, rss_bytes: Option<u64>) {
+        self.dispatcher.instant_event(buck2_data::WorkerLifecycle {
+            worker_id: self.worker_id.0,
+            instance: self.instance,
+            argv: self.argv.clone(),
+            state: state as i32,
+            requests: self.requests(),
+            rss_bytes,
+        });
+    }
 }
 
 #[cfg(unix)]
@@ -404,6 +750,30 @@ impl WorkerHandle {
             None
         };
 
+        let res = match &self.connection {
+            WorkerConnection::Buck2 {
+                client,
+                stdout_path,
+                ..
+            } => self.exec_buck2_cmd(client, stdout_path, args, env).await,
+            WorkerConnection::Bazel(worker) => self.exec_bazel_cmd(worker, args).await,
+        };
+
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        *self.last_used.lock() = Instant::now();
+        if let GatherOutputStatus::SpawnFailed(_) = &res.0 {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+        res
+    }
+
+    async fn exec_buck2_cmd(
+        &self,
+        client: &WorkerClient<Channel>,
+        stdout_path: &AbsNormPathBuf,
+        args: &[String],
+        env: Vec<(OsString, OsString)>,
+    ) -> (GatherOutputStatus, Vec<u8>, Vec<u8>) {
         tracing::info!(
             "Sending worker command:\nExecuteCommand {{ argv: {:?}, env: {:?} }}\n",
             args,
@@ -413,7 +783,7 @@ impl WorkerHandle {
         let env: Vec<EnvironmentEntry> = env_entries(&env);
 
         let request = ExecuteCommand { argv, env };
-        let response = self.client.clone().execute(request).await;
+        let response = client.clone().execute(request).await;
 
         match response {
             Ok(response) => {
@@ -432,7 +802,7 @@ impl WorkerHandle {
                 (
                     GatherOutputStatus::SpawnFailed(format!(
                         "Error sending ExecuteCommand to worker: {:?}, see worker logs:\n{}\n{}",
-                        err, self.stdout_path, self.stderr_path,
+                        err, stdout_path, self.stderr_path,
                     )),
                     // stdout/stderr logs for worker are for multiple commands, probably do not want to dump contents here
                     vec![],
@@ -441,4 +811,274 @@ impl WorkerHandle {
             }
         }
     }
+
+    /// The Bazel protocol has no way to pass an environment, so workers only get the one they were
+    /// spawned with.
+    async fn exec_bazel_cmd(
+        &self,
+        worker: &BazelWorker,
+        args: &[String],
+    ) -> (GatherOutputStatus, Vec<u8>, Vec<u8>) {
+        tracing::info!(
+            "Sending worker command:\nWorkRequest {{ arguments: {:?} }}\n",
+            args
+        );
+        match worker.exec(args).await {
+            Ok(response) => {
+                tracing::info!("Worker response:\n{:?}\n", response);
+                (
+                    GatherOutputStatus::Finished {
+                        exit_code: response.exit_code,
+                        execution_stats: None,
+                    },
+                    vec![],
+                    // Bazel shows the output of failed actions the same way as their stderr.
+                    response.output.into_bytes(),
+                )
+            }
+            Err(err) => match worker.startup_failure() {
+                // Reported like a Buck2 worker that exits before connecting, rather than as a
+                // crash that would be retried.
+                Some(exit_code) => (
+                    GatherOutputStatus::Finished {
+                        exit_code,
+                        execution_stats: None,
+                    },
+                    vec![],
+                    fs_util::read(&self.stderr_path).unwrap_or_default(),
+                ),
+                None => (
+                    GatherOutputStatus::SpawnFailed(format!(
+                        "Error sending WorkRequest to worker: {:#}, see worker logs:\n{}",
+                        err, self.stderr_path,
+                    )),
+                    vec![],
+                    vec![],
+                ),
+            },
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use tonic::transport::Endpoint;
+
+    use super::*;
+
+    fn worker(pid: Option<u32>) -> WorkerHandle {
+        let (_observer, guard) = LivelinessGuard::create();
+        let worker_dir = AbsNormPathBuf::from("/tmp/buck2_worker/test".to_owned()).unwrap();
+        WorkerHandle::new(
+            WorkerId(1),
+            1,
+            vec!["worker".to_owned()],
+            WorkerConnection::Buck2 {
+                client: WorkerClient::new(
+                    Endpoint::from_static("http://127.0.0.1:1").connect_lazy(),
+                ),
+                stdout_path: worker_dir.join(FileName::unchecked_new("stdout")),
+                exited: Arc::new(AtomicBool::new(false)),
+            },
+            worker_dir.join(FileName::unchecked_new("stderr")),
+            pid,
+            None,
+            EventDispatcher::null(),
+            guard,
+        )
+    }
+
+    /// An instance that finished initializing, as those are the only ones that get retired.
+    async fn instance(
+        worker: Result<WorkerHandle, WorkerInitError>,
+        load: usize,
+    ) -> WorkerInstance {
+        let worker = futures::future::ready(worker.map(Arc::new).map_err(Arc::new))
+            .boxed()
+            .shared();
+        let _ignored = worker.clone().await;
+        WorkerInstance {
+            worker,
+            load: Arc::new(AtomicUsize::new(load)),
+        }
+    }
+
+    fn idle_since(worker: &WorkerHandle, idle_time: Duration) {
+        *worker.last_used.lock() = Instant::now().checked_sub(idle_time).unwrap();
+    }
+
+    fn state(
+        config: &WorkerPoolConfig,
+        worker: &WorkerHandle,
+        load: usize,
+    ) -> Option<buck2_data::WorkerLifecycleState> {
+        config.retire_reason(worker, load).map(|(state, _)| state)
+    }
+
+    #[tokio::test]
+    async fn test_retire_reason_default() {
+        let worker = worker(None);
+        worker.requests.store(1000, Ordering::Relaxed);
+        idle_since(&worker, Duration::from_secs(3600));
+        assert_eq!(None, state(&WorkerPoolConfig::default(), &worker, 0));
+
+        worker.failed.store(true, Ordering::Relaxed);
+        assert_eq!(
+            Some(buck2_data::WorkerLifecycleState::WorkerCrashed),
+            state(&WorkerPoolConfig::default(), &worker, 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retire_reason_max_requests() {
+        let config = WorkerPoolConfig {
+            max_requests: Some(2),
+            ..Default::default()
+        };
+        let worker = worker(None);
+        worker.requests.store(1, Ordering::Relaxed);
+        assert_eq!(None, state(&config, &worker, 0));
+        worker.requests.store(2, Ordering::Relaxed);
+        assert_eq!(
+            Some(buck2_data::WorkerLifecycleState::WorkerMaxRequests),
+            state(&config, &worker, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retire_reason_idle_timeout() {
+        let config = WorkerPoolConfig {
+            idle_timeout: Some(Duration::from_secs(10)),
+            ..Default::default()
+        };
+        let worker = worker(None);
+        assert_eq!(None, state(&config, &worker, 0));
+        idle_since(&worker, Duration::from_secs(20));
+        assert_eq!(
+            Some(buck2_data::WorkerLifecycleState::WorkerIdle),
+            state(&config, &worker, 0)
+        );
+        // Instances with commands queued aren't idle.
+        assert_eq!(None, state(&config, &worker, 1));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_retire_reason_max_rss() {
+        let config = WorkerPoolConfig {
+            max_rss_bytes: Some(1),
+            ..Default::default()
+        };
+        // Without a pid, the memory usage isn't known.
+        assert_eq!(None, state(&config, &worker(None), 0));
+
+        // Our own process is bound to use more than a byte.
+        let worker = worker(Some(std::process::id()));
+        match config.retire_reason(&worker, 0) {
+            Some((buck2_data::WorkerLifecycleState::WorkerMaxRss, Some(rss))) => assert!(rss > 1),
+            reason => panic!("Unexpected reason: {:?}", reason),
+        }
+
+        let config = WorkerPoolConfig {
+            max_rss_bytes: Some(u64::MAX),
+            ..Default::default()
+        };
+        assert_eq!(None, state(&config, &worker, 0));
+    }
+
+    #[tokio::test]
+    async fn test_retire_workers() {
+        let config = WorkerPoolConfig {
+            idle_timeout: Some(Duration::from_secs(10)),
+            ..Default::default()
+        };
+
+        let crashed = worker(None);
+        crashed.failed.store(true, Ordering::Relaxed);
+        let busy = worker(None);
+        idle_since(&busy, Duration::from_secs(20));
+        let idle = worker(None);
+        idle_since(&idle, Duration::from_secs(20));
+        let recent = worker(None);
+
+        let mut instances = vec![
+            instance(Ok(crashed), 0).await,
+            instance(Ok(busy), 1).await,
+            instance(Ok(idle), 0).await,
+            instance(Ok(recent), 0).await,
+            // Still initializing.
+            WorkerInstance {
+                worker: futures::future::pending().boxed().shared(),
+                load: Arc::new(AtomicUsize::new(0)),
+            },
+            instance(Err(WorkerInitError::SpawnFailed("error".to_owned())), 0).await,
+        ];
+        retire_workers(&config, &mut instances);
+        assert_eq!(
+            vec![1, 0, 0],
+            instances.iter().map(|i| i.load()).collect::<Vec<_>>()
+        );
+        assert!(instances[2].worker.peek().is_none());
+    }
+
+    #[test]
+    fn test_worker_load() {
+        let load = Arc::new(AtomicUsize::new(0));
+        let first = WorkerLoad::new(&load);
+        let second = WorkerLoad::new(&load);
+        assert_eq!(2, load.load(Ordering::Relaxed));
+        drop(first);
+        assert_eq!(1, load.load(Ordering::Relaxed));
+        drop(second);
+        assert_eq!(0, load.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_pick_instance() {
+        let config = WorkerPoolConfig {
+            max_instances: 3,
+            ..Default::default()
+        };
+        let loads = |instances: &[WorkerInstance]| -> Vec<usize> {
+            instances.iter().map(|i| i.load()).collect()
+        };
+
+        let mut instances = vec![instance(Ok(worker(None)), 2).await];
+        // There is room for another instance.
+        assert!(pick_instance(&config, &instances).is_none());
+
+        // Idle instances are preferred over starting a new one.
+        instances.push(instance(Ok(worker(None)), 0).await);
+        let (_, load) = pick_instance(&config, &instances).unwrap();
+        assert_eq!(vec![2, 1], loads(&instances));
+        assert!(pick_instance(&config, &instances).is_none());
+
+        // The pool is full, so the least loaded instance gets the command.
+        instances.push(instance(Ok(worker(None)), 3).await);
+        let (_, other_load) = pick_instance(&config, &instances).unwrap();
+        assert_eq!(vec![2, 2, 3], loads(&instances));
+        drop(load);
+        drop(other_load);
+        assert_eq!(vec![2, 0, 3], loads(&instances));
+
+        // Instances that failed to start are never picked, and don't count towards the maximum.
+        let failed = || instance(Err(WorkerInitError::SpawnFailed("error".to_owned())), 0);
+        let instances = vec![failed().await];
+        assert!(pick_instance(&config, &instances).is_none());
+        let instances = vec![
+            instance(Ok(worker(None)), 1).await,
+            failed().await,
+            instance(Ok(worker(None)), 1).await,
+        ];
+        assert!(pick_instance(&config, &instances).is_none());
+        let instances = vec![
+            instance(Ok(worker(None)), 1).await,
+            failed().await,
+            instance(Ok(worker(None)), 1).await,
+            instance(Ok(worker(None)), 2).await,
+        ];
+        let (worker, _load) = pick_instance(&config, &instances).unwrap();
+        assert!(matches!(worker.peek(), Some(Ok(_))));
+        assert_eq!(vec![2, 0, 1, 2], loads(&instances));
+    }
 }
@@ -23,11 +23,13 @@ use futures::future::FutureExt;
 use futures::stream;
 use futures::stream::StreamExt;
 use tokio::process::Child;
+use tokio::sync::oneshot;
 use tonic::transport::Channel;
 use tonic::Request;
 
 use crate::convert::decode_event_stream;
 use crate::run::decode_command_event_stream;
+use crate::run::CommandEvent;
 use crate::run::GatherOutputStatus;
 
 #[derive(Clone, Dupe, Allocative)]
@@ -93,6 +95,31 @@ impl ForkserverClient {
         req: buck2_forkserver_proto::CommandRequest,
         cancel: C,
     ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>
+    where
+        C: Future<Output = ()> + Send + 'static,
+    {
+        self.execute_impl(req, cancel, None).await
+    }
+
+    /// Like `execute`, but also sends the pid of the command to `pid` once it was spawned.
+    pub async fn execute_with_pid<C>(
+        &self,
+        req: buck2_forkserver_proto::CommandRequest,
+        cancel: C,
+        pid: oneshot::Sender<u32>,
+    ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>
+    where
+        C: Future<Output = ()> + Send + 'static,
+    {
+        self.execute_impl(req, cancel, Some(pid)).await
+    }
+
+    async fn execute_impl<C>(
+        &self,
+        req: buck2_forkserver_proto::CommandRequest,
+        cancel: C,
+        mut pid: Option<oneshot::Sender<u32>>,
+    ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>
     where
         C: Future<Output = ()> + Send + 'static,
     {
@@ -123,7 +150,14 @@ impl ForkserverClient {
             .await
             .context("Error dispatching command to Forkserver")?
             .into_inner();
-        let stream = decode_event_stream(stream);
+        let stream = decode_event_stream(stream).inspect(move |event| {
+            if let Ok(CommandEvent::Spawned(spawned)) = event {
+                if let Some(pid) = pid.take() {
+                    // The caller might not be waiting for it anymore.
+                    let _ignored = pid.send(*spawned);
+                }
+            }
+        });
         decode_command_event_stream(stream).await
     }
 
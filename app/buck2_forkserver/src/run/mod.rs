@@ -70,6 +70,8 @@ impl From<DecodedStatus> for GatherOutputStatus {
 
 #[derive(Debug)]
 pub enum CommandEvent {
+    /// The pid of the command. Only sent by the forkserver, before any other event.
+    Spawned(u32),
     Stdout(Bytes),
     Stderr(Bytes),
     Exit(GatherOutputStatus),
@@ -176,6 +178,7 @@ pub fn stream_command_events<T>(
     decoder: impl StatusDecoder,
     kill_process: impl KillProcess,
     stream_stdio: bool,
+    termination_grace_period: Option<Duration>,
 ) -> anyhow::Result<impl Stream<Item = anyhow::Result<CommandEvent>>>
 where
     T: Future<Output = anyhow::Result<GatherOutputStatus>> + Send,
@@ -236,6 +239,14 @@ where
         anyhow::Ok(match execute.await? {
             Outcome::Finished(status) => decoder.decode_status(status).await?.into(),
             Outcome::Cancelled(res) => {
+                if let (GatherOutputStatus::TimedOut(..), Some(grace_period)) =
+                    (&res, termination_grace_period)
+                {
+                    terminate_gracefully(&mut child, grace_period)
+                        .await
+                        .context("Failed to terminate child after timeout")?;
+                }
+
                 kill_process
                     .kill(&mut child)
                     .context("Failed to terminate child after timeout")?;
@@ -273,6 +284,7 @@ where
 
     while let Some(event) = stream.try_next().await? {
         match event {
+            CommandEvent::Spawned(_) => {}
             CommandEvent::Stdout(bytes) => stdout.extend(&bytes),
             CommandEvent::Stderr(bytes) => stderr.extend(&bytes),
             CommandEvent::Exit(exit) => return Ok((exit, stdout, stderr)),
@@ -287,6 +299,7 @@ where
 pub async fn gather_output<T>(
     cmd: Command,
     cancellation: T,
+    termination_grace_period: Option<Duration>,
 ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>
 where
     T: Future<Output = anyhow::Result<GatherOutputStatus>> + Send,
@@ -300,6 +313,7 @@ where
         DefaultStatusDecoder,
         DefaultKillProcess,
         true,
+        termination_grace_period,
     )?;
     decode_command_event_stream(stream).await
 }
@@ -350,6 +364,52 @@ fn kill_process_impl(pid: u32) -> anyhow::Result<()> {
         .with_context(|| format!("Failed to kill process {}", pid))
 }
 
+/// Sends SIGTERM to the process group of the child, and waits up to `grace_period` for the child
+/// to exit. The caller still has to kill the process group after that.
+#[cfg(unix)]
+async fn terminate_gracefully(child: &mut Child, grace_period: Duration) -> anyhow::Result<()> {
+    use nix::errno::Errno;
+    use nix::sys::signal;
+    use nix::sys::signal::Signal;
+    use nix::unistd::Pid;
+
+    let pid = match child.id() {
+        Some(pid) => pid,
+        None => return Ok(()),
+    };
+    tracing::info!("Terminating process {}", pid);
+    let pgid = Pid::from_raw(pid.try_into().context("PID does not fit a i32")?);
+
+    match signal::killpg(pgid, Signal::SIGTERM) {
+        Ok(()) => {}
+        Err(Errno::ESRCH) => return Ok(()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to terminate process {}", pid));
+        }
+    }
+
+    if tokio::time::timeout(grace_period, child.wait())
+        .await
+        .is_ok()
+    {
+        // The child is gone, so killing it won't reach the rest of its process group anymore, if
+        // any of it is still around.
+        match signal::killpg(pgid, Signal::SIGKILL) {
+            Ok(()) | Err(Errno::ESRCH) => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to kill process {}", pid));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn terminate_gracefully(_child: &mut Child, _grace_period: Duration) -> anyhow::Result<()> {
+    Ok(())
+}
+
 /// Unify the the behavior of using a relative path for the executable between Unix and Windows. On
 /// UNIX, the path is understood to be relative to the cwd of the *spawned process*, whereas on
 /// Windows, it's relative ot the cwd of the *spawning* process.
@@ -447,7 +507,7 @@ mod tests {
         };
         cmd.args(["-c", "echo hello"]);
 
-        let (status, stdout, stderr) = gather_output(cmd, futures::future::pending()).await?;
+        let (status, stdout, stderr) = gather_output(cmd, futures::future::pending(), None).await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
         assert_eq!(str::from_utf8(&stdout)?.trim(), "hello");
         assert_eq!(stderr, b"");
@@ -476,6 +536,7 @@ mod tests {
         let (status, stdout, stderr) = gather_output(
             cmd,
             timeout_into_cancellation(Some(Duration::from_secs(timeout))),
+            None,
         )
         .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
@@ -500,6 +561,7 @@ mod tests {
         let (status, stdout, stderr) = gather_output(
             cmd,
             timeout_into_cancellation(Some(Duration::from_secs(timeout))),
+            None,
         )
         .await?;
         assert!(matches!(status, GatherOutputStatus::TimedOut(..)));
@@ -570,8 +632,12 @@ mod tests {
         // This command will spawn 2 subprocesses (subshells) and print the PID of the 2nd shell.
         let mut cmd = background_command("sh");
         cmd.arg("-c").arg("( ( echo $$ && sleep 1000 ) )");
-        let (_status, stdout, _stderr) =
-            gather_output(cmd, timeout_into_cancellation(Some(Duration::from_secs(1)))).await?;
+        let (_status, stdout, _stderr) = gather_output(
+            cmd,
+            timeout_into_cancellation(Some(Duration::from_secs(1))),
+            None,
+        )
+        .await?;
         let pid = i32::from_str(std::str::from_utf8(&stdout)?.trim())?;
 
         for _ in 0..10 {
@@ -589,6 +655,26 @@ mod tests {
         Err(anyhow::anyhow!("PID did not exit: {}", pid))
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_timeout_terminates_gracefully() -> anyhow::Result<()> {
+        let mut cmd = background_command("sh");
+        cmd.args([
+            "-c",
+            "trap 'echo terminated; exit 1' TERM; echo hello; sleep 1000 & wait",
+        ]);
+        let (status, stdout, _stderr) = gather_output(
+            cmd,
+            timeout_into_cancellation(Some(Duration::from_secs(1))),
+            Some(Duration::from_secs(10)),
+        )
+        .await?;
+        assert_matches!(status, GatherOutputStatus::TimedOut(..));
+        assert_eq!(str::from_utf8(&stdout)?, "hello\nterminated\n");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_stream_command_events_ends() -> anyhow::Result<()> {
         let mut cmd = if cfg!(windows) {
@@ -605,6 +691,7 @@ mod tests {
             DefaultStatusDecoder,
             DefaultKillProcess,
             true,
+            None,
         )?
         .boxed();
         assert_matches!(events.next().await, Some(Ok(CommandEvent::Exit(..))));
@@ -619,7 +706,8 @@ mod tests {
 
         let mut cmd = background_command("sh");
         cmd.arg("-c").arg("kill -KILL \"$$\"");
-        let (status, _stdout, _stderr) = gather_output(cmd, futures::future::pending()).await?;
+        let (status, _stdout, _stderr) =
+            gather_output(cmd, futures::future::pending(), None).await?;
 
         assert_matches!(
             status,
@@ -687,6 +775,7 @@ mod tests {
                 killed: killed.dupe(),
             },
             true,
+            None,
         )?;
 
         let (status, _stdout, _stderr) = decode_command_event_stream(stream).await?;
@@ -716,6 +805,7 @@ mod tests {
             DefaultStatusDecoder,
             DefaultKillProcess,
             false,
+            None,
         )?
         .boxed();
         assert_matches!(events.next().await, Some(Ok(CommandEvent::Exit(..))));
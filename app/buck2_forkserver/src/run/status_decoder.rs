@@ -121,6 +121,9 @@ impl StatusDecoder for MiniperfStatusDecoder {
                                 cpu_instructions_kernel: Some(
                                     counters.kernel_instructions.adjusted_count(),
                                 ),
+                                memory_peak: None,
+                                cpu_pressure_stall_us: None,
+                                oom_killed: false,
                             });
 
                     if let Err(e) = execution_stats.as_ref() {
@@ -0,0 +1,369 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Per-command cgroups (v2 only).
+//!
+//! Cgroup v2 doesn't allow a cgroup to both contain processes and distribute resources to its
+//! children, so the daemon takes over the cgroup it runs in (which must have been delegated to us,
+//! e.g. by systemd) when it starts: it moves itself into a `buck2_daemon` child, where the
+//! forkserver it launches also ends up, and the forkserver creates one cgroup per command in an
+//! `actions` child:
+//!
+//! ```text
+//! <cgroup of the daemon>
+//! ├── buck2_daemon
+//! └── actions
+//!     ├── <command>
+//!     └── <command>
+//! ```
+
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_forkserver_proto::CgroupLimits;
+use rand::distributions::Alphanumeric;
+use rand::distributions::DistString;
+
+use crate::run::status_decoder::DecodedStatus;
+use crate::run::status_decoder::StatusDecoder;
+
+const DAEMON_CGROUP: &str = "buck2_daemon";
+const ACTIONS_CGROUP: &str = "actions";
+const CONTROLLERS: &str = "+memory +cpu";
+/// The `cpu.max` period, in microseconds.
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Moves the daemon into the `buck2_daemon` cgroup and creates the `actions` cgroup. This blocks,
+/// and must be called before launching the forkserver, which inherits the cgroup of the daemon.
+pub fn setup_daemon_cgroup() -> anyhow::Result<()> {
+    if !cfg!(target_os = "linux") {
+        return Err(anyhow::anyhow!("Cgroups are only supported on Linux"));
+    }
+
+    let current = current_cgroup()?;
+
+    // If a previous daemon set things up and we were started from there, we're already where we
+    // want to be.
+    let root = match current.file_name() {
+        Some(name) if name == DAEMON_CGROUP => current
+            .parent()
+            .context("Daemon cgroup has no parent")?
+            .to_owned(),
+        _ => {
+            // SAFETY: This never fails.
+            let ours = unsafe { libc::getpid() };
+            let procs = read_procs(&current)?;
+            if let Some(other) = procs.iter().find(|p| **p != ours) {
+                return Err(anyhow::anyhow!(
+                    "Cgroup `{}` contains process {} that is not buck2, \
+                    run buck2 in a dedicated delegated cgroup to use cgroups",
+                    current.display(),
+                    other
+                ));
+            }
+
+            let daemon = current.join(DAEMON_CGROUP);
+            create_dir(&daemon)?;
+            write(&daemon.join("cgroup.procs"), &ours.to_string())?;
+            current
+        }
+    };
+
+    write(&root.join("cgroup.subtree_control"), CONTROLLERS)?;
+
+    let actions = root.join(ACTIONS_CGROUP);
+    create_dir(&actions)?;
+    write(&actions.join("cgroup.subtree_control"), CONTROLLERS)?;
+
+    // Those are left over from a previous daemon, and are empty unless something is still running
+    // there.
+    if let Ok(entries) = std::fs::read_dir(&actions) {
+        for entry in entries.flatten() {
+            if entry.file_type().map_or(false, |t| t.is_dir()) {
+                drop(std::fs::remove_dir(entry.path()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) struct CgroupPool {
+    actions: PathBuf,
+}
+
+impl CgroupPool {
+    /// Returns `None` if the daemon did not set up cgroups (see `setup_daemon_cgroup`).
+    pub(crate) fn new() -> Option<Self> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+
+        let current = current_cgroup().ok()?;
+        if current.file_name()? != DAEMON_CGROUP {
+            return None;
+        }
+
+        let actions = current.parent()?.join(ACTIONS_CGROUP);
+        if !actions.is_dir() {
+            return None;
+        }
+
+        Some(Self { actions })
+    }
+
+    pub(crate) async fn create(&self, limits: CgroupLimits) -> anyhow::Result<ActionCgroup> {
+        let actions = self.actions.clone();
+        tokio::task::spawn_blocking(move || ActionCgroup::create(&actions, &limits)).await?
+    }
+}
+
+/// The cgroup of one command. It's removed on drop, but `cleanup` should be used to also kill
+/// whatever the command left behind.
+pub(crate) struct ActionCgroup {
+    path: PathBuf,
+    procs: CString,
+}
+
+struct CgroupStats {
+    memory_peak: Option<u64>,
+    cpu_pressure_stall_us: Option<u64>,
+    oom_killed: bool,
+}
+
+impl ActionCgroup {
+    fn create(actions: &Path, limits: &CgroupLimits) -> anyhow::Result<Self> {
+        let name = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+        let path = actions.join(name);
+        create_dir(&path)?;
+
+        let cgroup = ActionCgroup {
+            procs: CString::new(path.join("cgroup.procs").as_os_str().as_bytes())?,
+            path,
+        };
+
+        if let Some(memory_max) = limits.memory_max {
+            write(&cgroup.path.join("memory.max"), &memory_max.to_string())?;
+            // Swapping would let the command exceed its limit, just slower. Swap might not be
+            // enabled, so this is best-effort.
+            drop(write(&cgroup.path.join("memory.swap.max"), "0"));
+            // Kill the whole command, not just the process that reached the limit.
+            drop(write(&cgroup.path.join("memory.oom.group"), "1"));
+        }
+
+        if let Some(cpu_max_millis) = limits.cpu_max_millis {
+            // The kernel rejects quotas under 1ms.
+            let quota = std::cmp::max(cpu_max_millis * CPU_PERIOD_US / 1000, 1000);
+            write(
+                &cgroup.path.join("cpu.max"),
+                &format!("{} {}", quota, CPU_PERIOD_US),
+            )?;
+        }
+
+        Ok(cgroup)
+    }
+
+    /// The `cgroup.procs` file of this cgroup, to pass to `enter_cgroup`.
+    pub(crate) fn procs(&self) -> CString {
+        self.procs.clone()
+    }
+
+    fn stats(&self) -> CgroupStats {
+        let read = |file: &str| std::fs::read_to_string(self.path.join(file)).ok();
+        CgroupStats {
+            memory_peak: read("memory.peak").and_then(|s| s.trim().parse().ok()),
+            cpu_pressure_stall_us: read("cpu.pressure").and_then(|s| parse_pressure_total(&s)),
+            oom_killed: read("memory.events")
+                .and_then(|s| parse_flat_keyed(&s, "oom_kill"))
+                .map_or(false, |n| n > 0),
+        }
+    }
+
+    async fn cleanup(self) {
+        // Processes that escaped the process group of the command would otherwise keep running.
+        let kill = self.path.join("cgroup.kill");
+        drop(tokio::task::spawn_blocking(move || write(&kill, "1")).await);
+
+        // Killed processes leave the cgroup asynchronously.
+        for _ in 0..50 {
+            match std::fs::remove_dir(&self.path) {
+                Ok(()) => break,
+                Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                Err(e) => {
+                    tracing::warn!("Error removing cgroup `{}`: {}", self.path.display(), e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ActionCgroup {
+    fn drop(&mut self) {
+        if self.path.exists() {
+            drop(std::fs::remove_dir(&self.path));
+        }
+    }
+}
+
+/// Adds the resource usage of the cgroup to the stats of the command, and removes the cgroup.
+pub(crate) struct CgroupStatusDecoder<D> {
+    inner: D,
+    cgroup: Option<ActionCgroup>,
+}
+
+impl<D> CgroupStatusDecoder<D> {
+    pub(crate) fn new(inner: D, cgroup: Option<ActionCgroup>) -> Self {
+        Self { inner, cgroup }
+    }
+}
+
+#[async_trait]
+impl<D> StatusDecoder for CgroupStatusDecoder<D>
+where
+    D: StatusDecoder + Send,
+{
+    async fn decode_status(self, status: ExitStatus) -> anyhow::Result<DecodedStatus> {
+        let mut decoded = self.inner.decode_status(status).await?;
+
+        if let Some(cgroup) = self.cgroup {
+            if let DecodedStatus::Status {
+                execution_stats, ..
+            } = &mut decoded
+            {
+                let stats = cgroup.stats();
+                let execution_stats = execution_stats.get_or_insert_with(Default::default);
+                execution_stats.memory_peak = stats.memory_peak;
+                execution_stats.cpu_pressure_stall_us = stats.cpu_pressure_stall_us;
+                execution_stats.oom_killed = stats.oom_killed;
+            }
+            cgroup.cleanup().await;
+        }
+
+        Ok(decoded)
+    }
+
+    async fn cancel(self) -> anyhow::Result<()> {
+        self.inner.cancel().await?;
+        if let Some(cgroup) = self.cgroup {
+            cgroup.cleanup().await;
+        }
+        Ok(())
+    }
+}
+
+/// Moves the current process into the cgroup whose `cgroup.procs` file is `procs`. Only call this
+/// in a forked child.
+pub(crate) unsafe fn enter_cgroup(procs: &CStr) -> io::Result<()> {
+    let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let written = libc::write(fd, b"0".as_ptr().cast(), 1);
+    let err = io::Error::last_os_error();
+    libc::close(fd);
+    if written < 0 {
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn current_cgroup() -> anyhow::Result<PathBuf> {
+    let cgroups = std::fs::read_to_string("/proc/self/cgroup")
+        .context("Error reading `/proc/self/cgroup`")?;
+    let path = cgroups
+        .lines()
+        .find_map(|l| l.strip_prefix("0::"))
+        .context("Not running in a cgroup v2 hierarchy")?
+        .trim_start_matches('/');
+    if path.is_empty() {
+        return Err(anyhow::anyhow!("Running in the root cgroup"));
+    }
+    Ok(Path::new("/sys/fs/cgroup").join(OsStr::new(path)))
+}
+
+fn read_procs(cgroup: &Path) -> anyhow::Result<Vec<libc::pid_t>> {
+    let path = cgroup.join("cgroup.procs");
+    std::fs::read_to_string(&path)
+        .with_context(|| format!("Error reading `{}`", path.display()))?
+        .lines()
+        .map(|l| {
+            l.parse()
+                .with_context(|| format!("Invalid pid in `{}`: `{}`", path.display(), l))
+        })
+        .collect()
+}
+
+fn create_dir(path: &Path) -> anyhow::Result<()> {
+    match std::fs::create_dir(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Error creating cgroup `{}`", path.display())),
+    }
+}
+
+fn write(path: &Path, value: &str) -> anyhow::Result<()> {
+    std::fs::write(path, value)
+        .with_context(|| format!("Error writing `{}` to `{}`", value, path.display()))
+}
+
+/// Returns the `total` of the `some` line of a PSI file, e.g. `cpu.pressure`.
+fn parse_pressure_total(pressure: &str) -> Option<u64> {
+    pressure
+        .lines()
+        .find_map(|l| l.strip_prefix("some "))?
+        .split_whitespace()
+        .find_map(|f| f.strip_prefix("total="))?
+        .parse()
+        .ok()
+}
+
+/// Returns a value of a flat keyed file, e.g. `memory.events`.
+fn parse_flat_keyed(contents: &str, key: &str) -> Option<u64> {
+    contents.lines().find_map(|l| {
+        let (k, v) = l.split_once(' ')?;
+        if k == key {
+            v.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pressure_total() {
+        let pressure = "some avg10=0.00 avg60=0.12 avg300=0.03 total=123456\n\
+            full avg10=0.00 avg60=0.00 avg300=0.00 total=789\n";
+        assert_eq!(Some(123456), parse_pressure_total(pressure));
+        assert_eq!(None, parse_pressure_total(""));
+    }
+
+    #[test]
+    fn test_parse_flat_keyed() {
+        let events = "low 0\nhigh 0\nmax 12\noom 1\noom_kill 1\noom_group_kill 0\n";
+        assert_eq!(Some(1), parse_flat_keyed(events, "oom_kill"));
+        assert_eq!(Some(12), parse_flat_keyed(events, "max"));
+        assert_eq!(None, parse_flat_keyed(events, "missing"));
+    }
+}
@@ -0,0 +1,453 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Tracing of the files a command accesses, using ptrace.
+//!
+//! When tracing, the process we spawn forks once more before `exec`: the child goes on to `exec`
+//! the command as a tracee, and the parent becomes its tracer. The tracer records the path
+//! arguments of the file system calls made by the command and its descendants, then exits with
+//! the same status as the command.
+//!
+//! Like the sandbox, this happens between `fork` and `exec`, so the tracer doesn't allocate, and
+//! writes the paths it records (as absolute, NUL-terminated paths) to a file opened in advance.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use anyhow::Context as _;
+
+pub(crate) struct FileAccessTracer {
+    output: File,
+}
+
+impl FileAccessTracer {
+    pub(crate) fn new(output: &Path) -> anyhow::Result<Self> {
+        if !cfg!(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        )) {
+            return Err(anyhow::anyhow!(
+                "Tracing file accesses is only supported on Linux (x86_64 and aarch64)"
+            ));
+        }
+
+        let output = File::create(output)
+            .with_context(|| format!("Error creating `{}`", output.display()))?;
+
+        Ok(Self { output })
+    }
+
+    /// Splits the current process into the tracer and the tracee. This only returns in the
+    /// tracee, which should then `exec` the command. Only call this in a forked child.
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    pub(crate) unsafe fn apply(&self) -> io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+
+        let pid = libc::fork();
+        if pid < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if pid == 0 {
+            if ptrace(libc::PTRACE_TRACEME, 0, 0, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // Wait for the tracer to set its options.
+            libc::raise(libc::SIGSTOP);
+            return Ok(());
+        }
+
+        let output = self.output.as_raw_fd();
+        close_fds_except(output);
+        let status = linux::trace(pid, output);
+        exit_with_status(status)
+    }
+
+    #[cfg(not(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )))]
+    pub(crate) unsafe fn apply(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Tracing file accesses is not supported on this platform",
+        ))
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+unsafe fn ptrace(
+    request: libc::c_uint,
+    pid: libc::pid_t,
+    addr: usize,
+    data: usize,
+) -> libc::c_long {
+    libc::ptrace(
+        request,
+        pid,
+        addr as *mut libc::c_void,
+        data as *mut libc::c_void,
+    )
+}
+
+/// The tracer must not hold on to any of the fds it inherited: the stdio pipes, and notably the
+/// pipe that the standard library uses to learn whether `exec` succeeded, would not be closed until
+/// the command exits otherwise.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+unsafe fn close_fds_except(keep: libc::c_int) {
+    let keep = keep as libc::c_uint;
+    let closed = (keep == 0 || libc::syscall(libc::SYS_close_range, 0, keep - 1, 0) == 0)
+        && libc::syscall(libc::SYS_close_range, keep + 1, libc::c_uint::MAX, 0) == 0;
+
+    if !closed {
+        for fd in 0..1024 {
+            if fd != keep as libc::c_int {
+                libc::close(fd);
+            }
+        }
+    }
+}
+
+/// Exit the tracer the same way the command did.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+unsafe fn exit_with_status(status: Option<libc::c_int>) -> ! {
+    match status {
+        Some(status) if libc::WIFSIGNALED(status) => {
+            let signal = libc::WTERMSIG(status);
+            libc::signal(signal, libc::SIG_DFL);
+            let mut set = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+            libc::sigaddset(&mut set, signal);
+            libc::sigprocmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut());
+            libc::kill(libc::getpid(), signal);
+            libc::_exit(128 + signal)
+        }
+        Some(status) if libc::WIFEXITED(status) => libc::_exit(libc::WEXITSTATUS(status)),
+        _ => libc::_exit(127),
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod linux {
+    use super::ptrace;
+
+    const PTRACE_GET_SYSCALL_INFO: libc::c_uint = 0x420e;
+    const PTRACE_SYSCALL_INFO_ENTRY: u8 = 1;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc00000b7;
+
+    const PATH_MAX: usize = libc::PATH_MAX as usize;
+
+    /// `struct ptrace_syscall_info`, for syscall entries.
+    #[repr(C)]
+    struct SyscallInfo {
+        op: u8,
+        _pad: [u8; 3],
+        arch: u32,
+        _instruction_pointer: u64,
+        _stack_pointer: u64,
+        nr: u64,
+        args: [u64; 6],
+    }
+
+    /// Returns the directory fd and the address of the path that this syscall accesses.
+    fn path_argument(nr: libc::c_long, args: &[u64; 6]) -> Option<(libc::c_int, u64)> {
+        let at_dirfd = (args[0] as libc::c_int, args[1]);
+        let at_cwd = (libc::AT_FDCWD, args[0]);
+
+        #[cfg(target_arch = "x86_64")]
+        match nr {
+            libc::SYS_open
+            | libc::SYS_stat
+            | libc::SYS_lstat
+            | libc::SYS_access
+            | libc::SYS_readlink => return Some(at_cwd),
+            _ => {}
+        }
+
+        match nr {
+            libc::SYS_execve => Some(at_cwd),
+            libc::SYS_openat
+            | libc::SYS_newfstatat
+            | libc::SYS_statx
+            | libc::SYS_faccessat
+            | libc::SYS_readlinkat
+            | libc::SYS_execveat
+            | libc::SYS_openat2
+            | libc::SYS_faccessat2 => Some(at_dirfd),
+            _ => None,
+        }
+    }
+
+    /// A fixed-size buffer for the records we write.
+    struct Output {
+        fd: libc::c_int,
+        buf: [u8; 16 * 1024],
+        len: usize,
+    }
+
+    impl Output {
+        unsafe fn push(&mut self, data: &[u8]) {
+            if self.len + data.len() > self.buf.len() {
+                self.flush();
+            }
+            if data.len() > self.buf.len() {
+                write_all(self.fd, data);
+                return;
+            }
+            self.buf[self.len..self.len + data.len()].copy_from_slice(data);
+            self.len += data.len();
+        }
+
+        unsafe fn flush(&mut self) {
+            write_all(self.fd, &self.buf[..self.len]);
+            self.len = 0;
+        }
+    }
+
+    unsafe fn write_all(fd: libc::c_int, mut data: &[u8]) {
+        while !data.is_empty() {
+            let written = libc::write(fd, data.as_ptr().cast(), data.len());
+            if written <= 0 {
+                if *libc::__errno_location() == libc::EINTR {
+                    continue;
+                }
+                return;
+            }
+            data = &data[written as usize..];
+        }
+    }
+
+    /// Reads a NUL-terminated string from the tracee, returning its length. Reads stop at page
+    /// boundaries, so that a string at the end of a mapping doesn't make the whole read fail.
+    unsafe fn read_string(pid: libc::pid_t, addr: u64, buf: &mut [u8; PATH_MAX]) -> Option<usize> {
+        const PAGE_SIZE: u64 = 4096;
+
+        let mut len = 0;
+        while len < buf.len() {
+            let start = addr + len as u64;
+            let chunk = std::cmp::min((PAGE_SIZE - start % PAGE_SIZE) as usize, buf.len() - len);
+            let local = libc::iovec {
+                iov_base: buf[len..].as_mut_ptr().cast(),
+                iov_len: chunk,
+            };
+            let remote = libc::iovec {
+                iov_base: start as *mut libc::c_void,
+                iov_len: chunk,
+            };
+            let read = libc::process_vm_readv(pid, &local, 1, &remote, 1, 0);
+            if read <= 0 {
+                return None;
+            }
+            let read = read as usize;
+            if let Some(nul) = buf[len..len + read].iter().position(|c| *c == 0) {
+                return Some(len + nul);
+            }
+            len += read;
+        }
+        None
+    }
+
+    /// Writes `/proc/<pid>/<name>/<fd>` (without the fd if it's `None`) to `buf`, NUL-terminated.
+    fn proc_path(buf: &mut [u8; 64], pid: libc::pid_t, name: &[u8], fd: Option<libc::c_int>) {
+        fn push(buf: &mut [u8; 64], len: &mut usize, data: &[u8]) {
+            buf[*len..*len + data.len()].copy_from_slice(data);
+            *len += data.len();
+        }
+
+        fn push_number(buf: &mut [u8; 64], len: &mut usize, mut n: u32) {
+            let mut digits = [0u8; 10];
+            let mut i = digits.len();
+            loop {
+                i -= 1;
+                digits[i] = b'0' + (n % 10) as u8;
+                n /= 10;
+                if n == 0 {
+                    break;
+                }
+            }
+            push(buf, len, &digits[i..]);
+        }
+
+        let mut len = 0;
+        push(buf, &mut len, b"/proc/");
+        push_number(buf, &mut len, pid as u32);
+        push(buf, &mut len, b"/");
+        push(buf, &mut len, name);
+        if let Some(fd) = fd {
+            push(buf, &mut len, b"/");
+            push_number(buf, &mut len, fd as u32);
+        }
+        push(buf, &mut len, b"\0");
+    }
+
+    unsafe fn record(output: &mut Output, pid: libc::pid_t, dirfd: libc::c_int, addr: u64) {
+        let mut path = [0u8; PATH_MAX];
+        let path_len = match read_string(pid, addr, &mut path) {
+            Some(len) if len > 0 => len,
+            _ => return,
+        };
+        let path = &path[..path_len];
+
+        if path[0] != b'/' {
+            let mut link = [0u8; 64];
+            if dirfd == libc::AT_FDCWD {
+                proc_path(&mut link, pid, b"cwd", None);
+            } else {
+                proc_path(&mut link, pid, b"fd", Some(dirfd));
+            }
+            let mut dir = [0u8; PATH_MAX];
+            let dir_len = libc::readlink(link.as_ptr().cast(), dir.as_mut_ptr().cast(), PATH_MAX);
+            if dir_len <= 0 {
+                return;
+            }
+            output.push(&dir[..dir_len as usize]);
+            output.push(b"/");
+        }
+
+        output.push(path);
+        output.push(b"\0");
+    }
+
+    /// Traces `root` and its descendants until `root` exits, and returns its wait status.
+    pub(super) unsafe fn trace(root: libc::pid_t, output_fd: libc::c_int) -> Option<libc::c_int> {
+        let mut output = Output {
+            fd: output_fd,
+            buf: [0; 16 * 1024],
+            len: 0,
+        };
+
+        let mut status = 0;
+        if libc::waitpid(root, &mut status, libc::__WALL) != root || !libc::WIFSTOPPED(status) {
+            return None;
+        }
+
+        let options = libc::PTRACE_O_TRACESYSGOOD
+            | libc::PTRACE_O_TRACEFORK
+            | libc::PTRACE_O_TRACEVFORK
+            | libc::PTRACE_O_TRACECLONE
+            | libc::PTRACE_O_TRACEEXEC;
+        if ptrace(libc::PTRACE_SETOPTIONS, root, 0, options as usize) < 0 {
+            libc::kill(root, libc::SIGKILL);
+            return None;
+        }
+        ptrace(libc::PTRACE_SYSCALL, root, 0, 0);
+
+        loop {
+            let pid = libc::waitpid(-1, &mut status, libc::__WALL);
+            if pid < 0 {
+                if *libc::__errno_location() == libc::EINTR {
+                    continue;
+                }
+                // We lost track of the command.
+                output.flush();
+                return None;
+            }
+
+            if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+                if pid == root {
+                    output.flush();
+                    return Some(status);
+                }
+                continue;
+            }
+
+            if !libc::WIFSTOPPED(status) {
+                continue;
+            }
+
+            let signal = match libc::WSTOPSIG(status) {
+                s if s == libc::SIGTRAP | 0x80 => {
+                    let mut info: SyscallInfo = std::mem::zeroed();
+                    let ret = ptrace(
+                        PTRACE_GET_SYSCALL_INFO,
+                        pid,
+                        std::mem::size_of::<SyscallInfo>(),
+                        &mut info as *mut SyscallInfo as usize,
+                    );
+                    if ret > 0 && info.op == PTRACE_SYSCALL_INFO_ENTRY && info.arch == AUDIT_ARCH {
+                        if let Some((dirfd, addr)) =
+                            path_argument(info.nr as libc::c_long, &info.args)
+                        {
+                            record(&mut output, pid, dirfd, addr);
+                        }
+                    }
+                    0
+                }
+                // PTRACE_EVENT stops (fork, exec, ...).
+                libc::SIGTRAP if status >> 16 != 0 => 0,
+                // New tracees start with a SIGSTOP.
+                libc::SIGSTOP => 0,
+                s => s,
+            };
+
+            ptrace(libc::PTRACE_SYSCALL, pid, 0, signal as usize);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    #[test]
+    fn test_trace() -> anyhow::Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::process::CommandExt;
+        use std::process::Command;
+
+        use super::*;
+
+        let tempdir = tempfile::tempdir()?;
+        let log = tempdir.path().join("log");
+        std::fs::write(tempdir.path().join("input"), "")?;
+
+        let tracer = FileAccessTracer::new(&log)?;
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "cat input > /dev/null; exit 3"])
+            .current_dir(tempdir.path());
+        unsafe {
+            cmd.pre_exec(move || tracer.apply());
+        }
+        let status = cmd.status()?;
+        assert_eq!(Some(3), status.code());
+
+        let log = std::fs::read(&log)?;
+        let input = std::fs::canonicalize(tempdir.path())?.join("input");
+        assert!(
+            log.split(|c| *c == 0)
+                .any(|p| p == input.as_os_str().as_bytes()),
+            "{}",
+            String::from_utf8_lossy(&log)
+        );
+
+        Ok(())
+    }
+}
@@ -7,9 +7,13 @@
  * of this source tree.
  */
 
+mod cgroup;
 mod command;
+mod file_access;
 mod launch;
+mod sandbox;
 mod service;
 
+pub use cgroup::setup_daemon_cgroup;
 pub use command::run_forkserver;
 pub use launch::launch_forkserver;
@@ -0,0 +1,563 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Filesystem sandbox for local actions.
+//!
+//! The command is started in new user and mount namespaces, where the project root is replaced by
+//! an empty tmpfs into which we bind mount the inputs and writable directories of the action.
+//! Directories that only contain inputs are mounted as a whole rather than file by file. The
+//! original files are reached through a file descriptor for the project root opened before the
+//! tmpfs hides it (and reopened in the new mount namespace, since we can't bind mount from another
+//! one).
+//!
+//! All of this happens between `fork` and `exec` in a process that might have had other threads,
+//! so `SandboxPlan::apply` only makes system calls on data prepared in advance.
+
+use std::collections::BTreeSet;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Once;
+
+use anyhow::Context as _;
+use buck2_forkserver_proto::Sandbox;
+
+/// A bind mount of a path of the project, from the original project root into the sandbox.
+struct BindMount {
+    source: CString,
+    target: CString,
+    is_dir: bool,
+    /// Whether the action may write there. Otherwise, the mount is made read-only.
+    writable: bool,
+    /// The flags of the mount the source is on. Remounting from a user namespace must keep them.
+    locked_flags: libc::c_ulong,
+}
+
+impl BindMount {
+    fn new(
+        source_root: &Path,
+        project_root: &Path,
+        path: &Path,
+        is_dir: bool,
+        writable: bool,
+    ) -> anyhow::Result<Self> {
+        let source = cstring(&source_root.join(path))?;
+        let locked_flags = if writable { 0 } else { mount_flags(&source)? };
+        Ok(Self {
+            source,
+            target: cstring(&project_root.join(path))?,
+            is_dir,
+            writable,
+            locked_flags,
+        })
+    }
+}
+
+pub(crate) struct SandboxPlan {
+    /// Kept open so that the original files remain reachable via `/proc/self/fd`.
+    project_root_fd: std::fs::File,
+    project_root: CString,
+    cwd: CString,
+    uid_map: CString,
+    gid_map: CString,
+    private_tmp: bool,
+    block_network: bool,
+    /// In creation order (parents first).
+    dirs: Vec<CString>,
+    mounts: Vec<BindMount>,
+}
+
+impl SandboxPlan {
+    pub(crate) fn new(sandbox: &Sandbox, cwd: &Path) -> anyhow::Result<Self> {
+        if !cfg!(target_os = "linux") {
+            return Err(anyhow::anyhow!("Sandboxing is only supported on Linux"));
+        }
+
+        let project_root = Path::new(OsStr::from_bytes(&sandbox.project_root));
+        if !cwd.starts_with(project_root) {
+            return Err(anyhow::anyhow!(
+                "Sandboxed command must run in the project root `{}`, not `{}`",
+                project_root.display(),
+                cwd.display()
+            ));
+        }
+
+        let project_root_fd = std::fs::File::open(project_root)
+            .with_context(|| format!("Error opening `{}`", project_root.display()))?;
+        let source_root = Path::new("/proc/self/fd")
+            .join(std::os::unix::io::AsRawFd::as_raw_fd(&project_root_fd).to_string());
+
+        let writable_dirs: Vec<&Path> = sandbox
+            .writable_dirs
+            .iter()
+            .map(|d| Path::new(OsStr::from_bytes(d)))
+            .collect();
+        let is_writable = |path: &Path| writable_dirs.iter().any(|d| path.starts_with(d));
+
+        let mut dirs = BTreeSet::new();
+        let mut mounts = Vec::new();
+
+        // Writable directories go first, and we skip anything inside them since it's visible
+        // already (and creating mount points there would create files in the actual project).
+        for (i, dir) in writable_dirs.iter().enumerate() {
+            let nested = writable_dirs
+                .iter()
+                .enumerate()
+                .any(|(j, d)| j != i && dir.starts_with(d) && (dir != d || j < i));
+            if nested {
+                continue;
+            }
+            add_dir(&mut dirs, dir);
+            mounts.push(BindMount::new(&source_root, project_root, dir, true, true)?);
+        }
+
+        for dir in &sandbox.dirs {
+            let dir = Path::new(OsStr::from_bytes(dir));
+            if !is_writable(dir) {
+                add_dir(&mut dirs, dir);
+            }
+        }
+
+        let rel_cwd = cwd.strip_prefix(project_root)?;
+        if !is_writable(rel_cwd) {
+            add_dir(&mut dirs, rel_cwd);
+        }
+
+        // Inputs that don't exist can't be accessed anyway.
+        let mut visible = BTreeSet::new();
+        for input in &sandbox.inputs {
+            let input = Path::new(OsStr::from_bytes(input));
+            if !is_writable(input) && project_root.join(input).exists() {
+                visible.insert(input.to_owned());
+            }
+        }
+
+        // Directories are mounted as a whole when the action may see all they contain, so that we
+        // don't make a mount for each file of large input directories. That would hide the
+        // writable directories and the directories we create in them, though.
+        let keep_out: Vec<&Path> = writable_dirs
+            .iter()
+            .copied()
+            .chain(
+                dirs.iter()
+                    .filter(|d| !project_root.join(d).exists())
+                    .map(|d| d.as_path()),
+            )
+            .collect();
+        let trees = complete_dirs(project_root, &visible, &keep_out)?;
+
+        for input in &visible {
+            if input.ancestors().skip(1).any(|a| trees.contains(a)) {
+                continue;
+            }
+            let is_dir = trees.contains(input) || project_root.join(input).is_dir();
+            if is_dir {
+                add_dir(&mut dirs, input);
+            } else if let Some(parent) = input.parent() {
+                add_dir(&mut dirs, parent);
+            }
+            mounts.push(BindMount::new(
+                &source_root,
+                project_root,
+                input,
+                is_dir,
+                false,
+            )?);
+        }
+        for tree in &trees {
+            if visible.contains(tree) || tree.ancestors().skip(1).any(|a| trees.contains(a)) {
+                continue;
+            }
+            add_dir(&mut dirs, tree);
+            mounts.push(BindMount::new(
+                &source_root,
+                project_root,
+                tree,
+                true,
+                false,
+            )?);
+        }
+
+        let dirs = dirs
+            .iter()
+            // Those are visible via the writable directories or trees they're in.
+            .filter(|d| !writable_dirs.iter().any(|w| d.starts_with(w) && d != w))
+            .filter(|d| !d.ancestors().skip(1).any(|a| trees.contains(a)))
+            .map(|d| cstring(&project_root.join(d)))
+            .collect::<anyhow::Result<_>>()?;
+
+        // SAFETY: Those never fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+        // A tmpfs on /tmp would hide the project.
+        let private_tmp = !project_root.starts_with("/tmp");
+        if !private_tmp {
+            static WARN_SHARED_TMP: Once = Once::new();
+            WARN_SHARED_TMP.call_once(|| {
+                tracing::warn!(
+                    "The project root `{}` is in /tmp, so sandboxed actions share /tmp with the host",
+                    project_root.display()
+                )
+            });
+        }
+
+        Ok(Self {
+            project_root_fd,
+            project_root: cstring(project_root)?,
+            cwd: cstring(cwd)?,
+            uid_map: CString::new(format!("{} {} 1", uid, uid))?,
+            gid_map: CString::new(format!("{} {} 1", gid, gid))?,
+            private_tmp,
+            block_network: sandbox.block_network,
+            dirs,
+            mounts,
+        })
+    }
+
+    /// Sets up the sandbox in the current process. Only call this in a forked child.
+    #[cfg(target_os = "linux")]
+    pub(crate) unsafe fn apply(&self) -> io::Result<()> {
+        let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS;
+        if self.block_network {
+            flags |= libc::CLONE_NEWNET;
+        }
+        check(libc::unshare(flags))?;
+
+        write_file(b"/proc/self/setgroups\0", b"deny")?;
+        write_file(b"/proc/self/uid_map\0", self.uid_map.as_bytes())?;
+        write_file(b"/proc/self/gid_map\0", self.gid_map.as_bytes())?;
+
+        // Don't propagate any of the mounts below outside of the sandbox.
+        check(libc::mount(
+            std::ptr::null(),
+            b"/\0".as_ptr().cast(),
+            std::ptr::null(),
+            libc::MS_REC | libc::MS_PRIVATE,
+            std::ptr::null(),
+        ))?;
+
+        // The source paths of the mounts go through a descriptor for the project root in the mount
+        // namespace of the forkserver, which can't be bind mounted from, so we replace it with one
+        // for the same directory in our namespace.
+        let fd = libc::open(
+            self.project_root.as_ptr(),
+            libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC,
+        );
+        check(fd)?;
+        let dup = libc::dup3(
+            fd,
+            std::os::unix::io::AsRawFd::as_raw_fd(&self.project_root_fd),
+            libc::O_CLOEXEC,
+        );
+        libc::close(fd);
+        check(dup)?;
+
+        if self.private_tmp {
+            mount_tmpfs(b"/tmp\0".as_ptr().cast())?;
+        }
+        mount_tmpfs(self.project_root.as_ptr())?;
+
+        for dir in &self.dirs {
+            if libc::mkdir(dir.as_ptr(), 0o755) != 0
+                && io::Error::last_os_error().raw_os_error() != Some(libc::EEXIST)
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        for mount in &self.mounts {
+            if !mount.is_dir {
+                let fd = libc::open(
+                    mount.target.as_ptr(),
+                    libc::O_CREAT | libc::O_WRONLY | libc::O_CLOEXEC,
+                    0o644,
+                );
+                check(fd)?;
+                libc::close(fd);
+            }
+            check(libc::mount(
+                mount.source.as_ptr(),
+                mount.target.as_ptr(),
+                std::ptr::null(),
+                libc::MS_BIND | libc::MS_REC,
+                std::ptr::null(),
+            ))?;
+            if !mount.writable {
+                // A bind mount gets the flags of its source, it takes a remount to change them.
+                check(libc::mount(
+                    std::ptr::null(),
+                    mount.target.as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_BIND
+                        | libc::MS_REMOUNT
+                        | libc::MS_RDONLY
+                        | libc::MS_REC
+                        | mount.locked_flags,
+                    std::ptr::null(),
+                ))?;
+            }
+        }
+
+        // The working directory was set before we replaced the project root.
+        check(libc::chdir(self.cwd.as_ptr()))?;
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) unsafe fn apply(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Sandboxing is only supported on Linux",
+        ))
+    }
+}
+
+/// The directories (relative to the project root) that only contain paths in `visible`, or other
+/// such directories, and don't contain any of `keep_out`. Those are visible directories, or
+/// ancestors of visible paths.
+fn complete_dirs(
+    project_root: &Path,
+    visible: &BTreeSet<PathBuf>,
+    keep_out: &[&Path],
+) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let may_mount = |dir: &Path| !keep_out.iter().any(|k| k.starts_with(dir));
+
+    let mut candidates = BTreeSet::new();
+    for path in visible {
+        for ancestor in path.ancestors().skip(1) {
+            if ancestor.as_os_str().is_empty() {
+                break;
+            }
+            candidates.insert(ancestor);
+        }
+    }
+
+    let mut complete: BTreeSet<PathBuf> = visible
+        .iter()
+        .filter(|p| may_mount(p) && project_root.join(p).is_dir())
+        .cloned()
+        .collect();
+
+    // Children before their parents.
+    let mut candidates: Vec<&Path> = candidates.into_iter().collect();
+    candidates.sort_by_key(|p| std::cmp::Reverse(p.components().count()));
+    for dir in candidates {
+        if complete.contains(dir) || !may_mount(dir) {
+            continue;
+        }
+        let path = project_root.join(dir);
+        let mut all_visible = true;
+        for entry in std::fs::read_dir(&path)
+            .with_context(|| format!("Error listing `{}`", path.display()))?
+        {
+            let child = dir.join(entry?.file_name());
+            if !visible.contains(&child) && !complete.contains(&child) {
+                all_visible = false;
+                break;
+            }
+        }
+        if all_visible {
+            complete.insert(dir.to_owned());
+        }
+    }
+
+    Ok(complete)
+}
+
+/// Adds a directory (relative to the project root) and its parents.
+fn add_dir(dirs: &mut BTreeSet<PathBuf>, path: &Path) {
+    for ancestor in path.ancestors() {
+        if ancestor.as_os_str().is_empty() {
+            break;
+        }
+        dirs.insert(ancestor.to_owned());
+    }
+}
+
+/// The `MS_*` flags of the mount `path` is on, among those that a user namespace can't change.
+#[cfg(target_os = "linux")]
+fn mount_flags(path: &CString) -> anyhow::Result<libc::c_ulong> {
+    // SAFETY: `statvfs` only writes to `stat`.
+    let stat = unsafe {
+        let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+        check(libc::statvfs(path.as_ptr(), stat.as_mut_ptr()))
+            .with_context(|| format!("Error getting the mount of `{}`", path.to_string_lossy()))?;
+        stat.assume_init()
+    };
+    Ok([
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ]
+    .iter()
+    .filter(|(st, _)| stat.f_flag & st != 0)
+    .fold(0, |flags, (_, ms)| flags | ms))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mount_flags(_path: &CString) -> anyhow::Result<libc::c_ulong> {
+    Ok(0)
+}
+
+fn cstring(path: &Path) -> anyhow::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .with_context(|| format!("Invalid path: `{}`", path.display()))
+}
+
+#[cfg(target_os = "linux")]
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn mount_tmpfs(target: *const libc::c_char) -> io::Result<()> {
+    check(libc::mount(
+        b"tmpfs\0".as_ptr().cast(),
+        target,
+        b"tmpfs\0".as_ptr().cast(),
+        libc::MS_NOSUID | libc::MS_NODEV,
+        b"mode=0755\0".as_ptr().cast(),
+    ))
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn write_file(path: &[u8], data: &[u8]) -> io::Result<()> {
+    let fd = libc::open(path.as_ptr().cast(), libc::O_WRONLY | libc::O_CLOEXEC);
+    check(fd)?;
+    let written = libc::write(fd, data.as_ptr().cast(), data.len());
+    libc::close(fd);
+    if written < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() -> anyhow::Result<()> {
+        if !cfg!(target_os = "linux") {
+            return Ok(());
+        }
+
+        let tempdir = tempfile::tempdir()?;
+        let root = tempdir.path();
+        std::fs::create_dir_all(root.join("src/sub"))?;
+        std::fs::write(root.join("src/sub/a.h"), "")?;
+        std::fs::create_dir_all(root.join("out/gen"))?;
+        std::fs::write(root.join("out/gen/b.h"), "")?;
+        // Not all of `lib` is an input, so it can't be mounted as a whole.
+        std::fs::create_dir_all(root.join("lib"))?;
+        std::fs::write(root.join("lib/c.h"), "")?;
+        std::fs::write(root.join("lib/hidden.h"), "")?;
+
+        let sandbox = Sandbox {
+            project_root: root.as_os_str().as_bytes().to_vec(),
+            inputs: vec![
+                b"src/sub/a.h".to_vec(),
+                b"out/gen/b.h".to_vec(),
+                b"lib/c.h".to_vec(),
+                b"missing.h".to_vec(),
+            ],
+            dirs: vec![b"empty".to_vec()],
+            writable_dirs: vec![b"out".to_vec(), b"out/gen".to_vec()],
+            block_network: false,
+        };
+        let plan = SandboxPlan::new(&sandbox, root)?;
+
+        let rel = |c: &CString| {
+            Path::new(OsStr::from_bytes(c.as_bytes()))
+                .strip_prefix(root)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned()
+        };
+        assert_eq!(
+            vec!["empty", "lib", "out", "src"],
+            plan.dirs.iter().map(rel).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                ("out".to_owned(), true, true),
+                ("lib/c.h".to_owned(), false, false),
+                ("src".to_owned(), true, false),
+            ],
+            plan.mounts
+                .iter()
+                .map(|m| (rel(&m.target), m.is_dir, m.writable))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(!root.starts_with("/tmp"), plan.private_tmp);
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_inputs_are_read_only() -> anyhow::Result<()> {
+        use std::os::unix::process::CommandExt;
+        use std::process::Command;
+
+        let tempdir = tempfile::tempdir()?;
+        let root = tempdir.path();
+        std::fs::create_dir_all(root.join("src"))?;
+        std::fs::write(root.join("src/a.h"), "a")?;
+        std::fs::create_dir_all(root.join("out"))?;
+
+        let sandbox = Sandbox {
+            project_root: root.as_os_str().as_bytes().to_vec(),
+            inputs: vec![b"src/a.h".to_vec()],
+            dirs: Vec::new(),
+            writable_dirs: vec![b"out".to_vec()],
+            block_network: false,
+        };
+        let plan = SandboxPlan::new(&sandbox, root)?;
+        let input = cstring(&root.join("src/a.h"))?;
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo b > out/b.h"]).current_dir(root);
+        unsafe {
+            cmd.pre_exec(move || {
+                plan.apply()?;
+                let fd = libc::open(input.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                check(fd)?;
+                libc::close(fd);
+                Ok(())
+            });
+        }
+        let err = cmd.status().expect_err("input should not be writable");
+        assert_eq!(Some(libc::EROFS), err.raw_os_error(), "{}", err);
+        assert_eq!("a", std::fs::read_to_string(root.join("src/a.h"))?);
+
+        // Writable directories remain writable.
+        let plan = SandboxPlan::new(&sandbox, root)?;
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo b > out/b.h"]).current_dir(root);
+        unsafe {
+            cmd.pre_exec(move || plan.apply());
+        }
+        assert!(cmd.status()?.success());
+        assert_eq!("b\n", std::fs::read_to_string(root.join("out/b.h"))?);
+
+        Ok(())
+    }
+}
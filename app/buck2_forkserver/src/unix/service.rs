@@ -10,8 +10,10 @@
 use std::ffi::OsStr;
 use std::fs::File;
 use std::fs::OpenOptions;
+use std::io;
 use std::io::Write;
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
 use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -49,8 +51,14 @@ use crate::run::status_decoder::DefaultStatusDecoder;
 use crate::run::status_decoder::MiniperfStatusDecoder;
 use crate::run::stream_command_events;
 use crate::run::timeout_into_cancellation;
+use crate::run::CommandEvent;
 use crate::run::DefaultKillProcess;
 use crate::run::GatherOutputStatus;
+use crate::unix::cgroup::enter_cgroup;
+use crate::unix::cgroup::CgroupPool;
+use crate::unix::cgroup::CgroupStatusDecoder;
+use crate::unix::file_access::FileAccessTracer;
+use crate::unix::sandbox::SandboxPlan;
 
 // Not quite BoxStream: it has to be Sync (...)
 type RunStream =
@@ -61,6 +69,9 @@ pub struct UnixForkserverService {
 
     /// State for Miniperf.
     miniperf: Option<MiniperfContainer>,
+
+    /// `None` if the daemon did not set up cgroups.
+    cgroup_pool: Option<CgroupPool>,
 }
 
 impl UnixForkserverService {
@@ -73,6 +84,7 @@ impl UnixForkserverService {
         Ok(Self {
             log_reload_handle,
             miniperf,
+            cgroup_pool: CgroupPool::new(),
         })
     }
 }
@@ -114,6 +126,10 @@ impl Forkserver for UnixForkserverService {
                 timeout,
                 enable_miniperf,
                 std_redirects,
+                sandbox,
+                file_access_log,
+                cgroup,
+                termination_grace_period,
             } = msg;
 
             let exe = OsStr::from_bytes(&exe);
@@ -124,6 +140,10 @@ impl Forkserver for UnixForkserverService {
                 .map(|t| t.try_into_duration())
                 .transpose()
                 .context("Invalid timeout")?;
+            let termination_grace_period = termination_grace_period
+                .map(|t| t.try_into_duration())
+                .transpose()
+                .context("Invalid termination grace period")?;
 
             let exe = maybe_absolutize_exe(exe, cwd)?;
 
@@ -159,13 +179,66 @@ impl Forkserver for UnixForkserverService {
                 }
             }
 
+            let cgroup = match cgroup {
+                Some(limits) => match &self.cgroup_pool {
+                    Some(pool) => Some(pool.create(limits).await.context("Error creating cgroup")?),
+                    None => None,
+                },
+                None => None,
+            };
+
+            // This must happen first: we can't change cgroups once in the sandbox.
+            if let Some(cgroup) = &cgroup {
+                let procs = cgroup.procs();
+                // SAFETY: `enter_cgroup` only makes system calls.
+                unsafe {
+                    cmd.pre_exec(move || {
+                        enter_cgroup(&procs).map_err(|e| {
+                            io::Error::new(e.kind(), format!("Error entering cgroup: {}", e))
+                        })
+                    });
+                }
+            }
+
+            if let Some(sandbox) = sandbox {
+                let plan =
+                    SandboxPlan::new(&sandbox, cwd.as_path()).context("Error preparing sandbox")?;
+                // SAFETY: `apply` only makes system calls.
+                unsafe {
+                    cmd.pre_exec(move || {
+                        plan.apply().map_err(|e| {
+                            io::Error::new(e.kind(), format!("Error setting up sandbox: {}", e))
+                        })
+                    });
+                }
+            }
+
+            if let Some(file_access_log) = file_access_log {
+                let tracer = FileAccessTracer::new(Path::new(OsStr::from_bytes(&file_access_log)))?;
+                // SAFETY: `apply` only makes system calls.
+                unsafe {
+                    cmd.pre_exec(move || {
+                        tracer.apply().map_err(|e| {
+                            io::Error::new(e.kind(), format!("Error tracing file accesses: {}", e))
+                        })
+                    });
+                }
+            }
+
             let mut cmd = prepare_command(cmd);
             let stream_stdio = std_redirects.is_none();
             if let Some(std_redirects) = std_redirects {
                 cmd.stdout(File::create(OsStr::from_bytes(&std_redirects.stdout))?);
                 cmd.stderr(File::create(OsStr::from_bytes(&std_redirects.stderr))?);
+                if let Some(stdin) = &std_redirects.stdin {
+                    cmd.stdin(File::open(OsStr::from_bytes(stdin))?);
+                }
             }
             let child = cmd.spawn();
+            let spawned = match &child {
+                Ok(child) => child.id().map(CommandEvent::Spawned),
+                Err(_) => None,
+            };
 
             let timeout = timeout_into_cancellation(timeout);
 
@@ -175,21 +248,24 @@ impl Forkserver for UnixForkserverService {
                 Some(out) => stream_command_events(
                     child,
                     cancellation,
-                    MiniperfStatusDecoder::new(out),
+                    CgroupStatusDecoder::new(MiniperfStatusDecoder::new(out), cgroup),
                     DefaultKillProcess,
                     stream_stdio,
+                    termination_grace_period,
                 )?
                 .left_stream(),
                 None => stream_command_events(
                     child,
                     cancellation,
-                    DefaultStatusDecoder,
+                    CgroupStatusDecoder::new(DefaultStatusDecoder, cgroup),
                     DefaultKillProcess,
                     stream_stdio,
+                    termination_grace_period,
                 )?
                 .right_stream(),
             };
 
+            let stream = futures::stream::iter(spawned.map(anyhow::Ok)).chain(stream);
             let stream = encode_event_stream(stream);
             Ok(Box::pin(stream) as _)
         })
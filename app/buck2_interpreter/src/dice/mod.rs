@@ -17,6 +17,7 @@
 //! onto the dice graph).
 
 pub mod starlark_debug;
+pub mod starlark_limits;
 pub mod starlark_profiler;
 pub mod starlark_provider;
 pub mod starlark_types;
@@ -0,0 +1,89 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Limits on the resources used by a Starlark evaluation, configured in the root
+//! cell buckconfig:
+//!
+//! ```ini
+//! [buck2]
+//! loading_max_instructions = 100000000
+//! loading_max_heap_bytes = 1000000000
+//! analysis_max_instructions = 100000000
+//! analysis_max_heap_bytes = 1000000000
+//! ```
+//!
+//! All limits are unset by default. There is no wall-clock limit: the result of an evaluation
+//! is cached by DICE, so it must not depend on how busy the machine was.
+
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+use dice::DiceComputations;
+use dupe::Dupe;
+use starlark::eval::Evaluator;
+
+/// The kind of evaluation which limits are configured for.
+#[derive(Debug, Copy, Clone, Dupe, Eq, PartialEq)]
+pub enum StarlarkEvalKind {
+    /// Evaluation of `BUCK`, `PACKAGE` and `.bzl` files.
+    Loading,
+    /// Evaluation of rule implementations.
+    Analysis,
+}
+
+impl StarlarkEvalKind {
+    fn config_prefix(self) -> &'static str {
+        match self {
+            StarlarkEvalKind::Loading => "loading",
+            StarlarkEvalKind::Analysis => "analysis",
+        }
+    }
+}
+
+/// Limits applied to the [`Evaluator`] of a Starlark evaluation.
+#[derive(Debug, Default, Copy, Clone, Dupe, Eq, PartialEq)]
+pub struct StarlarkEvalLimits {
+    /// Maximum number of bytecode instructions executed.
+    pub max_instructions: Option<u64>,
+    /// Maximum size of the Starlark heap.
+    pub max_heap_bytes: Option<usize>,
+}
+
+impl StarlarkEvalLimits {
+    /// No limits.
+    pub fn none() -> StarlarkEvalLimits {
+        StarlarkEvalLimits::default()
+    }
+
+    /// Read the limits for `kind` from the root cell buckconfig.
+    pub async fn from_buckconfig(
+        ctx: &DiceComputations,
+        kind: StarlarkEvalKind,
+    ) -> anyhow::Result<StarlarkEvalLimits> {
+        let root_cell = ctx.get_cell_resolver().await?.root_cell();
+        let key = |name: &str| format!("{}_{}", kind.config_prefix(), name);
+        Ok(StarlarkEvalLimits {
+            max_instructions: ctx
+                .parse_legacy_config_property(root_cell, "buck2", &key("max_instructions"))
+                .await?,
+            max_heap_bytes: ctx
+                .parse_legacy_config_property(root_cell, "buck2", &key("max_heap_bytes"))
+                .await?,
+        })
+    }
+
+    /// Set the limits on a newly created evaluator.
+    pub fn apply(&self, eval: &mut Evaluator) {
+        if let Some(max_instructions) = self.max_instructions {
+            eval.set_max_instructions(max_instructions);
+        }
+        if let Some(max_heap_bytes) = self.max_heap_bytes {
+            eval.set_max_heap_bytes(max_heap_bytes);
+        }
+    }
+}
@@ -13,6 +13,7 @@ use starlark::environment::Module;
 use starlark::eval::Evaluator;
 
 use crate::dice::starlark_debug::HasStarlarkDebugger;
+use crate::dice::starlark_limits::StarlarkEvalLimits;
 use crate::factory::StarlarkEvaluatorProvider;
 use crate::starlark_debug::StarlarkDebugController;
 use crate::starlark_profiler::StarlarkProfilerOrInstrumentation;
@@ -26,11 +27,14 @@ use crate::starlark_profiler::StarlarkProfilerOrInstrumentation;
 ///
 /// The description is used for the thread name when debugging.
 ///
+/// The `limits` are applied to every evaluator the provider makes.
+///
 /// The provided closure will be invoked and passed an appropriate
 /// StarlarkEvaluatorProvider.
 pub async fn with_starlark_eval_provider<R>(
     ctx: &DiceComputations,
     profiler_instrumentation: &mut StarlarkProfilerOrInstrumentation<'_>,
+    limits: StarlarkEvalLimits,
     description: String,
     closure: impl FnOnce(&mut dyn StarlarkEvaluatorProvider) -> anyhow::Result<R>,
 ) -> anyhow::Result<R> {
@@ -43,11 +47,13 @@ pub async fn with_starlark_eval_provider<R>(
     struct EvalProvider<'a, 'b> {
         profiler: &'a mut StarlarkProfilerOrInstrumentation<'b>,
         debugger: Option<Box<dyn StarlarkDebugController>>,
+        limits: StarlarkEvalLimits,
     }
 
     impl StarlarkEvaluatorProvider for EvalProvider<'_, '_> {
         fn make<'v, 'a>(&mut self, module: &'v Module) -> anyhow::Result<Evaluator<'v, 'a>> {
             let mut eval = Evaluator::new(module);
+            self.limits.apply(&mut eval);
             self.profiler.initialize(&mut eval)?;
             if let Some(v) = &mut self.debugger {
                 v.initialize(&mut eval)?;
@@ -68,6 +74,7 @@ pub async fn with_starlark_eval_provider<R>(
         let mut provider = EvalProvider {
             profiler: profiler_instrumentation,
             debugger,
+            limits,
         };
 
         // If we're debugging, we need to move this to a tokio blocking task.
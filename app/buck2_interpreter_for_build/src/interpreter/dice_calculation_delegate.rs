@@ -32,6 +32,8 @@ use buck2_core::cells::name::CellName;
 use buck2_core::package::PackageLabel;
 use buck2_events::dispatch::span;
 use buck2_events::dispatch::span_async;
+use buck2_interpreter::dice::starlark_limits::StarlarkEvalKind;
+use buck2_interpreter::dice::starlark_limits::StarlarkEvalLimits;
 use buck2_interpreter::dice::starlark_provider::with_starlark_eval_provider;
 use buck2_interpreter::file_loader::LoadedModule;
 use buck2_interpreter::file_loader::ModuleDeps;
@@ -262,10 +264,13 @@ impl<'c> DiceCalculationDelegate<'c> {
         let loaded_modules = deps.get_loaded_modules();
         let buckconfig = self.get_legacy_buck_config_for_starlark().await?;
         let root_buckconfig = self.ctx.get_legacy_root_config_on_dice().await?;
+        let limits =
+            StarlarkEvalLimits::from_buckconfig(self.ctx, StarlarkEvalKind::Loading).await?;
 
         with_starlark_eval_provider(
             self.ctx,
             &mut StarlarkProfilerOrInstrumentation::disabled(),
+            limits,
             format!("load:{}", &starlark_file),
             move |provider| {
                 let evaluation = self
@@ -344,9 +349,12 @@ impl<'c> DiceCalculationDelegate<'c> {
 
         let buckconfig = self.get_legacy_buck_config_for_starlark().await?;
         let root_buckconfig = self.ctx.get_legacy_root_config_on_dice().await?;
+        let limits =
+            StarlarkEvalLimits::from_buckconfig(self.ctx, StarlarkEvalKind::Loading).await?;
         with_starlark_eval_provider(
             self.ctx,
             &mut StarlarkProfilerOrInstrumentation::disabled(),
+            limits,
             format!("load:{}", path),
             move |provider| {
                 self.configs
@@ -469,6 +477,8 @@ impl<'c> DiceCalculationDelegate<'c> {
             .await?;
         let buckconfig = self.get_legacy_buck_config_for_starlark().await?;
         let root_buckconfig = self.ctx.get_legacy_root_config_on_dice().await?;
+        let limits =
+            StarlarkEvalLimits::from_buckconfig(self.ctx, StarlarkEvalKind::Loading).await?;
         let module_id = build_file_path.to_string();
         let cell_str = build_file_path.cell().as_str().to_owned();
         let start_event = buck2_data::LoadBuildFileStart {
@@ -478,6 +488,7 @@ impl<'c> DiceCalculationDelegate<'c> {
         with_starlark_eval_provider(
             self.ctx,
             profiler_instrumentation,
+            limits,
             format!("load_buildfile:{}", &package),
             move |provider| {
                 span(start_event, move || {
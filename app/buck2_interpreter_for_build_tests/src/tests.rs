@@ -11,6 +11,7 @@ use buck2_build_api::interpreter::rule_defs::register_rule_defs;
 use buck2_common::dice::cells::SetCellResolver;
 use buck2_common::dice::data::testing::SetTestingIoProvider;
 use buck2_common::legacy_configs::dice::SetLegacyConfigs;
+use buck2_common::legacy_configs::testing::legacy_buck_config_from_entries;
 use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_common::legacy_configs::LegacyBuckConfigs;
 use buck2_core::bzl::ImportPath;
@@ -43,10 +44,17 @@ use dice::UserComputationData;
 use dupe::Dupe;
 use indoc::indoc;
 
-fn empty_configs(resolver: &CellResolver) -> LegacyBuckConfigs {
+fn configs(resolver: &CellResolver, root_config: LegacyBuckConfig) -> LegacyBuckConfigs {
     let config = resolver
         .cells()
-        .map(|(name, _)| (name, LegacyBuckConfig::empty()))
+        .map(|(name, _)| {
+            let config = if name == resolver.root_cell() {
+                root_config.dupe()
+            } else {
+                LegacyBuckConfig::empty()
+            };
+            (name, config)
+        })
         .collect();
 
     LegacyBuckConfigs::new(config)
@@ -57,6 +65,13 @@ pub(crate) fn root_cell() -> CellName {
 }
 
 pub(crate) async fn calculation(fs: &ProjectRootTemp) -> DiceTransaction {
+    calculation_with_root_config(fs, LegacyBuckConfig::empty()).await
+}
+
+async fn calculation_with_root_config(
+    fs: &ProjectRootTemp,
+    root_config: LegacyBuckConfig,
+) -> DiceTransaction {
     let mut dice = Dice::builder();
     dice.set(EventDispatcher::null());
     dice.set_testing_io_provider(fs);
@@ -71,7 +86,7 @@ pub(crate) async fn calculation(fs: &ProjectRootTemp) -> DiceTransaction {
         CellName::testing_new("root"),
         CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("".to_owned())),
     );
-    let cell_configs = empty_configs(&resolver);
+    let cell_configs = configs(&resolver, root_config);
 
     ctx.set_cell_resolver(resolver.dupe()).unwrap();
     ctx.set_interpreter_context(
@@ -246,3 +261,31 @@ async fn test_eval_build_file() {
 
     assert_eq!(vec!["invoke_some-exported", "java"], target_names);
 }
+
+#[tokio::test]
+async fn test_eval_build_file_max_instructions() {
+    let fs = ProjectRootTemp::new().unwrap();
+    fs.write_file(
+        "pkg/BUCK",
+        indoc!(
+            r#"
+                numbers = [i for i in range(1000000)]
+            "#
+        ),
+    );
+
+    let config =
+        legacy_buck_config_from_entries([("buck2", "loading_max_instructions", "1000")]).unwrap();
+    let ctx = calculation_with_root_config(&fs, config).await;
+
+    let err = ctx
+        .get_interpreter_results(PackageLabel::testing_parse("root//pkg"))
+        .await
+        .unwrap_err();
+    let err = format!("{:#}", err);
+    assert!(
+        err.contains("Evaluation exceeded the limit of 1000 executed instructions"),
+        "{}",
+        err
+    );
+}
@@ -184,6 +184,16 @@ pub struct Buck2OssReConfiguration {
     pub capabilities: Option<bool>,
     /// The instance name to use in requests.
     pub instance_name: Option<String>,
+    /// Whether to compress CAS transfers, using a compressor advertised by the RBE backend in its
+    /// capabilities. This has no effect if capabilities are not queried.
+    pub compression: Option<bool>,
+    /// Address of an HTTP/1.1 cache (e.g. bazel-remote) serving `/ac/<hash>` and `/cas/<hash>`.
+    /// When set, the action cache and CAS are accessed over HTTP instead of gRPC, and remote
+    /// execution is not available. This must include the scheme (`http://` or `https://`).
+    ///
+    /// This can contain environment variables using shell interpolation syntax (i.e. $VAR). They
+    /// will be substituted before using the value.
+    pub http_cache_address: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Allocative)]
@@ -237,6 +247,9 @@ impl Buck2OssReConfiguration {
                 .unwrap_or_default(), // Empty list is as good None.
             capabilities: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "capabilities")?,
             instance_name: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "instance_name")?,
+            compression: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "compression")?,
+            http_cache_address: legacy_config
+                .parse(BUCK2_RE_CLIENT_CFG_SECTION, "http_cache_address")?,
         })
     }
 }
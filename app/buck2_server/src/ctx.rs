@@ -13,6 +13,7 @@ use std::io::BufWriter;
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::Duration;
 
 use allocative::Allocative;
 use anyhow::Context;
@@ -72,13 +73,16 @@ use buck2_execute::execute::blocking::SetBlockingExecutor;
 use buck2_execute::execute::dice_data::set_fallback_executor_config;
 use buck2_execute::execute::dice_data::SetCommandExecutor;
 use buck2_execute::execute::dice_data::SetReClient;
+use buck2_execute::execute::request::LocalResourceLimits;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
+use buck2_execute::local_action_cache::LocalActionCache;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::materialize::materializer::SetMaterializer;
 use buck2_execute::re::client::RemoteExecutionClient;
 use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute::re::manager::ReConnectionObserver;
 use buck2_execute_impl::executors::worker::WorkerPool;
+use buck2_execute_impl::executors::worker::WorkerPoolConfig;
 use buck2_execute_impl::low_pass_filter::LowPassFilter;
 use buck2_execute_impl::re::paranoid_download::ParanoidDownloader;
 use buck2_file_watcher::file_watcher::FileWatcher;
@@ -116,6 +120,7 @@ use dupe::Dupe;
 use gazebo::prelude::SliceExt;
 use host_sharing::HostSharingBroker;
 use host_sharing::HostSharingStrategy;
+use host_sharing::ResourceCapacity;
 use more_futures::cancellation::ExplicitCancellationContext;
 use tokio::sync::Mutex;
 use tracing::warn;
@@ -414,6 +419,7 @@ impl<'a> ServerCommandContext<'a> {
                 .map_or(false, |opts| opts.keep_going),
             http_client: self.base_context.daemon.http_client.dupe(),
             paranoid: self.base_context.daemon.paranoid.dupe(),
+            local_action_cache: self.base_context.daemon.local_action_cache.dupe(),
             spawner: self.base_context.spawner.dupe(),
         }
     }
@@ -518,6 +524,7 @@ struct DiceCommandDataProvider {
     keep_going: bool,
     http_client: CountingHttpClient,
     paranoid: Option<ParanoidDownloader>,
+    local_action_cache: Option<Arc<LocalActionCache>>,
     spawner: Arc<BuckSpawner>,
 }
 
@@ -556,13 +563,48 @@ impl DiceDataProvider for DiceCommandDataProvider {
             .unwrap_or_else(RolloutPercentage::always)
             .roll();
 
+        let allow_cache_uploads = root_config
+            .parse::<bool>("buck2", "allow_cache_uploads")?
+            .unwrap_or(true);
+
+        let sandbox_local_actions = root_config
+            .parse::<bool>("buck2", "sandbox_local_actions")?
+            .unwrap_or(false);
+
+        let sandbox_block_network = root_config
+            .parse::<bool>("buck2", "sandbox_block_network")?
+            .unwrap_or(false);
+
+        let trace_undeclared_inputs = root_config
+            .parse::<bool>("buck2", "trace_undeclared_inputs")?
+            .unwrap_or(false);
+
+        let local_action_cgroups = root_config
+            .parse::<bool>("buck2", "local_action_cgroups")?
+            .unwrap_or(false);
+
         let executor_global_knobs = ExecutorGlobalKnobs {
             enable_miniperf,
             log_action_keys,
+            allow_cache_uploads,
+            sandbox_local_actions,
+            sandbox_block_network,
+            trace_undeclared_inputs,
+            local_action_cgroups,
+        };
+
+        let resource_capacity = ResourceCapacity {
+            memory: root_config.parse("buck2", "local_memory_capacity")?,
+            custom: root_config
+                .parse("buck2", "local_custom_resources")?
+                .unwrap_or_default(),
         };
 
-        let host_sharing_broker =
-            HostSharingBroker::new(HostSharingStrategy::SmallerTasksFirst, concurrency);
+        let host_sharing_broker = HostSharingBroker::with_resources(
+            HostSharingStrategy::SmallerTasksFirst,
+            concurrency,
+            resource_capacity,
+        );
 
         // We use the job count for the low pass filter too. The low pass filter prevents sending
         // RE-eligile tasks to local if their concurrency is higher than our threshold. While it
@@ -593,6 +635,13 @@ impl DiceDataProvider for DiceCommandDataProvider {
             .parse::<bool>("buck2", "enforce_re_timeouts")?
             .unwrap_or(true);
 
+        run_action_knobs.local_resource_limits = LocalResourceLimits {
+            memory_max: root_config.parse("buck2", "local_action_memory_max")?,
+            cpu_max_millis: root_config
+                .parse::<f64>("buck2", "local_action_cpu_max")?
+                .map(|cpus| (cpus * 1000.0) as u64),
+        };
+
         let mut data = UserComputationData {
             data,
             tracker: Arc::new(BuckDiceTracker::new(self.events.dupe())),
@@ -601,7 +650,16 @@ impl DiceDataProvider for DiceCommandDataProvider {
             ..Default::default()
         };
 
-        let worker_pool = Arc::new(WorkerPool::new());
+        let worker_pool = Arc::new(WorkerPool::new(WorkerPoolConfig {
+            max_instances: root_config
+                .parse("buck2", "worker_max_instances")?
+                .unwrap_or(1),
+            idle_timeout: root_config
+                .parse("buck2", "worker_idle_timeout_s")?
+                .map(Duration::from_secs),
+            max_requests: root_config.parse("buck2", "worker_max_requests")?,
+            max_rss_bytes: root_config.parse("buck2", "worker_max_rss_bytes")?,
+        }));
 
         let critical_path_backend = root_config
             .parse("buck2", "critical_path_backend2")?
@@ -627,6 +685,7 @@ impl DiceDataProvider for DiceCommandDataProvider {
                 .to_owned(),
             worker_pool,
             self.paranoid.dupe(),
+            self.local_action_cache.dupe(),
         )));
         data.set_blocking_executor(self.blocking_executor.dupe());
         data.set_http_client(self.http_client.dupe());
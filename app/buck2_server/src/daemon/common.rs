@@ -34,12 +34,15 @@ use buck2_execute::execute::prepared::PreparedCommandExecutor;
 use buck2_execute::execute::prepared::PreparedCommandOptionalExecutor;
 use buck2_execute::execute::request::ExecutorPreference;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
+use buck2_execute::local_action_cache::LocalActionCache;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute_impl::executors::action_cache::ActionCacheChecker;
 use buck2_execute_impl::executors::caching::CacheUploader;
 use buck2_execute_impl::executors::hybrid::HybridExecutor;
 use buck2_execute_impl::executors::local::LocalExecutor;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCacheChecker;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCacheUploader;
 use buck2_execute_impl::executors::re::ReExecutor;
 use buck2_execute_impl::executors::stacked::StackedExecutor;
 use buck2_execute_impl::executors::worker::WorkerPool;
@@ -83,6 +86,7 @@ pub struct CommandExecutorFactory {
     project_root: ProjectRoot,
     worker_pool: Arc<WorkerPool>,
     paranoid: Option<ParanoidDownloader>,
+    local_action_cache: Option<Arc<LocalActionCache>>,
 }
 
 impl CommandExecutorFactory {
@@ -101,6 +105,7 @@ impl CommandExecutorFactory {
         project_root: ProjectRoot,
         worker_pool: Arc<WorkerPool>,
         paranoid: Option<ParanoidDownloader>,
+        local_action_cache: Option<Arc<LocalActionCache>>,
     ) -> Self {
         Self {
             re_connection,
@@ -117,6 +122,7 @@ impl CommandExecutorFactory {
             project_root,
             worker_pool,
             paranoid,
+            local_action_cache,
         }
     }
 }
@@ -306,7 +312,9 @@ impl HasCommandExecutor for CommandExecutorFactory {
                     cache_checker_new()
                 };
 
-                let cache_uploader = if disable_caching {
+                let cache_uploader = if disable_caching
+                    || !self.executor_global_knobs.allow_cache_uploads
+                {
                     Arc::new(NoOpCacheUploader {}) as _
                 } else if let CacheUploadBehavior::Enabled { max_bytes } = cache_upload_behavior {
                     Arc::new(CacheUploader {
@@ -340,11 +348,35 @@ impl HasCommandExecutor for CommandExecutorFactory {
             }
         };
 
-        let response = response
+        let mut response = response
             .with_context(|| format!(
 "The desired execution strategy (`{:?}`) is incompatible with the executor config that was selected: {:?}",
 self.strategy, executor_config))?;
 
+        if let Some(cache) = &self.local_action_cache {
+            // The local cache is consulted before any remote cache, and stores results before
+            // they get uploaded.
+            if !self.skip_cache_read {
+                response.cache_checker = Arc::new(StackedExecutor {
+                    optional: LocalActionCacheChecker {
+                        artifact_fs: artifact_fs.clone(),
+                        materializer: self.materializer.dupe(),
+                        blocking_executor: self.blocking_executor.dupe(),
+                        cache: cache.dupe(),
+                    },
+                    fallback: response.cache_checker,
+                });
+            }
+            if !self.skip_cache_write {
+                response.cache_uploader = Arc::new(LocalActionCacheUploader {
+                    artifact_fs: artifact_fs.clone(),
+                    blocking_executor: self.blocking_executor.dupe(),
+                    cache: cache.dupe(),
+                    inner: response.cache_uploader,
+                });
+            }
+        }
+
         Ok(response)
     }
 }
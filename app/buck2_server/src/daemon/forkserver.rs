@@ -27,6 +27,21 @@ pub async fn maybe_launch_forkserver(
         return Ok(None);
     }
 
+    // This must happen before launching the forkserver, which ends up in the same cgroup as us.
+    if root_config
+        .parse::<bool>("buck2", "local_action_cgroups")?
+        .unwrap_or(false)
+    {
+        if let Err(e) =
+            tokio::task::spawn_blocking(buck2_forkserver::unix::setup_daemon_cgroup).await?
+        {
+            tracing::warn!(
+                "Cgroups are not available, commands will run without resource limits: {:#}",
+                e
+            );
+        }
+    }
+
     let exe = std::env::current_exe().context("Cannot access current_exe")?;
     Some(
         buck2_forkserver::unix::launch_forkserver(exe, &["forkserver"], forkserver_state_dir).await,
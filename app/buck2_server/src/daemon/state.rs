@@ -44,6 +44,7 @@ use buck2_events::EventSinkWithStats;
 use buck2_execute::digest_config::DigestConfig;
 use buck2_execute::execute::blocking::BlockingExecutor;
 use buck2_execute::execute::blocking::BuckBlockingExecutor;
+use buck2_execute::local_action_cache::LocalActionCache;
 use buck2_execute::materialize::materializer::MaterializationMethod;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::manager::ReConnectionManager;
@@ -78,6 +79,10 @@ use crate::daemon::forkserver::maybe_launch_forkserver;
 use crate::daemon::io_provider::create_io_provider;
 use crate::daemon::panic::DaemonStatePanicDiceDump;
 use crate::daemon::server::BuckdServerInitPreferences;
+
+/// Default size limit of the local action cache, when enabled.
+const DEFAULT_LOCAL_ACTION_CACHE_MAX_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+
 /// For a buckd process there is a single DaemonState created at startup and never destroyed.
 #[derive(Allocative)]
 pub struct DaemonState {
@@ -163,6 +168,10 @@ pub struct DaemonStateData {
     /// If enabled, paranoid RE downloads.
     pub paranoid: Option<ParanoidDownloader>,
 
+    /// If enabled, the on-disk cache of locally executed actions.
+    #[allocative(skip)]
+    pub local_action_cache: Option<Arc<LocalActionCache>>,
+
     /// Spawner
     pub spawner: Arc<BuckSpawner>,
 
@@ -484,6 +493,23 @@ impl DaemonState {
                 None
             };
 
+            let local_action_cache = if root_config
+                .parse("buck2", "local_action_cache")?
+                .unwrap_or(false)
+            {
+                let max_bytes = root_config
+                    .parse("buck2", "local_action_cache_max_bytes")?
+                    .unwrap_or(DEFAULT_LOCAL_ACTION_CACHE_MAX_BYTES);
+                let path = paths.local_action_cache_path();
+                Some(
+                    (blocking_executor.dupe() as Arc<dyn BlockingExecutor>)
+                        .execute_io_inline(|| LocalActionCache::open(path, max_bytes))
+                        .await?,
+                )
+            } else {
+                None
+            };
+
             // Kick off an initial sync eagerly. This gets Watchamn to start watching the path we care
             // about (potentially kicking off an initial crawl).
 
@@ -507,6 +533,7 @@ impl DaemonState {
                 enable_restarter,
                 http_client,
                 paranoid,
+                local_action_cache,
                 spawner: Arc::new(BuckSpawner::new(daemon_state_data_rt)),
                 use_tonic_rt,
             }))
@@ -674,6 +701,7 @@ impl DaemonState {
                 data.disk_state_options.sqlite_materializer_state
             ),
             format!("paranoid:{}", data.paranoid.is_some()),
+            format!("local-action-cache:{}", data.local_action_cache.is_some()),
             format!("use_tonic_rt:{}", data.use_tonic_rt),
         ];
 
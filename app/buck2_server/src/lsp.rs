@@ -8,9 +8,12 @@
  */
 
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::future::Future;
 use std::io;
 use std::io::ErrorKind;
+use std::iter;
 use std::path::Path;
 use std::thread;
 
@@ -22,7 +25,9 @@ use buck2_common::package_listing::dice::HasPackageListingResolver;
 use buck2_common::result::SharedResult;
 use buck2_core::bzl::ImportPath;
 use buck2_core::cells::build_file_cell::BuildFileCell;
+use buck2_core::cells::cell_path::CellPath;
 use buck2_core::cells::cell_path::CellPathRef;
+use buck2_core::cells::paths::CellRelativePath;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::abs_path::AbsPath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
@@ -52,6 +57,7 @@ use futures::channel::mpsc::UnboundedSender;
 use futures::FutureExt;
 use futures::SinkExt;
 use futures::StreamExt;
+use futures::TryStreamExt;
 use gazebo::prelude::StrExt;
 use itertools::Itertools;
 use lsp_server::Connection;
@@ -80,6 +86,13 @@ use crate::builtin_docs::docs::get_prelude_docs;
 static DOCS_DIRECTORY_KEY: &str = "directory";
 static DOCS_BUILTIN_KEY: &str = "builtin";
 
+/// How many files are read at once when looking for the files that load a file.
+const MAX_CONCURRENT_READS: usize = 64;
+
+/// How many files are considered at most when looking for the files that load a file, so that
+/// a large workspace doesn't stall the server.
+const MAX_LOADING_CANDIDATES: usize = 10_000;
+
 /// Errors when [`LspContext::resolve_load()`] cannot resolve a given path.
 #[derive(thiserror::Error, Debug)]
 enum ResolveLoadError {
@@ -420,9 +433,12 @@ impl<'a> BuckLspContext<'a> {
             .with_dice_ctx(|dice_ctx| async move { dice_ctx.get_cell_resolver().await })
             .await?;
         let cell_path = cell_resolver.get_cell_path(&relative_path)?;
+        Self::module_path(cell_path)
+    }
 
-        match path.extension() {
-            Some(e) if e == "bxl" => Ok(OwnedStarlarkModulePath::BxlFile(BxlFilePath::new(
+    fn module_path(cell_path: CellPath) -> anyhow::Result<OwnedStarlarkModulePath> {
+        match cell_path.path().extension() {
+            Some("bxl") => Ok(OwnedStarlarkModulePath::BxlFile(BxlFilePath::new(
                 cell_path,
             )?)),
             _ => {
@@ -569,6 +585,135 @@ impl<'a> BuckLspContext<'a> {
     fn find_target(ast: &AstModule, target: TargetName) -> Option<Span> {
         ast.find_function_call_with_name(target.as_str())
     }
+
+    /// Find the build files, `.bzl` files and `.bxl` files under `workspace_root` with a `load`
+    /// of the file at `path`. Without a workspace root, the cell of the file is searched. At most
+    /// [`MAX_LOADING_CANDIDATES`] files are considered.
+    async fn files_loading(
+        &self,
+        path: &Path,
+        workspace_root: Option<&Path>,
+    ) -> anyhow::Result<Vec<LspUrl>> {
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            return Ok(Vec::new());
+        };
+        let target = self.import_path(path).await?;
+        let (root, whole_cell) = match workspace_root {
+            // The workspace contains the whole project.
+            Some(root) if self.fs.root().as_path().starts_with(root) => {
+                (ProjectRelativePath::empty().to_buf(), false)
+            }
+            Some(root) => (self.fs.relativize_any(AbsPath::new(root)?)?, false),
+            None => (self.fs.relativize_any(AbsPath::new(path)?)?, true),
+        };
+        self.with_dice_ctx(async move |dice_ctx| {
+            let cell_resolver = dice_ctx.get_cell_resolver().await?;
+            let file_ops = dice_ctx.file_ops();
+
+            let mut root = cell_resolver.get_cell_path(&root)?;
+            if whole_cell {
+                root = CellPath::new(root.cell(), CellRelativePath::empty().to_owned());
+            }
+            let root_path = cell_resolver.resolve_path(root.as_ref())?;
+
+            // Cells nested in the workspace are searched from their own root, so that they use
+            // their own build file names. Directories are normalized to the cell they belong to,
+            // so none gets searched twice.
+            let mut dirs = VecDeque::new();
+            let mut seen = HashSet::new();
+            for dir in iter::once(root).chain(
+                cell_resolver
+                    .cells()
+                    .filter(|(_, cell)| {
+                        cell.path()
+                            .as_project_relative_path()
+                            .starts_with(&root_path)
+                    })
+                    .map(|(name, _)| CellPath::new(name, CellRelativePath::empty().to_owned())),
+            ) {
+                if seen.insert(dir.clone()) {
+                    dirs.push_back(dir);
+                }
+            }
+
+            let mut candidates = Vec::new();
+            'walk: while let Some(dir) = dirs.pop_front() {
+                let buildfiles = cell_resolver.get(dir.cell())?.buildfiles();
+                for entry in file_ops.read_dir(dir.as_ref()).await?.included.iter() {
+                    let child = dir.join(&entry.file_name);
+                    if entry.file_type.is_dir() {
+                        let child = cell_resolver
+                            .get_cell_path(&cell_resolver.resolve_path(child.as_ref())?)?;
+                        if seen.insert(child.clone()) {
+                            dirs.push_back(child);
+                        }
+                        continue;
+                    }
+                    let is_starlark = buildfiles.contains(&entry.file_name)
+                        || matches!(child.path().extension(), Some("bzl" | "bxl"));
+                    if is_starlark {
+                        if candidates.len() == MAX_LOADING_CANDIDATES {
+                            tracing::warn!(
+                                "Stopped looking for the files loading `{}` after {} files, results may be incomplete",
+                                target,
+                                MAX_LOADING_CANDIDATES
+                            );
+                            break 'walk;
+                        }
+                        candidates.push(child);
+                    }
+                }
+            }
+
+            let file_ops = &file_ops;
+            let cell_resolver = &cell_resolver;
+            let dice_ctx = &dice_ctx;
+            let target = target.path();
+            futures::stream::iter(candidates)
+                .map(|candidate| async move {
+                    let contents = <dyn FileOps>::read_file(file_ops, candidate.as_ref()).await?;
+                    // Only parse the files that could possibly load the target.
+                    if !contents.contains(file_name) {
+                        return anyhow::Ok(None);
+                    }
+                    let module_path = Self::module_path(candidate.clone())?;
+                    let module_path = module_path.borrow();
+                    let calculator = dice_ctx
+                        .get_interpreter_calculator(module_path.cell(), module_path.build_file_cell())
+                        .await?;
+                    let starlark_path = module_path.starlark_path();
+                    // Files that don't parse are skipped, they are reported when opened.
+                    let Ok(ast) = calculator.prepare_eval_with_content(starlark_path, contents)
+                    else {
+                        return Ok(None);
+                    };
+                    let mut loads_target = false;
+                    for load in ast.loads() {
+                        if let Ok(loaded) = calculator.resolve_load(starlark_path, load.module_id).await {
+                            if loaded.path() == target {
+                                loads_target = true;
+                                break;
+                            }
+                        }
+                    }
+                    if !loads_target {
+                        return Ok(None);
+                    }
+                    let abs_path = self
+                        .fs
+                        .resolve(&cell_resolver.resolve_path(candidate.as_ref())?);
+                    let url = Url::from_file_path(&abs_path).map_err(|()| {
+                        anyhow::anyhow!("`{}` can't be converted to a URL", abs_path)
+                    })?;
+                    Ok(Some(url.try_into()?))
+                })
+                .buffer_unordered(MAX_CONCURRENT_READS)
+                .try_filter_map(|url| async move { Ok(url) })
+                .try_collect()
+                .await
+        })
+        .await
+    }
 }
 
 impl<'a> LspContext for BuckLspContext<'a> {
@@ -751,6 +896,21 @@ impl<'a> LspContext for BuckLspContext<'a> {
     fn get_environment(&self, _uri: &LspUrl) -> DocModule {
         DocModule::default()
     }
+
+    fn get_files_loading(
+        &self,
+        uri: &LspUrl,
+        workspace_root: Option<&Path>,
+    ) -> anyhow::Result<Vec<LspUrl>> {
+        let dispatcher = self.server_ctx.events().dupe();
+        self.runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                match uri {
+                    LspUrl::File(path) => self.files_loading(path, workspace_root).await,
+                    _ => Ok(Vec::new()),
+                }
+            }))
+    }
 }
 
 pub(crate) async fn run_lsp_server_command(
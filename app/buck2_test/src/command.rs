@@ -14,6 +14,7 @@ use std::sync::Arc;
 
 use anyhow::Context;
 use async_trait::async_trait;
+use buck2_build_api::actions::artifact::get_artifact_fs::GetArtifactFs;
 use buck2_build_api::analysis::calculation::RuleAnalysisCalculation;
 use buck2_build_api::artifact_groups::calculation::ArtifactGroupCalculation;
 use buck2_build_api::artifact_groups::ArtifactGroup;
@@ -49,6 +50,7 @@ use buck2_core::target::label::TargetLabel;
 use buck2_core::target::name::TargetName;
 use buck2_events::dispatch::console_message;
 use buck2_events::dispatch::with_dispatcher_async;
+use buck2_execute::execute::blocking::HasBlockingExecutor;
 use buck2_execute::materialize::materializer::HasMaterializer;
 use buck2_node::load_patterns::MissingTargetBehavior;
 use buck2_node::nodes::configured_frontend::ConfiguredTargetNodeCalculation;
@@ -66,6 +68,7 @@ use buck2_server_ctx::test_command::TEST_COMMAND;
 use buck2_test_api::data::TestResult;
 use buck2_test_api::data::TestStatus;
 use buck2_test_api::protocol::TestExecutor;
+use buck2_test_api::sharding::TestShard;
 use dice::DiceComputations;
 use dice::DiceTransaction;
 use dupe::Dupe;
@@ -87,10 +90,12 @@ use crate::executor_launcher::ExecutorLaunch;
 use crate::executor_launcher::ExecutorLauncher;
 use crate::executor_launcher::OutOfProcessTestExecutor;
 use crate::local_resource_registry::LocalResourceRegistry;
+use crate::orchestrator::clean_stale_cached_outputs;
 use crate::orchestrator::BuckTestOrchestrator;
 use crate::orchestrator::ExecutorMessage;
 use crate::session::TestSession;
 use crate::session::TestSessionOptions;
+use crate::sharding::TestSharding;
 use crate::translations::build_configured_target_handle;
 
 #[derive(Debug, Serialize)]
@@ -184,10 +189,14 @@ struct TestStatuses {
     fatals: CounterWithExamples,
     listing_success: CounterWithExamples,
     listing_failed: CounterWithExamples,
+    flaky: CounterWithExamples,
+    /// Passes served by the test cache.
+    cached: CounterWithExamples,
 }
 impl TestStatuses {
     fn ingest(&mut self, result: &TestResult) {
         match result.status {
+            TestStatus::PASS if result.cached => self.cached.add(&result.name),
             TestStatus::PASS => self.passed.add(&result.name),
             TestStatus::FAIL => self.failed.add(&result.name),
             TestStatus::SKIP => self.skipped.add(&result.name),
@@ -198,6 +207,7 @@ impl TestStatuses {
             TestStatus::RERUN => {}
             TestStatus::LISTING_SUCCESS => self.listing_success.add(&result.name),
             TestStatus::LISTING_FAILED => self.listing_failed.add(&result.name),
+            TestStatus::FLAKY => self.flaky.add(&result.name),
         }
     }
 }
@@ -269,20 +279,40 @@ async fn test(
         .await?
         .filter(|s| !s.is_empty());
 
-    let (test_executor, test_executor_args) = match test_executor_config {
-        Some(config) => {
-            let test_executor = post_process_test_executor(config.as_ref())
-                .with_context(|| format!("Invalid `test.v2_test_executor`: {}", config))?;
-            let test_executor_args = Vec::new();
-            (test_executor, test_executor_args)
-        }
-        None => {
-            // If no v2_test_executor config was set, fall back to the internal test runner.
-            let test_executor = std::env::current_exe()?;
-            let test_executor_args = vec!["internal-test-runner".to_owned()];
-            (test_executor, test_executor_args)
-        }
-    };
+    let (test_executor, test_executor_args, supports_testcase_sharding, supports_coverage) =
+        match test_executor_config {
+            Some(config) => {
+                let test_executor = post_process_test_executor(config.as_ref())
+                    .with_context(|| format!("Invalid `test.v2_test_executor`: {}", config))?;
+                let test_executor_args = Vec::new();
+                // A custom test executor only gets `--shard-index`, `--shard-count` and
+                // `--coverage-report` if it says it understands them.
+                let supports_testcase_sharding = test_executor_supports(
+                    &ctx,
+                    &cell_resolver,
+                    "v2_test_executor_supports_sharding",
+                )
+                .await?;
+                let supports_coverage = test_executor_supports(
+                    &ctx,
+                    &cell_resolver,
+                    "v2_test_executor_supports_coverage",
+                )
+                .await?;
+                (
+                    test_executor,
+                    test_executor_args,
+                    supports_testcase_sharding,
+                    supports_coverage,
+                )
+            }
+            None => {
+                // If no v2_test_executor config was set, fall back to the internal test runner.
+                let test_executor = std::env::current_exe()?;
+                let test_executor_args = vec!["internal-test-runner".to_owned()];
+                (test_executor, test_executor_args, true, true)
+            }
+        };
 
     let parsed_patterns =
         parse_patterns_from_cli_args(&mut ctx, &request.target_patterns, cwd).await?;
@@ -310,8 +340,44 @@ async fn test(
         allow_re: options.allow_re,
         force_use_project_relative_paths: options.force_use_project_relative_paths,
         force_run_from_project_root: options.force_run_from_project_root,
+        test_cache: !options.no_test_cache,
     });
 
+    // Sharding test cases is left to the test executor, in which case we give it all the targets.
+    let mut test_executor_args = Vec::new();
+    let sharding = match &request.shard {
+        Some(shard) => {
+            let test_shard = TestShard::new(shard.index, shard.count)?;
+            if shard.by_testcase && supports_testcase_sharding {
+                test_executor_args.extend([
+                    format!("--shard-index={}", shard.index),
+                    format!("--shard-count={}", shard.count),
+                ]);
+                None
+            } else {
+                if shard.by_testcase {
+                    server_ctx.events().console_message(
+                        "The test executor does not declare support for sharding test cases \
+                        (`test.v2_test_executor_supports_sharding`), sharding by target instead"
+                            .to_owned(),
+                    );
+                }
+                Some(TestSharding::new(test_shard, &shard.target_durations))
+            }
+        }
+        None => None,
+    };
+    if let Some(coverage_report) = &request.coverage_report {
+        if !supports_coverage {
+            return Err(anyhow::anyhow!(
+                "The test executor does not declare support for coverage reports \
+                (`test.v2_test_executor_supports_coverage`), so `--coverage` cannot be used"
+            ));
+        }
+        test_executor_args.push(format!("--coverage-report={}", coverage_report));
+    }
+    test_executor_args.extend(request.test_executor_args.iter().cloned());
+
     let build_opts = request
         .build_opts
         .as_ref()
@@ -320,13 +386,14 @@ async fn test(
         ctx,
         resolved_pattern,
         global_target_platform,
-        request.test_executor_args.clone(),
+        test_executor_args,
         Arc::new(TestLabelFiltering::new(
             request.included_labels.clone(),
             request.excluded_labels.clone(),
             request.always_exclude,
             request.build_filtered_targets,
         )),
+        sharding,
         &*launcher,
         session,
         cell_resolver,
@@ -382,6 +449,20 @@ async fn test(
                 .listing_failed
                 .to_cli_proto_counter(),
         ),
+        flaky: Some(
+            test_outcome
+                .executor_report
+                .statuses
+                .flaky
+                .to_cli_proto_counter(),
+        ),
+        cached: Some(
+            test_outcome
+                .executor_report
+                .statuses
+                .cached
+                .to_cli_proto_counter(),
+        ),
     };
 
     Ok(TestResponse {
@@ -394,12 +475,30 @@ async fn test(
     })
 }
 
+/// Reads a `test.<key>` flag of the test executor, which is `false` by default.
+async fn test_executor_supports(
+    ctx: &DiceTransaction,
+    cell_resolver: &CellResolver,
+    key: &str,
+) -> anyhow::Result<bool> {
+    Ok(ctx
+        .get_legacy_config_property(cell_resolver.root_cell(), "test", key)
+        .await?
+        .map(|v| {
+            v.parse::<bool>()
+                .with_context(|| format!("Invalid `test.{}`: {}", key, v))
+        })
+        .transpose()?
+        .unwrap_or(false))
+}
+
 async fn test_targets(
     ctx: DiceTransaction,
     pattern: ResolvedPattern<ConfiguredProvidersPatternExtra>,
     global_target_platform: Option<TargetLabel>,
     external_runner_args: Vec<String>,
     label_filtering: Arc<TestLabelFiltering>,
+    sharding: Option<TestSharding>,
     launcher: &dyn ExecutorLauncher,
     session: TestSession,
     cell_resolver: CellResolver,
@@ -407,6 +506,17 @@ async fn test_targets(
     skip_incompatible_targets: bool,
     missing_target_behavior: MissingTargetBehavior,
 ) -> anyhow::Result<TestOutcome> {
+    if session.options().test_cache {
+        let fs = ctx.get_artifact_fs().await?;
+        if let Err(e) = ctx
+            .get_blocking_executor()
+            .execute_io_inline(|| clean_stale_cached_outputs(&fs))
+            .await
+        {
+            tracing::warn!("Error cleaning up outputs of cached tests: {:#}", e);
+        }
+    }
+
     let session = Arc::new(session);
     let (liveliness_observer, _guard) = LivelinessGuard::create();
 
@@ -479,6 +589,7 @@ async fn test_targets(
                 let mut driver = TestDriver::new(TestDriverState {
                     ctx: &ctx,
                     label_filtering: &label_filtering,
+                    sharding: sharding.as_ref(),
                     global_target_platform: &global_target_platform,
                     session: &session,
                     test_executor: &test_executor,
@@ -594,6 +705,7 @@ enum TestDriverTask {
 pub(crate) struct TestDriverState<'a, 'e> {
     ctx: &'a DiceComputations,
     label_filtering: &'a Arc<TestLabelFiltering>,
+    sharding: Option<&'a TestSharding>,
     global_target_platform: &'a Option<TargetLabel>,
     session: &'a TestSession,
     test_executor: &'a Arc<dyn TestExecutor + 'e>,
@@ -765,6 +877,7 @@ impl<'a, 'e> TestDriver<'a, 'e> {
                     state.test_executor.dupe(),
                     state.session,
                     state.label_filtering.dupe(),
+                    state.sharding,
                     state.cell_resolver,
                     state.working_dir_cell,
                 )
@@ -821,9 +934,17 @@ async fn test_target(
     test_executor: Arc<dyn TestExecutor + '_>,
     session: &TestSession,
     label_filtering: Arc<TestLabelFiltering>,
+    sharding: Option<&TestSharding>,
     cell_resolver: &CellResolver,
     working_dir_cell: CellName,
 ) -> anyhow::Result<Option<ConfiguredProvidersLabel>> {
+    // Targets of other shards aren't even built.
+    if let Some(sharding) = sharding {
+        if !sharding.includes(&target.target().unconfigured().to_string()) {
+            return Ok(None);
+        }
+    }
+
     // NOTE: We fail if we hit an incompatible target here. This can happen if we reach an
     // incompatible target via `tests = [...]`. This should perhaps change, but that's how it works
     // in v1: https://fb.workplace.com/groups/buckeng/posts/8520953297953210
@@ -9,9 +9,12 @@
 
 //! Implementation of the `TestOrchestrator` from `buck2_test_api`.
 
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::ffi::OsStr;
+use std::ops::ControlFlow;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
 
 use anyhow::Context as _;
@@ -33,6 +36,7 @@ use buck2_build_api::interpreter::rule_defs::provider::builtin::external_runner_
 use buck2_build_api::interpreter::rule_defs::provider::builtin::external_runner_test_info::TestCommandMember;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::events::HasEvents;
+use buck2_common::file_ops::FileDigest;
 use buck2_common::liveliness_observer::LivelinessObserver;
 use buck2_common::local_resource_state::LocalResourceState;
 use buck2_common::result::SharedError;
@@ -46,6 +50,7 @@ use buck2_core::execution_types::executor_config::LocalExecutorOptions;
 use buck2_core::execution_types::executor_config::PathSeparatorKind;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
 use buck2_core::fs::buck_out_path::BuckOutTestPath;
+use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
@@ -67,6 +72,7 @@ use buck2_execute::artifact::fs::ExecutorFs;
 use buck2_execute::digest_config::DigestConfig;
 use buck2_execute::digest_config::HasDigestConfig;
 use buck2_execute::execute::blocking::HasBlockingExecutor;
+use buck2_execute::execute::cache_uploader::CacheUploadInfo;
 use buck2_execute::execute::cache_uploader::NoOpCacheUploader;
 use buck2_execute::execute::claim::MutexClaimManager;
 use buck2_execute::execute::command_executor::CommandExecutor;
@@ -135,6 +141,21 @@ use crate::translations;
 
 const MAX_SUFFIX_LEN: usize = 1024;
 
+/// How long tests that time out get to exit after being sent SIGTERM, before they're killed.
+const TEST_TERMINATION_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Outputs of test executions that may be cached go in this directory, see
+/// `claim_cacheable_output_root`.
+const CACHED_OUTPUT_ROOT: &str = "cached";
+
+/// How long the outputs of cached test executions are kept after they were last written.
+const CACHED_OUTPUT_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The cacheable output roots claimed by the orchestrators of this daemon. Concurrent
+/// invocations executing the same test would otherwise write to the same directory.
+static CLAIMED_CACHED_OUTPUT_ROOTS: Mutex<BTreeSet<ForwardRelativePathBuf>> =
+    Mutex::new(BTreeSet::new());
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum ExecutorMessage {
     TestResult(TestResult),
@@ -151,6 +172,9 @@ pub struct BuckTestOrchestrator<'a> {
     digest_config: DigestConfig,
     cancellations: &'a CancellationContext<'a>,
     local_resource_state_registry: Arc<LocalResourceRegistry<'a>>,
+    /// Cacheable output roots claimed by this orchestrator, released when it is dropped, so
+    /// that the test runner can still read the outputs after the execution.
+    claimed_output_roots: Mutex<Vec<ForwardRelativePathBuf>>,
 }
 
 impl<'a> BuckTestOrchestrator<'a> {
@@ -195,6 +219,7 @@ impl<'a> BuckTestOrchestrator<'a> {
             digest_config,
             cancellations,
             local_resource_state_registry,
+            claimed_output_roots: Mutex::new(Vec::new()),
         }
     }
 
@@ -225,6 +250,18 @@ impl<'a> BuckTestOrchestrator<'a> {
         let fs = self.dice.get_artifact_fs().await?;
 
         let test_info = self.get_test_info(&test_target).await?;
+        let output_root = if self.session.options().test_cache {
+            self.claim_cacheable_output_root(
+                &test_target,
+                &cmd,
+                &env,
+                &pre_create_dirs,
+                executor_override.as_ref(),
+            )
+        } else {
+            None
+        }
+        .unwrap_or_else(|| self.unique_output_root());
         let test_executor = self
             .get_test_executor(&test_target, &test_info, executor_override, &fs)
             .await?;
@@ -235,6 +272,7 @@ impl<'a> BuckTestOrchestrator<'a> {
                 cmd,
                 env,
                 pre_create_dirs,
+                output_root,
                 &test_executor.executor_fs(),
             )
             .await?;
@@ -457,6 +495,7 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
                 cmd,
                 env,
                 pre_create_dirs,
+                self.unique_output_root(),
                 &executor.executor_fs(),
             )
             .await?;
@@ -548,11 +587,13 @@ impl<'b> BuckTestOrchestrator<'b> {
         ),
         ExecuteError,
     > {
-        let manager = CommandExecutionManager::new(
-            Box::new(MutexClaimManager::new()),
-            self.events.dupe(),
-            self.liveliness_observer.dupe(),
-        );
+        let new_manager = || {
+            CommandExecutionManager::new(
+                Box::new(MutexClaimManager::new()),
+                self.events.dupe(),
+                self.liveliness_observer.dupe(),
+            )
+        };
 
         let mut action_key_suffix = match &metadata {
             DisplayMetadata::Listing(_) => "listing".to_owned(),
@@ -569,8 +610,6 @@ impl<'b> BuckTestOrchestrator<'b> {
             action_key_suffix,
         };
 
-        // For test execution, we currently do not do any cache queries
-
         let prepared_action = executor.prepare_action(&request, self.digest_config)?;
         let prepared_command = PreparedCommand {
             target: &test_target as _,
@@ -578,7 +617,53 @@ impl<'b> BuckTestOrchestrator<'b> {
             prepared_action: &prepared_action,
             digest_config: self.digest_config,
         };
-        let command = executor.exec_cmd(manager, &prepared_command, self.cancellations);
+        // Only test executions are cached, and only if they don't depend on anything but their
+        // inputs. Local resources are set up outside of the action, so they may differ between
+        // executions.
+        let cacheable = self.session.options().test_cache
+            && matches!(metadata, DisplayMetadata::Testing { .. })
+            && request.required_local_resources().is_empty();
+
+        let command = async {
+            if !cacheable {
+                return executor
+                    .exec_cmd(new_manager(), &prepared_command, self.cancellations)
+                    .await;
+            }
+
+            let manager = match executor
+                .action_cache(new_manager(), &prepared_command, self.cancellations)
+                .await
+            {
+                ControlFlow::Break(result) => {
+                    if is_passing(&result) {
+                        return result;
+                    }
+                    // Only passing executions are reused, the others run again.
+                    new_manager()
+                }
+                ControlFlow::Continue(manager) => manager,
+            };
+            let result = executor
+                .exec_cmd(manager, &prepared_command, self.cancellations)
+                .await;
+            if !is_passing(&result) {
+                return result;
+            }
+
+            // Tests may depend on the machine they run on, so their results are only cached
+            // privately, rather than uploaded to a shared cache.
+            let info = CacheUploadInfo {
+                target: &test_target as _,
+                action_digest: prepared_action.action.dupe(),
+                digest_config: self.digest_config,
+                allow_cache_upload: false,
+            };
+            if let Err(e) = executor.cache_upload(&info, &result, None).await {
+                tracing::warn!("Error caching test result: {:#}", e);
+            }
+            result
+        };
 
         // instrument execution with a span.
         // TODO(brasselsprouts): migrate this into the executor to get better accuracy.
@@ -664,6 +749,16 @@ impl<'b> BuckTestOrchestrator<'b> {
                 Some(execution_kind),
                 outputs,
             ),
+            CommandExecutionStatus::OomKilled { execution_kind, .. } => (
+                stdout,
+                stderr,
+                ExecutionStatus::Finished {
+                    exitcode: exit_code.unwrap_or(1),
+                },
+                timing,
+                Some(execution_kind),
+                outputs,
+            ),
             CommandExecutionStatus::Error { stage: _, error } => (
                 ExecutionStream::Inline(Default::default()),
                 ExecutionStream::Inline(format!("{:?}", error).into_bytes()),
@@ -697,15 +792,22 @@ impl<'b> BuckTestOrchestrator<'b> {
         let CommandExecutorResponse {
             executor,
             platform,
-            cache_checker: _,
-            cache_uploader: _,
+            cache_checker,
+            cache_uploader,
         } = self.dice.get_command_executor(fs, executor_config)?;
+        let (cache_checker, cache_uploader) = if self.session.options().test_cache {
+            (cache_checker, cache_uploader)
+        } else {
+            (
+                Arc::new(NoOpCommandExecutor {}) as _,
+                Arc::new(NoOpCacheUploader {}) as _,
+            )
+        };
         let run_action_knobs = self.dice.per_transaction_data().get_run_action_knobs();
         let executor = CommandExecutor::new(
             executor,
-            // Caching is not enabled for tests yet. Use the NoOp
-            Arc::new(NoOpCommandExecutor {}),
-            Arc::new(NoOpCacheUploader {}),
+            cache_checker,
+            cache_uploader,
             fs.clone(),
             executor_config.options,
             platform,
@@ -803,15 +905,9 @@ impl<'b> BuckTestOrchestrator<'b> {
         cmd: Vec<ArgValue>,
         env: SortedVectorMap<String, ArgValue>,
         pre_create_dirs: Vec<DeclaredOutput>,
+        output_root: ForwardRelativePathBuf,
         executor_fs: &ExecutorFs<'_>,
     ) -> anyhow::Result<ExpandedTestExecutable> {
-        let output_root = self
-            .session
-            .prefix()
-            .join(ForwardRelativePathBuf::unchecked_new(
-                Uuid::new_v4().to_string(),
-            ));
-
         let mut declared_outputs = IndexMap::<BuckOutTestPath, OutputCreationBehavior>::new();
 
         let mut supports_re = true;
@@ -868,6 +964,52 @@ impl<'b> BuckTestOrchestrator<'b> {
         })
     }
 
+    /// A directory for the outputs of a single test execution.
+    fn unique_output_root(&self) -> ForwardRelativePathBuf {
+        self.session
+            .prefix()
+            .join(ForwardRelativePathBuf::unchecked_new(
+                Uuid::new_v4().to_string(),
+            ))
+    }
+
+    /// A directory for the outputs of a test execution that may be cached. The paths of outputs
+    /// end up in the command, hence in the action digest that results are cached by, so they must
+    /// be the same every time the same test is executed. Returns `None` if the directory is
+    /// claimed by another orchestrator, in which case the execution must use a unique directory.
+    fn claim_cacheable_output_root(
+        &self,
+        test_target: &ConfiguredProvidersLabel,
+        cmd: &[ArgValue],
+        env: &SortedVectorMap<String, ArgValue>,
+        pre_create_dirs: &[DeclaredOutput],
+        executor_override: Option<&ExecutorConfigOverride>,
+    ) -> Option<ForwardRelativePathBuf> {
+        let key = format!(
+            "{}\n{:?}\n{:?}\n{:?}\n{:?}",
+            test_target, cmd, env, pre_create_dirs, executor_override
+        );
+        let digest =
+            FileDigest::from_content(key.as_bytes(), self.digest_config.cas_digest_config());
+        let output_root = ForwardRelativePathBuf::unchecked_new(format!(
+            "{}/{}",
+            CACHED_OUTPUT_ROOT,
+            digest.raw_digest()
+        ));
+        let mut claimed_output_roots = self.claimed_output_roots.lock().unwrap();
+        if !claimed_output_roots.contains(&output_root) {
+            if !CLAIMED_CACHED_OUTPUT_ROOTS
+                .lock()
+                .unwrap()
+                .insert(output_root.clone())
+            {
+                return None;
+            }
+            claimed_output_roots.push(output_root.clone());
+        }
+        Some(output_root)
+    }
+
     async fn create_command_execution_request(
         &self,
         cwd: ProjectRelativePathBuf,
@@ -909,7 +1051,9 @@ impl<'b> BuckTestOrchestrator<'b> {
             .with_disable_miniperf(true)
             .with_required_local_resources(required_local_resources)?;
         if let Some(timeout) = timeout {
-            request = request.with_timeout(timeout)
+            request = request
+                .with_timeout(timeout)
+                .with_termination_grace_period(TEST_TERMINATION_GRACE_PERIOD)
         }
         if let Some(host_sharing_requirements) = host_sharing_requirements {
             request = request.with_host_sharing_requirements(host_sharing_requirements);
@@ -1067,6 +1211,13 @@ impl<'b> BuckTestOrchestrator<'b> {
                     String::from_utf8_lossy(&std_streams.stderr),
                 )));
             }
+            CommandExecutionStatus::OomKilled { .. } => {
+                return Err(SharedError::new(anyhow::anyhow!(
+                    "Local resource setup command was killed for running out of memory, stdout:\n{}\nstderr:\n{}\n",
+                    String::from_utf8_lossy(&std_streams.stdout),
+                    String::from_utf8_lossy(&std_streams.stderr),
+                )));
+            }
             CommandExecutionStatus::Error { stage: _, error } => {
                 return Err(SharedError::new(error));
             }
@@ -1088,6 +1239,12 @@ impl<'b> BuckTestOrchestrator<'b> {
 
 impl<'a> Drop for BuckTestOrchestrator<'a> {
     fn drop(&mut self) {
+        let mut claimed = CLAIMED_CACHED_OUTPUT_ROOTS.lock().unwrap();
+        for output_root in self.claimed_output_roots.get_mut().unwrap().drain(..) {
+            claimed.remove(&output_root);
+        }
+        drop(claimed);
+
         // If we didn't close the sender yet, then notify the receiver that our stream is
         // incomplete.
         let _ignored = self.results_channel.unbounded_send(Err(anyhow::Error::msg(
@@ -1385,8 +1542,46 @@ impl CommandExecutionTarget for LocalResourceTarget<'_> {
     }
 }
 
+/// Whether a test execution passed, so its result may be cached.
+fn is_passing(result: &CommandExecutionResult) -> bool {
+    matches!(result.report.status, CommandExecutionStatus::Success { .. })
+        && result.report.exit_code == Some(0)
+}
+
+/// Delete the outputs of cached test executions that weren't written for a while. Unlike other
+/// test outputs, they aren't grouped by session, so that executions of the same test in different
+/// sessions use the same paths, and nothing else would clean them up. Cache hits restore their
+/// outputs, so deleting them doesn't affect later executions.
+pub(crate) fn clean_stale_cached_outputs(fs: &ArtifactFs) -> anyhow::Result<()> {
+    let root = fs
+        .buck_out_path_resolver()
+        .resolve_test(&BuckOutTestPath::new(
+            ForwardRelativePathBuf::unchecked_new(CACHED_OUTPUT_ROOT.to_owned()),
+            ForwardRelativePathBuf::empty(),
+        ));
+    let entries = match fs_util::read_dir_if_exists(fs.fs().resolve(&root))? {
+        Some(entries) => entries,
+        None => return Ok(()),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let age = entry.metadata()?.modified()?.elapsed().unwrap_or_default();
+        if age > CACHED_OUTPUT_RETENTION {
+            fs_util::remove_all(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Mutex;
+
     use buck2_build_api::context::SetBuildContextData;
     use buck2_common::dice::cells::SetCellResolver;
     use buck2_common::dice::data::testing::SetTestingIoProvider;
@@ -1394,9 +1589,16 @@ mod tests {
     use buck2_core::cells::name::CellName;
     use buck2_core::cells::CellResolver;
     use buck2_core::configuration::data::ConfigurationData;
+    use buck2_core::fs::buck_out_path::BuckOutPathResolver;
     use buck2_core::fs::project::ProjectRootTemp;
     use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
     use buck2_events::dispatch::EventDispatcher;
+    use buck2_execute::execute::action_digest::ActionDigest;
+    use buck2_execute::execute::cache_uploader::DepFileEntry;
+    use buck2_execute::execute::cache_uploader::UploadCache;
+    use buck2_execute::execute::manager::CommandExecutionManagerExt;
+    use buck2_execute::execute::prepared::PreparedCommandExecutor;
+    use buck2_execute::execute::prepared::PreparedCommandOptionalExecutor;
     use buck2_test_api::data::TestStatus;
     use dice::testing::DiceBuilder;
     use dice::UserComputationData;
@@ -1406,10 +1608,20 @@ mod tests {
     use futures::stream::TryStreamExt;
 
     use super::*;
+    use crate::session::TestSessionOptions;
 
     async fn make() -> anyhow::Result<(
         BuckTestOrchestrator<'static>,
         UnboundedReceiver<anyhow::Result<ExecutorMessage>>,
+    )> {
+        make_with_options(Default::default()).await
+    }
+
+    async fn make_with_options(
+        options: TestSessionOptions,
+    ) -> anyhow::Result<(
+        BuckTestOrchestrator<'static>,
+        UnboundedReceiver<anyhow::Result<ExecutorMessage>>,
     )> {
         let fs = ProjectRootTemp::new().unwrap();
 
@@ -1431,7 +1643,7 @@ mod tests {
         Ok((
             BuckTestOrchestrator::from_parts(
                 dice,
-                Arc::new(TestSession::new(Default::default())),
+                Arc::new(TestSession::new(options)),
                 NoopLivelinessObserver::create(),
                 sender,
                 EventDispatcher::null(),
@@ -1462,6 +1674,7 @@ mod tests {
                     name: "First - test".to_owned(),
                     duration: Some(Duration::from_micros(1)),
                     details: "1".to_owned(),
+                    cached: false,
                 })
                 .await?;
 
@@ -1473,6 +1686,7 @@ mod tests {
                     name: "Second - test".to_owned(),
                     duration: Some(Duration::from_micros(2)),
                     details: "2".to_owned(),
+                    cached: false,
                 })
                 .await?;
 
@@ -1494,6 +1708,7 @@ mod tests {
                     name: "First - test".to_owned(),
                     duration: Some(Duration::from_micros(1)),
                     details: "1".to_owned(),
+                    cached: false,
                 }),
                 ExecutorMessage::TestResult(TestResult {
                     target,
@@ -1503,6 +1718,7 @@ mod tests {
                     name: "Second - test".to_owned(),
                     duration: Some(Duration::from_micros(2)),
                     details: "2".to_owned(),
+                    cached: false,
                 }),
                 ExecutorMessage::ExitCode(0),
             ]
@@ -1558,4 +1774,263 @@ mod tests {
 
         Ok(())
     }
+
+    /// Runs every test, passing or failing them all.
+    struct FakeTestExecutor {
+        pass: bool,
+        runs: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl PreparedCommandExecutor for FakeTestExecutor {
+        async fn exec_cmd(
+            &self,
+            command: &PreparedCommand<'_, '_>,
+            manager: CommandExecutionManager,
+            _cancellations: &CancellationContext,
+        ) -> CommandExecutionResult {
+            self.runs.fetch_add(1, Ordering::Relaxed);
+            let manager = manager.claim().await;
+            let execution_kind = CommandExecutionKind::Local {
+                digest: command.prepared_action.action.dupe(),
+                command: Vec::new(),
+                env: Default::default(),
+            };
+            if self.pass {
+                manager.success(
+                    execution_kind,
+                    IndexMap::new(),
+                    Default::default(),
+                    Default::default(),
+                )
+            } else {
+                manager.failure(
+                    execution_kind,
+                    IndexMap::new(),
+                    Default::default(),
+                    Some(1),
+                    Default::default(),
+                )
+            }
+        }
+
+        fn is_local_execution_possible(&self, _executor_preference: ExecutorPreference) -> bool {
+            true
+        }
+    }
+
+    /// Serves every action it was asked to store.
+    #[derive(Default)]
+    struct FakeCache {
+        stored: Mutex<HashSet<ActionDigest>>,
+    }
+
+    #[async_trait]
+    impl PreparedCommandOptionalExecutor for FakeCache {
+        async fn maybe_execute(
+            &self,
+            command: &PreparedCommand<'_, '_>,
+            manager: CommandExecutionManager,
+            _cancellations: &CancellationContext,
+        ) -> ControlFlow<CommandExecutionResult, CommandExecutionManager> {
+            let digest = command.prepared_action.action.dupe();
+            if !self.stored.lock().unwrap().contains(&digest) {
+                return ControlFlow::Continue(manager);
+            }
+            ControlFlow::Break(manager.claim().await.success(
+                CommandExecutionKind::LocalActionCache { digest },
+                IndexMap::new(),
+                Default::default(),
+                Default::default(),
+            ))
+        }
+    }
+
+    #[async_trait]
+    impl UploadCache for FakeCache {
+        async fn upload(
+            &self,
+            info: &CacheUploadInfo<'_>,
+            _execution_result: &CommandExecutionResult,
+            _dep_file_entry: Option<DepFileEntry>,
+        ) -> anyhow::Result<bool> {
+            self.stored
+                .lock()
+                .unwrap()
+                .insert(info.action_digest.dupe());
+            Ok(true)
+        }
+    }
+
+    struct CacheTester {
+        orchestrator: BuckTestOrchestrator<'static>,
+        executor: Arc<FakeTestExecutor>,
+        cache: Arc<FakeCache>,
+        command_executor: CommandExecutor,
+        artifact_fs: ArtifactFs,
+        _fs: ProjectRootTemp,
+    }
+
+    impl CacheTester {
+        async fn new(test_cache: bool, pass: bool) -> anyhow::Result<Self> {
+            let (orchestrator, _channel) = make_with_options(TestSessionOptions {
+                test_cache,
+                ..Default::default()
+            })
+            .await?;
+
+            let fs = ProjectRootTemp::new()?;
+            let artifact_fs = ArtifactFs::new(
+                CellResolver::testing_with_name_and_path(
+                    CellName::testing_new("cell"),
+                    CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("cell".to_owned())),
+                ),
+                BuckOutPathResolver::new(ProjectRelativePathBuf::unchecked_new(
+                    "buck_out/v2".to_owned(),
+                )),
+                fs.path().dupe(),
+            );
+            let executor = Arc::new(FakeTestExecutor {
+                pass,
+                runs: AtomicUsize::new(0),
+            });
+            let cache = Arc::new(FakeCache::default());
+            let command_executor = CommandExecutor::new(
+                executor.dupe(),
+                cache.dupe(),
+                cache.dupe(),
+                artifact_fs.clone(),
+                CommandGenerationOptions {
+                    path_separator: PathSeparatorKind::Unix,
+                    output_paths_behavior: Default::default(),
+                },
+                Default::default(),
+                false,
+            );
+
+            Ok(Self {
+                orchestrator,
+                executor,
+                cache,
+                command_executor,
+                artifact_fs,
+                _fs: fs,
+            })
+        }
+
+        /// Execute the test, returning how it was executed.
+        async fn execute(&self) -> anyhow::Result<CommandExecutionKind> {
+            let target = ConfiguredProvidersLabel::new(
+                ConfiguredTargetLabel::testing_parse(
+                    "cell//pkg:foo",
+                    ConfigurationData::testing_new(),
+                ),
+                Default::default(),
+            );
+            let request = CommandExecutionRequest::new(
+                vec!["test".to_owned()],
+                Vec::new(),
+                CommandExecutionPaths::new(
+                    Vec::new(),
+                    indexset![],
+                    &self.artifact_fs,
+                    self.orchestrator.digest_config,
+                )?,
+                Default::default(),
+            );
+            let metadata = DisplayMetadata::Testing {
+                suite: "foo".to_owned(),
+                testcases: Vec::new(),
+            };
+
+            let (_, _, _, _, execution_kind, _) = self
+                .orchestrator
+                .execute_shared(&target, metadata, &self.command_executor, request)
+                .await
+                .map_err(|e| match e {
+                    ExecuteError::Error(e) => e,
+                    ExecuteError::Cancelled(..) => anyhow::anyhow!("Cancelled"),
+                })?;
+            execution_kind.context("Missing execution kind")
+        }
+
+        fn runs(&self) -> usize {
+            self.executor.runs.load(Ordering::Relaxed)
+        }
+
+        fn stored(&self) -> usize {
+            self.cache.stored.lock().unwrap().len()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_miss_then_hit() -> anyhow::Result<()> {
+        let tester = CacheTester::new(true, true).await?;
+
+        let kind = tester.execute().await?;
+        assert!(matches!(kind, CommandExecutionKind::Local { .. }));
+        assert_eq!(tester.runs(), 1);
+        assert_eq!(tester.stored(), 1);
+
+        // The second execution is served by the cache, which shows as a cached result.
+        let kind = tester.execute().await?;
+        assert!(matches!(
+            kind.to_proto(false),
+            buck2_data::command_execution_kind::Command::LocalActionCacheCommand(..)
+        ));
+        assert_eq!(tester.runs(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_failures_are_not_stored() -> anyhow::Result<()> {
+        let tester = CacheTester::new(true, false).await?;
+
+        tester.execute().await?;
+        tester.execute().await?;
+        assert_eq!(tester.runs(), 2);
+        assert_eq!(tester.stored(), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cacheable_output_root_claims() -> anyhow::Result<()> {
+        let (first, _channel) = make().await?;
+        let (second, _channel) = make().await?;
+        let target = ConfiguredProvidersLabel::new(
+            ConfiguredTargetLabel::testing_parse(
+                "cell//pkg:claimed",
+                ConfigurationData::testing_new(),
+            ),
+            Default::default(),
+        );
+        let claim = |orchestrator: &BuckTestOrchestrator| {
+            orchestrator.claim_cacheable_output_root(&target, &[], &Default::default(), &[], None)
+        };
+
+        let output_root = claim(&first).context("Missing output root")?;
+        assert_eq!(claim(&first), Some(output_root.clone()));
+        assert_eq!(claim(&second), None);
+
+        drop(first);
+        assert_eq!(claim(&second), Some(output_root));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_no_test_cache() -> anyhow::Result<()> {
+        let tester = CacheTester::new(false, true).await?;
+
+        let kind = tester.execute().await?;
+        assert!(matches!(kind, CommandExecutionKind::Local { .. }));
+        let kind = tester.execute().await?;
+        assert!(matches!(kind, CommandExecutionKind::Local { .. }));
+        assert_eq!(tester.runs(), 2);
+        assert_eq!(tester.stored(), 0);
+
+        Ok(())
+    }
 }
@@ -25,6 +25,8 @@ pub struct TestSessionOptions {
     pub allow_re: bool,
     pub force_use_project_relative_paths: bool,
     pub force_run_from_project_root: bool,
+    /// Whether passing test executions are cached and reused, by their action digest.
+    pub test_cache: bool,
 }
 
 /// The state of a buck2 test command.
@@ -0,0 +1,101 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Sharding of test targets, applied before their specs are sent to the test executor.
+
+use std::collections::HashMap;
+
+use buck2_test_api::sharding::TestShard;
+
+pub(crate) struct TestSharding {
+    shard: TestShard,
+    /// The shards of the targets we know past durations of, which we balance.
+    balanced: HashMap<String, u32>,
+}
+
+impl TestSharding {
+    pub(crate) fn new(shard: TestShard, target_durations: &HashMap<String, f64>) -> Self {
+        Self {
+            shard,
+            balanced: balance(shard.count, target_durations),
+        }
+    }
+
+    /// Whether the target with this (unconfigured) label is tested in this shard.
+    pub(crate) fn includes(&self, target: &str) -> bool {
+        match self.balanced.get(target) {
+            Some(shard) => *shard == self.shard.index,
+            None => self.shard.includes(target),
+        }
+    }
+}
+
+/// Assigns the targets to shards so that they take about as long, by assigning the longest
+/// targets first to the shard with the least work. This only depends on the durations, so all
+/// shards agree on it as long as they use the same ones.
+fn balance(count: u32, target_durations: &HashMap<String, f64>) -> HashMap<String, u32> {
+    let mut targets: Vec<(&String, f64)> = target_durations
+        .iter()
+        .map(|(target, duration)| (target, duration.max(0.0)))
+        .filter(|(_, duration)| duration.is_finite())
+        .collect();
+    targets.sort_by(|(t1, d1), (t2, d2)| d2.total_cmp(d1).then_with(|| t1.cmp(t2)));
+
+    let mut loads = vec![0.0f64; count as usize];
+    targets
+        .into_iter()
+        .map(|(target, duration)| {
+            let (shard, load) = loads
+                .iter_mut()
+                .enumerate()
+                .min_by(|(_, l1), (_, l2)| l1.total_cmp(l2))
+                .expect("There is at least one shard");
+            *load += duration;
+            (target.clone(), shard as u32)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance() {
+        let durations: HashMap<_, _> = [("a", 10.0), ("b", 6.0), ("c", 5.0), ("d", 4.0)]
+            .into_iter()
+            .map(|(t, d)| (t.to_owned(), d))
+            .collect();
+        let shards = balance(2, &durations);
+        assert_eq!(
+            vec![0, 1, 1, 0],
+            ["a", "b", "c", "d"].map(|t| shards[t]).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_includes() -> anyhow::Result<()> {
+        let durations: HashMap<_, _> = [("root//:slow".to_owned(), 100.0)].into_iter().collect();
+        let shards = (0..2)
+            .map(|index| Ok(TestSharding::new(TestShard::new(index, 2)?, &durations)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        assert!(shards[0].includes("root//:slow"));
+        assert!(!shards[1].includes("root//:slow"));
+        // Other targets are hashed.
+        for target in ["root//:a", "root//:b", "root//:c"] {
+            assert_eq!(
+                1,
+                shards.iter().filter(|s| s.includes(target)).count(),
+                "{}",
+                target
+            );
+        }
+        Ok(())
+    }
+}
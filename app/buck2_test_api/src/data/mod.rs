@@ -93,6 +93,8 @@ pub struct TestResult {
     pub duration: Option<Duration>,
     // the output of the test execution (combining stdout and stderr)
     pub details: String,
+    // whether the test execution was served by a cache rather than run
+    pub cached: bool,
 }
 
 /// different possible test results
@@ -112,6 +114,8 @@ pub enum TestStatus {
     RERUN,
     LISTING_SUCCESS,
     LISTING_FAILED,
+    // Failed, then passed when retried.
+    FLAKY,
 }
 
 /// The set of information about a test rule that is passed to the test executor
@@ -135,6 +139,8 @@ pub struct ExternalRunnerSpec {
     pub oncall: Option<String>,
     /// Cell of current working directory for test command.
     pub working_dir_cell: CellName,
+    /// How long the test may run for, if the rule set a timeout.
+    pub timeout: Option<Duration>,
 }
 
 /// Command line argument or environment variable value
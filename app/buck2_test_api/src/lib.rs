@@ -30,3 +30,4 @@ pub mod convert;
 pub mod data;
 pub mod grpc;
 pub mod protocol;
+pub mod sharding;
@@ -0,0 +1,103 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Sharding of tests, to split a test run across machines.
+//!
+//! Both Buck (which shards targets) and test executors (which may shard the test cases of targets
+//! instead) must assign tests to the same shards on every machine, so the assignment is a hash of
+//! the test name that doesn't depend on the machine or the process.
+
+use std::fmt;
+
+/// One of `count` shards of the tests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TestShard {
+    /// In `0..count`.
+    pub index: u32,
+    pub count: u32,
+}
+
+impl TestShard {
+    pub fn new(index: u32, count: u32) -> anyhow::Result<Self> {
+        if count == 0 {
+            return Err(anyhow::anyhow!("The shard count must be at least 1"));
+        }
+        if index >= count {
+            return Err(anyhow::anyhow!(
+                "Shard index {} is out of range for {} shards (shards are numbered from 0)",
+                index,
+                count
+            ));
+        }
+        Ok(Self { index, count })
+    }
+
+    /// The shard a test with this name is assigned to.
+    pub fn shard_of(&self, name: &str) -> u32 {
+        (stable_hash(name) % u64::from(self.count)) as u32
+    }
+
+    /// Whether a test with this name is assigned to this shard.
+    pub fn includes(&self, name: &str) -> bool {
+        self.shard_of(name) == self.index
+    }
+}
+
+impl fmt::Display for TestShard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// 64-bit FNV-1a, which, unlike the hashers of `std`, is the same everywhere.
+fn stable_hash(s: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    s.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert!(TestShard::new(0, 1).is_ok());
+        assert!(TestShard::new(2, 2).is_err());
+        assert!(TestShard::new(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        // Reference values of FNV-1a.
+        assert_eq!(0xcbf29ce484222325, stable_hash(""));
+        assert_eq!(0xaf63dc4c8601ec8c, stable_hash("a"));
+        assert_eq!(0x85944171f73967e8, stable_hash("foobar"));
+    }
+
+    #[test]
+    fn test_every_test_is_in_one_shard() -> anyhow::Result<()> {
+        let count = 3;
+        let shards = (0..count)
+            .map(|index| TestShard::new(index, count))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut sizes = vec![0; count as usize];
+        for i in 0..300 {
+            let name = format!("root//foo:test_{}", i);
+            let including: Vec<_> = shards.iter().filter(|s| s.includes(&name)).collect();
+            assert_eq!(1, including.len());
+            sizes[including[0].index as usize] += 1;
+        }
+        assert!(sizes.iter().all(|size| *size > 50), "{:?}", sizes);
+        Ok(())
+    }
+}
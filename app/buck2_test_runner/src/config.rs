@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -19,7 +20,7 @@ pub struct Config {
     #[clap(long)]
     pub env: Vec<EnvValue>,
 
-    /// Max number of seconds allowed to run a test.
+    /// Max number of seconds allowed to run a test, unless its rule sets a timeout.
     #[clap(long, default_value = "600", parse(try_from_str=try_parse_timeout_from_str))]
     pub timeout: Duration,
 
@@ -31,6 +32,48 @@ pub struct Config {
     /// Available as a workaround for when test features are available.
     #[clap(long, multiple = true, allow_hyphen_values = true)]
     pub test_arg: Vec<String>,
+
+    /// Run each test case in its own process, instead of all the test cases of a target at once.
+    /// Only applies to test types whose test cases we can list.
+    #[clap(long)]
+    pub run_testcases_individually: bool,
+
+    /// Max number of times to run a failing test. Only the tests that failed are run again, and
+    /// the ones that pass on a later attempt are reported as flaky. Can be overridden for the
+    /// tests of a target with a `max_attempts=<n>` label.
+    #[clap(long, default_value = "1")]
+    pub max_attempts: u32,
+
+    /// File listing tests whose failures are reported but don't fail the run, one per line, as
+    /// `cell//package:target` or `cell//package:target - testcase`.
+    #[clap(long)]
+    pub quarantine_file: Option<PathBuf>,
+
+    /// Only run the tests of this shard, out of `--shard-count`. The test cases we can list are
+    /// sharded individually (and run in their own process), other targets as a whole.
+    #[clap(long, requires = "shard-count")]
+    pub shard_index: Option<u32>,
+
+    /// Number of shards the tests are split into.
+    #[clap(long, requires = "shard-index")]
+    pub shard_count: Option<u32>,
+
+    /// Collect the coverage of the tests, and merge it into an lcov report at this path. The
+    /// tests need to be built with instrumentation (see `buck2 test --coverage`).
+    #[clap(long)]
+    pub coverage_report: Option<PathBuf>,
+
+    /// `llvm-profdata` binary, to merge LLVM profiles.
+    #[clap(long, default_value = "llvm-profdata")]
+    pub llvm_profdata: String,
+
+    /// `llvm-cov` binary, to convert LLVM profiles to lcov.
+    #[clap(long, default_value = "llvm-cov")]
+    pub llvm_cov: String,
+
+    /// coverage.py binary, to convert Python coverage data to lcov.
+    #[clap(long, default_value = "coverage")]
+    pub python_coverage: String,
 }
 
 /// Uiltity that can be used to parse Env values from CLI arguments.
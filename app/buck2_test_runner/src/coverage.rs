@@ -0,0 +1,428 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Collection of the coverage of tests, merged into a single lcov report.
+//!
+//! Every test execution gets a declared output directory, which the env vars of the coverage
+//! tools of each language point at: LLVM profiles (C++, Rust), coverage.py data files (Python),
+//! and Go coverprofiles. Python tests are run under coverage.py by the test main of the prelude,
+//! when passed `--collect-coverage`. Once all tests ran, those are converted to lcov and merged.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fmt::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_test_api::data::ArgValue;
+use buck2_test_api::data::ArgValueContent;
+use buck2_test_api::data::DeclaredOutput;
+use parking_lot::Mutex;
+
+use crate::config::Config;
+use crate::framework::verbatim;
+
+/// Env var pointing at the coverage output directory, for tests that handle coverage themselves.
+const COVERAGE_DIR_ENV: &str = "BUCK_COVERAGE_DIR";
+
+const PROFRAW_EXTENSION: &str = "profraw";
+const PYTHON_COVERAGE_FILE: &str = "python.coverage";
+const GO_COVERPROFILE: &str = "go.coverprofile";
+
+/// The directory every test execution writes its coverage to.
+pub(crate) fn coverage_output() -> DeclaredOutput {
+    DeclaredOutput {
+        name: ForwardRelativePathBuf::unchecked_new("coverage".to_owned()),
+    }
+}
+
+fn in_coverage_output(format: String) -> ArgValue {
+    ArgValue {
+        content: ArgValueContent::DeclaredOutput(coverage_output()),
+        format: Some(format),
+    }
+}
+
+/// Env vars making the tests write their coverage to the coverage output.
+pub(crate) fn coverage_env() -> Vec<(String, ArgValue)> {
+    vec![
+        (
+            COVERAGE_DIR_ENV.to_owned(),
+            in_coverage_output("{}".to_owned()),
+        ),
+        // One profile per process and binary, which doesn't clobber the others.
+        (
+            "LLVM_PROFILE_FILE".to_owned(),
+            in_coverage_output(format!("{{}}/%p-%m.{}", PROFRAW_EXTENSION)),
+        ),
+        (
+            "COVERAGE_FILE".to_owned(),
+            in_coverage_output(format!("{{}}/{}", PYTHON_COVERAGE_FILE)),
+        ),
+    ]
+}
+
+/// Args making tests of this type write their coverage to the coverage output, for the tools
+/// that aren't configured with env vars.
+pub(crate) fn coverage_args(test_type: &str) -> Vec<ArgValue> {
+    match test_type {
+        "go" => vec![in_coverage_output(format!(
+            "-test.coverprofile={{}}/{}",
+            GO_COVERPROFILE
+        ))],
+        "pyunit" => vec![verbatim("--collect-coverage")],
+        _ => Vec::new(),
+    }
+}
+
+/// Whether the directory has LLVM profiles, which need the instrumented binaries to be read.
+pub(crate) fn has_llvm_profiles(dir: &Path) -> bool {
+    list_files(dir).iter().any(|f| is_profraw(f))
+}
+
+fn is_profraw(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new(PROFRAW_EXTENSION))
+}
+
+fn is_python_coverage(path: &Path) -> bool {
+    // coverage.py adds suffixes to the data files of parallel runs.
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map_or(false, |n| {
+            n.starts_with(PYTHON_COVERAGE_FILE) && !n.ends_with(".lcov")
+        })
+}
+
+fn is_go_coverprofile(path: &Path) -> bool {
+    path.file_name() == Some(OsStr::new(GO_COVERPROFILE))
+}
+
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| Some(e.ok()?.path()))
+            .filter(|p| p.is_file())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// The coverage outputs of the test executions so far.
+pub(crate) struct CoverageCollector {
+    report: PathBuf,
+    collected: Mutex<Collected>,
+}
+
+#[derive(Default)]
+struct Collected {
+    dirs: Vec<PathBuf>,
+    /// Instrumented binaries that wrote LLVM profiles.
+    objects: BTreeSet<PathBuf>,
+}
+
+impl CoverageCollector {
+    pub(crate) fn new(report: PathBuf) -> Self {
+        Self {
+            report,
+            collected: Mutex::new(Collected::default()),
+        }
+    }
+
+    pub(crate) fn report(&self) -> &Path {
+        &self.report
+    }
+
+    pub(crate) fn add(&self, dir: PathBuf, object: Option<PathBuf>) {
+        let mut collected = self.collected.lock();
+        collected.dirs.push(dir);
+        collected.objects.extend(object);
+    }
+
+    /// Converts everything that was collected to lcov, and writes it to the report.
+    pub(crate) async fn write_report(&self, config: &Config) -> anyhow::Result<()> {
+        let Collected { dirs, objects } = std::mem::take(&mut *self.collected.lock());
+        let files: Vec<PathBuf> = dirs.iter().flat_map(|d| list_files(d)).collect();
+
+        let mut report = LcovReport::default();
+
+        let profraws: Vec<&PathBuf> = files.iter().filter(|f| is_profraw(f)).collect();
+        if !profraws.is_empty() && !objects.is_empty() {
+            let profdata = self.report.with_extension("profdata");
+            run_tool(
+                &config.llvm_profdata,
+                [OsStr::new("merge"), OsStr::new("-sparse"), OsStr::new("-o")]
+                    .into_iter()
+                    .chain([profdata.as_os_str()])
+                    .chain(profraws.iter().map(|p| p.as_os_str())),
+            )
+            .await?;
+
+            let mut args: Vec<OsString> = vec![
+                "export".into(),
+                "-format=lcov".into(),
+                format!("-instr-profile={}", profdata.display()).into(),
+            ];
+            for (i, object) in objects.iter().enumerate() {
+                if i > 0 {
+                    args.push("-object".into());
+                }
+                args.push(object.as_os_str().to_owned());
+            }
+            report.add_lcov(&run_tool(&config.llvm_cov, args).await?)?;
+        }
+
+        for data_file in files.iter().filter(|f| is_python_coverage(f)) {
+            let lcov = PathBuf::from(format!("{}.lcov", data_file.display()));
+            run_tool(
+                &config.python_coverage,
+                [
+                    OsStr::new("lcov"),
+                    OsStr::new("--data-file"),
+                    data_file.as_os_str(),
+                    OsStr::new("-o"),
+                    lcov.as_os_str(),
+                ],
+            )
+            .await?;
+            report.add_lcov(&read_to_string(&lcov)?)?;
+        }
+
+        for profile in files.iter().filter(|f| is_go_coverprofile(f)) {
+            report.add_go_coverprofile(&read_to_string(profile)?)?;
+        }
+
+        if let Some(parent) = self.report.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Error creating `{}`", parent.display()))?;
+        }
+        std::fs::write(&self.report, report.to_lcov())
+            .with_context(|| format!("Error writing `{}`", self.report.display()))
+    }
+}
+
+fn read_to_string(path: &Path) -> anyhow::Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Error reading `{}`", path.display()))
+}
+
+async fn run_tool(
+    tool: &str,
+    args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+) -> anyhow::Result<String> {
+    let output = tokio::process::Command::new(tool)
+        .args(args)
+        .output()
+        .await
+        .with_context(|| format!("Error running `{}`", tool))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "`{}` failed with {}: {}",
+            tool,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    String::from_utf8(output.stdout).with_context(|| format!("Invalid output of `{}`", tool))
+}
+
+/// Line coverage, as hit counts by line of each source file.
+#[derive(Default, Debug, PartialEq)]
+pub(crate) struct LcovReport {
+    files: BTreeMap<String, BTreeMap<u32, u64>>,
+}
+
+impl LcovReport {
+    fn add_hits(&mut self, file: &str, line: u32, hits: u64) {
+        let lines = match self.files.get_mut(file) {
+            Some(lines) => lines,
+            None => self.files.entry(file.to_owned()).or_default(),
+        };
+        *lines.entry(line).or_default() += hits;
+    }
+
+    /// Adds the line coverage of an lcov tracefile. Other records (functions, branches) aren't
+    /// merged.
+    pub(crate) fn add_lcov(&mut self, lcov: &str) -> anyhow::Result<()> {
+        let mut file = None;
+        for line in lcov.lines().map(str::trim) {
+            if let Some(source) = line.strip_prefix("SF:") {
+                file = Some(source);
+            } else if let Some(data) = line.strip_prefix("DA:") {
+                let file = file.with_context(|| format!("`{}` outside of a record", line))?;
+                // `DA:<line>,<hits>[,<checksum>]`
+                let mut fields = data.split(',');
+                let (line_number, hits) = match (fields.next(), fields.next()) {
+                    (Some(l), Some(h)) => (l, h),
+                    _ => return Err(anyhow::anyhow!("Invalid lcov line `{}`", line)),
+                };
+                let line_number = line_number
+                    .parse()
+                    .with_context(|| format!("Invalid lcov line `{}`", line))?;
+                // Some tools report negative counts when counters overflow.
+                let hits = hits.parse::<i64>().map_or(0, |h| h.max(0) as u64);
+                self.add_hits(file, line_number, hits);
+            } else if line == "end_of_record" {
+                file = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the line coverage of a Go coverprofile, whose lines are
+    /// `<file>:<line>.<column>,<line>.<column> <statements> <count>`.
+    pub(crate) fn add_go_coverprofile(&mut self, profile: &str) -> anyhow::Result<()> {
+        // Blocks may share lines, which are hit as often as the most hit of them.
+        let mut hits = BTreeMap::<(&str, u32), u64>::new();
+        for line in profile.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with("mode:") {
+                continue;
+            }
+            let parse = || -> Option<(&str, u32, u32, u64)> {
+                let (block, counts) = line.rsplit_once(':')?;
+                let mut fields = counts.split_whitespace();
+                let (start, end) = fields.next()?.split_once(',')?;
+                let start = start.split_once('.')?.0.parse().ok()?;
+                let end = end.split_once('.')?.0.parse().ok()?;
+                let count = fields.nth(1)?.parse().ok()?;
+                Some((block, start, end, count))
+            };
+            let (file, start, end, count) =
+                parse().with_context(|| format!("Invalid coverprofile line `{}`", line))?;
+            for line_number in start..=end {
+                let line_hits = hits.entry((file, line_number)).or_default();
+                *line_hits = (*line_hits).max(count);
+            }
+        }
+        for ((file, line_number), count) in hits {
+            self.add_hits(file, line_number, count);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn to_lcov(&self) -> String {
+        let mut lcov = String::new();
+        for (file, lines) in &self.files {
+            writeln!(lcov, "SF:{}", file).unwrap();
+            for (line, hits) in lines {
+                writeln!(lcov, "DA:{},{}", line, hits).unwrap();
+            }
+            writeln!(lcov, "LF:{}", lines.len()).unwrap();
+            writeln!(lcov, "LH:{}", lines.values().filter(|h| **h > 0).count()).unwrap();
+            writeln!(lcov, "end_of_record").unwrap();
+        }
+        lcov
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_lcov() -> anyhow::Result<()> {
+        let mut report = LcovReport::default();
+        report.add_lcov("TN:\nSF:/src/a.rs\nFN:1,main\nDA:1,1\nDA:2,0\nend_of_record\n")?;
+        report.add_lcov(
+            "SF:/src/a.rs\nDA:2,3,abc\nend_of_record\nSF:/src/b.rs\nDA:5,0\nend_of_record\n",
+        )?;
+        assert_eq!(
+            "SF:/src/a.rs\nDA:1,1\nDA:2,3\nLF:2\nLH:2\nend_of_record\n\
+             SF:/src/b.rs\nDA:5,0\nLF:1\nLH:0\nend_of_record\n",
+            report.to_lcov()
+        );
+        assert!(report.add_lcov("DA:1,1\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_go_coverprofile() -> anyhow::Result<()> {
+        let mut report = LcovReport::default();
+        report.add_go_coverprofile(
+            "mode: set\n\
+             example.com/foo/foo.go:3.20,5.2 1 1\n\
+             example.com/foo/foo.go:5.2,6.3 1 0\n\
+             example.com/foo/foo.go:8.10,8.20 1 0\n",
+        )?;
+        assert_eq!(
+            "SF:example.com/foo/foo.go\nDA:3,1\nDA:4,1\nDA:5,1\nDA:6,0\nDA:8,0\nLF:5\nLH:3\nend_of_record\n",
+            report.to_lcov()
+        );
+        assert!(report.add_go_coverprofile("foo.go 1 1\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_coverage_args() {
+        assert_eq!(
+            vec![verbatim("--collect-coverage")],
+            coverage_args("pyunit")
+        );
+        assert_eq!(1, coverage_args("go").len());
+        assert!(coverage_args("rust").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_write_report() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        use clap::Parser;
+
+        let temp = tempfile::tempdir()?;
+        let dir = temp.path().join("coverage");
+        std::fs::create_dir(&dir)?;
+        std::fs::write(dir.join("python.coverage.host.1.2"), "")?;
+        std::fs::write(
+            dir.join(GO_COVERPROFILE),
+            "mode: set\nexample.com/foo/foo.go:1.1,2.3 1 1\n",
+        )?;
+
+        // Stands in for coverage.py, as `coverage lcov --data-file <data> -o <lcov>`.
+        let python_coverage = temp.path().join("coverage.sh");
+        std::fs::write(
+            &python_coverage,
+            "#!/bin/sh\nprintf 'SF:/src/a.py\\nDA:1,1\\nDA:2,0\\nend_of_record\\n' > \"$5\"\n",
+        )?;
+        std::fs::set_permissions(&python_coverage, std::fs::Permissions::from_mode(0o755))?;
+
+        let config = Config::try_parse_from([
+            OsStr::new("runner"),
+            OsStr::new("--buck-test-info=info.json"),
+            OsStr::new("--python-coverage"),
+            python_coverage.as_os_str(),
+        ])?;
+        let collector = CoverageCollector::new(temp.path().join("report").join("coverage.lcov"));
+        collector.add(dir, None);
+        collector.write_report(&config).await?;
+
+        assert_eq!(
+            "SF:/src/a.py\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\n\
+             SF:example.com/foo/foo.go\nDA:1,1\nDA:2,1\nLF:2\nLH:2\nend_of_record\n",
+            read_to_string(collector.report())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_coverage_files() {
+        assert!(is_profraw(Path::new("/out/coverage/123-456.profraw")));
+        assert!(is_python_coverage(Path::new(
+            "/out/coverage/python.coverage.host.1.2"
+        )));
+        assert!(!is_python_coverage(Path::new(
+            "/out/coverage/python.coverage.lcov"
+        )));
+        assert!(is_go_coverprofile(Path::new(
+            "/out/coverage/go.coverprofile"
+        )));
+    }
+}
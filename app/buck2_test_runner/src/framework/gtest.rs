@@ -0,0 +1,202 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! [GoogleTest](https://google.github.io/googletest/).
+
+use std::time::Duration;
+
+use anyhow::Context;
+use buck2_test_api::data::ArgValue;
+use buck2_test_api::data::DeclaredOutput;
+use buck2_test_api::data::TestStatus;
+use serde::Deserialize;
+
+use crate::framework::output_arg;
+use crate::framework::report_output;
+use crate::framework::verbatim;
+use crate::framework::TestCaseResult;
+use crate::framework::TestFramework;
+use crate::framework::TestOutput;
+
+pub(crate) struct GTest;
+
+const REPORT: &str = "gtest.json";
+
+#[derive(Deserialize)]
+struct Report {
+    #[serde(default)]
+    testsuites: Vec<Suite>,
+}
+
+#[derive(Deserialize)]
+struct Suite {
+    name: String,
+    #[serde(default)]
+    testsuite: Vec<Case>,
+}
+
+#[derive(Deserialize)]
+struct Case {
+    name: String,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    result: String,
+    time: Option<String>,
+    #[serde(default)]
+    failures: Vec<Failure>,
+}
+
+#[derive(Deserialize)]
+struct Failure {
+    failure: String,
+}
+
+impl TestFramework for GTest {
+    fn list_args(&self) -> Vec<ArgValue> {
+        vec![verbatim("--gtest_list_tests")]
+    }
+
+    fn parse_listing(&self, stdout: &str) -> anyhow::Result<Vec<String>> {
+        // Suites are followed by their test cases, indented. Both may be followed by a comment
+        // describing their parameters, e.g.:
+        //
+        // Suite.
+        //   Case
+        // Typed/0.  # TypeParam = int
+        //   Case/0  # GetParam() = 1
+        let mut suite = None;
+        let mut testcases = Vec::new();
+        for line in stdout.lines() {
+            let name = match line.split_whitespace().next() {
+                Some(name) => name,
+                None => continue,
+            };
+            if line.starts_with(char::is_whitespace) {
+                let suite =
+                    suite.with_context(|| format!("Test case `{}` is not in a suite", name))?;
+                testcases.push(format!("{}{}", suite, name));
+            } else {
+                suite = Some(name);
+            }
+        }
+        Ok(testcases)
+    }
+
+    fn run_args(&self, testcase: Option<&str>) -> Vec<ArgValue> {
+        let mut args = Vec::new();
+        if let Some(testcase) = testcase {
+            args.push(verbatim(format!("--gtest_filter={}", testcase)));
+        }
+        args.push(output_arg(report_output(REPORT), "--gtest_output=json:{}"));
+        args
+    }
+
+    fn report(&self) -> Option<DeclaredOutput> {
+        Some(report_output(REPORT))
+    }
+
+    fn parse_results(&self, output: &TestOutput<'_>) -> anyhow::Result<Vec<TestCaseResult>> {
+        let report = output
+            .report
+            .context("Test binary did not write a report")?;
+        let report: Report =
+            serde_json::from_str(report).context("Error parsing GoogleTest report")?;
+
+        let mut results = Vec::new();
+        for suite in report.testsuites {
+            for case in suite.testsuite {
+                let status = if case.status == "NOTRUN"
+                    || case.result == "SKIPPED"
+                    || case.result == "SUPPRESSED"
+                {
+                    TestStatus::SKIP
+                } else if case.failures.is_empty() {
+                    TestStatus::PASS
+                } else {
+                    TestStatus::FAIL
+                };
+                let details = case
+                    .failures
+                    .into_iter()
+                    .map(|f| f.failure)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                results.push(TestCaseResult {
+                    name: format!("{}.{}", suite.name, case.name),
+                    status,
+                    duration: case.time.as_deref().and_then(parse_time),
+                    msg: None,
+                    details,
+                });
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Parses a time like `0.123s`.
+fn parse_time(time: &str) -> Option<Duration> {
+    let seconds: f64 = time.strip_suffix('s')?.parse().ok()?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listing() -> anyhow::Result<()> {
+        let stdout = "Suite.\n  A\n  B\nTyped/0.  # TypeParam = int\n  A/0  # GetParam() = 1\n";
+        assert_eq!(
+            vec!["Suite.A", "Suite.B", "Typed/0.A/0"],
+            GTest.parse_listing(stdout)?
+        );
+        assert!(GTest.parse_listing("  A\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_results() -> anyhow::Result<()> {
+        let report = r#"{
+            "testsuites": [{
+                "name": "Suite",
+                "testsuite": [
+                    {"name": "A", "status": "RUN", "result": "COMPLETED", "time": "0.5s"},
+                    {
+                        "name": "B",
+                        "status": "RUN",
+                        "result": "COMPLETED",
+                        "time": "0s",
+                        "failures": [{"failure": "a.cpp:1\nExpected: 1", "type": ""}]
+                    },
+                    {"name": "C", "status": "RUN", "result": "SKIPPED", "time": "0s"}
+                ]
+            }]
+        }"#;
+        let results = GTest.parse_results(&TestOutput {
+            stdout: "",
+            report: Some(report),
+        })?;
+        assert_eq!(
+            vec![
+                ("Suite.A", TestStatus::PASS),
+                ("Suite.B", TestStatus::FAIL),
+                ("Suite.C", TestStatus::SKIP),
+            ],
+            results
+                .iter()
+                .map(|r| (r.name.as_str(), r.status.clone()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(Duration::from_millis(500)), results[0].duration);
+        assert_eq!("a.cpp:1\nExpected: 1", results[1].details);
+        Ok(())
+    }
+}
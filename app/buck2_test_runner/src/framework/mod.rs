@@ -0,0 +1,97 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Support for the test frameworks whose test cases we can list and report individually.
+
+mod gtest;
+mod pyunit;
+mod rust;
+
+use std::time::Duration;
+
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_test_api::data::ArgValue;
+use buck2_test_api::data::ArgValueContent;
+use buck2_test_api::data::DeclaredOutput;
+use buck2_test_api::data::ExternalRunnerSpecValue;
+use buck2_test_api::data::TestStatus;
+
+use crate::framework::gtest::GTest;
+use crate::framework::pyunit::PyUnit;
+use crate::framework::rust::RustTest;
+
+/// The result of one test case, as reported by the test binary.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TestCaseResult {
+    pub(crate) name: String,
+    pub(crate) status: TestStatus,
+    pub(crate) duration: Option<Duration>,
+    pub(crate) msg: Option<String>,
+    pub(crate) details: String,
+}
+
+/// The output of a test binary that ran test cases.
+pub(crate) struct TestOutput<'a> {
+    pub(crate) stdout: &'a str,
+    /// The contents of the report requested via `TestFramework::report`, if the binary wrote it.
+    pub(crate) report: Option<&'a str>,
+}
+
+pub(crate) trait TestFramework: Send + Sync {
+    /// Arguments that make the test binary list its test cases instead of running them.
+    fn list_args(&self) -> Vec<ArgValue>;
+
+    /// Returns the names of the test cases from the output of the listing.
+    fn parse_listing(&self, stdout: &str) -> anyhow::Result<Vec<String>>;
+
+    /// Arguments that make the test binary run one test case, or all of them if `None`.
+    fn run_args(&self, testcase: Option<&str>) -> Vec<ArgValue>;
+
+    /// The output the test binary writes its structured report to, if it's not its stdout.
+    fn report(&self) -> Option<DeclaredOutput> {
+        None
+    }
+
+    /// Returns the results of the test cases that ran.
+    fn parse_results(&self, output: &TestOutput<'_>) -> anyhow::Result<Vec<TestCaseResult>>;
+}
+
+/// Returns the framework for a `test_type` of `ExternalRunnerSpec`, if we support it.
+pub(crate) fn framework_for(test_type: &str) -> Option<&'static dyn TestFramework> {
+    match test_type {
+        "gtest" => Some(&GTest),
+        "rust" => Some(&RustTest),
+        "pyunit" => Some(&PyUnit),
+        _ => None,
+    }
+}
+
+pub(crate) fn verbatim(arg: impl Into<String>) -> ArgValue {
+    ArgValue {
+        content: ArgValueContent::ExternalRunnerSpecValue(ExternalRunnerSpecValue::Verbatim(
+            arg.into(),
+        )),
+        format: None,
+    }
+}
+
+fn report_output(name: &str) -> DeclaredOutput {
+    DeclaredOutput {
+        name: ForwardRelativePathBuf::unchecked_new(name.to_owned()),
+    }
+}
+
+/// An argument that expands to the path of `output`, formatted with `format` (where `{}` is
+/// replaced by the path).
+fn output_arg(output: DeclaredOutput, format: &str) -> ArgValue {
+    ArgValue {
+        content: ArgValueContent::DeclaredOutput(output),
+        format: Some(format.to_owned()),
+    }
+}
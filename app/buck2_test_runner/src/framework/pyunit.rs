@@ -0,0 +1,186 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Python tests, run by the `__test_main__.py` of the prelude.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use buck2_test_api::data::ArgValue;
+use buck2_test_api::data::DeclaredOutput;
+use buck2_test_api::data::TestStatus;
+use serde::Deserialize;
+
+use crate::framework::output_arg;
+use crate::framework::report_output;
+use crate::framework::verbatim;
+use crate::framework::TestCaseResult;
+use crate::framework::TestFramework;
+use crate::framework::TestOutput;
+
+pub(crate) struct PyUnit;
+
+const REPORT: &str = "pyunit.json";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Case {
+    test_case_name: String,
+    test_case: String,
+    #[serde(rename = "type")]
+    status: String,
+    time: Option<u64>,
+    #[serde(default)]
+    message: String,
+    stacktrace: Option<String>,
+    #[serde(default)]
+    std_out: String,
+    #[serde(default)]
+    std_err: String,
+}
+
+/// The report also contains entries that aren't test cases (e.g. coverage), which we skip.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Case(Case),
+    Other(serde::de::IgnoredAny),
+}
+
+impl TestFramework for PyUnit {
+    fn list_args(&self) -> Vec<ArgValue> {
+        vec![verbatim("--list-tests"), verbatim("--list-format=buck")]
+    }
+
+    fn parse_listing(&self, stdout: &str) -> anyhow::Result<Vec<String>> {
+        // Tests are listed as `module.Class#method`, but selected as `module.Class.method`.
+        stdout
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                let (class, method) = l
+                    .trim()
+                    .split_once('#')
+                    .with_context(|| format!("Invalid test name: `{}`", l))?;
+                Ok(format!("{}.{}", class, method))
+            })
+            .collect()
+    }
+
+    fn run_args(&self, testcase: Option<&str>) -> Vec<ArgValue> {
+        let mut args = vec![output_arg(report_output(REPORT), "--output={}")];
+        if let Some(testcase) = testcase {
+            args.push(verbatim(testcase));
+        }
+        args
+    }
+
+    fn report(&self) -> Option<DeclaredOutput> {
+        Some(report_output(REPORT))
+    }
+
+    fn parse_results(&self, output: &TestOutput<'_>) -> anyhow::Result<Vec<TestCaseResult>> {
+        let report = output
+            .report
+            .context("Test binary did not write a report")?;
+        let entries: Vec<Entry> =
+            serde_json::from_str(report).context("Error parsing Python test report")?;
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::Case(case) => Some(case),
+                Entry::Other(..) => None,
+            })
+            .map(|case| {
+                let status = match case.status.as_str() {
+                    "SUCCESS" => TestStatus::PASS,
+                    "FAILURE" => TestStatus::FAIL,
+                    "ASSUMPTION_VIOLATION" | "EXCLUDED" => TestStatus::SKIP,
+                    _ => TestStatus::UNKNOWN,
+                };
+                let mut details = String::new();
+                for (header, section) in [
+                    ("STACKTRACE", case.stacktrace.as_deref().unwrap_or_default()),
+                    ("STDOUT", case.std_out.as_str()),
+                    ("STDERR", case.std_err.as_str()),
+                ] {
+                    if !section.is_empty() {
+                        details.push_str(&format!("---- {} ----\n{}\n", header, section));
+                    }
+                }
+                TestCaseResult {
+                    name: format!("{}.{}", case.test_case_name, case.test_case),
+                    status,
+                    duration: case.time.map(Duration::from_millis),
+                    msg: Some(case.message).filter(|m| !m.is_empty()),
+                    details,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listing() -> anyhow::Result<()> {
+        let stdout = "tests.test_a.ATest#test_one\ntests.test_a.ATest#test_two\n";
+        assert_eq!(
+            vec!["tests.test_a.ATest.test_one", "tests.test_a.ATest.test_two"],
+            PyUnit.parse_listing(stdout)?
+        );
+        assert!(PyUnit.parse_listing("<unittest.suite>").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_results() -> anyhow::Result<()> {
+        let report = r#"[
+            {
+                "testCaseName": "tests.test_a.ATest",
+                "testCase": "test_one",
+                "type": "SUCCESS",
+                "time": 12,
+                "message": "",
+                "stacktrace": null,
+                "stdOut": "",
+                "stdErr": ""
+            },
+            {
+                "testCaseName": "tests.test_a.ATest",
+                "testCase": "test_two",
+                "type": "FAILURE",
+                "time": 3,
+                "message": "AssertionError: 1 != 2",
+                "stacktrace": "Traceback ...",
+                "stdOut": "hello",
+                "stdErr": ""
+            },
+            {"coverage": {}}
+        ]"#;
+        let results = PyUnit.parse_results(&TestOutput {
+            stdout: "",
+            report: Some(report),
+        })?;
+        assert_eq!(2, results.len());
+        assert_eq!("tests.test_a.ATest.test_one", results[0].name);
+        assert_eq!(TestStatus::PASS, results[0].status);
+        assert_eq!(Some(Duration::from_millis(12)), results[0].duration);
+        assert_eq!(TestStatus::FAIL, results[1].status);
+        assert_eq!(Some("AssertionError: 1 != 2"), results[1].msg.as_deref());
+        assert_eq!(
+            "---- STACKTRACE ----\nTraceback ...\n---- STDOUT ----\nhello\n",
+            results[1].details
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! The libtest harness of Rust tests.
+//!
+//! Its structured output is unstable, so we parse the human readable one.
+
+use std::collections::HashMap;
+
+use buck2_test_api::data::ArgValue;
+use buck2_test_api::data::TestStatus;
+
+use crate::framework::verbatim;
+use crate::framework::TestCaseResult;
+use crate::framework::TestFramework;
+use crate::framework::TestOutput;
+
+pub(crate) struct RustTest;
+
+impl TestFramework for RustTest {
+    fn list_args(&self) -> Vec<ArgValue> {
+        vec![verbatim("--list"), verbatim("--format=terse")]
+    }
+
+    fn parse_listing(&self, stdout: &str) -> anyhow::Result<Vec<String>> {
+        // One `<name>: test` line per test (benchmarks are listed as `<name>: bench`).
+        Ok(stdout
+            .lines()
+            .filter_map(|l| l.strip_suffix(": test"))
+            .map(|name| name.to_owned())
+            .collect())
+    }
+
+    fn run_args(&self, testcase: Option<&str>) -> Vec<ArgValue> {
+        match testcase {
+            Some(testcase) => vec![verbatim(testcase), verbatim("--exact")],
+            None => Vec::new(),
+        }
+    }
+
+    fn parse_results(&self, output: &TestOutput<'_>) -> anyhow::Result<Vec<TestCaseResult>> {
+        let captured = parse_captured_output(output.stdout);
+
+        let mut results = Vec::new();
+        for line in output.stdout.lines() {
+            // e.g. `test tests::a ... ok`.
+            let (name, outcome) = match line
+                .strip_prefix("test ")
+                .and_then(|l| l.split_once(" ... "))
+            {
+                Some(result) => result,
+                None => continue,
+            };
+            let status = match outcome {
+                "ok" => TestStatus::PASS,
+                "FAILED" => TestStatus::FAIL,
+                o if o.starts_with("ignored") => TestStatus::SKIP,
+                _ => continue,
+            };
+            results.push(TestCaseResult {
+                name: name.to_owned(),
+                status,
+                duration: None,
+                msg: None,
+                details: captured.get(name).copied().unwrap_or_default().to_owned(),
+            });
+        }
+        Ok(results)
+    }
+}
+
+/// Returns the output of failed tests, which libtest prints after running all of them in sections
+/// like:
+///
+/// ```text
+/// ---- tests::a stdout ----
+/// thread 'tests::a' panicked at ...
+/// ```
+fn parse_captured_output(stdout: &str) -> HashMap<&str, &str> {
+    let mut captured = HashMap::new();
+    let mut current: Option<(&str, usize)> = None;
+    let mut offset = 0;
+    for line in stdout.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        let header = trimmed
+            .strip_prefix("---- ")
+            .and_then(|l| l.strip_suffix(" stdout ----"));
+        if header.is_some() || trimmed == "failures:" || trimmed == "successes:" {
+            if let Some((name, start)) = current.take() {
+                captured.insert(name, stdout[start..offset].trim_end());
+            }
+        }
+        offset += line.len();
+        if let Some(name) = header {
+            current = Some((name, offset));
+        }
+    }
+    if let Some((name, start)) = current {
+        captured.insert(name, stdout[start..].trim_end());
+    }
+    captured
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listing() -> anyhow::Result<()> {
+        let stdout = "tests::a: test\ntests::b: test\nbench::c: bench\n";
+        assert_eq!(
+            vec!["tests::a", "tests::b"],
+            RustTest.parse_listing(stdout)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_results() -> anyhow::Result<()> {
+        let stdout = "\nrunning 3 tests\n\
+            test tests::a ... ok\n\
+            test tests::b ... FAILED\n\
+            test tests::c ... ignored, slow\n\
+            \n\
+            failures:\n\
+            \n\
+            ---- tests::b stdout ----\n\
+            thread 'tests::b' panicked at 'assertion failed', src/lib.rs:10:5\n\
+            \n\
+            \n\
+            failures:\n    tests::b\n\n\
+            test result: FAILED. 1 passed; 1 failed; 1 ignored\n";
+        let results = RustTest.parse_results(&TestOutput {
+            stdout,
+            report: None,
+        })?;
+        assert_eq!(
+            vec![
+                ("tests::a", TestStatus::PASS),
+                ("tests::b", TestStatus::FAIL),
+                ("tests::c", TestStatus::SKIP),
+            ],
+            results
+                .iter()
+                .map(|r| (r.name.as_str(), r.status.clone()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "thread 'tests::b' panicked at 'assertion failed', src/lib.rs:10:5",
+            results[1].details
+        );
+        Ok(())
+    }
+}
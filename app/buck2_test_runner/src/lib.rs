@@ -10,7 +10,10 @@
 #![feature(async_closure)]
 
 mod config;
+mod coverage;
 mod executor;
+mod framework;
+mod retry;
 mod runner;
 mod service;
 pub mod tcp;
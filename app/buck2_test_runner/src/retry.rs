@@ -0,0 +1,91 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Retries of failed tests, and quarantine of tests known to be broken.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::Context;
+use buck2_test_api::data::TestStatus;
+
+/// Label overriding the max number of attempts for the tests of a target, e.g.
+/// `max_attempts=3`.
+const MAX_ATTEMPTS_LABEL: &str = "max_attempts=";
+
+/// Returns the max number of times to run the tests of a target with those labels.
+pub(crate) fn max_attempts(default: u32, labels: &[String]) -> u32 {
+    labels
+        .iter()
+        .filter_map(|l| l.strip_prefix(MAX_ATTEMPTS_LABEL)?.parse().ok())
+        .last()
+        .unwrap_or(default)
+        .max(1)
+}
+
+pub(crate) fn is_retriable(status: &TestStatus) -> bool {
+    matches!(
+        status,
+        TestStatus::FAIL | TestStatus::FATAL | TestStatus::TIMEOUT
+    )
+}
+
+/// Tests whose failures are reported but don't fail the run.
+#[derive(Default)]
+pub(crate) struct Quarantine {
+    tests: HashSet<String>,
+}
+
+impl Quarantine {
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Error reading quarantine file `{}`", path.display()))?;
+        Ok(Self::parse(&contents))
+    }
+
+    /// One test per line, either a target (which quarantines all its test cases) or a test case
+    /// as `<target> - <test case>`. Empty lines and lines starting with `#` are ignored.
+    fn parse(contents: &str) -> Self {
+        Self {
+            tests: contents
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(|l| l.to_owned())
+                .collect(),
+        }
+    }
+
+    pub(crate) fn contains(&self, target: &str, name: &str) -> bool {
+        self.tests.contains(target) || self.tests.contains(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_attempts() {
+        let labels = |labels: &[&str]| labels.iter().map(|l| (*l).to_owned()).collect::<Vec<_>>();
+        assert_eq!(2, max_attempts(2, &labels(&["unit"])));
+        assert_eq!(5, max_attempts(2, &labels(&["unit", "max_attempts=5"])));
+        assert_eq!(1, max_attempts(2, &labels(&["max_attempts=0"])));
+        assert_eq!(2, max_attempts(2, &labels(&["max_attempts=many"])));
+    }
+
+    #[test]
+    fn test_quarantine() {
+        let quarantine =
+            Quarantine::parse("# Broken on CI\nroot//foo:a\n\n  root//foo:b - tests::flaky  \n");
+        assert!(quarantine.contains("root//foo:a", "root//foo:a - tests::x"));
+        assert!(quarantine.contains("root//foo:b", "root//foo:b - tests::flaky"));
+        assert!(!quarantine.contains("root//foo:b", "root//foo:b - tests::other"));
+    }
+}
@@ -7,6 +7,8 @@
  * of this source tree.
  */
 
+use std::path::PathBuf;
+
 use anyhow::Context;
 use buck2_test_api::data::ArgValue;
 use buck2_test_api::data::ArgValueContent;
@@ -15,12 +17,15 @@ use buck2_test_api::data::DisplayMetadata;
 use buck2_test_api::data::ExecuteResponse;
 use buck2_test_api::data::ExecutionResult2;
 use buck2_test_api::data::ExecutionStatus;
+use buck2_test_api::data::ExecutionStream;
 use buck2_test_api::data::ExternalRunnerSpec;
 use buck2_test_api::data::ExternalRunnerSpecValue;
+use buck2_test_api::data::Output;
 use buck2_test_api::data::RequiredLocalResources;
 use buck2_test_api::data::TestResult;
 use buck2_test_api::data::TestStatus;
 use buck2_test_api::grpc::TestOrchestratorClient;
+use buck2_test_api::sharding::TestShard;
 use clap::Parser;
 use futures::channel::mpsc::UnboundedReceiver;
 use futures::StreamExt;
@@ -29,6 +34,18 @@ use parking_lot::Mutex;
 
 use crate::config::Config;
 use crate::config::EnvValue;
+use crate::coverage::coverage_args;
+use crate::coverage::coverage_env;
+use crate::coverage::coverage_output;
+use crate::coverage::has_llvm_profiles;
+use crate::coverage::CoverageCollector;
+use crate::framework::framework_for;
+use crate::framework::TestCaseResult;
+use crate::framework::TestFramework;
+use crate::framework::TestOutput;
+use crate::retry::is_retriable;
+use crate::retry::max_attempts;
+use crate::retry::Quarantine;
 
 pub type SpecReceiver = UnboundedReceiver<ExternalRunnerSpec>;
 
@@ -43,6 +60,9 @@ pub struct Buck2TestRunner {
     orchestrator_client: TestOrchestratorClient,
     spec_receiver: Mutex<Option<SpecReceiver>>,
     config: Config,
+    quarantine: Quarantine,
+    shard: Option<TestShard>,
+    coverage: Option<CoverageCollector>,
 }
 
 impl Buck2TestRunner {
@@ -52,10 +72,25 @@ impl Buck2TestRunner {
         args: Vec<String>,
     ) -> anyhow::Result<Self> {
         let config = Config::try_parse_from(args).context("Error parsing test runner arguments")?;
+        let quarantine = match &config.quarantine_file {
+            Some(path) => Quarantine::load(path)?,
+            None => Quarantine::default(),
+        };
+        let shard = match (config.shard_index, config.shard_count) {
+            (Some(index), Some(count)) => Some(TestShard::new(index, count)?),
+            _ => None,
+        };
+        let coverage = config
+            .coverage_report
+            .as_ref()
+            .map(|report| CoverageCollector::new(report.clone()));
         Ok(Self {
             orchestrator_client,
             spec_receiver: Mutex::new(Some(spec_receiver)),
             config,
+            quarantine,
+            shard,
+            coverage,
         })
     }
 
@@ -70,30 +105,9 @@ impl Buck2TestRunner {
         }
         let run_verdict = receiver
             .map(async move |spec| {
-                let name = format!(
-                    "{}//{}:{}",
-                    spec.target.cell, spec.target.package, spec.target.target
-                );
-                let target_handle = spec.target.handle.to_owned();
-
-                let execution_response = self
-                    .execute_test_from_spec(spec)
+                self.run_spec(spec)
                     .await
-                    .expect("Test execution request failed");
-
-                let execution_result = match execution_response {
-                    ExecuteResponse::Result(r) => r,
-                    ExecuteResponse::Cancelled => return TestStatus::OMITTED,
-                };
-
-                let test_result = get_test_result(name, target_handle, execution_result);
-                let test_status = test_result.status.clone();
-
-                self.report_test_result(test_result)
-                    .await
-                    .expect("Test result reporting failed");
-
-                test_status
+                    .expect("Test execution request failed")
             })
             // Use an arbitrarily large buffer -- execution throttling will be handled by the Buck2
             // executor, so no need to hold back on requests here.
@@ -101,8 +115,8 @@ impl Buck2TestRunner {
             // If any individual test failed, consider the entire run to have failed.
             .fold(
                 RunVerdict::Pass,
-                async move |mut run_verdict, test_status| {
-                    if test_status != TestStatus::PASS {
+                async move |mut run_verdict, test_statuses| {
+                    if !test_statuses.iter().all(is_passing) {
                         run_verdict = RunVerdict::Fail;
                     }
                     run_verdict
@@ -110,20 +124,325 @@ impl Buck2TestRunner {
             )
             .await;
 
+        if let Some(coverage) = &self.coverage {
+            let message = match coverage.write_report(&self.config).await {
+                Ok(()) => format!("Coverage report: {}", coverage.report().display()),
+                Err(e) => format!("Error writing coverage report: {:#}", e),
+            };
+            self.orchestrator_client
+                .attach_info_message(message)
+                .await?;
+        }
+
         self.orchestrator_client
             .end_of_test_results(run_verdict.exit_code())
             .await
     }
 
-    async fn execute_test_from_spec(
+    /// Runs the tests of a target and reports their results. If we know its framework, we list
+    /// its test cases and report a result for each of them, otherwise one for the whole target.
+    /// Failed tests are retried according to the retry policy, and only the tests of our shard
+    /// are run, if any. Returns the statuses that decide whether the run passes.
+    async fn run_spec(&self, spec: ExternalRunnerSpec) -> anyhow::Result<Vec<TestStatus>> {
+        let name = format!(
+            "{}//{}:{}",
+            spec.target.cell, spec.target.package, spec.target.target
+        );
+        let max_attempts = max_attempts(self.config.max_attempts, &spec.labels);
+
+        let framework = framework_for(&spec.test_type);
+        if let (Some(shard), None) = (&self.shard, framework) {
+            if !shard.includes(&name) {
+                return Ok(Vec::new());
+            }
+        }
+
+        let batches: Vec<(Option<String>, Vec<String>)> = match framework {
+            Some(framework) => {
+                let mut testcases = match self.list_testcases(&spec, framework, &name).await? {
+                    Some(testcases) => testcases,
+                    None if self.quarantine.contains(&name, &name) => return Ok(Vec::new()),
+                    None => return Ok(vec![TestStatus::LISTING_FAILED]),
+                };
+                if let Some(shard) = &self.shard {
+                    testcases.retain(|t| shard.includes(&format!("{} - {}", name, t)));
+                }
+                // Frameworks select a single test case, so that's how we run a shard of them.
+                if self.config.run_testcases_individually || self.shard.is_some() {
+                    testcases
+                        .into_iter()
+                        .map(|t| (Some(t.clone()), vec![t]))
+                        .collect()
+                } else if testcases.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![(None, testcases)]
+                }
+            }
+            None => vec![(None, Vec::new())],
+        };
+
+        let mut runs = match self.run_batches(&spec, framework, &name, batches).await? {
+            Some(runs) => runs,
+            None => return Ok(vec![TestStatus::OMITTED]),
+        };
+
+        let mut statuses = Vec::new();
+        let mut attempt = 1;
+        loop {
+            let mut retries = Vec::new();
+            for TestRun {
+                testcase,
+                mut result,
+            } in runs
+            {
+                if attempt < max_attempts && is_retriable(&result.status) {
+                    result.status = TestStatus::RERUN;
+                    self.report_test_result(result).await?;
+                    retries.push(testcase);
+                    continue;
+                }
+
+                if attempt > 1 && result.status == TestStatus::PASS {
+                    result.status = TestStatus::FLAKY;
+                }
+                if !is_passing(&result.status) && self.quarantine.contains(&name, &result.name) {
+                    result.msg = Some(match result.msg {
+                        Some(msg) => format!("Quarantined: {}", msg),
+                        None => "Quarantined".to_owned(),
+                    });
+                } else {
+                    statuses.push(result.status.clone());
+                }
+                self.report_test_result(result).await?;
+            }
+
+            if retries.is_empty() {
+                return Ok(statuses);
+            }
+
+            // Only what failed is run again.
+            attempt += 1;
+            let batches = retries
+                .into_iter()
+                .map(|testcase| (testcase.clone(), testcase.into_iter().collect()))
+                .collect();
+            runs = match self.run_batches(&spec, framework, &name, batches).await? {
+                Some(runs) => runs,
+                None => {
+                    statuses.push(TestStatus::OMITTED);
+                    return Ok(statuses);
+                }
+            };
+        }
+    }
+
+    /// Runs batches of test cases concurrently (see `run_testcases`), or the whole target if we
+    /// don't know its framework. Returns `None` if the test run was cancelled.
+    async fn run_batches(
         &self,
-        spec: ExternalRunnerSpec,
-    ) -> anyhow::Result<ExecuteResponse> {
+        spec: &ExternalRunnerSpec,
+        framework: Option<&dyn TestFramework>,
+        name: &str,
+        batches: Vec<(Option<String>, Vec<String>)>,
+    ) -> anyhow::Result<Option<Vec<TestRun>>> {
+        let runs = futures::future::try_join_all(batches.into_iter().map(
+            |(selected, testcases)| async move {
+                match framework {
+                    Some(framework) => {
+                        self.run_testcases(spec, framework, name, selected, testcases)
+                            .await
+                    }
+                    None => self.run_target(spec, name).await,
+                }
+            },
+        ))
+        .await?;
+
+        Ok(runs
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .map(|runs| runs.into_iter().flatten().collect()))
+    }
+
+    async fn run_target(
+        &self,
+        spec: &ExternalRunnerSpec,
+        name: &str,
+    ) -> anyhow::Result<Option<Vec<TestRun>>> {
         let display_metadata = DisplayMetadata::Testing {
-            suite: spec.target.target,
+            suite: spec.target.target.clone(),
             testcases: Vec::new(),
         };
+        let execution_result = match self
+            .execute_test_from_spec(spec, display_metadata, Vec::new())
+            .await?
+        {
+            ExecuteResponse::Result(r) => r,
+            ExecuteResponse::Cancelled => return Ok(None),
+        };
+        Ok(Some(vec![TestRun {
+            testcase: None,
+            result: get_test_result(name.to_owned(), spec.target.handle, execution_result),
+        }]))
+    }
+
+    /// Lists the test cases of a target and reports them as discovered. Returns `None` if the
+    /// listing failed (which is reported as the result of the target) or was cancelled.
+    async fn list_testcases(
+        &self,
+        spec: &ExternalRunnerSpec,
+        framework: &dyn TestFramework,
+        name: &str,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        let suite = spec.target.target.clone();
+        let display_metadata = DisplayMetadata::Listing(suite.clone());
+        let execution_result = match self
+            .execute_test_from_spec(spec, display_metadata, framework.list_args())
+            .await?
+        {
+            ExecuteResponse::Result(r) => r,
+            ExecuteResponse::Cancelled => return Ok(None),
+        };
+
+        let testcases = match execution_result.status {
+            ExecutionStatus::Finished { exitcode: 0 } => {
+                framework.parse_listing(&stream_to_string(&execution_result.stdout))
+            }
+            ExecutionStatus::Finished { exitcode } => {
+                Err(anyhow::anyhow!("Listing exited with code {}", exitcode))
+            }
+            ExecutionStatus::TimedOut { duration } => Err(anyhow::anyhow!(
+                "Listing timed out after {}s",
+                duration.as_secs()
+            )),
+        };
+
+        match testcases {
+            Ok(testcases) => {
+                self.orchestrator_client
+                    .report_tests_discovered(spec.target.handle, suite, testcases.clone())
+                    .await?;
+                Ok(Some(testcases))
+            }
+            Err(e) => {
+                self.report_test_result(TestResult {
+                    target: spec.target.handle,
+                    name: name.to_owned(),
+                    status: TestStatus::LISTING_FAILED,
+                    msg: Some(format!("{:#}", e)),
+                    duration: Some(execution_result.execution_time),
+                    details: output_details(&execution_result),
+                    cached: false,
+                })
+                .await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Runs `testcases` (all of them unless one is `selected`) and returns their results.
+    async fn run_testcases(
+        &self,
+        spec: &ExternalRunnerSpec,
+        framework: &dyn TestFramework,
+        name: &str,
+        selected: Option<String>,
+        testcases: Vec<String>,
+    ) -> anyhow::Result<Option<Vec<TestRun>>> {
+        let display_metadata = DisplayMetadata::Testing {
+            suite: spec.target.target.clone(),
+            testcases: testcases.clone(),
+        };
+        let execution_result = match self
+            .execute_test_from_spec(
+                spec,
+                display_metadata,
+                framework.run_args(selected.as_deref()),
+            )
+            .await?
+        {
+            ExecuteResponse::Result(r) => r,
+            ExecuteResponse::Cancelled => return Ok(None),
+        };
+
+        let report = match framework
+            .report()
+            .and_then(|output| execution_result.outputs.get(&output))
+        {
+            Some(Output::LocalPath(path)) => std::fs::read_to_string(path).ok(),
+            None => None,
+        };
+        let stdout = stream_to_string(&execution_result.stdout);
+        let (mut results, parse_error) = match framework.parse_results(&TestOutput {
+            stdout: &stdout,
+            report: report.as_deref(),
+        }) {
+            Ok(results) => (results, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+
+        // Test cases that didn't report a result, e.g. because the binary crashed.
+        for testcase in testcases {
+            if results.iter().any(|r| r.name == testcase) {
+                continue;
+            }
+            let status = match execution_result.status {
+                ExecutionStatus::Finished { exitcode: 0 } => TestStatus::UNKNOWN,
+                ExecutionStatus::Finished { .. } => TestStatus::FATAL,
+                ExecutionStatus::TimedOut { .. } => TestStatus::TIMEOUT,
+            };
+            let msg = match &parse_error {
+                Some(e) => format!("Error parsing test results: {:#}", e),
+                None => "Test case did not report a result".to_owned(),
+            };
+            results.push(TestCaseResult {
+                name: testcase,
+                status,
+                duration: None,
+                msg: Some(msg),
+                details: String::new(),
+            });
+        }
+
+        let cached = is_cached(&execution_result);
+        let runs = results
+            .into_iter()
+            .map(|result| {
+                let details = if result.details.is_empty() && !is_passing(&result.status) {
+                    output_details(&execution_result)
+                } else {
+                    result.details
+                };
+                TestRun {
+                    result: TestResult {
+                        target: spec.target.handle,
+                        name: format!("{} - {}", name, result.name),
+                        status: result.status,
+                        msg: result.msg,
+                        // When running a single test case, that's how long it took.
+                        duration: result.duration.or_else(|| {
+                            selected
+                                .is_some()
+                                .then_some(execution_result.execution_time)
+                        }),
+                        details,
+                        cached,
+                    },
+                    testcase: Some(result.name),
+                }
+            })
+            .collect();
+        Ok(Some(runs))
+    }
 
+    /// Runs the command of the spec, followed by `extra_args` and the test args from the config.
+    async fn execute_test_from_spec(
+        &self,
+        spec: &ExternalRunnerSpec,
+        display_metadata: DisplayMetadata,
+        extra_args: Vec<ArgValue>,
+    ) -> anyhow::Result<ExecuteResponse> {
         let config_args = self.config.test_arg.iter().map(|arg| ArgValue {
             content: ArgValueContent::ExternalRunnerSpecValue(ExternalRunnerSpecValue::Verbatim(
                 arg.to_owned(),
@@ -133,12 +452,17 @@ impl Buck2TestRunner {
 
         let command = spec
             .command
-            .into_iter()
+            .iter()
             .map(|spec_value| ArgValue {
-                content: ArgValueContent::ExternalRunnerSpecValue(spec_value),
+                content: ArgValueContent::ExternalRunnerSpecValue(spec_value.clone()),
                 format: None,
             })
+            .chain(extra_args)
             .chain(config_args)
+            .chain(match &self.coverage {
+                Some(_) => coverage_args(&spec.test_type),
+                None => Vec::new(),
+            })
             .collect();
 
         let config_env = self.config.env.iter().map(|EnvValue { name, value }| {
@@ -155,37 +479,88 @@ impl Buck2TestRunner {
 
         let env = spec
             .env
-            .into_iter()
+            .iter()
             .map(|(key, value)| {
                 (
-                    key,
+                    key.to_owned(),
                     ArgValue {
-                        content: ArgValueContent::ExternalRunnerSpecValue(value),
+                        content: ArgValueContent::ExternalRunnerSpecValue(value.clone()),
                         format: None,
                     },
                 )
             })
             .chain(config_env)
+            .chain(match &self.coverage {
+                Some(_) => coverage_env(),
+                None => Vec::new(),
+            })
             .collect();
 
         let target_handle = spec.target.handle;
         let host_sharing_requirements = HostSharingRequirements::default();
-        let pre_create_dirs = Vec::new();
+        let pre_create_dirs = match &self.coverage {
+            Some(_) => vec![coverage_output()],
+            None => Vec::new(),
+        };
         let executor_override = None;
 
-        self.orchestrator_client
+        let response = self
+            .orchestrator_client
             .execute2(
-                display_metadata,
+                display_metadata.clone(),
                 target_handle,
                 command,
                 env,
-                self.config.timeout,
+                spec.timeout.unwrap_or(self.config.timeout),
                 host_sharing_requirements,
                 pre_create_dirs,
                 executor_override,
                 RequiredLocalResources { resources: vec![] },
             )
-            .await
+            .await?;
+
+        if let (Some(coverage), ExecuteResponse::Result(result)) = (&self.coverage, &response) {
+            if let Some(Output::LocalPath(dir)) = result.outputs.get(&coverage_output()) {
+                let object = if has_llvm_profiles(dir.as_path()) {
+                    self.instrumented_binary(spec, display_metadata).await?
+                } else {
+                    None
+                };
+                coverage.add(dir.as_path().to_path_buf(), object);
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// The binary LLVM profiles of a test are written by, which is needed to read them. That's
+    /// the first argument of its command.
+    async fn instrumented_binary(
+        &self,
+        spec: &ExternalRunnerSpec,
+        display_metadata: DisplayMetadata,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let binary = match spec.command.first() {
+            Some(binary) => binary.clone(),
+            None => return Ok(None),
+        };
+        let prepared = self
+            .orchestrator_client
+            .prepare_for_local_execution(
+                display_metadata,
+                spec.target.handle,
+                vec![ArgValue {
+                    content: ArgValueContent::ExternalRunnerSpecValue(binary),
+                    format: None,
+                }],
+                Default::default(),
+                Vec::new(),
+            )
+            .await?;
+        Ok(prepared
+            .cmd
+            .first()
+            .map(|binary| prepared.cwd.as_path().join(binary)))
     }
 
     async fn report_test_result(&self, test_result: TestResult) -> anyhow::Result<()> {
@@ -213,13 +588,56 @@ fn get_test_result(
         status,
         msg: None,
         duration: Some(execution_result.execution_time),
-        details: format!(
-            "---- STDOUT ----\n{:?}\n---- STDERR ----\n{:?}\n",
-            execution_result.stdout, execution_result.stderr
-        ),
+        details: output_details(&execution_result),
+        cached: is_cached(&execution_result),
     }
 }
 
+/// Whether the result was served by the local or remote action cache rather than executed.
+fn is_cached(execution_result: &ExecutionResult2) -> bool {
+    use buck2_data::command_execution_kind::Command;
+
+    matches!(
+        execution_result
+            .execution_details
+            .execution_kind
+            .as_ref()
+            .and_then(|k| k.command.as_ref()),
+        Some(Command::RemoteCommand(buck2_data::RemoteCommand {
+            cache_hit: true,
+            ..
+        })) | Some(Command::LocalActionCacheCommand(..))
+    )
+}
+
+/// A test that ran, and how to run it again.
+struct TestRun {
+    /// The test case to select to run it again, or `None` to run the whole target.
+    testcase: Option<String>,
+    result: TestResult,
+}
+
+fn stream_to_string(stream: &ExecutionStream) -> String {
+    match stream {
+        ExecutionStream::Inline(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+fn output_details(execution_result: &ExecutionResult2) -> String {
+    format!(
+        "---- STDOUT ----\n{:?}\n---- STDERR ----\n{:?}\n",
+        execution_result.stdout, execution_result.stderr
+    )
+}
+
+/// Skipped and flaky tests don't fail the run.
+fn is_passing(status: &TestStatus) -> bool {
+    matches!(
+        status,
+        TestStatus::PASS | TestStatus::SKIP | TestStatus::FLAKY
+    )
+}
+
 #[derive(Debug)]
 enum RunVerdict {
     Pass,
@@ -26,6 +26,7 @@ use buck2_core::configuration::transition::applied::TransitionApplied;
 use buck2_core::configuration::transition::id::TransitionId;
 use buck2_core::target::label::TargetLabel;
 use buck2_events::dispatch::get_dispatcher;
+use buck2_interpreter::dice::starlark_limits::StarlarkEvalLimits;
 use buck2_interpreter::dice::starlark_provider::with_starlark_eval_provider;
 use buck2_interpreter::print_handler::EventDispatcherPrintHandler;
 use buck2_interpreter::starlark_profiler::StarlarkProfilerOrInstrumentation;
@@ -135,6 +136,7 @@ async fn do_apply_transition(
     with_starlark_eval_provider(
         ctx,
         &mut StarlarkProfilerOrInstrumentation::disabled(),
+        StarlarkEvalLimits::none(),
         format!("transition:{}", transition_id),
         move |provider| {
             let mut eval = provider.make(&module)?;
@@ -8,3 +8,8 @@
  */
 
 tonic::include_proto!("worker");
+
+/// Bazel's persistent worker protocol.
+pub mod bazel {
+    tonic::include_proto!("blaze.worker");
+}
@@ -14,7 +14,12 @@ use anyhow::Context;
 use futures_intrusive::sync::SharedSemaphore;
 use futures_intrusive::sync::SharedSemaphoreReleaser;
 
+use crate::resources::ResourceGuard;
+use crate::resources::SystemHostMemory;
 use crate::NamedSemaphores;
+use crate::ResourceBroker;
+use crate::ResourceCapacity;
+use crate::ResourceRequirements;
 
 const SINGLE_RUN: usize = 1;
 
@@ -101,6 +106,7 @@ impl Default for HostSharingRequirements {
 pub struct HostSharingGuard {
     _run_guard: SharedSemaphoreReleaser,
     _name_guard: Option<SharedSemaphoreReleaser>,
+    _resources_guard: Option<ResourceGuard>,
 }
 
 /// Used to ensure that host resources are properly reserved before executing a command spec.
@@ -108,6 +114,7 @@ pub struct HostSharingBroker {
     permits: SharedSemaphore,
     num_machine_permits: usize,
     named_semaphores: NamedSemaphores,
+    resources: ResourceBroker,
 }
 
 pub struct RequestedPermits {
@@ -144,6 +151,18 @@ impl HostSharingBroker {
     }
 
     pub fn new(host_sharing_strategy: HostSharingStrategy, num_machine_permits: usize) -> Self {
+        Self::with_resources(
+            host_sharing_strategy,
+            num_machine_permits,
+            ResourceCapacity::default(),
+        )
+    }
+
+    pub fn with_resources(
+        host_sharing_strategy: HostSharingStrategy,
+        num_machine_permits: usize,
+        resource_capacity: ResourceCapacity,
+    ) -> Self {
         let permits = match host_sharing_strategy {
             HostSharingStrategy::Fifo => SharedSemaphore::new(true, num_machine_permits),
             HostSharingStrategy::SmallerTasksFirst => {
@@ -155,6 +174,7 @@ impl HostSharingBroker {
             permits,
             num_machine_permits,
             named_semaphores: NamedSemaphores::new(),
+            resources: ResourceBroker::new(resource_capacity, Box::new(SystemHostMemory::new())),
         }
     }
 
@@ -162,10 +182,33 @@ impl HostSharingBroker {
         self.num_machine_permits
     }
 
+    /// Whether a command with those resource requirements would have to wait for other commands
+    /// (or other processes on the host) to release resources.
+    pub fn is_saturated(&self, resource_requirements: &ResourceRequirements) -> bool {
+        self.resources.is_saturated(resource_requirements)
+    }
+
     pub async fn acquire(
         &self,
         host_sharing_requirements: &HostSharingRequirements,
     ) -> HostSharingGuard {
+        self.acquire_with_resources(host_sharing_requirements, &ResourceRequirements::default())
+            .await
+    }
+
+    pub async fn acquire_with_resources(
+        &self,
+        host_sharing_requirements: &HostSharingRequirements,
+        resource_requirements: &ResourceRequirements,
+    ) -> HostSharingGuard {
+        // Like for the identifier semaphore below, we don't hold permits while waiting for
+        // resources.
+        let _resources_guard = if resource_requirements.is_empty() {
+            None
+        } else {
+            Some(self.resources.acquire(resource_requirements).await)
+        };
+
         match host_sharing_requirements {
             HostSharingRequirements::Shared(weight_class) => {
                 let permits = self.requested_permits(weight_class).into_count();
@@ -173,6 +216,7 @@ impl HostSharingBroker {
                 HostSharingGuard {
                     _run_guard,
                     _name_guard: None,
+                    _resources_guard,
                 }
             }
             HostSharingRequirements::ExclusiveAccess => {
@@ -180,6 +224,7 @@ impl HostSharingBroker {
                 HostSharingGuard {
                     _run_guard,
                     _name_guard: None,
+                    _resources_guard,
                 }
             }
             HostSharingRequirements::OnePerToken(identifier, weight_class) => {
@@ -194,6 +239,7 @@ impl HostSharingBroker {
                 HostSharingGuard {
                     _run_guard,
                     _name_guard,
+                    _resources_guard,
                 }
             }
         }
@@ -18,3 +18,9 @@ pub use crate::host_sharing::HostSharingRequirements;
 pub use crate::host_sharing::HostSharingStrategy;
 pub use crate::host_sharing::WeightClass;
 pub use crate::host_sharing::WeightPercentage;
+
+pub mod resources;
+pub use crate::resources::CustomResources;
+pub use crate::resources::ResourceBroker;
+pub use crate::resources::ResourceCapacity;
+pub use crate::resources::ResourceRequirements;
@@ -0,0 +1,474 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use allocative::Allocative;
+use anyhow::Context;
+use parking_lot::Mutex;
+use sysinfo::System;
+use sysinfo::SystemExt;
+use tokio::sync::Notify;
+
+/// How often we check the memory available on the host when a command is waiting for it.
+const HOST_MEMORY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a reading of the memory available on the host is reused for. Hybrid execution asks
+/// for every command whether we are saturated, so we don't want to query the system each time.
+const HOST_MEMORY_REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Resources a command declares it needs while it runs, on top of the permits of its
+/// `WeightClass`. Commands that don't declare anything are not subject to admission control.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Allocative)]
+pub struct ResourceRequirements {
+    /// Memory, in bytes.
+    pub memory: u64,
+    /// Custom resources (e.g. GPUs), by name.
+    pub custom: CustomResources,
+}
+
+impl ResourceRequirements {
+    pub fn is_empty(&self) -> bool {
+        self.memory == 0 && self.custom.0.values().all(|v| *v == 0)
+    }
+}
+
+/// Amounts of named resources. Parses from e.g. `gpu=2,license=1`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Allocative)]
+pub struct CustomResources(pub BTreeMap<String, u64>);
+
+impl FromStr for CustomResources {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut resources = BTreeMap::new();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, amount) = entry
+                .split_once('=')
+                .with_context(|| format!("Expected `name=amount`, got `{}`", entry))?;
+            let amount = amount
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid amount for resource `{}`", name.trim()))?;
+            resources.insert(name.trim().to_owned(), amount);
+        }
+        Ok(Self(resources))
+    }
+}
+
+impl fmt::Display for CustomResources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, amount)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}={}", name, amount)?;
+        }
+        Ok(())
+    }
+}
+
+/// What the host makes available to local commands.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceCapacity {
+    /// Memory that commands may reserve, in bytes. Defaults to the total memory of the host.
+    pub memory: Option<u64>,
+    /// Custom resources. Resources that aren't listed here are not limited.
+    pub custom: CustomResources,
+}
+
+/// Reports how much memory is currently available on the host.
+pub trait HostMemory: Send + Sync + 'static {
+    fn total(&self) -> u64;
+
+    fn available(&self) -> u64;
+}
+
+pub struct SystemHostMemory {
+    system: Mutex<SystemMemory>,
+}
+
+struct SystemMemory {
+    system: System,
+    /// The last reading of the available memory, and when it was taken.
+    available: Option<(Instant, u64)>,
+}
+
+impl SystemHostMemory {
+    pub fn new() -> Self {
+        Self {
+            system: Mutex::new(SystemMemory {
+                system: System::new(),
+                available: None,
+            }),
+        }
+    }
+}
+
+impl Default for SystemHostMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostMemory for SystemHostMemory {
+    fn total(&self) -> u64 {
+        let mut memory = self.system.lock();
+        memory.system.refresh_memory();
+        memory.system.total_memory()
+    }
+
+    fn available(&self) -> u64 {
+        let mut memory = self.system.lock();
+        if let Some((at, available)) = memory.available {
+            if at.elapsed() < HOST_MEMORY_REFRESH_INTERVAL {
+                return available;
+            }
+        }
+        memory.system.refresh_memory();
+        let available = memory.system.available_memory();
+        memory.available = Some((Instant::now(), available));
+        available
+    }
+}
+
+#[derive(Default)]
+struct ReservedResources {
+    memory: u64,
+    custom: BTreeMap<String, u64>,
+    /// Number of commands holding a reservation.
+    holders: usize,
+    /// Tickets of the commands waiting to be admitted, in the order they asked.
+    waiting: VecDeque<u64>,
+    next_ticket: u64,
+}
+
+/// Admission control for commands that declare `ResourceRequirements`.
+///
+/// A command is admitted when its requirements fit in what is left of the capacity after the
+/// reservations of running commands, and when the host currently has enough memory available for
+/// it (which accounts for memory used by anything else running on the host). Like permits,
+/// requirements are capped to the capacity so that a command can always run on its own.
+///
+/// Commands are admitted in the order they asked, so that a large command isn't starved by
+/// smaller ones which keep fitting in before it.
+pub struct ResourceBroker {
+    memory_capacity: u64,
+    custom_capacity: CustomResources,
+    host_memory: Box<dyn HostMemory>,
+    state: Arc<BrokerState>,
+}
+
+/// Shared with the guards, which release their reservation when dropped.
+struct BrokerState {
+    reserved: Mutex<ReservedResources>,
+    released: Notify,
+}
+
+impl ResourceBroker {
+    pub fn new(capacity: ResourceCapacity, host_memory: Box<dyn HostMemory>) -> Self {
+        Self {
+            memory_capacity: capacity.memory.unwrap_or_else(|| host_memory.total()),
+            custom_capacity: capacity.custom,
+            host_memory,
+            state: Arc::new(BrokerState {
+                reserved: Mutex::new(ReservedResources::default()),
+                released: Notify::new(),
+            }),
+        }
+    }
+
+    fn capped<'a>(&self, requirements: &'a ResourceRequirements) -> (u64, Vec<(&'a str, u64)>) {
+        let memory = requirements.memory.min(self.memory_capacity);
+        let custom = requirements
+            .custom
+            .0
+            .iter()
+            .filter_map(|(name, amount)| {
+                let capacity = *self.custom_capacity.0.get(name)?;
+                Some((name.as_str(), (*amount).min(capacity)))
+            })
+            .collect();
+        (memory, custom)
+    }
+
+    fn fits(&self, reserved: &ReservedResources, memory: u64, custom: &[(&str, u64)]) -> bool {
+        if reserved.memory + memory > self.memory_capacity {
+            return false;
+        }
+
+        for (name, amount) in custom {
+            let used = reserved.custom.get(*name).copied().unwrap_or(0);
+            if used + amount > self.custom_capacity.0[*name] {
+                return false;
+            }
+        }
+
+        // If nothing we admitted is running, waiting wouldn't free up any memory.
+        memory == 0 || reserved.holders == 0 || self.host_memory.available() >= memory
+    }
+
+    /// Whether a command with those requirements would have to wait to be admitted.
+    pub fn is_saturated(&self, requirements: &ResourceRequirements) -> bool {
+        if requirements.is_empty() {
+            return false;
+        }
+        let (memory, custom) = self.capped(requirements);
+        let reserved = self.state.reserved.lock();
+        !reserved.waiting.is_empty() || !self.fits(&reserved, memory, &custom)
+    }
+
+    fn reserve(
+        &self,
+        reserved: &mut ReservedResources,
+        memory: u64,
+        custom: Vec<(&str, u64)>,
+    ) -> ResourceGuard {
+        reserved.memory += memory;
+        for (name, amount) in &custom {
+            *reserved.custom.entry((*name).to_owned()).or_default() += amount;
+        }
+        reserved.holders += 1;
+
+        ResourceGuard {
+            state: self.state.clone(),
+            memory,
+            custom: custom
+                .into_iter()
+                .map(|(name, amount)| (name.to_owned(), amount))
+                .collect(),
+        }
+    }
+
+    /// Waits until a command with those requirements can be admitted, and reserves the resources
+    /// it needs until the returned guard is dropped.
+    pub async fn acquire(&self, requirements: &ResourceRequirements) -> ResourceGuard {
+        let (memory, custom) = self.capped(requirements);
+
+        let waiting = {
+            let mut reserved = self.state.reserved.lock();
+            if reserved.waiting.is_empty() && self.fits(&reserved, memory, &custom) {
+                return self.reserve(&mut reserved, memory, custom);
+            }
+            let ticket = reserved.next_ticket;
+            reserved.next_ticket += 1;
+            reserved.waiting.push_back(ticket);
+            WaitingTicket {
+                state: &self.state,
+                ticket,
+            }
+        };
+
+        loop {
+            // Created before checking so that we don't miss a release in between.
+            let released = self.state.released.notified();
+            {
+                let mut reserved = self.state.reserved.lock();
+                if reserved.waiting.front() == Some(&waiting.ticket)
+                    && self.fits(&reserved, memory, &custom)
+                {
+                    reserved.waiting.pop_front();
+                    // Dropping `waiting` then lets the next command in line check whether it fits.
+                    return self.reserve(&mut reserved, memory, custom);
+                }
+            }
+            // Memory used by other processes on the host doesn't notify us, so we also poll.
+            let _ = tokio::time::timeout(HOST_MEMORY_POLL_INTERVAL, released).await;
+        }
+    }
+}
+
+/// A place in the queue of commands waiting to be admitted. Leaves the queue when dropped (e.g.
+/// when the command is cancelled), and wakes up the other commands so the next one in line can go.
+struct WaitingTicket<'a> {
+    state: &'a BrokerState,
+    ticket: u64,
+}
+
+impl Drop for WaitingTicket<'_> {
+    fn drop(&mut self) {
+        self.state
+            .reserved
+            .lock()
+            .waiting
+            .retain(|ticket| *ticket != self.ticket);
+        self.state.released.notify_waiters();
+    }
+}
+
+pub struct ResourceGuard {
+    state: Arc<BrokerState>,
+    memory: u64,
+    custom: Vec<(String, u64)>,
+}
+
+impl Drop for ResourceGuard {
+    fn drop(&mut self) {
+        {
+            let mut reserved = self.state.reserved.lock();
+            reserved.memory -= self.memory;
+            for (name, amount) in &self.custom {
+                if let Some(used) = reserved.custom.get_mut(name) {
+                    *used -= amount;
+                }
+            }
+            reserved.holders -= 1;
+        }
+        self.state.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+
+    struct FakeHostMemory {
+        available: Arc<AtomicU64>,
+    }
+
+    impl HostMemory for FakeHostMemory {
+        fn total(&self) -> u64 {
+            100
+        }
+
+        fn available(&self) -> u64 {
+            self.available.load(Ordering::Relaxed)
+        }
+    }
+
+    fn new_broker(custom: &str) -> (ResourceBroker, Arc<AtomicU64>) {
+        let available = Arc::new(AtomicU64::new(100));
+        let broker = ResourceBroker::new(
+            ResourceCapacity {
+                memory: None,
+                custom: custom.parse().unwrap(),
+            },
+            Box::new(FakeHostMemory {
+                available: available.clone(),
+            }),
+        );
+        (broker, available)
+    }
+
+    fn memory(memory: u64) -> ResourceRequirements {
+        ResourceRequirements {
+            memory,
+            custom: CustomResources::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_custom_resources() -> anyhow::Result<()> {
+        let resources: CustomResources = " gpu=2, license = 1,".parse()?;
+        assert_eq!("gpu=2,license=1", resources.to_string());
+        assert!("gpu".parse::<CustomResources>().is_err());
+        assert!("gpu=x".parse::<CustomResources>().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_reservations() {
+        let (broker, _available) = new_broker("");
+
+        let a = broker.acquire(&memory(60)).await;
+        assert!(broker.is_saturated(&memory(60)));
+        assert!(!broker.is_saturated(&memory(40)));
+        assert!(!broker.is_saturated(&memory(0)));
+
+        drop(a);
+        // Capped to the capacity, so this can run once nothing else does.
+        let _b = broker.acquire(&memory(200)).await;
+    }
+
+    #[tokio::test]
+    async fn test_host_memory() {
+        let (broker, available) = new_broker("");
+
+        let _a = broker.acquire(&memory(10)).await;
+        available.store(20, Ordering::Relaxed);
+        assert!(broker.is_saturated(&memory(30)));
+        assert!(!broker.is_saturated(&memory(20)));
+    }
+
+    #[tokio::test]
+    async fn test_host_memory_when_idle() {
+        let (broker, available) = new_broker("");
+
+        // Nothing we admitted is using memory, so we don't wait for it.
+        available.store(0, Ordering::Relaxed);
+        let _a = broker.acquire(&memory(30)).await;
+    }
+
+    #[tokio::test]
+    async fn test_custom_resources() {
+        let (broker, _available) = new_broker("gpu=2");
+        let gpus = |gpu: u64| ResourceRequirements {
+            memory: 0,
+            custom: CustomResources([("gpu".to_owned(), gpu)].into_iter().collect()),
+        };
+        let unknown = ResourceRequirements {
+            memory: 0,
+            custom: "fpga=10".parse().unwrap(),
+        };
+
+        let _a = broker.acquire(&gpus(1)).await;
+        let _b = broker.acquire(&gpus(1)).await;
+        assert!(broker.is_saturated(&gpus(1)));
+        assert!(!broker.is_saturated(&unknown));
+    }
+
+    #[tokio::test]
+    async fn test_admitted_in_order() {
+        let (broker, _available) = new_broker("");
+        let pending = Duration::from_millis(10);
+
+        let (large, small) = (memory(70), memory(30));
+
+        let a = broker.acquire(&memory(60)).await;
+        let mut large = Box::pin(broker.acquire(&large));
+        assert!(tokio::time::timeout(pending, &mut large).await.is_err());
+
+        // This one would fit, but the large command asked first.
+        assert!(broker.is_saturated(&small));
+        let mut small = Box::pin(broker.acquire(&small));
+        assert!(tokio::time::timeout(pending, &mut small).await.is_err());
+
+        drop(a);
+        let _large = large.await;
+        let _small = small.await;
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_leaves_queue() {
+        let (broker, _available) = new_broker("");
+
+        let large = memory(70);
+
+        let _a = broker.acquire(&memory(60)).await;
+        let mut large = Box::pin(broker.acquire(&large));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), &mut large)
+                .await
+                .is_err()
+        );
+        drop(large);
+
+        assert!(!broker.is_saturated(&memory(30)));
+        let _small = broker.acquire(&memory(30)).await;
+    }
+}
@@ -66,7 +66,8 @@ prebuilt_rust_library = prelude_rule(
 
 _RUST_COMMON_ATTRIBUTES = {
     "contacts": attrs.list(attrs.string(), default = []),
-    "coverage": attrs.bool(default = False),
+    # `buck2 test --coverage` sets `test.coverage`, to build instrumented variants.
+    "coverage": attrs.bool(default = read_root_config("test", "coverage", "false").lower() == "true"),
     "default_host_platform": attrs.option(attrs.configuration_label(), default = None),
     "default_platform": attrs.option(attrs.string(), default = None),
     "flagged_deps": attrs.list(attrs.tuple(attrs.dep(), attrs.list(attrs.string())), default = []),
@@ -740,6 +740,11 @@ class MainProgram(object):
 
         self.cov.stop()
 
+        # When run by a test runner collecting coverage (e.g. `buck2 test --coverage`), which
+        # points COVERAGE_FILE at where it reads the data from.
+        if os.environ.get("COVERAGE_FILE"):
+            self.cov.save()
+
         try:
             f = StringIO()
             self.cov.report(file=f)
@@ -442,7 +442,8 @@ inlined_extra_attributes = {
         "_go_toolchain": toolchains_common.go(),
     },
     "go_test": {
-        "coverage_mode": attrs.option(attrs.enum(GoCoverageMode.values()), default = None),
+        # `buck2 test --coverage` sets `test.coverage`, to build instrumented variants.
+        "coverage_mode": attrs.option(attrs.enum(GoCoverageMode.values()), default = "set" if read_root_config("test", "coverage", "false").lower() == "true" else None),
         "embedcfg": attrs.option(attrs.source(allow_directory = False), default = None),
         "resources": attrs.list(attrs.source(allow_directory = True), default = []),
         "_go_toolchain": toolchains_common.go(),
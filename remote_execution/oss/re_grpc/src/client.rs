@@ -21,6 +21,7 @@ use futures::future::Future;
 use futures::stream::BoxStream;
 use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
+use futures::SinkExt;
 use futures::Stream;
 use gazebo::prelude::*;
 use once_cell::sync::Lazy;
@@ -41,11 +42,15 @@ use re_grpc_proto::build::bazel::remote::execution::v2::Digest;
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteOperationMetadata;
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteRequest as GExecuteRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteResponse as GExecuteResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecutedActionMetadata;
 use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsResponse;
 use re_grpc_proto::build::bazel::remote::execution::v2::GetActionResultRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::GetCapabilitiesRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::OutputDirectory;
+use re_grpc_proto::build::bazel::remote::execution::v2::OutputFile;
 use re_grpc_proto::build::bazel::remote::execution::v2::ResultsCachePolicy;
+use re_grpc_proto::build::bazel::remote::execution::v2::UpdateActionResultRequest;
 use re_grpc_proto::google::bytestream::byte_stream_client::ByteStreamClient;
 use re_grpc_proto::google::bytestream::ReadRequest;
 use re_grpc_proto::google::bytestream::ReadResponse;
@@ -69,15 +74,24 @@ use tonic::transport::Channel;
 use tonic::transport::Identity;
 use tonic::transport::Uri;
 
+use crate::compression::blob_resource_name;
+use crate::compression::compress;
+use crate::compression::decompress;
+use crate::compression::is_valid_committed_size;
+use crate::compression::Compressor;
+use crate::compression::Decompressor;
+use crate::compression::RECompression;
 use crate::error::*;
+use crate::http_cache::HttpCacheClient;
 use crate::metadata::*;
 use crate::request::*;
 use crate::response::*;
+use crate::stats::NetworkStats;
 
 // RBE Services (e.g. Buildbarn) may not be robust against having too many files open at
 // once. Limit to an arbitrary reasonable number since this information is not expressed
 // in the Capabilities message query.
-const CONCURRENT_UPLOAD_LIMIT: usize = 64;
+pub(crate) const CONCURRENT_UPLOAD_LIMIT: usize = 64;
 
 const DEFAULT_MAX_MSG_SIZE: usize = 4 * 1000 * 1000;
 
@@ -96,7 +110,7 @@ fn tdigest_from(digest: Digest) -> TDigest {
     }
 }
 
-fn tstatus_ok() -> TStatus {
+pub(crate) fn tstatus_ok() -> TStatus {
     TStatus {
         code: TCode::OK,
         message: "".to_owned(),
@@ -115,6 +129,13 @@ fn check_status(status: Status) -> Result<(), REClientError> {
     })
 }
 
+fn ttimestamp_to(ts: TTimestamp) -> Option<::prost_types::Timestamp> {
+    Some(::prost_types::Timestamp {
+        seconds: ts.seconds,
+        nanos: ts.nanos,
+    })
+}
+
 fn ttimestamp_from(ts: Option<::prost_types::Timestamp>) -> TTimestamp {
     match ts {
         Some(timestamp) => TTimestamp {
@@ -205,6 +226,8 @@ pub struct RECapabilities {
     max_msg_size: usize,
     /// Does the remote server support execution.
     exec_enabled: bool,
+    /// Compressors to use for CAS transfers.
+    compression: RECompression,
 }
 
 struct InstanceName(Option<String>);
@@ -229,6 +252,20 @@ pub struct REClientBuilder;
 
 impl REClientBuilder {
     pub async fn build_and_connect(opts: &Buck2OssReConfiguration) -> anyhow::Result<REClient> {
+        if let Some(http_cache_address) = &opts.http_cache_address {
+            let http_cache = HttpCacheClient::from_config(http_cache_address, opts)
+                .context("Error creating HTTP cache client")?;
+            return Ok(REClient::new(
+                REBackend::Http(http_cache),
+                RECapabilities {
+                    exec_enabled: false,
+                    max_msg_size: DEFAULT_MAX_MSG_SIZE,
+                    compression: RECompression::default(),
+                },
+                InstanceName(opts.instance_name.clone()),
+            ));
+        }
+
         // We just always create this just in case, so that we implicitly validate it if set.
         let tls_config = create_tls_config(opts)
             .await
@@ -287,16 +324,23 @@ impl REClientBuilder {
                 capabilities.context("Error creating Capabilities client")?,
                 interceptor.dupe(),
             ),
+            stats: Arc::new(NetworkStats::default()),
         };
 
         let instance_name = InstanceName(opts.instance_name.clone());
 
         let capabilities = if opts.capabilities.unwrap_or(true) {
-            Self::fetch_rbe_capabilities(&mut grpc_clients, &instance_name).await?
+            Self::fetch_rbe_capabilities(
+                &mut grpc_clients,
+                &instance_name,
+                opts.compression.unwrap_or(true),
+            )
+            .await?
         } else {
             RECapabilities {
                 exec_enabled: true,
                 max_msg_size: DEFAULT_MAX_MSG_SIZE,
+                compression: RECompression::default(),
             }
         };
 
@@ -304,12 +348,17 @@ impl REClientBuilder {
             return Err(anyhow::anyhow!("Server has remote execution disabled."));
         }
 
-        Ok(REClient::new(grpc_clients, capabilities, instance_name))
+        Ok(REClient::new(
+            REBackend::Grpc(grpc_clients),
+            capabilities,
+            instance_name,
+        ))
     }
 
     async fn fetch_rbe_capabilities(
         clients: &mut GRPCClients,
         instance_name: &InstanceName,
+        compression_enabled: bool,
     ) -> anyhow::Result<RECapabilities> {
         // TODO use more of the capabilities of the remote build executor
 
@@ -325,6 +374,7 @@ impl REClientBuilder {
         // with enough room for headers.
         let mut max_msg_size = DEFAULT_MAX_MSG_SIZE;
         let mut exec_enabled = true;
+        let mut compression = RECompression::default();
 
         if let Some(cache_cap) = resp.cache_capabilities {
             let size = cache_cap.max_batch_total_size_bytes as usize;
@@ -332,6 +382,13 @@ impl REClientBuilder {
             if size != 0 {
                 max_msg_size = size;
             }
+
+            if compression_enabled {
+                compression = RECompression::negotiate(
+                    &cache_cap.supported_compressors,
+                    &cache_cap.supported_batch_update_compressors,
+                );
+            }
         }
 
         if let Some(exec_cap) = resp.execution_capabilities {
@@ -341,6 +398,7 @@ impl REClientBuilder {
         Ok(RECapabilities {
             max_msg_size,
             exec_enabled,
+            compression,
         })
     }
 }
@@ -397,10 +455,18 @@ pub struct GRPCClients {
     action_cache_client: ActionCacheClient<InterceptedService<Channel, InjectHeadersInterceptor>>,
     bytestream_client: ByteStreamClient<InterceptedService<Channel, InjectHeadersInterceptor>>,
     capabilities_client: CapabilitiesClient<InterceptedService<Channel, InjectHeadersInterceptor>>,
+    /// Shared with the streams of the uploads in progress.
+    stats: Arc<NetworkStats>,
+}
+
+enum REBackend {
+    Grpc(GRPCClients),
+    /// A bazel-remote style cache. This only supports the action cache and CAS.
+    Http(HttpCacheClient),
 }
 
 pub struct REClient {
-    grpc_clients: GRPCClients,
+    backend: REBackend,
     capabilities: RECapabilities,
     instance_name: InstanceName,
 }
@@ -416,7 +482,7 @@ impl Drop for REClient {
 /// Used to defer reading of NamedDigest contents till
 /// actual execution of upload and prevent opening too many
 /// files at the same time.
-enum BatchUploadRequest {
+pub(crate) enum BatchUploadRequest {
     Blob(InlinedBlobWithDigest),
     File(NamedDigest),
 }
@@ -461,13 +527,9 @@ impl BatchUploadReqAggregator {
 }
 
 impl REClient {
-    fn new(
-        grpc_clients: GRPCClients,
-        capabilities: RECapabilities,
-        instance_name: InstanceName,
-    ) -> Self {
+    fn new(backend: REBackend, capabilities: RECapabilities, instance_name: InstanceName) -> Self {
         REClient {
-            grpc_clients,
+            backend,
             capabilities,
             instance_name,
         }
@@ -478,7 +540,12 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: ActionResultRequest,
     ) -> anyhow::Result<ActionResultResponse> {
-        let mut client = self.grpc_clients.action_cache_client.clone();
+        let grpc_clients = match &self.backend {
+            REBackend::Grpc(grpc_clients) => grpc_clients,
+            REBackend::Http(http_cache) => return http_cache.get_action_result(request).await,
+        };
+
+        let mut client = grpc_clients.action_cache_client.clone();
 
         let res = client
             .get_action_result(with_internal_metadata(
@@ -499,10 +566,31 @@ impl REClient {
 
     pub async fn write_action_result(
         &self,
-        _metadata: RemoteExecutionMetadata,
-        _request: WriteActionResultRequest,
+        metadata: RemoteExecutionMetadata,
+        request: WriteActionResultRequest,
     ) -> anyhow::Result<WriteActionResultResponse> {
-        Err(anyhow::anyhow!("Not supported"))
+        let grpc_clients = match &self.backend {
+            REBackend::Grpc(grpc_clients) => grpc_clients,
+            REBackend::Http(http_cache) => return http_cache.write_action_result(request).await,
+        };
+
+        let mut client = grpc_clients.action_cache_client.clone();
+
+        // The outputs, as well as stdout / stderr if they were not inlined, are expected to have
+        // been uploaded to the CAS by the caller at this point.
+        client
+            .update_action_result(with_internal_metadata(
+                UpdateActionResultRequest {
+                    instance_name: self.instance_name.as_str().to_owned(),
+                    action_digest: Some(tdigest_to(request.action_digest)),
+                    action_result: Some(convert_t_action_result2(request.action_result)),
+                    results_cache_policy: None,
+                },
+                metadata,
+            ))
+            .await?;
+
+        Ok(WriteActionResultResponse {})
     }
 
     pub async fn execute_with_progress(
@@ -510,10 +598,19 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         mut execute_request: ExecuteRequest,
     ) -> anyhow::Result<BoxStream<'static, anyhow::Result<ExecuteWithProgressResponse>>> {
+        let grpc_clients = match &self.backend {
+            REBackend::Grpc(grpc_clients) => grpc_clients,
+            REBackend::Http(_) => {
+                return Err(anyhow::anyhow!(
+                    "Remote execution is not available with an HTTP cache (`http_cache_address`)"
+                ));
+            }
+        };
+
         // TODO(aloiscochard): Map those properly in the request
         // use crate::proto::build::bazel::remote::execution::v2::ExecutionPolicy;
 
-        let mut client = self.grpc_clients.execution_client.clone();
+        let mut client = grpc_clients.execution_client.clone();
 
         let action_digest = tdigest_to(execute_request.action_digest.clone());
 
@@ -627,13 +724,22 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: UploadRequest,
     ) -> anyhow::Result<UploadResponse> {
+        let grpc_clients = match &self.backend {
+            REBackend::Grpc(grpc_clients) => grpc_clients,
+            REBackend::Http(http_cache) => return http_cache.upload(request).await,
+        };
+
         upload_impl(
             &self.instance_name,
             request,
             self.capabilities.max_msg_size,
+            self.capabilities.compression,
             |re_request| async {
                 let metadata = metadata.clone();
-                let mut cas_client = self.grpc_clients.cas_client.clone();
+                let mut cas_client = grpc_clients.cas_client.clone();
+                grpc_clients
+                    .stats
+                    .record_uploaded(re_request.requests.iter().map(|r| r.data.len()).sum());
                 let resp = cas_client
                     .batch_update_blobs(with_internal_metadata(re_request, metadata))
                     .await?;
@@ -641,8 +747,11 @@ impl REClient {
             },
             |segments| async {
                 let metadata = metadata.clone();
-                let mut bytestream_client = self.grpc_clients.bytestream_client.clone();
-                let requests = futures::stream::iter(segments);
+                let mut bytestream_client = grpc_clients.bytestream_client.clone();
+                let requests = RecordedSegments {
+                    segments,
+                    stats: grpc_clients.stats.dupe(),
+                };
                 let resp = bytestream_client
                     .write(with_internal_metadata(requests, metadata))
                     .await?;
@@ -667,27 +776,39 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: DownloadRequest,
     ) -> anyhow::Result<DownloadResponse> {
+        let grpc_clients = match &self.backend {
+            REBackend::Grpc(grpc_clients) => grpc_clients,
+            REBackend::Http(http_cache) => return http_cache.download(request).await,
+        };
+
         download_impl(
             &self.instance_name,
             request,
             self.capabilities.max_msg_size,
+            self.capabilities.compression,
             |re_request| async {
                 let metadata = metadata.clone();
-                let mut client = self.grpc_clients.cas_client.clone();
-                Ok(client
+                let mut client = grpc_clients.cas_client.clone();
+                let response = client
                     .batch_read_blobs(with_internal_metadata(re_request, metadata))
                     .await?
-                    .into_inner())
+                    .into_inner();
+                grpc_clients
+                    .stats
+                    .record_downloaded(response.responses.iter().map(|r| r.data.len()).sum());
+                Ok(response)
             },
             |read_request| {
                 let metadata = metadata.clone();
                 async move {
-                    let mut client = self.grpc_clients.bytestream_client.clone();
+                    let mut client = grpc_clients.bytestream_client.clone();
                     let response = client
                         .read(with_internal_metadata(read_request, metadata))
                         .await?
                         .into_inner();
-                    Ok(Box::pin(response.into_stream()))
+                    Ok(Box::pin(response.into_stream().inspect_ok(
+                        |r: &ReadResponse| grpc_clients.stats.record_downloaded(r.data.len()),
+                    )))
                 }
             },
         )
@@ -699,7 +820,12 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: GetDigestsTtlRequest,
     ) -> anyhow::Result<GetDigestsTtlResponse> {
-        let mut cas_client = self.grpc_clients.cas_client.clone();
+        let grpc_clients = match &self.backend {
+            REBackend::Grpc(grpc_clients) => grpc_clients,
+            REBackend::Http(http_cache) => return http_cache.get_digests_ttl(request).await,
+        };
+
+        let mut cas_client = grpc_clients.cas_client.clone();
         let mut remote_ttl: HashMap<TDigest, DigestWithTtl> = HashMap::new();
 
         for digest_chunk in request.digests.chunks(100) {
@@ -769,9 +895,17 @@ impl REClient {
     pub fn get_experiment_name(&self) -> anyhow::Result<Option<String>> {
         Ok(None)
     }
+
+    /// Bytes this client transferred to and from the CAS.
+    pub fn get_network_stats(&self) -> NetworkStatisticsResponse {
+        match &self.backend {
+            REBackend::Grpc(grpc_clients) => grpc_clients.stats.to_response(),
+            REBackend::Http(http_cache) => http_cache.stats().to_response(),
+        }
+    }
 }
 
-fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionResult2> {
+pub(crate) fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionResult2> {
     let execution_metadata = action_result
         .execution_metadata
         .with_context(|| "The execution metadata are not defined.")?;
@@ -856,10 +990,67 @@ fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionR
     Ok(action_result)
 }
 
+pub(crate) fn convert_t_action_result2(t_action_result: TActionResult2) -> ActionResult {
+    let t_execution_metadata = t_action_result.execution_metadata;
+    let execution_metadata = Some(ExecutedActionMetadata {
+        worker: t_execution_metadata.worker,
+        queued_timestamp: ttimestamp_to(t_execution_metadata.queued_timestamp),
+        worker_start_timestamp: ttimestamp_to(t_execution_metadata.worker_start_timestamp),
+        worker_completed_timestamp: ttimestamp_to(t_execution_metadata.worker_completed_timestamp),
+        input_fetch_start_timestamp: ttimestamp_to(
+            t_execution_metadata.input_fetch_start_timestamp,
+        ),
+        input_fetch_completed_timestamp: ttimestamp_to(
+            t_execution_metadata.input_fetch_completed_timestamp,
+        ),
+        execution_start_timestamp: ttimestamp_to(t_execution_metadata.execution_start_timestamp),
+        execution_completed_timestamp: ttimestamp_to(
+            t_execution_metadata.execution_completed_timestamp,
+        ),
+        output_upload_start_timestamp: ttimestamp_to(
+            t_execution_metadata.output_upload_start_timestamp,
+        ),
+        output_upload_completed_timestamp: ttimestamp_to(
+            t_execution_metadata.output_upload_completed_timestamp,
+        ),
+        ..Default::default()
+    });
+
+    let output_files = t_action_result
+        .output_files
+        .into_map(|output_file| OutputFile {
+            path: output_file.name,
+            digest: Some(tdigest_to(output_file.digest.digest)),
+            is_executable: output_file.executable,
+            ..Default::default()
+        });
+
+    let output_directories = t_action_result
+        .output_directories
+        .into_map(|output_directory| OutputDirectory {
+            path: output_directory.path,
+            tree_digest: Some(tdigest_to(output_directory.tree_digest)),
+            ..Default::default()
+        });
+
+    ActionResult {
+        output_files,
+        output_directories,
+        exit_code: t_action_result.exit_code,
+        stdout_raw: t_action_result.stdout_raw.unwrap_or_default(),
+        stdout_digest: t_action_result.stdout_digest.map(tdigest_to),
+        stderr_raw: t_action_result.stderr_raw.unwrap_or_default(),
+        stderr_digest: t_action_result.stderr_digest.map(tdigest_to),
+        execution_metadata,
+        ..Default::default()
+    }
+}
+
 async fn download_impl<Byt, BytRet, Cas>(
     instance_name: &InstanceName,
     request: DownloadRequest,
     max_msg_size: usize,
+    compression: RECompression,
     cas_f: impl Fn(BatchReadBlobsRequest) -> Cas,
     bystream_fut: impl Fn(ReadRequest) -> Byt + Sync + Send + Copy,
 ) -> anyhow::Result<DownloadResponse>
@@ -873,10 +1064,9 @@ where
         let size_in_bytes = digest.size_in_bytes;
 
         let resource_name = format!(
-            "{}blobs/{}/{}",
+            "{}{}",
             instance_name.as_resource_prefix(),
-            hash,
-            size_in_bytes
+            blob_resource_name(compression.bytestream, &hash, size_in_bytes),
         );
 
         bystream_fut(ReadRequest {
@@ -911,7 +1101,7 @@ where
             let read_blob_req = BatchReadBlobsRequest {
                 instance_name: instance_name.as_str().to_owned(),
                 digests: std::mem::take(&mut curr_digests),
-                acceptable_compressors: vec![compressor::Value::Identity as i32],
+                acceptable_compressors: compression.acceptable_compressors(),
             };
             requests.push(read_blob_req);
         }
@@ -922,7 +1112,7 @@ where
         let read_blob_req = BatchReadBlobsRequest {
             instance_name: instance_name.as_str().to_owned(),
             digests: std::mem::take(&mut curr_digests),
-            acceptable_compressors: vec![compressor::Value::Identity as i32],
+            acceptable_compressors: compression.acceptable_compressors(),
         };
         requests.push(read_blob_req);
     }
@@ -935,7 +1125,12 @@ where
         for r in resp.responses.into_iter() {
             let digest = tdigest_from(r.digest.context("Response digest not found.")?);
             check_status(r.status.unwrap_or_default())?;
-            batched_blobs_response.insert(digest, r.data);
+            let compressor = compressor::Value::from_i32(r.compressor).with_context(|| {
+                format!("Invalid compressor for `{}`: {}", digest, r.compressor)
+            })?;
+            let data = decompress(compressor, r.data, digest.size_in_bytes as usize)
+                .with_context(|| format!("Failed to decompress `{}`", digest))?;
+            batched_blobs_response.insert(digest, data);
         }
     }
 
@@ -954,13 +1149,15 @@ where
     for digest in inlined_digests {
         let data = if digest.size_in_bytes as usize >= max_msg_size {
             let mut accum = vec![];
+            let mut decompressor = Decompressor::new(compression.bytestream)?;
             let mut responses = bystream_fut(digest.clone()).await?;
             while let Some(resp) = responses.next().await {
                 let data = resp
                     .with_context(|| format!("Failed to fetch inline digest: {digest}"))?
                     .data;
-                accum.extend_from_slice(&data);
+                accum.extend_from_slice(&decompressor.push(data)?);
             }
+            accum.extend_from_slice(&decompressor.finish()?);
             accum
         } else {
             get(&digest)?
@@ -999,15 +1196,21 @@ where
                     .await
                     .with_context(|| format!("Error writing: {}", req.named_digest.digest))?;
             } else {
+                let mut decompressor = Decompressor::new(compression.bytestream)?;
                 let mut responses = bystream_fut(req.named_digest.digest.clone()).await?;
                 while let Some(resp) = responses.next().await {
                     let data = resp
                         .with_context(|| format!("Failed to fetch file: {:?}", file))?
                         .data;
+                    let data = decompressor.push(data)?;
                     file.write_all(&data).await.with_context(|| {
                         format!("Error writing chunk of: {}", req.named_digest.digest)
                     })?;
                 }
+                let data = decompressor.finish()?;
+                file.write_all(&data).await.with_context(|| {
+                    format!("Error writing chunk of: {}", req.named_digest.digest)
+                })?;
             }
             file.flush().await.context("Error flushing")?;
             anyhow::Ok(())
@@ -1028,12 +1231,127 @@ where
     })
 }
 
+/// Split data to upload via the Bytestream service into `WriteRequest`s of at most `max_msg_size`.
+fn bytestream_write_requests(
+    resource_name: &str,
+    data: &[u8],
+    max_msg_size: usize,
+) -> Vec<WriteRequest> {
+    let mut upload_segments = vec![];
+    for (i, chunk) in data.chunks(max_msg_size).enumerate() {
+        upload_segments.push(WriteRequest {
+            resource_name: resource_name.to_owned(),
+            write_offset: (i * max_msg_size) as i64,
+            finish_write: false,
+            data: chunk.to_owned(),
+        });
+    }
+    if let Some(last) = upload_segments.last_mut() {
+        last.finish_write = true;
+    }
+    upload_segments
+}
+
+/// The segments of an upload, counted in `stats` as they are sent. This also keeps the trait
+/// object out of the type of the bytestream write future, which rustc can't prove `Send`
+/// otherwise.
+struct RecordedSegments {
+    segments: BoxStream<'static, WriteRequest>,
+    stats: Arc<NetworkStats>,
+}
+
+impl Stream for RecordedSegments {
+    type Item = WriteRequest;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<WriteRequest>> {
+        let res = self.segments.poll_next_unpin(cx);
+        if let std::task::Poll::Ready(Some(segment)) = &res {
+            self.stats.record_uploaded(segment.data.len());
+        }
+        res
+    }
+}
+
+/// Reads the file `name`, compresses it, and sends it to `tx` in `WriteRequest`s of at most
+/// `max_msg_size`. Returns how many bytes were sent, or `None` if `tx` was closed before the end.
+async fn send_file_segments(
+    name: &str,
+    resource_name: &str,
+    compression: compressor::Value,
+    max_msg_size: usize,
+    mut tx: futures::channel::mpsc::Sender<WriteRequest>,
+) -> anyhow::Result<Option<i64>> {
+    let mut file = tokio::fs::File::open(name)
+        .await
+        .with_context(|| format!("Opening `{name}` for reading failed"))?;
+
+    let mut compressor = Some(Compressor::new(compression)?);
+    let mut data = vec![0; max_msg_size];
+    // Data to upload (i.e. compressed, if need be) that wasn't sent yet.
+    let mut pending = Vec::new();
+    let mut write_offset = 0;
+
+    while let Some(mut c) = compressor.take() {
+        let length = file
+            .read(&mut data)
+            .await
+            .with_context(|| format!("Error reading from {name}"))?;
+        if length == 0 {
+            pending.extend(
+                c.finish()
+                    .with_context(|| format!("Error compressing {name}"))?,
+            );
+        } else {
+            pending.extend(
+                c.push(&data[..length])
+                    .with_context(|| format!("Error compressing {name}"))?,
+            );
+            compressor = Some(c);
+        }
+
+        // We keep the last segment until the end since it must finish the write.
+        while pending.len() > max_msg_size {
+            let rest = pending.split_off(max_msg_size);
+            let segment = std::mem::replace(&mut pending, rest);
+            let request = WriteRequest {
+                resource_name: resource_name.to_owned(),
+                write_offset,
+                finish_write: false,
+                data: segment,
+            };
+            if tx.send(request).await.is_err() {
+                return Ok(None);
+            }
+            write_offset += max_msg_size as i64;
+        }
+    }
+
+    if pending.is_empty() && write_offset == 0 {
+        return Err(anyhow::anyhow!("Read no segments from `{name}`"));
+    }
+    let uploaded_size = write_offset + pending.len() as i64;
+    let request = WriteRequest {
+        resource_name: resource_name.to_owned(),
+        write_offset,
+        finish_write: true,
+        data: pending,
+    };
+    if tx.send(request).await.is_err() {
+        return Ok(None);
+    }
+    Ok(Some(uploaded_size))
+}
+
 async fn upload_impl<Byt, Cas>(
     instance_name: &InstanceName,
     request: UploadRequest,
     max_msg_size: usize,
+    compression: RECompression,
     cas_f: impl Fn(BatchUpdateBlobsRequest) -> Cas + Sync + Send + Copy,
-    bystream_fut: impl Fn(Vec<WriteRequest>) -> Byt + Sync + Send + Copy,
+    bystream_fut: impl Fn(BoxStream<'static, WriteRequest>) -> Byt + Sync + Send + Copy,
 ) -> anyhow::Result<UploadResponse>
 where
     Cas: Future<Output = anyhow::Result<BatchUpdateBlobsResponse>> + Send,
@@ -1059,27 +1377,22 @@ where
         let data = blob.blob;
         let client_uuid = uuid::Uuid::new_v4().to_string();
         let resource_name = format!(
-            "{}uploads/{}/blobs/{}/{}",
+            "{}uploads/{}/{}",
             instance_name.as_resource_prefix(),
             client_uuid,
-            hash,
-            size
+            blob_resource_name(compression.bytestream, &hash, size),
         );
         let fut = async move {
-            // Number of complete (non-partial) messages
-            let mut upload_segments = vec![];
-            for (i, chunk) in data.chunks(max_msg_size).enumerate() {
-                upload_segments.push(WriteRequest {
-                    resource_name: resource_name.to_owned(),
-                    write_offset: (i * max_msg_size) as i64,
-                    finish_write: false,
-                    data: chunk.to_owned(),
-                });
-            }
-            upload_segments.last_mut().unwrap().finish_write = true;
-
-            let resp = bystream_fut(upload_segments).await?;
-            if resp.committed_size != size {
+            let data = compress(compression.bytestream, data)?;
+            let upload_segments = bytestream_write_requests(&resource_name, &data, max_msg_size);
+
+            let resp = bystream_fut(futures::stream::iter(upload_segments).boxed()).await?;
+            if !is_valid_committed_size(
+                compression.bytestream,
+                resp.committed_size,
+                size,
+                data.len() as i64,
+            ) {
                 return Err(anyhow::anyhow!(
                     "Failed to upload inline blob: invalid committed_size from WriteResponse"
                 ));
@@ -1101,43 +1414,43 @@ where
         }
         let client_uuid = uuid::Uuid::new_v4().to_string();
         let resource_name = format!(
-            "{}uploads/{}/blobs/{}/{}",
+            "{}uploads/{}/{}",
             instance_name.as_resource_prefix(),
             client_uuid,
-            hash.clone(),
-            size
+            blob_resource_name(compression.bytestream, &hash, size),
         );
         let fut = async move {
-            let mut file = tokio::fs::File::open(&name)
-                .await
-                .with_context(|| format!("Opening `{name}` for reading failed"))?;
-            let mut data = vec![0; max_msg_size];
-
-            let mut write_offset = 0;
-            let mut upload_segments = Vec::new();
-            loop {
-                let length = file
-                    .read(&mut data)
-                    .await
-                    .with_context(|| format!("Error reading from {name}"))?;
-                if length == 0 {
-                    break;
-                }
-                upload_segments.push(WriteRequest {
-                    resource_name: resource_name.to_owned(),
-                    write_offset,
-                    finish_write: false,
-                    data: data[..length].to_owned(),
-                });
-                write_offset += length as i64;
+            if max_msg_size == 0 {
+                return Err(anyhow::anyhow!(
+                    "Cannot upload `{name}` with a maximum message size of 0"
+                ));
             }
-            upload_segments
-                .last_mut()
-                .with_context(|| format!("Read no segments from `{name} "))?
-                .finish_write = true;
 
-            let resp = bystream_fut(upload_segments).await?;
-            if resp.committed_size != size {
+            // The segments are sent as they are read, so only a couple of them are in memory.
+            let (tx, rx) = futures::channel::mpsc::channel(1);
+            let (uploaded_size, resp) = futures::future::join(
+                send_file_segments(
+                    &name,
+                    &resource_name,
+                    compression.bytestream,
+                    max_msg_size,
+                    tx,
+                ),
+                bystream_fut(rx.boxed()),
+            )
+            .await;
+            // A failure to read the file is what made the upload fail, if it did.
+            let uploaded_size = uploaded_size?;
+            let resp = resp?;
+            // The server can finish the write before we sent all of it, e.g. if it already has
+            // the blob, in which case it must report its full size.
+            let uploaded_size = uploaded_size.unwrap_or(size);
+            if !is_valid_committed_size(
+                compression.bytestream,
+                resp.committed_size,
+                size,
+                uploaded_size,
+            ) {
                 return Err(anyhow::anyhow!(
                     "Failed to upload `{name}`: invalid committed_size from WriteResponse"
                 ));
@@ -1161,8 +1474,8 @@ where
                     BatchUploadRequest::Blob(blob) => {
                         re_request.requests.push(Request {
                             digest: Some(tdigest_to(blob.digest.clone())),
-                            data: blob.blob.clone(),
-                            compressor: compressor::Value::Identity as i32,
+                            data: compress(compression.batch_update, blob.blob)?,
+                            compressor: compression.batch_update as i32,
                         });
                     }
                     BatchUploadRequest::File(file) => {
@@ -1175,8 +1488,8 @@ where
 
                         re_request.requests.push(Request {
                             digest: Some(tdigest_to(file.digest.clone())),
-                            data,
-                            compressor: compressor::Value::Identity as i32,
+                            data: compress(compression.batch_update, data)?,
+                            compressor: compression.batch_update as i32,
                         });
                     }
                 }
@@ -1255,7 +1568,7 @@ fn with_internal_metadata<T>(t: T, metadata: RemoteExecutionMetadata) -> tonic::
 }
 
 /// Replace occurrences of $FOO in a string with the value of the env var $FOO.
-fn substitute_env_vars(s: &str) -> anyhow::Result<String> {
+pub(crate) fn substitute_env_vars(s: &str) -> anyhow::Result<String> {
     substitute_env_vars_impl(s, |v| std::env::var(v))
 }
 
@@ -1358,6 +1671,7 @@ mod tests {
             &InstanceName(None),
             req,
             10000,
+            RECompression::default(),
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1464,6 +1778,7 @@ mod tests {
             &InstanceName(None),
             req,
             10, // kept small to simulate a large file download
+            RECompression::default(),
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1545,6 +1860,7 @@ mod tests {
             &InstanceName(None),
             req,
             100000,
+            RECompression::default(),
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1618,6 +1934,7 @@ mod tests {
             &InstanceName(None),
             req,
             10, // intentionally small value to keep data in the test blobs small
+            RECompression::default(),
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1673,6 +1990,7 @@ mod tests {
             &InstanceName(None),
             req,
             100000,
+            RECompression::default(),
             |req| {
                 let res = res.clone();
                 async move {
@@ -1711,6 +2029,7 @@ mod tests {
             &InstanceName(Some("instance".to_owned())),
             req,
             0,
+            RECompression::default(),
             |_req| async { panic!("not called") },
             |req| async move {
                 assert_eq!(req.resource_name, "instance/blobs/aa/0");
@@ -1780,6 +2099,7 @@ mod tests {
             &InstanceName(None),
             req,
             10000,
+            RECompression::default(),
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1862,6 +2182,7 @@ mod tests {
             &InstanceName(None),
             req,
             10, // kept small to simulate a large file upload
+            RECompression::default(),
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1875,6 +2196,7 @@ mod tests {
             |write_reqs| {
                 let blob_data = blob_data.clone();
                 async move {
+                    let write_reqs = write_reqs.collect::<Vec<_>>().await;
                     assert_eq!(write_reqs.len(), 2);
                     assert_eq!(write_reqs[0].write_offset, 0);
                     assert!(!write_reqs[0].finish_write);
@@ -1935,6 +2257,7 @@ mod tests {
             &InstanceName(None),
             req,
             10, // kept small to simulate a large inlined upload
+            RECompression::default(),
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1949,6 +2272,7 @@ mod tests {
             |write_reqs| {
                 let blob_data2 = blob_data2.clone();
                 async move {
+                    let write_reqs = write_reqs.collect::<Vec<_>>().await;
                     assert_eq!(write_reqs.len(), 2);
                     assert_eq!(write_reqs[0].write_offset, 0);
                     assert!(!write_reqs[0].finish_write);
@@ -1995,6 +2319,7 @@ mod tests {
             &InstanceName(None), // TODO
             req,
             10,
+            RECompression::default(),
             |_req| async move {
                 panic!("This should not be called as there are no blobs to upload in batch");
             },
@@ -2055,10 +2380,12 @@ mod tests {
             &InstanceName(None),
             req,
             3,
+            RECompression::default(),
             |_req| async move {
                 panic!("Not called");
             },
             |write_reqs| async move {
+                let write_reqs = write_reqs.collect::<Vec<_>>().await;
                 assert_eq!(write_reqs.len(), 2);
                 assert!(write_reqs[1].finish_write);
                 anyhow::Ok(WriteResponse { committed_size: 6 })
@@ -2095,6 +2422,7 @@ mod tests {
             &InstanceName(None),
             req,
             0,
+            RECompression::default(),
             |_req| async move {
                 panic!("Not called");
             },
@@ -2140,10 +2468,12 @@ mod tests {
             &InstanceName(Some("instance".to_owned())),
             req,
             1,
+            RECompression::default(),
             |_req| async move {
                 panic!("Not called");
             },
             |write_reqs| async move {
+                let write_reqs = write_reqs.collect::<Vec<_>>().await;
                 assert!(write_reqs[0].resource_name.starts_with("instance/uploads/"));
                 assert!(write_reqs[0].resource_name.ends_with("/blobs/aa/3"));
                 anyhow::Ok(WriteResponse { committed_size: 3 })
@@ -2154,6 +2484,401 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_download_compressed() -> anyhow::Result<()> {
+        let compression = RECompression {
+            bytestream: compressor::Value::Zstd,
+            batch_update: compressor::Value::Zstd,
+        };
+
+        let blob_data1 = b"aaa".to_vec();
+        let digest1 = &TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+
+        let blob_data2 = b"x".repeat(18);
+        let digest2 = &TDigest {
+            hash: "xl".to_owned(),
+            size_in_bytes: 18,
+            ..Default::default()
+        };
+
+        let req = DownloadRequest {
+            inlined_digests: Some(vec![digest1.clone(), digest2.clone()]),
+            ..Default::default()
+        };
+
+        let res = BatchReadBlobsResponse {
+            responses: vec![batch_read_blobs_response::Response {
+                digest: Some(tdigest_to(digest1.clone())),
+                data: compress(compressor::Value::Zstd, blob_data1.clone())?,
+                compressor: compressor::Value::Zstd as i32,
+                ..Default::default()
+            }],
+        };
+
+        let compressed2 = compress(compressor::Value::Zstd, blob_data2.clone())?;
+        let (chunk1, chunk2) = compressed2.split_at(compressed2.len() / 2);
+        let read_response1 = ReadResponse {
+            data: chunk1.to_vec(),
+        };
+        let read_response2 = ReadResponse {
+            data: chunk2.to_vec(),
+        };
+
+        let res = download_impl(
+            &InstanceName(None),
+            req,
+            10,
+            compression,
+            |req| {
+                let res = res.clone();
+                async move {
+                    assert_eq!(
+                        req.acceptable_compressors,
+                        vec![
+                            compressor::Value::Identity as i32,
+                            compressor::Value::Zstd as i32
+                        ]
+                    );
+                    Ok(res)
+                }
+            },
+            |req| {
+                let read_response1 = read_response1.clone();
+                let read_response2 = read_response2.clone();
+                async move {
+                    assert_eq!(req.resource_name, "compressed-blobs/zstd/xl/18");
+                    anyhow::Ok(Box::pin(futures::stream::iter(vec![
+                        Ok(read_response1),
+                        Ok(read_response2),
+                    ])))
+                }
+            },
+        )
+        .await?;
+
+        let inlined_blobs = res.inlined_blobs.unwrap();
+        assert_eq!(inlined_blobs[0].blob, blob_data1);
+        assert_eq!(inlined_blobs[1].blob, blob_data2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_compressed() -> anyhow::Result<()> {
+        let compression = RECompression {
+            bytestream: compressor::Value::Zstd,
+            batch_update: compressor::Value::Deflate,
+        };
+
+        let blob_data1 = b"aaa".to_vec();
+        let digest1 = TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+
+        let blob_data2 = b"x".repeat(18);
+        let digest2 = TDigest {
+            hash: "xl".to_owned(),
+            size_in_bytes: 18,
+            ..Default::default()
+        };
+
+        let req = UploadRequest {
+            inlined_blobs_with_digest: Some(vec![
+                InlinedBlobWithDigest {
+                    blob: blob_data1.clone(),
+                    digest: digest1.clone(),
+                    ..Default::default()
+                },
+                InlinedBlobWithDigest {
+                    blob: blob_data2.clone(),
+                    digest: digest2.clone(),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        upload_impl(
+            &InstanceName(None),
+            req,
+            10,
+            compression,
+            |req| {
+                let blob_data1 = blob_data1.clone();
+                async move {
+                    assert_eq!(req.requests.len(), 1);
+                    assert_eq!(
+                        req.requests[0].compressor,
+                        compressor::Value::Deflate as i32
+                    );
+                    assert_eq!(
+                        decompress(compressor::Value::Deflate, req.requests[0].data.clone(), 3)?,
+                        blob_data1
+                    );
+                    Ok(BatchUpdateBlobsResponse {
+                        responses: vec![batch_update_blobs_response::Response {
+                            digest: req.requests[0].digest.clone(),
+                            status: Some(Status::default()),
+                        }],
+                    })
+                }
+            },
+            |write_reqs| {
+                let blob_data2 = blob_data2.clone();
+                async move {
+                    let write_reqs = write_reqs.collect::<Vec<_>>().await;
+                    assert!(write_reqs[0].resource_name.starts_with("uploads/"));
+                    assert!(
+                        write_reqs[0]
+                            .resource_name
+                            .ends_with("/compressed-blobs/zstd/xl/18")
+                    );
+                    assert!(write_reqs.last().unwrap().finish_write);
+                    let data = write_reqs
+                        .into_iter()
+                        .flat_map(|r| r.data)
+                        .collect::<Vec<_>>();
+                    assert_eq!(decompress(compressor::Value::Zstd, data, 18)?, blob_data2);
+                    // The server already had this blob.
+                    anyhow::Ok(WriteResponse { committed_size: -1 })
+                }
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_named_write_fails() -> anyhow::Result<()> {
+        let work = tempfile::tempdir()?;
+        let path = work.path().join("path");
+        let path = path.to_str().context("tempdir is not utf8")?;
+        tokio::fs::write(path, vec![b'a'; 100]).await?;
+
+        let req = UploadRequest {
+            files_with_digest: Some(vec![NamedDigest {
+                name: path.to_owned(),
+                digest: TDigest {
+                    hash: "aa".to_owned(),
+                    size_in_bytes: 100,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        // The server gives up after the first segment, which must not leave the upload waiting
+        // to send the rest.
+        let res = upload_impl(
+            &InstanceName(None),
+            req,
+            10,
+            RECompression::default(),
+            |_req| async { panic!("not called") },
+            |mut write_reqs| async move {
+                let first = write_reqs.next().await.context("no segment")?;
+                assert_eq!(first.data.len(), 10);
+                Err::<WriteResponse, _>(anyhow::anyhow!("write failed"))
+            },
+        )
+        .await;
+
+        let err = res.err().context("upload should fail")?;
+        assert!(format!("{:#}", err).contains("write failed"), "{:#}", err);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_named_finished_early() -> anyhow::Result<()> {
+        let work = tempfile::tempdir()?;
+        let path = work.path().join("path");
+        let path = path.to_str().context("tempdir is not utf8")?;
+        tokio::fs::write(path, vec![b'a'; 100]).await?;
+
+        let req = UploadRequest {
+            files_with_digest: Some(vec![NamedDigest {
+                name: path.to_owned(),
+                digest: TDigest {
+                    hash: "aa".to_owned(),
+                    size_in_bytes: 100,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        // The server already has the blob, so it completes the write after the first segment.
+        upload_impl(
+            &InstanceName(None),
+            req,
+            10,
+            RECompression::default(),
+            |_req| async { panic!("not called") },
+            |mut write_reqs| async move {
+                write_reqs.next().await.context("no segment")?;
+                anyhow::Ok(WriteResponse {
+                    committed_size: 100,
+                })
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_compressed_named() -> anyhow::Result<()> {
+        let compression = RECompression {
+            bytestream: compressor::Value::Deflate,
+            batch_update: compressor::Value::Deflate,
+        };
+
+        // Not very compressible, so that it spans several segments once compressed.
+        let blob_data = (0..200u32)
+            .map(|i| (i * 7919 % 251) as u8)
+            .collect::<Vec<_>>();
+
+        let work = tempfile::tempdir()?;
+        let path = work.path().join("path");
+        let path = path.to_str().context("tempdir is not utf8")?;
+        tokio::fs::write(path, &blob_data).await?;
+
+        let req = UploadRequest {
+            files_with_digest: Some(vec![NamedDigest {
+                name: path.to_owned(),
+                digest: TDigest {
+                    hash: "xl".to_owned(),
+                    size_in_bytes: 200,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        upload_impl(
+            &InstanceName(None),
+            req,
+            10,
+            compression,
+            |_req| async { panic!("not called") },
+            |write_reqs| {
+                let blob_data = blob_data.clone();
+                async move {
+                    let write_reqs = write_reqs.collect::<Vec<_>>().await;
+                    assert!(write_reqs.len() > 1);
+                    let mut write_offset = 0;
+                    for (i, r) in write_reqs.iter().enumerate() {
+                        assert!(
+                            r.resource_name
+                                .ends_with("/compressed-blobs/deflate/xl/200")
+                        );
+                        assert_eq!(r.write_offset, write_offset);
+                        assert!(!r.data.is_empty() && r.data.len() <= 10);
+                        assert_eq!(r.finish_write, i == write_reqs.len() - 1);
+                        write_offset += r.data.len() as i64;
+                    }
+                    let data = write_reqs
+                        .into_iter()
+                        .flat_map(|r| r.data)
+                        .collect::<Vec<_>>();
+                    assert_eq!(
+                        decompress(compressor::Value::Deflate, data, 200)?,
+                        blob_data
+                    );
+                    anyhow::Ok(WriteResponse {
+                        committed_size: write_offset,
+                    })
+                }
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_t_action_result2_roundtrip() -> anyhow::Result<()> {
+        let digest1 = TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+
+        let digest2 = TDigest {
+            hash: "bb".to_owned(),
+            size_in_bytes: 30,
+            ..Default::default()
+        };
+
+        let t_action_result = TActionResult2 {
+            output_files: vec![TFile {
+                digest: DigestWithStatus {
+                    digest: digest1.clone(),
+                    status: tstatus_ok(),
+                    ..Default::default()
+                },
+                name: "out/foo".to_owned(),
+                executable: true,
+                ..Default::default()
+            }],
+            output_directories: vec![TDirectory2 {
+                path: "out/bar".to_owned(),
+                tree_digest: digest2.clone(),
+                root_directory_digest: digest2.clone(),
+                ..Default::default()
+            }],
+            exit_code: 0,
+            stdout_raw: Some(b"stdout".to_vec()),
+            stderr_digest: Some(digest1.clone()),
+            execution_metadata: TExecutedActionMetadata {
+                worker: "worker".to_owned(),
+                execution_start_timestamp: TTimestamp {
+                    seconds: 10,
+                    nanos: 20,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let action_result = convert_t_action_result2(t_action_result);
+        assert_eq!(action_result.output_files[0].path, "out/foo");
+        assert!(action_result.output_files[0].is_executable);
+        assert_eq!(
+            action_result.output_directories[0].tree_digest,
+            Some(tdigest_to(digest2.clone()))
+        );
+
+        let converted = convert_action_result(action_result)?;
+        assert_eq!(converted.output_files[0].digest.digest, digest1);
+        assert_eq!(converted.output_files[0].name, "out/foo");
+        assert_eq!(converted.output_directories[0].tree_digest, digest2);
+        assert_eq!(converted.stdout_raw, Some(b"stdout".to_vec()));
+        assert_eq!(converted.stderr_raw, Some(Vec::new()));
+        assert_eq!(converted.stderr_digest, Some(digest1));
+        assert_eq!(converted.execution_metadata.worker, "worker");
+        assert_eq!(
+            converted
+                .execution_metadata
+                .execution_start_timestamp
+                .seconds,
+            10
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_substitute_env_vars() {
         let getter = |s: &str| match s {
@@ -0,0 +1,332 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::io::Write;
+
+use anyhow::Context;
+use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
+
+/// Compression level used for zstd. Level 1 is what most REAPI clients default to: it is cheap on
+/// CPU and still gets most of the bandwidth savings on build outputs.
+const ZSTD_LEVEL: i32 = 1;
+
+/// The compressors we negotiated with the remote, based on what it advertised in its
+/// `CacheCapabilities`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RECompression {
+    /// Compressor used for ByteStream reads and writes, as well as for `BatchReadBlobs`.
+    pub bytestream: compressor::Value,
+    /// Compressor used for `BatchUpdateBlobs`.
+    pub batch_update: compressor::Value,
+}
+
+impl Default for RECompression {
+    fn default() -> Self {
+        Self {
+            bytestream: compressor::Value::Identity,
+            batch_update: compressor::Value::Identity,
+        }
+    }
+}
+
+impl RECompression {
+    /// Pick the compressors to use given the ones supported by the server.
+    pub fn negotiate(
+        supported_compressors: &[i32],
+        supported_batch_update_compressors: &[i32],
+    ) -> Self {
+        Self {
+            bytestream: pick_compressor(supported_compressors),
+            batch_update: pick_compressor(supported_batch_update_compressors),
+        }
+    }
+
+    /// The compressors acceptable in a `BatchReadBlobsRequest`.
+    pub fn acceptable_compressors(&self) -> Vec<i32> {
+        let mut res = vec![compressor::Value::Identity as i32];
+        if self.bytestream != compressor::Value::Identity {
+            res.push(self.bytestream as i32);
+        }
+        res
+    }
+}
+
+/// We prefer zstd, which is both faster and compresses better than deflate.
+fn pick_compressor(supported: &[i32]) -> compressor::Value {
+    for preferred in [compressor::Value::Zstd, compressor::Value::Deflate] {
+        if supported.contains(&(preferred as i32)) {
+            return preferred;
+        }
+    }
+    compressor::Value::Identity
+}
+
+/// The name of a compressor as used in `compressed-blobs` resource names.
+pub fn compressor_name(compressor: compressor::Value) -> &'static str {
+    match compressor {
+        compressor::Value::Identity => "identity",
+        compressor::Value::Zstd => "zstd",
+        compressor::Value::Deflate => "deflate",
+    }
+}
+
+/// The trailing part of the ByteStream resource name used to read or write a blob.
+pub fn blob_resource_name(compressor: compressor::Value, hash: &str, size: i64) -> String {
+    match compressor {
+        compressor::Value::Identity => format!("blobs/{}/{}", hash, size),
+        c => format!("compressed-blobs/{}/{}/{}", compressor_name(c), hash, size),
+    }
+}
+
+/// Whether the `committed_size` returned by a ByteStream write is valid.
+pub fn is_valid_committed_size(
+    compressor: compressor::Value,
+    committed_size: i64,
+    uncompressed_size: i64,
+    compressed_size: i64,
+) -> bool {
+    match compressor {
+        compressor::Value::Identity => committed_size == uncompressed_size,
+        // Servers return -1 for compressed uploads of blobs that were already present. Otherwise,
+        // implementations disagree on whether to report the compressed or uncompressed size.
+        _ => {
+            committed_size == -1
+                || committed_size == uncompressed_size
+                || committed_size == compressed_size
+        }
+    }
+}
+
+pub fn compress(compressor: compressor::Value, data: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    match compressor {
+        compressor::Value::Identity => Ok(data),
+        compressor::Value::Zstd => {
+            zstd::bulk::compress(&data, ZSTD_LEVEL).context("Error compressing with zstd")
+        }
+        compressor::Value::Deflate => {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+            encoder
+                .write_all(&data)
+                .context("Error compressing with deflate")?;
+            encoder.finish().context("Error compressing with deflate")
+        }
+    }
+}
+
+pub fn decompress(
+    compressor: compressor::Value,
+    data: Vec<u8>,
+    uncompressed_size: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let res = match compressor {
+        compressor::Value::Identity => return Ok(data),
+        compressor::Value::Zstd => zstd::bulk::decompress(&data, uncompressed_size)
+            .context("Error decompressing with zstd")?,
+        compressor::Value::Deflate => {
+            let mut decoder = flate2::write::DeflateDecoder::new(Vec::new());
+            decoder
+                .write_all(&data)
+                .context("Error decompressing with deflate")?;
+            decoder
+                .finish()
+                .context("Error decompressing with deflate")?
+        }
+    };
+
+    if res.len() != uncompressed_size {
+        return Err(anyhow::anyhow!(
+            "Decompressed size mismatch: expected {}, got {}",
+            uncompressed_size,
+            res.len()
+        ));
+    }
+
+    Ok(res)
+}
+
+/// Incrementally compresses a blob sent in chunks over the ByteStream API.
+pub enum Compressor {
+    Identity,
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+    Deflate(flate2::write::DeflateEncoder<Vec<u8>>),
+}
+
+impl Compressor {
+    pub fn new(compressor: compressor::Value) -> anyhow::Result<Self> {
+        Ok(match compressor {
+            compressor::Value::Identity => Self::Identity,
+            compressor::Value::Zstd => Self::Zstd(
+                zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL)
+                    .context("Error creating zstd encoder")?,
+            ),
+            compressor::Value::Deflate => Self::Deflate(flate2::write::DeflateEncoder::new(
+                Vec::new(),
+                flate2::Compression::fast(),
+            )),
+        })
+    }
+
+    /// Feed a chunk of data, and return whatever compressed data is available.
+    pub fn push(&mut self, chunk: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Identity => Ok(chunk.to_vec()),
+            Self::Zstd(encoder) => {
+                encoder
+                    .write_all(chunk)
+                    .context("Error compressing with zstd")?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+            Self::Deflate(encoder) => {
+                encoder
+                    .write_all(chunk)
+                    .context("Error compressing with deflate")?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+        }
+    }
+
+    /// Signal the end of the data, and return the remaining compressed data.
+    pub fn finish(self) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Identity => Ok(Vec::new()),
+            Self::Zstd(encoder) => encoder.finish().context("Error compressing with zstd"),
+            Self::Deflate(encoder) => encoder.finish().context("Error compressing with deflate"),
+        }
+    }
+}
+
+/// Incrementally decompresses a blob received in chunks over the ByteStream API.
+pub enum Decompressor {
+    Identity,
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+    Deflate(flate2::write::DeflateDecoder<Vec<u8>>),
+}
+
+impl Decompressor {
+    pub fn new(compressor: compressor::Value) -> anyhow::Result<Self> {
+        Ok(match compressor {
+            compressor::Value::Identity => Self::Identity,
+            compressor::Value::Zstd => Self::Zstd(
+                zstd::stream::write::Decoder::new(Vec::new())
+                    .context("Error creating zstd decoder")?,
+            ),
+            compressor::Value::Deflate => {
+                Self::Deflate(flate2::write::DeflateDecoder::new(Vec::new()))
+            }
+        })
+    }
+
+    /// Feed a chunk of compressed data, and return whatever uncompressed data is available.
+    pub fn push(&mut self, chunk: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Identity => Ok(chunk),
+            Self::Zstd(decoder) => {
+                decoder
+                    .write_all(&chunk)
+                    .and_then(|()| decoder.flush())
+                    .context("Error decompressing with zstd")?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            Self::Deflate(decoder) => {
+                decoder
+                    .write_all(&chunk)
+                    .and_then(|()| decoder.flush())
+                    .context("Error decompressing with deflate")?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+        }
+    }
+
+    /// Signal the end of the stream, and return any remaining uncompressed data.
+    pub fn finish(self) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Identity => Ok(Vec::new()),
+            Self::Zstd(mut decoder) => {
+                decoder.flush().context("Error decompressing with zstd")?;
+                Ok(decoder.into_inner())
+            }
+            Self::Deflate(decoder) => decoder.finish().context("Error decompressing with deflate"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let zstd = compressor::Value::Zstd as i32;
+        let deflate = compressor::Value::Deflate as i32;
+
+        assert_eq!(RECompression::negotiate(&[], &[]), RECompression::default());
+        assert_eq!(
+            RECompression::negotiate(&[deflate, zstd], &[deflate]),
+            RECompression {
+                bytestream: compressor::Value::Zstd,
+                batch_update: compressor::Value::Deflate,
+            }
+        );
+    }
+
+    #[test]
+    fn test_blob_resource_name() {
+        assert_eq!(
+            blob_resource_name(compressor::Value::Identity, "aa", 3),
+            "blobs/aa/3"
+        );
+        assert_eq!(
+            blob_resource_name(compressor::Value::Zstd, "aa", 3),
+            "compressed-blobs/zstd/aa/3"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() -> anyhow::Result<()> {
+        let data = b"aaaaaaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbbbbbbbbbbb".repeat(100);
+
+        for compressor in [
+            compressor::Value::Identity,
+            compressor::Value::Zstd,
+            compressor::Value::Deflate,
+        ] {
+            let compressed = compress(compressor, data.clone())?;
+            assert_eq!(
+                decompress(compressor, compressed.clone(), data.len())?,
+                data
+            );
+
+            let mut decompressor = Decompressor::new(compressor)?;
+            let mut streamed = Vec::new();
+            for chunk in compressed.chunks(7) {
+                streamed.extend(decompressor.push(chunk.to_vec())?);
+            }
+            streamed.extend(decompressor.finish()?);
+            assert_eq!(streamed, data);
+
+            let mut encoder = Compressor::new(compressor)?;
+            let mut compressed = Vec::new();
+            for chunk in data.chunks(7) {
+                compressed.extend(encoder.push(chunk)?);
+            }
+            compressed.extend(encoder.finish()?);
+            assert_eq!(decompress(compressor, compressed, data.len())?, data);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decompress_size_mismatch() -> anyhow::Result<()> {
+        let compressed = compress(compressor::Value::Deflate, b"abc".to_vec())?;
+        assert!(decompress(compressor::Value::Deflate, compressed, 4).is_err());
+        Ok(())
+    }
+}
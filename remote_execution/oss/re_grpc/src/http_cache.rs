@@ -0,0 +1,915 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A client for HTTP/1.1 remote caches such as bazel-remote. Those store blobs under
+//! `<address>/cas/<hash>` and serialized `ActionResult` messages under `<address>/ac/<hash>`,
+//! and don't offer remote execution.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use buck2_common::cas_digest::DigestAlgorithm;
+use buck2_common::cas_digest::Digester;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileDigestKind;
+use buck2_common::http::http_client_for_oss_with_certs;
+use buck2_common::http::retries;
+use buck2_common::http::retries::http_retry;
+use buck2_common::http::retries::AsHttpError;
+use buck2_common::http::HttpClient;
+use buck2_common::http::HttpError;
+use buck2_re_configuration::Buck2OssReConfiguration;
+use bytes::Bytes;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use http::Method;
+use http::Request;
+use http::StatusCode;
+use hyper::Body;
+use prost::Message;
+use re_grpc_proto::build::bazel::remote::execution::v2::ActionResult;
+use thiserror::Error;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+use crate::client::convert_action_result;
+use crate::client::convert_t_action_result2;
+use crate::client::substitute_env_vars;
+use crate::client::tstatus_ok;
+use crate::client::BatchUploadRequest;
+use crate::client::CONCURRENT_UPLOAD_LIMIT;
+use crate::error::REClientError;
+use crate::error::TCode;
+use crate::request::*;
+use crate::response::*;
+use crate::stats::NetworkStats;
+
+/// Size of the chunks files are uploaded in.
+const UPLOAD_CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Error)]
+enum HttpCacheError {
+    #[error("Error querying HTTP cache")]
+    Client(#[from] retries::HttpError),
+
+    #[error("HTTP cache returned a blob with digest `{obtained}` for `{expected}`")]
+    InvalidDigest { expected: String, obtained: String },
+
+    #[error(transparent)]
+    Io(anyhow::Error),
+}
+
+impl AsHttpError for HttpCacheError {
+    fn as_http_error(&self) -> Option<&retries::HttpError> {
+        match self {
+            Self::Client(e) => Some(e),
+            Self::InvalidDigest { .. } | Self::Io(..) => None,
+        }
+    }
+}
+
+fn retry_intervals() -> Vec<Duration> {
+    vec![2, 4, 8].into_iter().map(Duration::from_secs).collect()
+}
+
+/// HTTP caches address blobs by their SHA256 (or SHA1), which we tell apart by their length.
+fn blob_digester(digest: &TDigest) -> Result<Digester<FileDigestKind>, HttpCacheError> {
+    let algorithm = match digest.hash.len() {
+        40 => DigestAlgorithm::Sha1,
+        64 => DigestAlgorithm::Sha256,
+        _ => {
+            return Err(HttpCacheError::Io(anyhow::anyhow!(
+                "Digest `{}` is neither a SHA1 nor a SHA256, which HTTP caches require",
+                digest
+            )));
+        }
+    };
+    Ok(FileDigest::digester_for_algorithm(algorithm))
+}
+
+pub(crate) struct HttpCacheClient {
+    client: Arc<dyn HttpClient>,
+    /// Base URL of the cache, without a trailing slash.
+    address: String,
+    headers: Vec<(String, String)>,
+    stats: NetworkStats,
+}
+
+impl HttpCacheClient {
+    pub(crate) fn new(
+        client: Arc<dyn HttpClient>,
+        address: &str,
+        headers: Vec<(String, String)>,
+    ) -> anyhow::Result<Self> {
+        let uri: http::Uri = address
+            .parse()
+            .with_context(|| format!("Invalid HTTP cache address `{}`", address))?;
+        match uri.scheme_str() {
+            Some("http") | Some("https") => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Invalid HTTP cache address `{}` (expected an `http://` or `https://` URL)",
+                    address
+                ));
+            }
+        }
+
+        Ok(Self {
+            client,
+            address: address.trim_end_matches('/').to_owned(),
+            headers,
+            stats: NetworkStats::default(),
+        })
+    }
+
+    pub(crate) fn from_config(
+        address: &str,
+        opts: &Buck2OssReConfiguration,
+    ) -> anyhow::Result<Self> {
+        let address = substitute_env_vars(address).context("Invalid `http_cache_address`")?;
+
+        let ca_certs = opts
+            .tls_ca_certs
+            .as_ref()
+            .map(|c| substitute_env_vars(c).context("Invalid `tls_ca_certs`"))
+            .transpose()?;
+        let client_cert = opts
+            .tls_client_cert
+            .as_ref()
+            .map(|c| substitute_env_vars(c).context("Invalid `tls_client_cert`"))
+            .transpose()?;
+        let client = http_client_for_oss_with_certs(
+            ca_certs.as_ref().map(Path::new),
+            client_cert.as_ref().map(Path::new),
+        )?;
+
+        let mut headers = Vec::with_capacity(opts.http_headers.len());
+        for header in &opts.http_headers {
+            headers.push((
+                substitute_env_vars(&header.key).context("Invalid `http_headers`")?,
+                substitute_env_vars(&header.value).context("Invalid `http_headers`")?,
+            ));
+        }
+
+        Self::new(client, &address, headers)
+    }
+
+    pub(crate) fn stats(&self) -> &NetworkStats {
+        &self.stats
+    }
+
+    fn request(&self, method: &Method, uri: &str) -> http::request::Builder {
+        let mut req = Request::builder().method(method.clone()).uri(uri);
+        for (key, value) in &self.headers {
+            req = req.header(key.as_str(), value.as_str());
+        }
+        req
+    }
+
+    /// Send a request to `<address>/<path>` and return the response body, or `None` if the cache
+    /// responded with a 404. Transient errors are retried.
+    async fn send(&self, method: Method, path: &str, body: Bytes) -> anyhow::Result<Option<Bytes>> {
+        let uri = format!("{}/{}", self.address, path);
+
+        let res = http_retry(
+            || async {
+                let req = self
+                    .request(&method, &uri)
+                    .body(body.clone())
+                    .map_err(|e| retries::HttpError::Client(HttpError::BuildRequest(e)))?;
+
+                let mut stream = self
+                    .client
+                    .request(req)
+                    .await
+                    .map_err(retries::HttpError::Client)?
+                    .into_body();
+
+                let mut data = Vec::new();
+                while let Some(chunk) = stream.next().await {
+                    let chunk = chunk.map_err(|source| retries::HttpError::Transfer {
+                        received: data.len() as u64,
+                        url: uri.clone(),
+                        source,
+                    })?;
+                    data.extend_from_slice(&chunk);
+                }
+
+                Result::<_, HttpCacheError>::Ok(Bytes::from(data))
+            },
+            retry_intervals(),
+        )
+        .await;
+
+        found(res, &method, &uri)
+    }
+
+    fn blob_uri(&self, digest: &TDigest) -> String {
+        format!("{}/cas/{}", self.address, digest.hash)
+    }
+
+    /// Download the blob `digest` to `writer`, checking that its contents match it.
+    async fn receive_blob(
+        &self,
+        uri: &str,
+        digest: &TDigest,
+        writer: &mut (impl AsyncWrite + Unpin),
+    ) -> Result<(), HttpCacheError> {
+        let mut digester = blob_digester(digest)?;
+        let req = self
+            .request(&Method::GET, uri)
+            .body(Bytes::new())
+            .map_err(|e| retries::HttpError::Client(HttpError::BuildRequest(e)))?;
+        let mut stream = self
+            .client
+            .request(req)
+            .await
+            .map_err(retries::HttpError::Client)?
+            .into_body();
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|source| retries::HttpError::Transfer {
+                received: digester.bytes_read(),
+                url: uri.to_owned(),
+                source,
+            })?;
+            digester.update(&chunk);
+            // Don't write more than the blob can hold.
+            if digester.bytes_read() > digest.size_in_bytes as u64 {
+                break;
+            }
+            writer
+                .write_all(&chunk)
+                .await
+                .context("Error writing")
+                .map_err(HttpCacheError::Io)?;
+        }
+        writer
+            .flush()
+            .await
+            .context("Error flushing")
+            .map_err(HttpCacheError::Io)?;
+
+        let size = digester.bytes_read();
+        let obtained = digester.finalize();
+        if size != digest.size_in_bytes as u64 || obtained.raw_digest().to_string() != digest.hash {
+            return Err(HttpCacheError::InvalidDigest {
+                expected: digest.to_string(),
+                obtained: obtained.to_string(),
+            });
+        }
+
+        self.stats.record_downloaded(size as usize);
+        Ok(())
+    }
+
+    async fn get_blob(&self, digest: &TDigest) -> anyhow::Result<Vec<u8>> {
+        if digest.size_in_bytes == 0 {
+            return Ok(Vec::new());
+        }
+
+        let uri = self.blob_uri(digest);
+        let res = http_retry(
+            || async {
+                let mut data = Vec::new();
+                self.receive_blob(&uri, digest, &mut data).await?;
+                Ok(data)
+            },
+            retry_intervals(),
+        )
+        .await;
+
+        Ok(found(res, &Method::GET, &uri)?.ok_or_else(|| blob_not_found(digest))?)
+    }
+
+    /// Download the blob `digest` to the file `name`, which is removed if that fails.
+    async fn get_blob_to_file(
+        &self,
+        digest: &TDigest,
+        name: &str,
+        is_executable: bool,
+    ) -> anyhow::Result<()> {
+        let mut opts = OpenOptions::new();
+        opts.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            if is_executable {
+                opts.mode(0o755);
+            } else {
+                opts.mode(0o644);
+            }
+        }
+        #[cfg(not(unix))]
+        let _unused = is_executable;
+
+        if digest.size_in_bytes == 0 {
+            opts.open(name).await.context("Error opening")?;
+            return Ok(());
+        }
+
+        let uri = self.blob_uri(digest);
+        let res = http_retry(
+            || async {
+                let mut file = opts
+                    .open(name)
+                    .await
+                    .context("Error opening")
+                    .map_err(HttpCacheError::Io)?;
+                self.receive_blob(&uri, digest, &mut file).await
+            },
+            retry_intervals(),
+        )
+        .await;
+
+        let res = found(res, &Method::GET, &uri)
+            .and_then(|found| found.ok_or_else(|| blob_not_found(digest).into()));
+        if res.is_err() {
+            // Don't leave a partial or wrong blob behind.
+            let _ignored = tokio::fs::remove_file(name).await;
+        }
+        res
+    }
+
+    async fn put_blob(&self, digest: &TDigest, data: Vec<u8>) -> anyhow::Result<()> {
+        self.stats.record_uploaded(data.len());
+        self.send(Method::PUT, &format!("cas/{}", digest.hash), data.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Upload the file `name` as the blob `digest`, reading it as it is sent.
+    async fn put_file(&self, digest: &TDigest, name: &str) -> anyhow::Result<()> {
+        let uri = self.blob_uri(digest);
+        let res = http_retry(
+            || async {
+                let file = tokio::fs::File::open(name)
+                    .await
+                    .with_context(|| format!("Error opening `{}`", name))
+                    .map_err(HttpCacheError::Io)?;
+                let (sender, body) = Body::channel();
+                // Caches need the length to tell whether they got all of the blob.
+                let req = self
+                    .request(&Method::PUT, &uri)
+                    .header(http::header::CONTENT_LENGTH, digest.size_in_bytes)
+                    .body(body)
+                    .map_err(|e| retries::HttpError::Client(HttpError::BuildRequest(e)))?;
+
+                let (sent, resp) = futures::future::join(
+                    self.send_file(name, file, digest, sender),
+                    self.client.request_streaming(req),
+                )
+                .await;
+                // A failure to read the file is what made the request fail, if it did.
+                sent.map_err(HttpCacheError::Io)?;
+                resp.map_err(retries::HttpError::Client)?;
+                Ok(())
+            },
+            retry_intervals(),
+        )
+        .await;
+
+        found(res, &Method::PUT, &uri)?;
+        Ok(())
+    }
+
+    async fn send_file(
+        &self,
+        name: &str,
+        file: tokio::fs::File,
+        digest: &TDigest,
+        mut sender: hyper::body::Sender,
+    ) -> anyhow::Result<()> {
+        let res = self.send_file_data(name, file, digest, &mut sender).await;
+        if res.is_err() {
+            // Make the request fail rather than send part of the file.
+            sender.abort();
+        }
+        res
+    }
+
+    async fn send_file_data(
+        &self,
+        name: &str,
+        mut file: tokio::fs::File,
+        digest: &TDigest,
+        sender: &mut hyper::body::Sender,
+    ) -> anyhow::Result<()> {
+        let size = file
+            .metadata()
+            .await
+            .with_context(|| format!("Error reading the metadata of `{}`", name))?
+            .len();
+        if size as i64 != digest.size_in_bytes {
+            return Err(anyhow::anyhow!(
+                "`{}` has {} bytes, but its digest is `{}`",
+                name,
+                size,
+                digest
+            ));
+        }
+
+        let mut buf = vec![0; UPLOAD_CHUNK_SIZE];
+        let mut sent = 0;
+        loop {
+            let length = file
+                .read(&mut buf)
+                .await
+                .with_context(|| format!("Error reading `{}`", name))?;
+            if length == 0 {
+                break;
+            }
+            if sender
+                .send_data(Bytes::copy_from_slice(&buf[..length]))
+                .await
+                .is_err()
+            {
+                // The request failed, which it reports itself.
+                return Ok(());
+            }
+            self.stats.record_uploaded(length);
+            sent += length as i64;
+        }
+        if sent != digest.size_in_bytes {
+            return Err(anyhow::anyhow!("`{}` changed while it was uploaded", name));
+        }
+        Ok(())
+    }
+
+    async fn contains_blob(&self, digest: &TDigest) -> anyhow::Result<bool> {
+        if digest.size_in_bytes == 0 {
+            return Ok(true);
+        }
+
+        Ok(self
+            .send(Method::HEAD, &format!("cas/{}", digest.hash), Bytes::new())
+            .await?
+            .is_some())
+    }
+
+    pub(crate) async fn get_action_result(
+        &self,
+        request: ActionResultRequest,
+    ) -> anyhow::Result<ActionResultResponse> {
+        let data = self
+            .send(
+                Method::GET,
+                &format!("ac/{}", request.digest.hash),
+                Bytes::new(),
+            )
+            .await?
+            .ok_or_else(|| REClientError {
+                code: TCode::NOT_FOUND,
+                message: format!("Action `{}` not found in HTTP cache", request.digest),
+            })?;
+
+        let mut action_result =
+            ActionResult::decode(data).context("Invalid action result in HTTP cache")?;
+        // Results written by other clients don't necessarily have execution metadata.
+        action_result
+            .execution_metadata
+            .get_or_insert_with(Default::default);
+
+        Ok(ActionResultResponse {
+            action_result: convert_action_result(action_result)?,
+            ttl: 0,
+        })
+    }
+
+    pub(crate) async fn write_action_result(
+        &self,
+        request: WriteActionResultRequest,
+    ) -> anyhow::Result<WriteActionResultResponse> {
+        // As with gRPC, the outputs are expected to already be in the CAS. Caches that validate
+        // action results (like bazel-remote does by default) will reject them otherwise.
+        let data = convert_t_action_result2(request.action_result).encode_to_vec();
+        self.send(
+            Method::PUT,
+            &format!("ac/{}", request.action_digest.hash),
+            data.into(),
+        )
+        .await?;
+        Ok(WriteActionResultResponse {})
+    }
+
+    pub(crate) async fn upload(&self, request: UploadRequest) -> anyhow::Result<UploadResponse> {
+        let upload_only_missing = request.upload_only_missing;
+
+        let blobs = request
+            .inlined_blobs_with_digest
+            .unwrap_or_default()
+            .into_iter()
+            .map(BatchUploadRequest::Blob);
+        let files = request
+            .files_with_digest
+            .unwrap_or_default()
+            .into_iter()
+            .map(BatchUploadRequest::File);
+
+        futures::stream::iter(blobs.chain(files))
+            .map(|upload| async move {
+                let digest = match &upload {
+                    BatchUploadRequest::Blob(blob) => blob.digest.clone(),
+                    BatchUploadRequest::File(file) => file.digest.clone(),
+                };
+                // The empty blob is always available.
+                if digest.size_in_bytes == 0
+                    || (upload_only_missing && self.contains_blob(&digest).await?)
+                {
+                    return anyhow::Ok(());
+                }
+
+                match upload {
+                    BatchUploadRequest::Blob(blob) => self.put_blob(&digest, blob.blob).await,
+                    BatchUploadRequest::File(file) => self.put_file(&digest, &file.name).await,
+                }
+                .with_context(|| format!("Error uploading `{}`", digest))
+            })
+            .buffer_unordered(CONCURRENT_UPLOAD_LIMIT)
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        Ok(UploadResponse {})
+    }
+
+    pub(crate) async fn download(
+        &self,
+        request: DownloadRequest,
+    ) -> anyhow::Result<DownloadResponse> {
+        let inlined_blobs = futures::stream::iter(request.inlined_digests.unwrap_or_default())
+            .map(|digest| async move {
+                let blob = self.get_blob(&digest).await?;
+                anyhow::Ok(InlinedDigestWithStatus {
+                    digest,
+                    status: tstatus_ok(),
+                    blob,
+                })
+            })
+            .buffered(CONCURRENT_UPLOAD_LIMIT)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        futures::stream::iter(request.file_digests.unwrap_or_default())
+            .map(|req| async move {
+                let fut = self.get_blob_to_file(
+                    &req.named_digest.digest,
+                    &req.named_digest.name,
+                    req.is_executable,
+                );
+                fut.await.with_context(|| {
+                    format!(
+                        "Error downloading digest `{}` to `{}`",
+                        req.named_digest.digest, req.named_digest.name,
+                    )
+                })
+            })
+            .buffer_unordered(CONCURRENT_UPLOAD_LIMIT)
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        Ok(DownloadResponse {
+            inlined_blobs: Some(inlined_blobs),
+            directories: None,
+        })
+    }
+
+    pub(crate) async fn get_digests_ttl(
+        &self,
+        request: GetDigestsTtlRequest,
+    ) -> anyhow::Result<GetDigestsTtlResponse> {
+        let digests_with_ttl = futures::stream::iter(request.digests)
+            .map(|digest| async move {
+                // HTTP caches don't tell us how long they'll keep blobs for, so like with RBE,
+                // use an arbitrary TTL for those that are present.
+                let ttl = if self.contains_blob(&digest).await? {
+                    60
+                } else {
+                    0
+                };
+                anyhow::Ok(DigestWithTtl { digest, ttl })
+            })
+            .buffer_unordered(CONCURRENT_UPLOAD_LIMIT)
+            .try_collect()
+            .await?;
+
+        Ok(GetDigestsTtlResponse { digests_with_ttl })
+    }
+}
+
+/// Turns a 404 from the cache into `None`.
+fn found<T>(
+    res: Result<T, HttpCacheError>,
+    method: &Method,
+    uri: &str,
+) -> anyhow::Result<Option<T>> {
+    match res {
+        Ok(data) => Ok(Some(data)),
+        Err(HttpCacheError::Client(retries::HttpError::Client(HttpError::Status {
+            status: StatusCode::NOT_FOUND,
+            ..
+        }))) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("{} `{}`", method, uri)),
+    }
+}
+
+fn blob_not_found(digest: &TDigest) -> REClientError {
+    REClientError {
+        code: TCode::NOT_FOUND,
+        message: format!("Blob `{}` not found in HTTP cache", digest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::http::http_client_for_oss;
+    use httptest::matchers::*;
+    use httptest::responders;
+    use httptest::Expectation;
+    use httptest::Server;
+
+    use super::*;
+
+    /// SHA256 of `blob`.
+    const BLOB_HASH: &str = "fa2c8cc4f28176bbeed4b736df569a34c79cd3723e9ec42f9674b4d46ac6b8b8";
+    /// SHA256 of `file`.
+    const FILE_HASH: &str = "3b9c358f36f0a31b6ad3e14f309c7cf198ac9246e8316f9ce543d5b19ac02b80";
+
+    fn digest(hash: &str, size_in_bytes: i64) -> TDigest {
+        TDigest {
+            hash: hash.to_owned(),
+            size_in_bytes,
+            ..Default::default()
+        }
+    }
+
+    fn cache_client(server: &Server) -> anyhow::Result<HttpCacheClient> {
+        HttpCacheClient::new(
+            http_client_for_oss()?,
+            &server.url_str("/"),
+            vec![("Authorization".to_owned(), "Bearer token".to_owned())],
+        )
+    }
+
+    #[test]
+    fn test_invalid_address() -> anyhow::Result<()> {
+        assert!(HttpCacheClient::new(http_client_for_oss()?, "grpc://foo:8980", vec![]).is_err());
+        assert!(HttpCacheClient::new(http_client_for_oss()?, "foo:8080", vec![]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_action_result_roundtrip() -> anyhow::Result<()> {
+        let server = Server::run();
+
+        let action_result = ActionResult {
+            exit_code: 1,
+            stdout_raw: b"out".to_vec(),
+            ..Default::default()
+        };
+
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("PUT", "/ac/aa"),
+                request::headers(contains(("authorization", "Bearer token"))),
+            ])
+            .respond_with(responders::status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/ac/aa"))
+                .respond_with(responders::status_code(200).body(action_result.encode_to_vec())),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/ac/bb"))
+                .respond_with(responders::status_code(404)),
+        );
+
+        let client = cache_client(&server)?;
+
+        client
+            .write_action_result(WriteActionResultRequest {
+                action_digest: digest("aa", 10),
+                action_result: TActionResult2 {
+                    exit_code: 1,
+                    stdout_raw: Some(b"out".to_vec()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .await?;
+
+        let res = client
+            .get_action_result(ActionResultRequest {
+                digest: digest("aa", 10),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(res.action_result.exit_code, 1);
+        assert_eq!(res.action_result.stdout_raw, Some(b"out".to_vec()));
+
+        let err = client
+            .get_action_result(ActionResultRequest {
+                digest: digest("bb", 10),
+                ..Default::default()
+            })
+            .await
+            .err()
+            .context("Expected a miss")?;
+        assert_eq!(
+            err.downcast_ref::<REClientError>().map(|e| e.code.clone()),
+            Some(TCode::NOT_FOUND)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_download() -> anyhow::Result<()> {
+        let server = Server::run();
+        let work = tempfile::tempdir()?;
+
+        let input = work.path().join("input");
+        std::fs::write(&input, b"file")?;
+        let input = input.to_str().context("tempdir is not utf8")?.to_owned();
+
+        let output = work.path().join("output");
+        let output = output.to_str().context("tempdir is not utf8")?.to_owned();
+
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path(
+                    "PUT",
+                    "/cas/fa2c8cc4f28176bbeed4b736df569a34c79cd3723e9ec42f9674b4d46ac6b8b8"
+                ),
+                request::body("blob"),
+            ])
+            .respond_with(responders::status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path(
+                    "PUT",
+                    "/cas/3b9c358f36f0a31b6ad3e14f309c7cf198ac9246e8316f9ce543d5b19ac02b80"
+                ),
+                request::body("file"),
+            ])
+            .respond_with(responders::status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path(
+                "GET",
+                "/cas/fa2c8cc4f28176bbeed4b736df569a34c79cd3723e9ec42f9674b4d46ac6b8b8",
+            ))
+            .respond_with(responders::status_code(200).body("blob")),
+        );
+        server.expect(
+            Expectation::matching(request::method_path(
+                "GET",
+                "/cas/3b9c358f36f0a31b6ad3e14f309c7cf198ac9246e8316f9ce543d5b19ac02b80",
+            ))
+            .respond_with(responders::status_code(200).body("file")),
+        );
+
+        let client = cache_client(&server)?;
+
+        client
+            .upload(UploadRequest {
+                inlined_blobs_with_digest: Some(vec![
+                    InlinedBlobWithDigest {
+                        blob: b"blob".to_vec(),
+                        digest: digest(BLOB_HASH, 4),
+                        ..Default::default()
+                    },
+                    // Empty blobs don't need to be uploaded.
+                    InlinedBlobWithDigest {
+                        blob: Vec::new(),
+                        digest: digest("ee", 0),
+                        ..Default::default()
+                    },
+                ]),
+                files_with_digest: Some(vec![NamedDigest {
+                    name: input,
+                    digest: digest(FILE_HASH, 4),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            })
+            .await?;
+
+        let res = client
+            .download(DownloadRequest {
+                inlined_digests: Some(vec![digest(BLOB_HASH, 4), digest("ee", 0)]),
+                file_digests: Some(vec![NamedDigestWithPermissions {
+                    named_digest: NamedDigest {
+                        name: output.clone(),
+                        digest: digest(FILE_HASH, 4),
+                        ..Default::default()
+                    },
+                    is_executable: false,
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            })
+            .await?;
+
+        let inlined_blobs = res.inlined_blobs.context("No inlined blobs")?;
+        assert_eq!(inlined_blobs.len(), 2);
+        assert_eq!(inlined_blobs[0].blob, b"blob");
+        assert_eq!(inlined_blobs[1].blob, b"");
+        assert_eq!(std::fs::read(&output)?, b"file");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_invalid_digest() -> anyhow::Result<()> {
+        let server = Server::run();
+        let work = tempfile::tempdir()?;
+
+        let output = work.path().join("output");
+        let output = output.to_str().context("tempdir is not utf8")?.to_owned();
+
+        server.expect(
+            Expectation::matching(request::method_path(
+                "GET",
+                "/cas/fa2c8cc4f28176bbeed4b736df569a34c79cd3723e9ec42f9674b4d46ac6b8b8",
+            ))
+            .times(2)
+            .respond_with(responders::status_code(200).body("blxb")),
+        );
+
+        let client = cache_client(&server)?;
+
+        let err = client
+            .download(DownloadRequest {
+                inlined_digests: Some(vec![digest(BLOB_HASH, 4)]),
+                ..Default::default()
+            })
+            .await
+            .err()
+            .context("Download should fail")?;
+        assert!(format!("{:#}", err).contains("HTTP cache returned a blob with digest"));
+
+        let err = client
+            .download(DownloadRequest {
+                file_digests: Some(vec![NamedDigestWithPermissions {
+                    named_digest: NamedDigest {
+                        name: output.clone(),
+                        digest: digest(BLOB_HASH, 4),
+                        ..Default::default()
+                    },
+                    is_executable: false,
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            })
+            .await
+            .err()
+            .context("Download should fail")?;
+        assert!(format!("{:#}", err).contains("HTTP cache returned a blob with digest"));
+        assert!(!Path::new(&output).exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_digests_ttl() -> anyhow::Result<()> {
+        let server = Server::run();
+
+        server.expect(
+            Expectation::matching(request::method_path("HEAD", "/cas/aa"))
+                .respond_with(responders::status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("HEAD", "/cas/bb"))
+                .respond_with(responders::status_code(404)),
+        );
+
+        let client = cache_client(&server)?;
+
+        let res = client
+            .get_digests_ttl(GetDigestsTtlRequest {
+                digests: vec![digest("aa", 4), digest("bb", 4)],
+                ..Default::default()
+            })
+            .await?;
+
+        let mut ttls = res
+            .digests_with_ttl
+            .into_iter()
+            .map(|d| (d.digest.hash, d.ttl))
+            .collect::<Vec<_>>();
+        ttls.sort();
+        assert_eq!(ttls, vec![("aa".to_owned(), 60), ("bb".to_owned(), 0)]);
+
+        Ok(())
+    }
+}
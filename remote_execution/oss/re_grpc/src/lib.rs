@@ -13,12 +13,15 @@
 #![cfg_attr(feature = "gazebo_lint", plugin(gazebo_lint))]
 
 mod client;
+mod compression;
 mod digest;
 mod error;
 mod grpc;
+mod http_cache;
 mod metadata;
 mod request;
 mod response;
+mod stats;
 pub use client::*;
 pub use digest::*;
 pub use error::*;
@@ -27,11 +30,7 @@ pub use metadata::*;
 pub use request::*;
 pub use response::*;
 
+/// The bytes that all the clients of this process transferred so far.
 pub fn get_network_stats() -> anyhow::Result<NetworkStatisticsResponse> {
-    // TODO: Support this in this client.
-    Ok(NetworkStatisticsResponse {
-        downloaded: 0,
-        uploaded: 0,
-        _dot_dot_default: (),
-    })
+    Ok(stats::NetworkStats::process().to_response())
 }
@@ -0,0 +1,60 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use crate::response::NetworkStatisticsResponse;
+
+/// Bytes a client transferred to and from the CAS, as sent over the wire (i.e. after compression).
+/// Those also count towards the totals for the process, which `get_network_stats` returns.
+#[derive(Default)]
+pub(crate) struct NetworkStats {
+    uploaded: AtomicU64,
+    downloaded: AtomicU64,
+}
+
+/// All the clients this process created, including those that were since dropped, so that those
+/// numbers only grow.
+static PROCESS_STATS: NetworkStats = NetworkStats::new();
+
+impl NetworkStats {
+    const fn new() -> Self {
+        Self {
+            uploaded: AtomicU64::new(0),
+            downloaded: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn process() -> &'static NetworkStats {
+        &PROCESS_STATS
+    }
+
+    pub(crate) fn record_uploaded(&self, bytes: usize) {
+        self.uploaded.fetch_add(bytes as u64, Ordering::Relaxed);
+        PROCESS_STATS
+            .uploaded
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_downloaded(&self, bytes: usize) {
+        self.downloaded.fetch_add(bytes as u64, Ordering::Relaxed);
+        PROCESS_STATS
+            .downloaded
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn to_response(&self) -> NetworkStatisticsResponse {
+        NetworkStatisticsResponse {
+            uploaded: self.uploaded.load(Ordering::Relaxed) as _,
+            downloaded: self.downloaded.load(Ordering::Relaxed) as _,
+            _dot_dot_default: (),
+        }
+    }
+}
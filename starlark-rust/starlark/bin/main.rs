@@ -25,6 +25,8 @@
 use std::ffi::OsStr;
 use std::fmt;
 use std::fmt::Display;
+use std::fs;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -47,6 +49,7 @@ use starlark::errors::EvalMessage;
 use starlark::errors::EvalSeverity;
 use starlark::lsp;
 use starlark::read_line::ReadLine;
+use starlark::syntax::AstModule;
 use walkdir::WalkDir;
 
 use crate::eval::ContextMode;
@@ -65,6 +68,8 @@ struct Args {
             "check",
             "json",
             "docs",
+            "format",
+            "fix",
             "evaluate",
             "files",
         ],
@@ -80,6 +85,8 @@ struct Args {
             "check",
             "json",
             "docs",
+            "format",
+            "fix",
             "extension",
             "prelude",
             "evaluate",
@@ -109,6 +116,22 @@ struct Args {
     )]
     docs: Option<ArgsDoc>,
 
+    #[arg(
+        long = "format",
+        help = "Format the files in place.",
+        conflicts_with_all = &["lsp", "dap", "docs", "evaluate"],
+        requires = "files",
+    )]
+    format: bool,
+
+    #[arg(
+        long = "fix",
+        help = "Apply the automatic fixes for lints to the files in place.",
+        conflicts_with_all = &["lsp", "dap", "docs", "format", "evaluate"],
+        requires = "files",
+    )]
+    fix: bool,
+
     #[arg(
         long = "extension",
         help = "File extension when searching directories."
@@ -248,6 +271,32 @@ fn terminate_on_panic() {
     }));
 }
 
+/// Format a file in place, returning whether it changed.
+fn format_file(path: &Path) -> anyhow::Result<bool> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("reading `{}`", path.display()))?;
+    let module = AstModule::parse(&path.to_string_lossy(), contents.clone(), &eval::dialect())?;
+    let formatted = module.format();
+    if formatted == contents {
+        return Ok(false);
+    }
+    fs::write(path, formatted).with_context(|| format!("writing `{}`", path.display()))?;
+    Ok(true)
+}
+
+/// Apply the fixes for lints to a file in place, returning whether it changed.
+fn fix_file(path: &Path) -> anyhow::Result<bool> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("reading `{}`", path.display()))?;
+    let module = AstModule::parse(&path.to_string_lossy(), contents.clone(), &eval::dialect())?;
+    let fixed = module.fix(None)?;
+    if fixed == contents {
+        return Ok(false);
+    }
+    fs::write(path, fixed).with_context(|| format!("writing `{}`", path.display()))?;
+    Ok(true)
+}
+
 fn main() -> anyhow::Result<()> {
     terminate_on_panic();
 
@@ -297,6 +346,18 @@ fn main() -> anyhow::Result<()> {
                 }
                 ArgsDoc::Code => println!("{}", render_docs_as_code(&builtin)),
             };
+        } else if args.format {
+            for file in expand_dirs(ext, args.files) {
+                if format_file(&file)? {
+                    println!("Formatted {}", file.display());
+                }
+            }
+        } else if args.fix {
+            for file in expand_dirs(ext, args.files) {
+                if fix_file(&file)? {
+                    println!("Fixed {}", file.display());
+                }
+            }
         } else if is_interactive {
             interactive(&ctx)?;
         } else {
@@ -0,0 +1,192 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Applying the fixes attached to lints.
+
+use std::collections::HashSet;
+
+use crate::analysis::Lint;
+use crate::codemap::CodeMap;
+use crate::codemap::Pos;
+use crate::codemap::Span;
+use crate::syntax::AstModule;
+
+/// Fixed code is linted and fixed again at most this many times.
+const MAX_FIX_ROUNDS: usize = 10;
+
+/// The span to remove to delete the statements at `span`. If nothing else is on
+/// their lines, that is the whole lines, so no blank lines are left behind.
+pub(crate) fn whole_lines(codemap: &CodeMap, span: Span) -> Span {
+    let first_line = codemap.line_span(codemap.find_line(span.begin()));
+    if !codemap
+        .source_span(Span::new(first_line.begin(), span.begin()))
+        .trim()
+        .is_empty()
+    {
+        return span;
+    }
+    // Compound statements already end at the start of the next line.
+    let end = if codemap.source_span(span).ends_with('\n') {
+        span.end()
+    } else {
+        let last_line = codemap.line_span(codemap.find_line(span.end()));
+        if !codemap
+            .source_span(Span::new(span.end(), last_line.end()))
+            .trim()
+            .is_empty()
+        {
+            return span;
+        }
+        last_line.end()
+    };
+    Span::new(first_line.begin(), end)
+}
+
+/// Apply the fixes of `lints` to the code in `codemap`. Where fixes overlap,
+/// only the first is applied.
+fn apply_fixes(codemap: &CodeMap, lints: &[Lint]) -> String {
+    let mut fixes: Vec<_> = lints.iter().filter_map(|x| x.fix.as_ref()).collect();
+    fixes.sort_by_key(|x| (x.location.span.begin(), x.location.span.end()));
+
+    let mut res = String::new();
+    let mut last = Pos::new(0);
+    for fix in fixes {
+        let span = fix.location.span;
+        if span.begin() < last {
+            continue;
+        }
+        res.push_str(codemap.source_span(Span::new(last, span.begin())));
+        res.push_str(&fix.replacement);
+        last = span.end();
+    }
+    res.push_str(codemap.source_span(Span::new(last, codemap.full_span().end())));
+    res
+}
+
+impl AstModule {
+    /// Apply the fixes attached to the lints produced by [`lint`](AstModule::lint),
+    /// returning the fixed source code. Fixes which overlap with others are applied by
+    /// linting the fixed code again.
+    pub fn fix(&self, globals: Option<&HashSet<String>>) -> anyhow::Result<String> {
+        let mut source = self.codemap.source().to_owned();
+        let mut fixed = apply_fixes(&self.codemap, &self.lint(globals));
+        for _ in 1..MAX_FIX_ROUNDS {
+            if fixed == source {
+                break;
+            }
+            let module = AstModule::parse(self.codemap.filename(), fixed.clone(), &self.dialect)?;
+            source = fixed;
+            fixed = apply_fixes(&module.codemap, &module.lint(globals));
+        }
+        Ok(fixed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::Dialect;
+
+    fn fix(code: &str) -> String {
+        AstModule::parse("X", code.to_owned(), &Dialect::Extended)
+            .unwrap()
+            .fix(None)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_fix() {
+        assert_eq!(
+            r#"
+load("a.bzl", "used", y = "also_used")
+def f(x):
+    if type(x) == type(""):
+        return used + y
+    return 1
+"#,
+            fix(r#"
+load("a.bzl", "unused1", "used", "unused2", y = "also_used", z = "unused3")
+load("b.bzl", "unused4")
+def f(x):
+    if type(x) == str:
+        return used + y
+    return 1
+    print("unreachable")
+    x = 2
+"#)
+        );
+    }
+
+    #[test]
+    fn test_fix_load_all_unused() {
+        assert_eq!("x = 1\n", fix("load(\"a.bzl\", \"a\", \"b\")\nx = 1\n"));
+        assert_eq!(
+            "x = 1\n",
+            fix("load(\n    \"a.bzl\",\n    \"a\",\n)\nx = 1\n")
+        );
+    }
+
+    #[test]
+    fn test_fix_load_keeps_comments() {
+        assert_eq!(
+            r#"
+load(
+    "a.bzl",
+    # Comment about a.
+    "a",  # Trailing comment.
+    # About unused2.
+    "b",
+)
+x = a + b
+"#,
+            fix(r#"
+load(
+    "a.bzl",
+    # Comment about a.
+    "a",  # Trailing comment.
+    "unused1",
+    "unused2",  # About unused2.
+    "b",
+    "unused3",
+)
+x = a + b
+"#)
+        );
+        assert_eq!(
+            "load(\"a.bzl\", \"a\"  # Comment.\n     )\nx = a\n",
+            fix("load(\"a.bzl\", \"a\",  # Comment.\n     \"unused\")\nx = a\n")
+        );
+    }
+
+    #[test]
+    fn test_fix_unreachable_same_line() {
+        assert_eq!(
+            "def f():\n    return 1\n",
+            fix("def f():\n    return 1; print(1)\n")
+        );
+        assert_eq!(
+            "def f():\n    return 1\n",
+            fix("def f():\n    return 1; print(1); print(2);\n")
+        );
+    }
+
+    #[test]
+    fn test_no_fixes() {
+        let code = "def f(x):\n    return x\n";
+        assert_eq!(code, fix(code));
+    }
+}
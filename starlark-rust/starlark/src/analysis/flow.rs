@@ -17,6 +17,7 @@
 
 use thiserror::Error;
 
+use crate::analysis::fix::whole_lines;
 use crate::analysis::types::LintT;
 use crate::analysis::types::LintWarning;
 use crate::analysis::EvalSeverity;
@@ -190,6 +191,27 @@ fn stmt(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) {
     x.visit_stmt(|x| stmt(codemap, x, res));
 }
 
+/// The span to remove to delete the `unreachable` statements following `aborts`.
+/// If they share a line with `aborts`, the `;` separators around them are removed too.
+fn unreachable_span(codemap: &CodeMap, aborts: Span, unreachable: Span) -> Span {
+    let lines = whole_lines(codemap, unreachable);
+    if lines != unreachable {
+        return lines;
+    }
+    let separator = Span::new(aborts.end(), unreachable.begin());
+    if codemap.source_span(separator).contains('\n') {
+        return unreachable;
+    }
+    // A trailing `;` after the last statement, with nothing else up to the end of the line.
+    let line = codemap.line_span(codemap.find_line(unreachable.end()));
+    let rest = codemap.source_span(Span::new(unreachable.end(), line.end()));
+    let end = match rest.trim_end_matches(['\r', '\n']).trim() {
+        ";" => unreachable.end() + rest.trim_end().len() as u32,
+        _ => unreachable.end(),
+    };
+    Span::new(aborts.end(), end)
+}
+
 // Returns true if the code aborts this sequence early, due to return, fail, break or continue
 fn reachable(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) -> bool {
     match &**x {
@@ -201,11 +223,25 @@ fn reachable(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) ->
                 let aborts = reachable(codemap, x, res);
                 if aborts {
                     if let Some(nxt) = i.next() {
-                        res.push(LintT::new(
+                        // Remove everything up to the end of the block.
+                        let unreachable = unreachable_span(
                             codemap,
-                            nxt.span,
-                            FlowIssue::Unreachable(nxt.node.to_string().trim().to_owned()),
-                        ))
+                            x.span,
+                            nxt.span.merge(xs.last().unwrap().span),
+                        );
+                        res.push(
+                            LintT::new(
+                                codemap,
+                                nxt.span,
+                                FlowIssue::Unreachable(nxt.node.to_string().trim().to_owned()),
+                            )
+                            .with_fix(
+                                codemap,
+                                unreachable,
+                                "Remove unreachable code".to_owned(),
+                                String::new(),
+                            ),
+                        )
                     }
                     // All the remaining statements are totally unreachable, but we declared that once
                     // so don't even bother looking at them
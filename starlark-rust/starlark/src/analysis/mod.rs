@@ -23,12 +23,14 @@ pub use lint_message::LintMessage;
 pub use types::EvalMessage;
 pub use types::EvalSeverity;
 pub use types::Lint;
+pub use types::LintFix;
 
 use crate::analysis::types::LintT;
 use crate::syntax::AstModule;
 
 mod dubious;
 mod find_call_name;
+mod fix;
 mod flow;
 mod incompatible;
 mod lint_message;
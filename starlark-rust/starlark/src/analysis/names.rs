@@ -32,10 +32,12 @@ use dupe::Dupe;
 use maplit::hashset;
 use thiserror::Error;
 
+use crate::analysis::fix::whole_lines;
 use crate::analysis::types::LintT;
 use crate::analysis::types::LintWarning;
 use crate::analysis::EvalSeverity;
 use crate::codemap::CodeMap;
+use crate::codemap::Pos;
 use crate::codemap::Span;
 use crate::codemap::Spanned;
 use crate::syntax::ast::AstAssign;
@@ -47,6 +49,7 @@ use crate::syntax::ast::AstTypeExpr;
 use crate::syntax::ast::Clause;
 use crate::syntax::ast::Expr;
 use crate::syntax::ast::ForClause;
+use crate::syntax::ast::Load;
 use crate::syntax::ast::Stmt;
 use crate::syntax::AstModule;
 
@@ -181,6 +184,8 @@ struct State<'a> {
     warned: HashSet<AstStr<'a>>,
     /// How many nested loops we are in
     loop_depth: usize,
+    /// The `load` statements, with their spans.
+    loads: Vec<(Span, &'a Load)>,
 }
 
 impl<'a> State<'a> {
@@ -512,6 +517,7 @@ impl<'a> State<'a> {
             }
             // These were handled by collecting the scopes
             Stmt::Load(x) => {
+                self.loads.push((stmt.span, x));
                 for (ident, _) in &x.args {
                     self.set_ident(ident, Kind::Load)
                 }
@@ -543,9 +549,109 @@ pub(crate) fn lint(
         warnings: Vec::new(),
         warned: HashSet::new(),
         loop_depth: 0,
+        loads: Vec::new(),
     };
     state.module(module);
-    state.warnings
+    fix_unused_loads(&module.codemap, &state.loads, state.warnings)
+}
+
+/// Attach fixes to unused loads, removing the unused names, or the whole `load`
+/// if none of its names are used.
+fn fix_unused_loads(
+    codemap: &CodeMap,
+    loads: &[(Span, &Load)],
+    warnings: Vec<LintT<NameWarning>>,
+) -> Vec<LintT<NameWarning>> {
+    let unused: HashSet<Span> = warnings
+        .iter()
+        .filter(|x| matches!(x.problem, NameWarning::UnusedLoad(_)))
+        .map(|x| x.location.span)
+        .collect();
+
+    // For each unused name, the span to replace and its replacement.
+    let mut removals = HashMap::new();
+    for (span, load) in loads {
+        if load
+            .args
+            .iter()
+            .all(|(ident, _)| unused.contains(&ident.span))
+        {
+            let span = whole_lines(codemap, *span);
+            for (ident, _) in &load.args {
+                removals.insert(ident.span, (span, String::new()));
+            }
+        } else {
+            let mut prev_end = load.module.span.end();
+            for (i, (ident, name)) in load.args.iter().enumerate() {
+                if unused.contains(&ident.span) {
+                    let last = i + 1 == load.args.len();
+                    removals.insert(
+                        ident.span,
+                        unused_load_arg_removal(
+                            codemap,
+                            *span,
+                            prev_end,
+                            ident.span.merge(name.span),
+                            last,
+                        ),
+                    );
+                }
+                prev_end = name.span.end();
+            }
+        }
+    }
+
+    warnings
+        .into_iter()
+        .map(|x| match (&x.problem, removals.get(&x.location.span)) {
+            (NameWarning::UnusedLoad(name), Some((span, replacement))) => {
+                let title = format!("Remove unused load of `{}`", name);
+                x.with_fix(codemap, *span, title, replacement.clone())
+            }
+            _ => x,
+        })
+        .collect()
+}
+
+/// The span to replace, and its replacement, to remove the argument at `arg` from the
+/// `load` statement at `load`, where `prev_end` is the end of the previous argument.
+/// Comments around the argument are kept.
+fn unused_load_arg_removal(
+    codemap: &CodeMap,
+    load: Span,
+    prev_end: Pos,
+    arg: Span,
+    last: bool,
+) -> (Span, String) {
+    // Extend the argument over a comma directly after it, and the spaces after that.
+    let after = codemap.source_span(Span::new(arg.end(), load.end()));
+    let comma = after.trim_start_matches([' ', '\t']);
+    let with_comma = match comma.strip_prefix(',') {
+        Some(rest) => {
+            let len = after.len() - rest.trim_start_matches([' ', '\t']).len();
+            Some(Span::new(arg.begin(), arg.end() + len as u32))
+        }
+        None => None,
+    };
+
+    let extended = with_comma.unwrap_or(arg);
+    let lines = whole_lines(codemap, extended);
+    if lines != extended {
+        return (lines, String::new());
+    }
+    match with_comma {
+        Some(span) if !last => (span, String::new()),
+        _ => {
+            // Remove the comma before the argument, keeping any comments after it.
+            let gap = codemap.source_span(Span::new(prev_end, arg.begin()));
+            let replacement = if gap.contains('#') {
+                gap.replacen(',', "", 1)
+            } else {
+                String::new()
+            };
+            (Span::new(prev_end, extended.end()), replacement)
+        }
+    }
 }
 
 #[cfg(test)]
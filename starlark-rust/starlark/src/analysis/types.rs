@@ -43,6 +43,7 @@ pub(crate) struct LintT<T> {
     pub location: FileSpan,
     pub original: String,
     pub problem: T,
+    pub fix: Option<LintFix>,
 }
 
 /// A lint produced by [`AstModule::lint`](crate::syntax::AstModule::lint).
@@ -59,6 +60,19 @@ pub struct Lint {
     pub problem: String,
     /// The source code at [`location`](Lint::location).
     pub original: String,
+    /// An edit which fixes the problem, if there is an obvious one.
+    pub fix: Option<LintFix>,
+}
+
+/// A machine-applicable edit fixing a [`Lint`].
+#[derive(Debug, Clone)]
+pub struct LintFix {
+    /// A short description of the edit, shown when offering the fix.
+    pub title: String,
+    /// The code to replace, which may be larger than the code the lint refers to.
+    pub location: FileSpan,
+    /// The code to replace it with.
+    pub replacement: String,
 }
 
 impl Display for Lint {
@@ -80,9 +94,26 @@ impl<T: LintWarning> LintT<T> {
             original: location.file.source_span(span).to_owned(),
             location,
             problem,
+            fix: None,
         }
     }
 
+    /// Attach a fix which replaces the code at `span` with `replacement`.
+    pub(crate) fn with_fix(
+        mut self,
+        codemap: &CodeMap,
+        span: Span,
+        title: String,
+        replacement: String,
+    ) -> Self {
+        self.fix = Some(LintFix {
+            title,
+            location: codemap.file_span(span),
+            replacement,
+        });
+        self
+    }
+
     pub(crate) fn erase(self) -> Lint {
         Lint {
             location: self.location,
@@ -90,6 +121,7 @@ impl<T: LintWarning> LintT<T> {
             severity: self.problem.severity(),
             problem: self.problem.to_string(),
             original: self.original,
+            fix: self.fix,
         }
     }
 }
@@ -630,7 +630,7 @@ impl DocParam {
         Some(indented)
     }
 
-    fn render_as_code(&self) -> String {
+    pub(crate) fn render_as_code(&self) -> String {
         match self {
             DocParam::Arg {
                 name,
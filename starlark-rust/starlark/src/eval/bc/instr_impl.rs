@@ -1726,8 +1726,7 @@ impl InstrNoFlowImpl for InstrPossibleGcImpl {
         _ip: BcPtrAddr,
         (): &(),
     ) -> anyhow::Result<()> {
-        possible_gc(eval);
-        Ok(())
+        possible_gc(eval)
     }
 }
 
@@ -536,14 +536,19 @@ impl Compiler<'_, '_, '_> {
 //
 // We also require that `extra_v` is None, since otherwise the user might have
 // additional values stashed somewhere.
-pub(crate) fn possible_gc(eval: &mut Evaluator) {
-    if !eval.disable_gc && eval.heap().allocated_bytes() >= eval.next_gc_level {
+pub(crate) fn possible_gc(eval: &mut Evaluator) -> anyhow::Result<()> {
+    let allocated_bytes = eval.heap().allocated_bytes();
+    let over_heap_limit = eval
+        .max_heap_bytes()
+        .map_or(false, |max| allocated_bytes > max);
+    if !eval.disable_gc && (allocated_bytes >= eval.next_gc_level || over_heap_limit) {
         // When we are at a module scope (as checked above) the eval contains
         // references to all values, so walking covers everything and the unsafe
         // is satisfied.
         unsafe { eval.garbage_collect() }
         eval.next_gc_level = cmp::max(eval.heap().allocated_bytes() * 2, GC_THRESHOLD);
     }
+    eval.check_heap_limit()
 }
 
 /// Implement lhs |= rhs, which is special in Starlark, because dicts are mutated,
@@ -37,9 +37,11 @@ use crate::syntax::ast::ExprP;
 use crate::syntax::ast::StmtP;
 use crate::syntax::type_expr::TypeExprUnpackP;
 use crate::syntax::uniplate::VisitMut;
+use crate::values::typing::callable::TypingCallable;
 use crate::values::typing::type_compiled::TypeCompiled;
 use crate::values::FrozenValue;
 use crate::values::Value;
+use crate::values::ValueLike;
 
 #[derive(Debug, thiserror::Error)]
 enum TypesError {
@@ -53,10 +55,12 @@ enum TypesError {
     ModuleVariableNotSet(String),
     #[error("Type payload not set (internal error)")]
     TypePayloadNotSet,
-    #[error("[] can only be applied to list function in type expression")]
+    #[error("[] can only be applied to list or set function in type expression")]
     TypeIndexOnNonList,
     #[error("[,] can only be applied to dict function in type expression")]
     TypeIndexOnNonDict,
+    #[error("[[...], ...] can only be applied to `typing.Callable` in type expression")]
+    TypeIndexOnNonCallable,
 }
 
 impl<'v> Compiler<'v, '_, '_> {
@@ -132,17 +136,26 @@ impl<'v> Compiler<'v, '_, '_> {
 
     /// We may use non-frozen values as types, so we don't reuse `expr_ident` function
     /// which is used in normal compilation.
-    fn eval_path_as_type(
+    fn eval_path(
         &mut self,
         first: &CstIdent,
         rem: &[Spanned<&str>],
-    ) -> Result<TypeCompiled<Value<'v>>, EvalException> {
+    ) -> Result<Value<'v>, EvalException> {
         let mut value = self.eval_ident_in_type_expr(first)?;
         for step in rem {
             value = value
                 .get_attr_error(step.node, self.eval.heap())
                 .map_err(|e| EvalException::new(e, step.span, &self.codemap))?;
         }
+        Ok(value)
+    }
+
+    fn eval_path_as_type(
+        &mut self,
+        first: &CstIdent,
+        rem: &[Spanned<&str>],
+    ) -> Result<TypeCompiled<Value<'v>>, EvalException> {
+        let value = self.eval_path(first, rem)?;
         let mut span = first.span;
         if let Some(last) = rem.last() {
             span = span.merge(last.span);
@@ -158,7 +171,9 @@ impl<'v> Compiler<'v, '_, '_> {
             TypeExprUnpackP::Path(ident, rem) => self.eval_path_as_type(ident, &rem),
             TypeExprUnpackP::Index(a, i) => {
                 let a = self.eval_ident_in_type_expr(a)?;
-                if !a.ptr_eq(Constants::get().fn_list.0.to_value()) {
+                if !a.ptr_eq(Constants::get().fn_list.0.to_value())
+                    && !a.ptr_eq(Constants::get().fn_set.0.to_value())
+                {
                     return Err(EvalException::new(
                         TypesError::TypeIndexOnNonList.into(),
                         expr.span,
@@ -191,6 +206,23 @@ impl<'v> Compiler<'v, '_, '_> {
                 Ok(TypeCompiled::new(t, self.eval.heap())
                     .map_err(|e| EvalException::new(e, expr.span, &self.codemap))?)
             }
+            TypeExprUnpackP::Callable(first, rem, params, result) => {
+                let callable = self.eval_path(first, &rem)?;
+                if callable.downcast_ref::<TypingCallable>().is_none() {
+                    return Err(EvalException::new(
+                        TypesError::TypeIndexOnNonCallable.into(),
+                        expr.span,
+                        &self.codemap,
+                    ));
+                }
+                let params = params.into_try_map(|x| self.eval_expr_as_type(x))?;
+                let result = self.eval_expr_as_type(*result)?;
+                Ok(TypeCompiled::type_callable(
+                    params,
+                    result,
+                    self.eval.heap(),
+                ))
+            }
             TypeExprUnpackP::Union(xs) => {
                 let xs = xs.into_try_map(|x| self.eval_expr_as_type(x))?;
                 Ok(TypeCompiled::type_any_of(xs, self.eval.heap()))
@@ -19,6 +19,7 @@ use std::collections::HashSet;
 use std::mem;
 use std::mem::MaybeUninit;
 use std::path::Path;
+use std::time::Instant;
 
 use dupe::Dupe;
 use thiserror::Error;
@@ -51,6 +52,7 @@ use crate::eval::runtime::before_stmt::BeforeStmtFunc;
 use crate::eval::runtime::call_stack::CheapCallStack;
 use crate::eval::runtime::frame_span::FrameSpan;
 use crate::eval::runtime::inlined_frame::InlinedFrames;
+use crate::eval::runtime::limits::EvalLimits;
 use crate::eval::runtime::profile::bc::BcProfile;
 use crate::eval::runtime::profile::data::ProfileData;
 use crate::eval::runtime::profile::heap::HeapProfile;
@@ -168,7 +170,9 @@ struct EvaluationInstrumentation<'a> {
     // Extra functions to run on each statement, usually empty
     before_stmt: BeforeStmt<'a>,
     heap_or_flame_profile: bool,
-    // Whether we need to instrument evaluation or not, should be set if before_stmt or bc_profile are enabled.
+    // Limits on instructions, heap size and time, usually unset.
+    limits: EvalLimits<'a>,
+    // Whether we need to instrument evaluation or not, should be set if before_stmt, bc_profile or limits are enabled.
     enabled: bool,
 }
 
@@ -178,6 +182,7 @@ impl<'a> EvaluationInstrumentation<'a> {
             bc_profile: BcProfile::new(),
             before_stmt: BeforeStmt::default(),
             heap_or_flame_profile: false,
+            limits: EvalLimits::default(),
             enabled: false,
         }
     }
@@ -188,8 +193,10 @@ impl<'a> EvaluationInstrumentation<'a> {
 
     fn change<F: FnOnce(&mut EvaluationInstrumentation<'a>)>(&mut self, f: F) {
         f(self);
-        self.enabled =
-            self.bc_profile.enabled() || self.before_stmt.enabled() || self.heap_or_flame_profile;
+        self.enabled = self.bc_profile.enabled()
+            || self.before_stmt.enabled()
+            || self.heap_or_flame_profile
+            || self.limits.enabled();
     }
 }
 
@@ -253,6 +260,53 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.static_typechecking = enable;
     }
 
+    /// Fail the evaluation with [`EvalLimitError::Instructions`](crate::eval::EvalLimitError)
+    /// after executing `max` bytecode instructions.
+    ///
+    /// Instructions are counted over everything this evaluator runs, including
+    /// functions called from Rust.
+    pub fn set_max_instructions(&mut self, max: u64) {
+        self.eval_instrumentation
+            .change(|v| v.limits.set_max_instructions(max));
+    }
+
+    /// Fail the evaluation with [`EvalLimitError::HeapBytes`](crate::eval::EvalLimitError)
+    /// if the heap grows larger than `max` bytes.
+    ///
+    /// Garbage is collected between top level statements (unless GC is disabled),
+    /// so within a function call memory which is no longer used still counts towards the limit.
+    /// The heap size is checked periodically, not on each allocation, so a single
+    /// native function may allocate past the limit before the evaluation fails.
+    pub fn set_max_heap_bytes(&mut self, max: usize) {
+        self.eval_instrumentation
+            .change(|v| v.limits.set_max_heap_bytes(max));
+    }
+
+    /// Fail the evaluation with [`EvalLimitError::Deadline`](crate::eval::EvalLimitError)
+    /// if it is still running at `deadline`.
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        self.eval_instrumentation
+            .change(|v| v.limits.set_deadline(deadline));
+    }
+
+    /// Periodically call `cancelled` during evaluation, and fail the evaluation with
+    /// [`EvalLimitError::Cancelled`](crate::eval::EvalLimitError) once it returns `true`.
+    pub fn set_cancellation(&mut self, cancelled: &'a (dyn Fn() -> bool + 'a)) {
+        self.eval_instrumentation
+            .change(|v| v.limits.set_cancellation(cancelled));
+    }
+
+    /// The heap size limit set with [`set_max_heap_bytes`](Evaluator::set_max_heap_bytes).
+    pub(crate) fn max_heap_bytes(&self) -> Option<usize> {
+        self.eval_instrumentation.limits.max_heap_bytes()
+    }
+
+    /// Fail if the heap is larger than the limit set with
+    /// [`set_max_heap_bytes`](Evaluator::set_max_heap_bytes).
+    pub(crate) fn check_heap_limit(&self) -> anyhow::Result<()> {
+        self.eval_instrumentation.limits.check_heap(self.heap())
+    }
+
     /// Set the [`FileLoader`] used to resolve `load()` statements.
     /// A list of all load statements can be obtained through
     /// [`AstModule::loads`](crate::syntax::AstModule::loads).
@@ -780,7 +834,20 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         if self.eval_instrumentation.heap_or_flame_profile {
             self.heap_profile.record_call_enter(def, self.heap());
             self.time_flame_profile.record_call_enter(def);
-            let res = bc.run(self, &mut EvalCallbacksDisabled);
+            let res = if self.eval_instrumentation.limits.enabled() {
+                bc.run(
+                    self,
+                    &mut EvalCallbacksEnabled {
+                        bc_profile: false,
+                        before_stmt: false,
+                        limits: true,
+                        stmt_locs: &bc.instrs.stmt_locs,
+                        bc_start_ptr: bc.instrs.start_ptr(),
+                    },
+                )
+            } else {
+                bc.run(self, &mut EvalCallbacksDisabled)
+            };
             self.heap_profile.record_call_exit(self.heap());
             self.time_flame_profile.record_call_exit();
             res
@@ -790,6 +857,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
                 &mut EvalCallbacksEnabled {
                     bc_profile: self.eval_instrumentation.bc_profile.enabled(),
                     before_stmt: self.eval_instrumentation.before_stmt.enabled(),
+                    limits: self.eval_instrumentation.limits.enabled(),
                     stmt_locs: &bc.instrs.stmt_locs,
                     bc_start_ptr: bc.instrs.start_ptr(),
                 },
@@ -808,19 +876,33 @@ impl<'v, 'a> Evaluator<'v, 'a> {
 }
 
 pub(crate) trait EvaluationCallbacks {
-    fn before_instr(&mut self, _eval: &mut Evaluator, _ip: BcPtrAddr, _opcode: BcOpcode);
+    /// Called before each instruction. Fails the evaluation if it returns an error.
+    fn before_instr(
+        &mut self,
+        _eval: &mut Evaluator,
+        _ip: BcPtrAddr,
+        _opcode: BcOpcode,
+    ) -> anyhow::Result<()>;
 }
 
 pub(crate) struct EvalCallbacksDisabled;
 
 impl EvaluationCallbacks for EvalCallbacksDisabled {
     #[inline(always)]
-    fn before_instr(&mut self, _eval: &mut Evaluator, _ip: BcPtrAddr, _opcode: BcOpcode) {}
+    fn before_instr(
+        &mut self,
+        _eval: &mut Evaluator,
+        _ip: BcPtrAddr,
+        _opcode: BcOpcode,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 pub(crate) struct EvalCallbacksEnabled<'a> {
     pub(crate) bc_profile: bool,
     pub(crate) before_stmt: bool,
+    pub(crate) limits: bool,
     pub(crate) stmt_locs: &'a BcStatementLocations,
     pub(crate) bc_start_ptr: BcPtrAddr<'a>,
 }
@@ -836,13 +918,23 @@ impl<'a> EvalCallbacksEnabled<'a> {
 
 impl<'a> EvaluationCallbacks for EvalCallbacksEnabled<'a> {
     #[inline(always)]
-    fn before_instr(&mut self, eval: &mut Evaluator, ip: BcPtrAddr, opcode: BcOpcode) {
+    fn before_instr(
+        &mut self,
+        eval: &mut Evaluator,
+        ip: BcPtrAddr,
+        opcode: BcOpcode,
+    ) -> anyhow::Result<()> {
         if self.bc_profile {
             eval.eval_instrumentation.bc_profile.before_instr(opcode)
         }
         if self.before_stmt {
             self.before_stmt(eval, ip);
         }
+        if self.limits {
+            let heap = eval.heap();
+            eval.eval_instrumentation.limits.before_instr(heap)?;
+        }
+        Ok(())
     }
 }
 
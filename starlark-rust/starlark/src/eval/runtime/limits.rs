@@ -0,0 +1,261 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Limits on the resources used by an evaluation.
+
+use std::time::Instant;
+
+use thiserror::Error;
+
+use crate::errors::Diagnostic;
+use crate::values::Heap;
+
+/// Number of instructions executed between the checks which are too expensive
+/// to perform on every instruction (heap size, deadline and cancellation).
+const SLOW_CHECK_INTERVAL: u64 = 1024;
+
+/// Error produced when an evaluation exceeds one of the limits set on the
+/// [`Evaluator`](crate::eval::Evaluator).
+///
+/// Like any other evaluation error, it is reported wrapped in a [`Diagnostic`] with
+/// the call stack at the point the limit was hit. Use [`EvalLimitError::from_error`]
+/// to find out whether an error was caused by a limit.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum EvalLimitError {
+    /// More instructions were executed than allowed by
+    /// [`set_max_instructions`](crate::eval::Evaluator::set_max_instructions).
+    #[error("Evaluation exceeded the limit of {0} executed instructions")]
+    Instructions(u64),
+    /// The heap grew larger than allowed by
+    /// [`set_max_heap_bytes`](crate::eval::Evaluator::set_max_heap_bytes).
+    #[error("Evaluation exceeded the limit of {limit} heap bytes ({allocated} bytes allocated)")]
+    HeapBytes {
+        /// The limit.
+        limit: usize,
+        /// Bytes allocated when the limit was checked.
+        allocated: usize,
+    },
+    /// The deadline set by [`set_deadline`](crate::eval::Evaluator::set_deadline) has passed.
+    #[error("Evaluation did not finish before its deadline")]
+    Deadline,
+    /// The function set by [`set_cancellation`](crate::eval::Evaluator::set_cancellation)
+    /// returned `true`.
+    #[error("Evaluation was cancelled")]
+    Cancelled,
+}
+
+impl EvalLimitError {
+    /// Find the limit error in an error returned from evaluation, if the evaluation
+    /// failed because it exceeded a limit.
+    pub fn from_error(error: &anyhow::Error) -> Option<&EvalLimitError> {
+        match error.downcast_ref::<EvalLimitError>() {
+            Some(e) => Some(e),
+            None => error
+                .downcast_ref::<Diagnostic>()
+                .and_then(|d| d.message.downcast_ref::<EvalLimitError>()),
+        }
+    }
+}
+
+/// Limits set on an evaluator, and the resources used so far.
+#[derive(Default)]
+pub(crate) struct EvalLimits<'a> {
+    max_instructions: Option<u64>,
+    max_heap_bytes: Option<usize>,
+    deadline: Option<Instant>,
+    cancellation: Option<&'a (dyn Fn() -> bool + 'a)>,
+    /// Number of instructions executed so far.
+    instructions: u64,
+}
+
+impl<'a> EvalLimits<'a> {
+    pub(crate) fn enabled(&self) -> bool {
+        self.max_instructions.is_some()
+            || self.max_heap_bytes.is_some()
+            || self.deadline.is_some()
+            || self.cancellation.is_some()
+    }
+
+    pub(crate) fn set_max_instructions(&mut self, max: u64) {
+        self.max_instructions = Some(max);
+    }
+
+    pub(crate) fn set_max_heap_bytes(&mut self, max: usize) {
+        self.max_heap_bytes = Some(max);
+    }
+
+    pub(crate) fn max_heap_bytes(&self) -> Option<usize> {
+        self.max_heap_bytes
+    }
+
+    pub(crate) fn set_deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(deadline);
+    }
+
+    pub(crate) fn set_cancellation(&mut self, cancelled: &'a (dyn Fn() -> bool + 'a)) {
+        self.cancellation = Some(cancelled);
+    }
+
+    /// Called before each instruction.
+    #[inline(always)]
+    pub(crate) fn before_instr(&mut self, heap: &Heap) -> anyhow::Result<()> {
+        self.instructions += 1;
+        if let Some(max) = self.max_instructions {
+            if self.instructions > max {
+                return Err(EvalLimitError::Instructions(max).into());
+            }
+        }
+        if self.instructions % SLOW_CHECK_INTERVAL == 0 {
+            self.slow_check(heap)?;
+        }
+        Ok(())
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn slow_check(&self, heap: &Heap) -> anyhow::Result<()> {
+        self.check_heap(heap)?;
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(EvalLimitError::Deadline.into());
+            }
+        }
+        if let Some(cancelled) = self.cancellation {
+            if cancelled() {
+                return Err(EvalLimitError::Cancelled.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the heap size. Garbage is only collected between top level statements,
+    /// so inside a function call, garbage counts towards the limit too.
+    pub(crate) fn check_heap(&self, heap: &Heap) -> anyhow::Result<()> {
+        if let Some(limit) = self.max_heap_bytes {
+            let allocated = heap.allocated_bytes();
+            if allocated > limit {
+                return Err(EvalLimitError::HeapBytes { limit, allocated }.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::errors::Diagnostic;
+    use crate::eval::runtime::limits::EvalLimitError;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn eval_with<'a>(program: &str, f: impl FnOnce(&mut Evaluator<'_, 'a>)) -> anyhow::Result<()> {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        f(&mut eval);
+        let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Extended)?;
+        eval.eval_module(ast, &Globals::standard())?;
+        Ok(())
+    }
+
+    const LOOP: &str = "
+def f():
+    for x in range(1000000000):
+        pass
+f()
+";
+
+    #[test]
+    fn test_max_instructions() {
+        eval_with("x = [i for i in range(100)]", |eval| {
+            eval.set_max_instructions(100000)
+        })
+        .unwrap();
+
+        let err = eval_with(LOOP, |eval| eval.set_max_instructions(100000)).unwrap_err();
+        assert_eq!(
+            Some(&EvalLimitError::Instructions(100000)),
+            EvalLimitError::from_error(&err)
+        );
+        // The error carries the call stack at the point the limit was hit.
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!(
+            "Traceback (most recent call last):\n  * x.star:5, in <module>\n      f()\n",
+            diagnostic.call_stack.to_string()
+        );
+    }
+
+    #[test]
+    fn test_max_heap_bytes() {
+        // Garbage from top level statements is collected.
+        eval_with(&"x = list(range(1000))\n".repeat(10000), |eval| {
+            eval.set_max_heap_bytes(10_000_000)
+        })
+        .unwrap();
+
+        let err = eval_with(
+            "
+def f():
+    xs = []
+    for x in range(1000000000):
+        xs.append(str(x))
+f()
+",
+            |eval| eval.set_max_heap_bytes(10_000_000),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            EvalLimitError::from_error(&err),
+            Some(EvalLimitError::HeapBytes {
+                limit: 10_000_000,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_deadline() {
+        let err = eval_with(LOOP, |eval| {
+            eval.set_deadline(std::time::Instant::now() + Duration::from_millis(10))
+        })
+        .unwrap_err();
+        assert_eq!(
+            Some(&EvalLimitError::Deadline),
+            EvalLimitError::from_error(&err)
+        );
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let calls = Cell::new(0);
+        let cancelled = || {
+            calls.set(calls.get() + 1);
+            calls.get() > 3
+        };
+        let err = eval_with(LOOP, |eval| eval.set_cancellation(&cancelled)).unwrap_err();
+        assert_eq!(
+            Some(&EvalLimitError::Cancelled),
+            EvalLimitError::from_error(&err)
+        );
+        assert_eq!(4, calls.get());
+    }
+}
@@ -33,36 +33,48 @@ use crate::typing::Ty;
 /// Given the AST node for a `def` statement, return a `DocFunction` if the
 /// `def` statement has a docstring as its first statement.
 pub(crate) fn get_doc_item_for_def<P: AstPayload>(def: &DefP<P>) -> Option<DocFunction> {
-    if let Some(doc_string) = peek_docstring(&def.body) {
-        let args: Vec<_> = def
-            .params
-            .iter()
-            .filter_map(|param| match &param.node {
-                ParameterP::Normal(p, _)
-                | ParameterP::WithDefaultValue(p, _, _)
-                | ParameterP::Args(p, _)
-                | ParameterP::KwArgs(p, _) => Some(DocParam::Arg {
-                    name: p.0.to_owned(),
-                    docs: None,
-                    typ: Ty::any(),
-                    default_value: None,
-                }),
-                _ => None,
-            })
-            .collect();
+    peek_docstring(&def.body).map(|doc_string| doc_function_for_def(def, Some(doc_string)))
+}
 
-        let doc_function = DocFunction::from_docstring(
-            DocStringKind::Starlark,
-            args,
-            // TODO: Figure out how to get a `Ty` from the `def.return_type`.
-            Ty::any(),
-            Some(doc_string),
-            None,
-        );
-        Some(doc_function)
-    } else {
-        None
-    }
+/// Given the AST node for a `def` statement, return a `DocFunction` with its parameters,
+/// and its docs if it has a docstring.
+pub(crate) fn get_signature_for_def<P: AstPayload>(def: &DefP<P>) -> DocFunction {
+    doc_function_for_def(def, peek_docstring(&def.body))
+}
+
+fn doc_function_for_def<P: AstPayload>(def: &DefP<P>, doc_string: Option<&str>) -> DocFunction {
+    let args: Vec<_> = def
+        .params
+        .iter()
+        .map(|param| match &param.node {
+            ParameterP::Normal(p, _) | ParameterP::WithDefaultValue(p, _, _) => DocParam::Arg {
+                name: p.0.to_owned(),
+                docs: None,
+                typ: Ty::any(),
+                default_value: None,
+            },
+            ParameterP::NoArgs => DocParam::NoArgs,
+            ParameterP::Args(p, _) => DocParam::Args {
+                name: format!("*{}", p.0),
+                docs: None,
+                typ: Ty::any(),
+            },
+            ParameterP::KwArgs(p, _) => DocParam::Kwargs {
+                name: format!("**{}", p.0),
+                docs: None,
+                typ: Ty::any(),
+            },
+        })
+        .collect();
+
+    DocFunction::from_docstring(
+        DocStringKind::Starlark,
+        args,
+        // TODO: Figure out how to get a `Ty` from the `def.return_type`.
+        Ty::any(),
+        doc_string,
+        None,
+    )
 }
 
 pub(crate) fn get_doc_item_for_assign<P: AstPayload>(
@@ -0,0 +1,230 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The outline of a module: its functions, assignments and rule invocations.
+
+use lsp_types::DocumentSymbol;
+use lsp_types::SymbolKind;
+
+use crate::codemap::CodeMap;
+use crate::codemap::Span;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::Stmt;
+
+/// Get the symbols defined in `ast`, nested by the functions they are defined in.
+pub(crate) fn get_document_symbols(codemap: &CodeMap, ast: &AstStmt) -> Vec<DocumentSymbol> {
+    let mut symbols = Vec::new();
+    add_symbols(codemap, ast, &mut symbols);
+    symbols
+}
+
+fn add_symbols(codemap: &CodeMap, stmt: &AstStmt, symbols: &mut Vec<DocumentSymbol>) {
+    match &**stmt {
+        Stmt::Statements(xs) => xs.iter().for_each(|x| add_symbols(codemap, x, symbols)),
+        Stmt::If(_, body) => add_symbols(codemap, body, symbols),
+        Stmt::IfElse(_, bodies) => {
+            add_symbols(codemap, &bodies.0, symbols);
+            add_symbols(codemap, &bodies.1, symbols);
+        }
+        Stmt::For(_, body) => add_symbols(codemap, &body.1, symbols),
+        Stmt::Def(def) => {
+            let mut children: Vec<_> = def
+                .params
+                .iter()
+                .filter_map(|param| {
+                    let name = param.ident()?;
+                    Some(make_symbol(
+                        codemap,
+                        name.0.clone(),
+                        None,
+                        SymbolKind::VARIABLE,
+                        param.span,
+                        name.span,
+                        Vec::new(),
+                    ))
+                })
+                .collect();
+            add_symbols(codemap, &def.body, &mut children);
+            symbols.push(make_symbol(
+                codemap,
+                def.name.0.clone(),
+                None,
+                SymbolKind::FUNCTION,
+                stmt.span,
+                def.name.span,
+                children,
+            ));
+        }
+        Stmt::Assign(dest, rhs) => {
+            let kind = match &rhs.1.node {
+                Expr::Lambda(_) => SymbolKind::FUNCTION,
+                _ => SymbolKind::VARIABLE,
+            };
+            dest.visit_lvalue(|name| {
+                symbols.push(make_symbol(
+                    codemap,
+                    name.0.clone(),
+                    None,
+                    kind,
+                    stmt.span,
+                    name.span,
+                    Vec::new(),
+                ));
+            });
+        }
+        Stmt::Expression(expr) => {
+            // A call with a literal `name` argument, e.g. a rule creating a target.
+            if let Expr::Call(function, args) = &expr.node {
+                let target_name = args.iter().find_map(|arg| match &arg.node {
+                    ArgumentP::Named(name, value) if name.node == "name" => match &value.node {
+                        Expr::Literal(AstLiteral::String(s)) => Some((s.node.clone(), value.span)),
+                        _ => None,
+                    },
+                    _ => None,
+                });
+                if let Some((target_name, span)) = target_name {
+                    symbols.push(make_symbol(
+                        codemap,
+                        target_name,
+                        Some(codemap.source_span(function.span).to_owned()),
+                        SymbolKind::CONSTANT,
+                        stmt.span,
+                        span,
+                        Vec::new(),
+                    ));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// `DocumentSymbol` has a deprecated field, which must still be given when constructing it.
+#[allow(deprecated)]
+fn make_symbol(
+    codemap: &CodeMap,
+    name: String,
+    detail: Option<String>,
+    kind: SymbolKind,
+    range: Span,
+    selection_range: Span,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: codemap.resolve_span(range).into(),
+        selection_range: codemap.resolve_span(selection_range).into(),
+        children: if children.is_empty() {
+            None
+        } else {
+            Some(children)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use textwrap::dedent;
+
+    use super::*;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn describe(symbols: &[DocumentSymbol], depth: usize, res: &mut Vec<String>) {
+        for symbol in symbols {
+            let kind = if symbol.kind == SymbolKind::FUNCTION {
+                "function"
+            } else if symbol.kind == SymbolKind::VARIABLE {
+                "variable"
+            } else {
+                "constant"
+            };
+            let detail = match &symbol.detail {
+                Some(detail) => format!(" ({})", detail),
+                None => String::new(),
+            };
+            res.push(format!(
+                "{}{} {} {}:{}{}",
+                "  ".repeat(depth),
+                symbol.name,
+                kind,
+                symbol.selection_range.start.line,
+                symbol.selection_range.start.character,
+                detail,
+            ));
+            describe(
+                symbol.children.as_deref().unwrap_or_default(),
+                depth + 1,
+                res,
+            );
+        }
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        let module = AstModule::parse(
+            "X",
+            dedent(
+                r#"
+                load("foo.bzl", "bar")
+                x, y = 1, 2
+                f = lambda a: a
+                def rule_macro(name, *args, **kwargs):
+                    inner = name + "_inner"
+                    bar(name = inner)
+                    native.genrule(name = "fixed")
+                if x:
+                    z = 3
+                rule_macro(name = "target", srcs = [])
+                print(x)
+                "#,
+            ),
+            &Dialect::Extended,
+        )
+        .unwrap();
+
+        let mut res = Vec::new();
+        describe(
+            &get_document_symbols(&module.codemap, &module.statement),
+            0,
+            &mut res,
+        );
+        assert_eq!(
+            vec![
+                "x variable 2:0",
+                "y variable 2:3",
+                "f function 3:0",
+                "rule_macro function 4:4",
+                "  name variable 4:15",
+                "  args variable 4:22",
+                "  kwargs variable 4:30",
+                "  inner variable 5:4",
+                "  fixed constant 7:26 (native.genrule)",
+                "z variable 9:4",
+                "target constant 10:18 (rule_macro)",
+            ],
+            res
+        );
+    }
+}
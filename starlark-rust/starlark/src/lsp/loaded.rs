@@ -17,6 +17,7 @@
 
 use dupe::Dupe;
 
+use crate::codemap::Span;
 use crate::syntax::ast::StmtP;
 use crate::syntax::AstModule;
 
@@ -28,6 +29,11 @@ pub struct LoadedSymbol<'a> {
     /// The file it's loaded from. Note that this is an unresolved path, so it
     /// might be a relative load.
     pub loaded_from: &'a str,
+    /// The location of the name of the symbol in the `load()` statement.
+    pub span: Span,
+    /// The location of the name the symbol is bound to. This is the same as `span`, unless
+    /// the symbol is given another name with `local = "name"`.
+    pub local_span: Span,
 }
 
 impl AstModule {
@@ -44,6 +50,8 @@ impl AstModule {
                 l.args.iter().map(|symbol| LoadedSymbol {
                     name: &symbol.1,
                     loaded_from: &l.module,
+                    span: symbol.1.span,
+                    local_span: symbol.0.span,
                 })
             })
             .collect()
@@ -22,10 +22,13 @@ mod bind;
 pub mod completion;
 mod definition;
 pub(crate) mod docs;
+mod document_symbols;
 mod exported;
 pub(crate) mod inspect;
 pub(crate) mod loaded;
+mod references;
 pub mod server;
+mod signature;
 mod symbols;
 #[cfg(all(test, not(windows)))]
 mod test;
@@ -0,0 +1,293 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Finding all of the identifiers in a module that refer to the same variable.
+
+use crate::codemap::Span;
+use crate::lsp::bind::scope;
+use crate::lsp::bind::Assigner;
+use crate::lsp::bind::Bind;
+use crate::lsp::bind::Scope;
+use crate::lsp::definition::LspModule;
+
+/// The variable that an identifier refers to.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum Binding {
+    /// A variable bound in this module. It is identified by the location where it is first
+    /// assigned in the scope it is bound in.
+    Local {
+        span: Span,
+        assigner: Assigner,
+        /// Whether the variable is bound at the top level of the module.
+        top_level: bool,
+    },
+    /// A variable that is not bound in this module, e.g. a global symbol.
+    Global,
+}
+
+/// An identifier in a module, either getting or setting a variable.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Reference {
+    pub(crate) name: String,
+    pub(crate) span: Span,
+    pub(crate) binding: Binding,
+}
+
+impl Reference {
+    /// Whether this identifier refers to the same variable as `other`.
+    fn same_variable(&self, other: &Reference) -> bool {
+        self.name == other.name && self.binding == other.binding
+    }
+
+    /// Where the variable is first assigned, if it is bound in this module.
+    pub(crate) fn declaration(&self) -> Option<Span> {
+        match &self.binding {
+            Binding::Local { span, .. } => Some(*span),
+            Binding::Global => None,
+        }
+    }
+}
+
+/// Resolve `name` in the innermost scope of `scopes` that binds it.
+fn resolve(scopes: &[&Scope], name: &str) -> Binding {
+    scopes
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(depth, scope)| {
+            scope
+                .bound
+                .get(name)
+                .map(|(assigner, span)| Binding::Local {
+                    span: *span,
+                    assigner: assigner.clone(),
+                    top_level: depth == 0,
+                })
+        })
+        .unwrap_or(Binding::Global)
+}
+
+fn collect_references<'a>(scope: &'a Scope, scopes: &mut Vec<&'a Scope>, res: &mut Vec<Reference>) {
+    scopes.push(scope);
+    for bind in &scope.inner {
+        let (name, span) = match bind {
+            Bind::Set(_, x) => (x.0.as_str(), x.span),
+            Bind::Get(x) => (x.node.0.as_str(), x.span),
+            Bind::GetDotted(x) => (x.variable.node.0.as_str(), x.variable.span),
+            Bind::Scope(inner) => {
+                collect_references(inner, scopes, res);
+                continue;
+            }
+            Bind::Flow => continue,
+        };
+        res.push(Reference {
+            name: name.to_owned(),
+            span,
+            binding: resolve(scopes, name),
+        });
+    }
+    scopes.pop();
+}
+
+/// Resolve `name` in the scope of the identifier at `span`, if it is in `scope`.
+fn resolve_at<'a>(
+    scope: &'a Scope,
+    scopes: &mut Vec<&'a Scope>,
+    span: Span,
+    name: &str,
+) -> Option<Binding> {
+    scopes.push(scope);
+    for bind in &scope.inner {
+        let bind_span = match bind {
+            Bind::Set(_, x) => x.span,
+            Bind::Get(x) => x.span,
+            Bind::GetDotted(x) => x.variable.span,
+            Bind::Scope(inner) => match resolve_at(inner, scopes, span, name) {
+                Some(binding) => return Some(binding),
+                None => continue,
+            },
+            Bind::Flow => continue,
+        };
+        if bind_span == span {
+            return Some(resolve(scopes, name));
+        }
+    }
+    scopes.pop();
+    None
+}
+
+impl LspModule {
+    /// All of the identifiers in the module, and the variables they refer to.
+    pub(crate) fn references(&self) -> Vec<Reference> {
+        let mut res = Vec::new();
+        collect_references(&scope(&self.ast), &mut Vec::new(), &mut res);
+        res
+    }
+
+    /// Find the identifier at the given location, if there is one.
+    ///
+    /// `line` and `col` are zero based.
+    pub(crate) fn find_reference_at_location(&self, line: u32, col: u32) -> Option<Reference> {
+        let line_span = self.ast.codemap.line_span_opt(line as usize)?;
+        let pos = std::cmp::min(line_span.begin() + col, line_span.end());
+        self.references()
+            .into_iter()
+            .find(|reference| reference.span.contains(pos))
+    }
+
+    /// Find the locations of all of the identifiers that refer to the same variable as
+    /// `target`, including `target` itself.
+    pub(crate) fn find_references_to(&self, target: &Reference) -> Vec<Span> {
+        self.references()
+            .into_iter()
+            .filter(|reference| reference.same_variable(target))
+            .map(|reference| reference.span)
+            .collect()
+    }
+
+    /// The variable that `name` would refer to if used in place of the identifier at `span`.
+    pub(crate) fn resolve_at(&self, span: Span, name: &str) -> Binding {
+        resolve_at(&scope(&self.ast), &mut Vec::new(), span, name).unwrap_or(Binding::Global)
+    }
+
+    /// Find an identifier that refers to the variable `name` defined (not loaded) at the top
+    /// level of this module, if there is one.
+    pub(crate) fn find_top_level_reference(&self, name: &str) -> Option<Reference> {
+        self.references().into_iter().find(|reference| {
+            reference.name == name
+                && matches!(
+                    &reference.binding,
+                    Binding::Local {
+                        top_level: true,
+                        assigner,
+                        ..
+                    } if !matches!(assigner, Assigner::Load { .. })
+                )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use textwrap::dedent;
+
+    use super::*;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn module(program: &str) -> LspModule {
+        LspModule::new(AstModule::parse("X", dedent(program), &Dialect::Extended).unwrap())
+    }
+
+    fn source_of(module: &LspModule, spans: Vec<Span>) -> Vec<String> {
+        spans
+            .into_iter()
+            .map(|span| module.ast.codemap.resolve_span(span).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_references_respect_scopes() {
+        let module = module(
+            r#"
+            x = 1
+            def f(x):
+                return x + 1
+            def g():
+                return x.y
+            print(x, f(x))
+            "#,
+        );
+
+        // The `x` in the parameter of `f`.
+        let param = module.find_reference_at_location(2, 6).unwrap();
+        assert_eq!(
+            vec!["3:7-8", "4:12-13"],
+            source_of(&module, module.find_references_to(&param))
+        );
+
+        // The global `x`.
+        let global = module.find_reference_at_location(6, 6).unwrap();
+        assert_eq!(
+            vec!["2:1-2", "6:12-13", "7:7-8", "7:12-13"],
+            source_of(&module, module.find_references_to(&global))
+        );
+        assert_eq!(
+            vec!["2:1-2"],
+            source_of(&module, global.declaration().into_iter().collect())
+        );
+        assert_eq!(
+            global.declaration(),
+            module
+                .find_top_level_reference("x")
+                .and_then(|reference| reference.declaration())
+        );
+    }
+
+    #[test]
+    fn test_references_to_loaded_and_global_symbols() {
+        let module = module(
+            r#"
+            load("foo.bzl", "bar", baz = "qux")
+            bar(baz)
+            print(bar)
+            print(1)
+            "#,
+        );
+
+        let bar = module.find_reference_at_location(2, 0).unwrap();
+        assert!(matches!(
+            bar.binding,
+            Binding::Local {
+                assigner: Assigner::Load { .. },
+                ..
+            }
+        ));
+        assert_eq!(
+            vec!["2:17-22", "3:1-4", "4:7-10"],
+            source_of(&module, module.find_references_to(&bar))
+        );
+        assert_eq!(None, module.find_top_level_reference("bar"));
+
+        let print = module.find_reference_at_location(3, 2).unwrap();
+        assert_eq!(Binding::Global, print.binding);
+        assert_eq!(
+            vec!["4:1-6", "5:1-6"],
+            source_of(&module, module.find_references_to(&print))
+        );
+    }
+
+    #[test]
+    fn test_resolve_at() {
+        let module = module(
+            r#"
+            x = 1
+            def f(y):
+                return x + y
+            print(x)
+            "#,
+        );
+
+        let x_in_f = module.find_reference_at_location(3, 11).unwrap();
+        let print_x = module.find_reference_at_location(4, 6).unwrap();
+        let y = module.find_reference_at_location(2, 6).unwrap();
+        assert_eq!(x_in_f.binding, module.resolve_at(print_x.span, "x"));
+        assert_eq!(y.binding, module.resolve_at(x_in_f.span, "y"));
+        assert_eq!(Binding::Global, module.resolve_at(print_x.span, "y"));
+        assert_eq!(Binding::Global, module.resolve_at(x_in_f.span, "z"));
+    }
+}
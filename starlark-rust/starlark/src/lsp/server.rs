@@ -20,6 +20,7 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Debug;
+use std::iter;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -42,9 +43,22 @@ use lsp_types::notification::DidCloseTextDocument;
 use lsp_types::notification::DidOpenTextDocument;
 use lsp_types::notification::LogMessage;
 use lsp_types::notification::PublishDiagnostics;
+use lsp_types::request::CodeActionRequest;
 use lsp_types::request::Completion;
+use lsp_types::request::DocumentSymbolRequest;
+use lsp_types::request::Formatting;
 use lsp_types::request::GotoDefinition;
 use lsp_types::request::HoverRequest;
+use lsp_types::request::References;
+use lsp_types::request::Rename;
+use lsp_types::request::SignatureHelpRequest;
+use lsp_types::request::WorkspaceSymbol;
+use lsp_types::CodeAction;
+use lsp_types::CodeActionKind;
+use lsp_types::CodeActionOrCommand;
+use lsp_types::CodeActionParams;
+use lsp_types::CodeActionProviderCapability;
+use lsp_types::CodeActionResponse;
 use lsp_types::CompletionItem;
 use lsp_types::CompletionItemKind;
 use lsp_types::CompletionOptions;
@@ -55,6 +69,9 @@ use lsp_types::Diagnostic;
 use lsp_types::DidChangeTextDocumentParams;
 use lsp_types::DidCloseTextDocumentParams;
 use lsp_types::DidOpenTextDocumentParams;
+use lsp_types::DocumentFormattingParams;
+use lsp_types::DocumentSymbolParams;
+use lsp_types::DocumentSymbolResponse;
 use lsp_types::Documentation;
 use lsp_types::GotoDefinitionParams;
 use lsp_types::GotoDefinitionResponse;
@@ -64,23 +81,33 @@ use lsp_types::HoverParams;
 use lsp_types::HoverProviderCapability;
 use lsp_types::InitializeParams;
 use lsp_types::LanguageString;
+use lsp_types::Location;
 use lsp_types::LocationLink;
 use lsp_types::LogMessageParams;
 use lsp_types::MarkedString;
 use lsp_types::MarkupContent;
 use lsp_types::MarkupKind;
 use lsp_types::MessageType;
+use lsp_types::NumberOrString;
 use lsp_types::OneOf;
 use lsp_types::Position;
 use lsp_types::PublishDiagnosticsParams;
 use lsp_types::Range;
+use lsp_types::ReferenceParams;
+use lsp_types::RenameParams;
 use lsp_types::ServerCapabilities;
+use lsp_types::SignatureHelp;
+use lsp_types::SignatureHelpOptions;
+use lsp_types::SignatureHelpParams;
+use lsp_types::SymbolInformation;
 use lsp_types::TextDocumentSyncCapability;
 use lsp_types::TextDocumentSyncKind;
 use lsp_types::TextEdit;
 use lsp_types::Url;
 use lsp_types::WorkDoneProgressOptions;
+use lsp_types::WorkspaceEdit;
 use lsp_types::WorkspaceFolder;
+use lsp_types::WorkspaceSymbolParams;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Deserializer;
@@ -96,15 +123,21 @@ use crate::docs::markdown::render_doc_member;
 use crate::docs::markdown::render_doc_param;
 use crate::docs::DocMember;
 use crate::docs::DocModule;
+use crate::lsp::bind::Assigner;
 use crate::lsp::definition::Definition;
 use crate::lsp::definition::DottedDefinition;
 use crate::lsp::definition::IdentifierDefinition;
 use crate::lsp::definition::LspModule;
+use crate::lsp::document_symbols::get_document_symbols;
 use crate::lsp::inspect::AutocompleteType;
+use crate::lsp::references::Binding;
+use crate::lsp::references::Reference;
 use crate::lsp::server::LoadContentsError::WrongScheme;
+use crate::lsp::signature::signature_information;
 use crate::lsp::symbols::find_symbols_at_location;
 use crate::syntax::ast::AssignIdentP;
 use crate::syntax::ast::AstPayload;
+use crate::syntax::lexer::lex_exactly_one_identifier;
 use crate::syntax::AstModule;
 
 /// The request to get the file contents for a starlark: URI
@@ -351,6 +384,18 @@ pub trait LspContext {
         current_file: &LspUrl,
         symbol: &str,
     ) -> anyhow::Result<Option<LspUrl>>;
+
+    /// Get the files that might load the file at `uri`, to find the references to the symbols
+    /// that it exports. Files that turn out not to load it are ignored.
+    ///
+    /// The files that are open are always searched, so by default no other files are returned.
+    fn get_files_loading(
+        &self,
+        _uri: &LspUrl,
+        _workspace_root: Option<&Path>,
+    ) -> anyhow::Result<Vec<LspUrl>> {
+        Ok(Vec::new())
+    }
 }
 
 /// Errors when [`LspContext::resolve_load()`] cannot resolve a given path.
@@ -369,12 +414,79 @@ pub(crate) enum LoadContentsError {
     WrongScheme(String, LspUrl),
 }
 
+/// Errors when renaming a symbol.
+#[derive(thiserror::Error, Debug)]
+enum RenameError {
+    #[error("`{}` is not a valid identifier", .0)]
+    InvalidName(String),
+    #[error("`{}` is not defined in a starlark file, so cannot be renamed", .0)]
+    NotDefined(String),
+    #[error("`{}` is already used in `{}`", .0, .1)]
+    Clash(String, LspUrl),
+}
+
+/// How a reference to a symbol changes when the symbol is renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenameKind {
+    /// An identifier, which becomes the new name.
+    Identifier,
+    /// The name of the symbol in a `load()` statement, which is a string.
+    LoadedName,
+    /// The name of the symbol in a `load()` statement that binds it under the same name,
+    /// when only the local name changes. It gets the new name as an alias.
+    LoadedNameAlias,
+}
+
+/// The references to a symbol in one file.
+struct FileReferences {
+    uri: LspUrl,
+    module: Arc<LspModule>,
+    references: Vec<(Span, RenameKind)>,
+    /// Where the symbol is defined, if it is in this file.
+    declaration: Option<Span>,
+}
+
+impl FileReferences {
+    /// Check that renaming the symbol to `new_name` doesn't change what any identifier in this
+    /// file refers to: `new_name` must not be visible where the symbol is bound or used, and no
+    /// use of a global `new_name` may be in the scope of the symbol.
+    fn check_rename(&self, new_name: &str) -> anyhow::Result<()> {
+        let clash = || RenameError::Clash(new_name.to_owned(), self.uri.clone());
+        let references = self.module.references();
+
+        // Only identifiers are renamed, not e.g. the name of a symbol loaded under an alias.
+        let renamed: Vec<&Reference> = self
+            .references
+            .iter()
+            .filter_map(|(span, _)| references.iter().find(|r| r.span == *span))
+            .collect();
+        for reference in &renamed {
+            if self.module.resolve_at(reference.span, new_name) != Binding::Global {
+                return Err(clash().into());
+            }
+        }
+        for reference in &references {
+            if reference.name == new_name
+                && reference.binding == Binding::Global
+                && renamed.iter().any(|renamed| {
+                    self.module.resolve_at(reference.span, &renamed.name) == renamed.binding
+                })
+            {
+                return Err(clash().into());
+            }
+        }
+        Ok(())
+    }
+}
+
 pub(crate) struct Backend<T: LspContext> {
     connection: Connection,
     pub(crate) context: T,
     /// The `AstModule` from the last time that a file was opened / changed and parsed successfully.
     /// Entries are evicted when the file is closed.
     pub(crate) last_valid_parse: RwLock<HashMap<LspUrl, Arc<LspModule>>>,
+    /// The open files whose current contents don't parse, so the last valid parse is stale.
+    failed_parses: RwLock<HashSet<LspUrl>>,
 }
 
 /// The logic implementations of stuff
@@ -410,6 +522,19 @@ impl<T: LspContext> Backend<T> {
                 ..Default::default()
             }),
             hover_provider: Some(HoverProviderCapability::Simple(true)),
+            references_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Left(true)),
+            document_symbol_provider: Some(OneOf::Left(true)),
+            signature_help_provider: Some(SignatureHelpOptions {
+                trigger_characters: Some(vec!["(".to_owned(), ",".to_owned()]),
+                retrigger_characters: None,
+                work_done_progress_options: WorkDoneProgressOptions {
+                    work_done_progress: None,
+                },
+            }),
+            workspace_symbol_provider: Some(OneOf::Left(true)),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
             ..ServerCapabilities::default()
         }
     }
@@ -440,6 +565,9 @@ impl<T: LspContext> Backend<T> {
             let module = Arc::new(LspModule::new(ast));
             let mut last_valid_parse = self.last_valid_parse.write().unwrap();
             last_valid_parse.insert(uri.clone(), module);
+            self.failed_parses.write().unwrap().remove(&uri);
+        } else {
+            self.failed_parses.write().unwrap().insert(uri.clone());
         }
         self.publish_diagnostics(uri.try_into()?, eval_result.diagnostics, version);
         Ok(())
@@ -465,8 +593,10 @@ impl<T: LspContext> Backend<T> {
 
     fn did_close(&self, params: DidCloseTextDocumentParams) -> anyhow::Result<()> {
         {
+            let uri = params.text_document.uri.clone().try_into()?;
             let mut last_valid_parse = self.last_valid_parse.write().unwrap();
-            last_valid_parse.remove(&params.text_document.uri.clone().try_into()?);
+            last_valid_parse.remove(&uri);
+            self.failed_parses.write().unwrap().remove(&uri);
         }
         self.publish_diagnostics(params.text_document.uri, Vec::new(), None);
         Ok(())
@@ -508,6 +638,61 @@ impl<T: LspContext> Backend<T> {
         self.send_response(new_response(id, self.hover_info(params, initialize_params)));
     }
 
+    /// Finds the references to the symbol at the current cursor, including in the files
+    /// that load it.
+    fn references(
+        &self,
+        id: RequestId,
+        params: ReferenceParams,
+        initialize_params: &InitializeParams,
+    ) {
+        self.send_response(new_response(
+            id,
+            self.find_references(params, initialize_params),
+        ));
+    }
+
+    /// Renames the symbol at the current cursor, including in the files that load it.
+    fn rename(&self, id: RequestId, params: RenameParams, initialize_params: &InitializeParams) {
+        self.send_response(new_response(
+            id,
+            self.rename_symbol(params, initialize_params),
+        ));
+    }
+
+    /// Lists the symbols defined in a file, for showing its outline.
+    fn document_symbols(&self, id: RequestId, params: DocumentSymbolParams) {
+        self.send_response(new_response(id, self.find_document_symbols(params)));
+    }
+
+    /// Describes the signature of the function being called at the current cursor.
+    fn signature_help(
+        &self,
+        id: RequestId,
+        params: SignatureHelpParams,
+        initialize_params: &InitializeParams,
+    ) {
+        self.send_response(new_response(
+            id,
+            self.find_signature_help(params, initialize_params),
+        ));
+    }
+
+    /// Searches the symbols defined in all of the files the server has parsed.
+    fn workspace_symbols(&self, id: RequestId, params: WorkspaceSymbolParams) {
+        self.send_response(new_response(id, self.find_workspace_symbols(params)));
+    }
+
+    /// Formats a file, if its current contents parse.
+    fn formatting(&self, id: RequestId, params: DocumentFormattingParams) {
+        self.send_response(new_response(id, self.format_document(params)));
+    }
+
+    /// Offers the fixes of the lints in a range as quick fixes.
+    fn code_action(&self, id: RequestId, params: CodeActionParams) {
+        self.send_response(new_response(id, self.find_code_actions(params)));
+    }
+
     /// Get the file contents of a starlark: URI.
     fn get_starlark_file_contents(&self, id: RequestId, params: StarlarkFileContentsParams) {
         let response: anyhow::Result<_> = match params.uri {
@@ -1151,6 +1336,461 @@ impl<T: LspContext> Backend<T> {
         })
     }
 
+    fn find_references(
+        &self,
+        params: ReferenceParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        let workspace_root =
+            Self::get_workspace_root(initialize_params.workspace_folders.as_ref(), &uri);
+
+        let Some((_, files)) = self.symbol_references(
+            &uri,
+            params.text_document_position.position,
+            workspace_root.as_deref(),
+        )?
+        else {
+            return Ok(None);
+        };
+
+        let mut locations = Vec::new();
+        for file in files {
+            let url: Url = (&file.uri).try_into()?;
+            locations.extend(
+                file.references
+                    .iter()
+                    .filter(|(span, _)| {
+                        params.context.include_declaration || Some(*span) != file.declaration
+                    })
+                    .map(|(span, _)| {
+                        Location::new(
+                            url.clone(),
+                            file.module.ast.codemap.resolve_span(*span).into(),
+                        )
+                    }),
+            );
+        }
+        Ok(Some(locations))
+    }
+
+    fn rename_symbol(
+        &self,
+        params: RenameParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        let workspace_root =
+            Self::get_workspace_root(initialize_params.workspace_folders.as_ref(), &uri);
+
+        let new_name = lex_exactly_one_identifier(&params.new_name)
+            .ok_or_else(|| RenameError::InvalidName(params.new_name.clone()))?;
+        let Some((reference, files)) = self.symbol_references(
+            &uri,
+            params.text_document_position.position,
+            workspace_root.as_deref(),
+        )?
+        else {
+            return Ok(None);
+        };
+        if reference.binding == Binding::Global {
+            return Err(RenameError::NotDefined(reference.name).into());
+        }
+        if new_name != reference.name {
+            for file in &files {
+                file.check_rename(&new_name)?;
+            }
+        }
+
+        let mut changes = HashMap::new();
+        for file in files {
+            let url: Url = (&file.uri).try_into()?;
+            let edits: Vec<TextEdit> = file
+                .references
+                .iter()
+                .map(|(span, kind)| {
+                    let text = match kind {
+                        RenameKind::Identifier => new_name.clone(),
+                        RenameKind::LoadedName => format!("\"{}\"", new_name),
+                        RenameKind::LoadedNameAlias => format!(
+                            "{} = {}",
+                            new_name,
+                            file.module.ast.codemap.source_span(*span)
+                        ),
+                    };
+                    TextEdit::new(file.module.ast.codemap.resolve_span(*span).into(), text)
+                })
+                .collect();
+            changes.insert(url, edits);
+        }
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }))
+    }
+
+    /// Find the symbol at the given position, and all of the references to it.
+    ///
+    /// If the symbol is exported from the module that defines it, the references in the files
+    /// that load it are found too. Otherwise, only the references in the current file are.
+    fn symbol_references(
+        &self,
+        uri: &LspUrl,
+        position: Position,
+        workspace_root: Option<&Path>,
+    ) -> anyhow::Result<Option<(Reference, Vec<FileReferences>)>> {
+        let Some(module) = self.get_ast(uri) else {
+            return Ok(None);
+        };
+        let Some(reference) = module.find_reference_at_location(position.line, position.character)
+        else {
+            return Ok(None);
+        };
+
+        match &reference.binding {
+            // A symbol loaded under its own name, so renaming it means renaming the symbol
+            // that is loaded, if that can be found.
+            Binding::Local {
+                assigner: Assigner::Load { path, name },
+                top_level: true,
+                ..
+            } if reference.name == name.node => {
+                if let Ok(load_uri) = self.resolve_load_path(&path.node, uri, workspace_root) {
+                    if let Some(loaded) = self.get_ast_or_load_from_disk(&load_uri)? {
+                        if loaded.find_exported_symbol(&name.node).is_some() {
+                            let files = self.exported_symbol_references(
+                                &load_uri,
+                                loaded,
+                                &name.node,
+                                uri,
+                                workspace_root,
+                            )?;
+                            return Ok(Some((reference, files)));
+                        }
+                    }
+                }
+            }
+            Binding::Local {
+                assigner,
+                top_level: true,
+                ..
+            } if !matches!(assigner, Assigner::Load { .. })
+                && module.find_exported_symbol(&reference.name).is_some() =>
+            {
+                let files = self.exported_symbol_references(
+                    uri,
+                    module.dupe(),
+                    &reference.name,
+                    uri,
+                    workspace_root,
+                )?;
+                return Ok(Some((reference, files)));
+            }
+            _ => {}
+        }
+
+        let loaded_names: HashSet<Span> = module
+            .get_loaded_symbols()
+            .into_iter()
+            .filter(|symbol| symbol.span == symbol.local_span)
+            .map(|symbol| symbol.span)
+            .collect();
+        let references = module
+            .find_references_to(&reference)
+            .into_iter()
+            .map(|span| {
+                if loaded_names.contains(&span) {
+                    (span, RenameKind::LoadedNameAlias)
+                } else {
+                    (span, RenameKind::Identifier)
+                }
+            })
+            .collect();
+        let file = FileReferences {
+            uri: uri.clone(),
+            module,
+            references,
+            declaration: reference.declaration(),
+        };
+        Ok(Some((reference, vec![file])))
+    }
+
+    /// Find the references to the symbol `name` that is exported from the module at `uri`,
+    /// both in that module and in the modules that load it.
+    fn exported_symbol_references(
+        &self,
+        uri: &LspUrl,
+        module: Arc<LspModule>,
+        name: &str,
+        current_uri: &LspUrl,
+        workspace_root: Option<&Path>,
+    ) -> anyhow::Result<Vec<FileReferences>> {
+        let mut files = Vec::new();
+        if let Some(reference) = module.find_top_level_reference(name) {
+            files.push(FileReferences {
+                uri: uri.clone(),
+                references: module
+                    .find_references_to(&reference)
+                    .into_iter()
+                    .map(|span| (span, RenameKind::Identifier))
+                    .collect(),
+                declaration: reference.declaration(),
+                module,
+            });
+        }
+
+        let open_files: Vec<LspUrl> = self
+            .last_valid_parse
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        let mut seen = HashSet::new();
+        seen.insert(uri.clone());
+        let candidates = iter::once(current_uri.clone())
+            .chain(open_files)
+            .chain(self.context.get_files_loading(uri, workspace_root)?)
+            .filter(|candidate| seen.insert(candidate.clone()));
+
+        for candidate in candidates {
+            // The candidates might be anything, so skip the ones that can't be parsed.
+            let Ok(Some(candidate_module)) = self.get_ast_or_load_from_disk(&candidate) else {
+                continue;
+            };
+            let mut references = Vec::new();
+            for symbol in candidate_module.get_loaded_symbols() {
+                if symbol.name != name
+                    || !matches!(
+                        self.resolve_load_path(symbol.loaded_from, &candidate, workspace_root),
+                        Ok(loaded) if &loaded == uri
+                    )
+                {
+                    continue;
+                }
+                references.push((symbol.span, RenameKind::LoadedName));
+                // If it is bound under its own name, its uses are references too.
+                if symbol.span == symbol.local_span {
+                    if let Some(local) = candidate_module
+                        .references()
+                        .into_iter()
+                        .find(|reference| reference.span == symbol.local_span)
+                    {
+                        references.extend(
+                            candidate_module
+                                .find_references_to(&local)
+                                .into_iter()
+                                .filter(|span| *span != symbol.span)
+                                .map(|span| (span, RenameKind::Identifier)),
+                        );
+                    }
+                }
+            }
+            if !references.is_empty() {
+                files.push(FileReferences {
+                    uri: candidate,
+                    module: candidate_module,
+                    references,
+                    declaration: None,
+                });
+            }
+        }
+        Ok(files)
+    }
+
+    fn find_document_symbols(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> anyhow::Result<Option<DocumentSymbolResponse>> {
+        let uri = params.text_document.uri.try_into()?;
+        Ok(self.get_ast(&uri).map(|document| {
+            DocumentSymbolResponse::Nested(get_document_symbols(
+                &document.ast.codemap,
+                &document.ast.statement,
+            ))
+        }))
+    }
+
+    fn find_signature_help(
+        &self,
+        params: SignatureHelpParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Option<SignatureHelp>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .try_into()?;
+        let position = params.text_document_position_params.position;
+        let workspace_root =
+            Self::get_workspace_root(initialize_params.workspace_folders.as_ref(), &uri);
+
+        let Some(document) = self.get_ast(&uri) else {
+            return Ok(None);
+        };
+        let Some(call) = document.find_call_arguments_at_location(position.line, position.character)
+        else {
+            return Ok(None);
+        };
+        let function_span = document.ast.codemap.resolve_span(call.function_span);
+        let function = match document.find_definition_at_location(
+            function_span.begin_line as u32,
+            function_span.begin_column as u32,
+        ) {
+            Definition::Identifier(identifier_definition) => match identifier_definition {
+                IdentifierDefinition::Location { destination, .. } => {
+                    document.find_def_signature(destination)
+                }
+                IdentifierDefinition::LoadedLocation { path, name, .. } => {
+                    let load_uri =
+                        self.resolve_load_path(&path, &uri, workspace_root.as_deref())?;
+                    self.get_ast_or_load_from_disk(&load_uri)?
+                        .and_then(|module| {
+                            module.find_def_signature(module.find_exported_symbol_span(&name)?)
+                        })
+                }
+                IdentifierDefinition::Unresolved { name, .. } => {
+                    // Maybe it's a global function.
+                    self.context
+                        .get_environment(&uri)
+                        .members
+                        .into_iter()
+                        .find_map(|(member_name, member)| match member {
+                            DocMember::Function(doc_function) if member_name == name => {
+                                Some(doc_function)
+                            }
+                            _ => None,
+                        })
+                }
+                IdentifierDefinition::LoadPath { .. }
+                | IdentifierDefinition::StringLiteral { .. }
+                | IdentifierDefinition::NotFound => None,
+            },
+            // Methods aren't resolved to their definitions yet.
+            Definition::Dotted(_) => None,
+        };
+
+        Ok(function.map(|function| {
+            let signature = signature_information(
+                document.ast.codemap.source_span(call.function_span),
+                &function,
+                &call.active_argument,
+            );
+            SignatureHelp {
+                active_signature: Some(0),
+                active_parameter: signature.active_parameter,
+                signatures: vec![signature],
+            }
+        }))
+    }
+
+    // `SymbolInformation` has a deprecated field, which must still be given when constructing it.
+    #[allow(deprecated)]
+    fn find_workspace_symbols(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> anyhow::Result<Vec<SymbolInformation>> {
+        let query = params.query.to_lowercase();
+        let modules: Vec<_> = self
+            .last_valid_parse
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(uri, module)| (uri.clone(), module.dupe()))
+            .collect();
+
+        let mut symbols = Vec::new();
+        for (uri, module) in modules {
+            let url: Url = (&uri).try_into()?;
+            for symbol in get_document_symbols(&module.ast.codemap, &module.ast.statement) {
+                if symbol.name.to_lowercase().contains(&query) {
+                    symbols.push(SymbolInformation {
+                        name: symbol.name,
+                        kind: symbol.kind,
+                        tags: None,
+                        deprecated: None,
+                        location: Location::new(url.clone(), symbol.range),
+                        container_name: None,
+                    });
+                }
+            }
+        }
+        symbols.sort_by(|a, b| {
+            (&a.name, a.location.uri.as_str()).cmp(&(&b.name, b.location.uri.as_str()))
+        });
+        Ok(symbols)
+    }
+
+    fn format_document(
+        &self,
+        params: DocumentFormattingParams,
+    ) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri.try_into()?;
+        if self.failed_parses.read().unwrap().contains(&uri) {
+            return Ok(None);
+        }
+        Ok(self.get_ast(&uri).map(|document| {
+            let codemap = &document.ast.codemap;
+            let formatted = document.ast.format();
+            if formatted == codemap.source() {
+                Vec::new()
+            } else {
+                vec![TextEdit {
+                    range: codemap.resolve_span(codemap.full_span()).into(),
+                    new_text: formatted,
+                }]
+            }
+        }))
+    }
+
+    fn find_code_actions(
+        &self,
+        params: CodeActionParams,
+    ) -> anyhow::Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri.clone().try_into()?;
+        if self.failed_parses.read().unwrap().contains(&uri) {
+            return Ok(None);
+        }
+        Ok(self.get_ast(&uri).map(|document| {
+            document
+                .ast
+                .lint(None)
+                .into_iter()
+                .filter_map(|lint| {
+                    let fix = lint.fix?;
+                    let range: Range = lint.location.resolve_span().into();
+                    if range.end < params.range.start || params.range.end < range.start {
+                        return None;
+                    }
+                    // The diagnostics the client showed for this lint.
+                    let code = Some(NumberOrString::String(lint.short_name));
+                    let diagnostics: Vec<_> = params
+                        .context
+                        .diagnostics
+                        .iter()
+                        .filter(|x| x.range == range && x.code == code)
+                        .cloned()
+                        .collect();
+                    let edit = TextEdit {
+                        range: fix.location.resolve_span().into(),
+                        new_text: fix.replacement,
+                    };
+                    Some(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: fix.title,
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: (!diagnostics.is_empty()).then_some(diagnostics),
+                        edit: Some(WorkspaceEdit::new(HashMap::from([(
+                            params.text_document.uri.clone(),
+                            vec![edit],
+                        )]))),
+                        is_preferred: Some(true),
+                        ..CodeAction::default()
+                    }))
+                })
+                .collect()
+        }))
+    }
+
     fn get_workspace_root(
         workspace_roots: Option<&Vec<WorkspaceFolder>>,
         target: &LspUrl,
@@ -1198,8 +1838,6 @@ impl<T: LspContext> Backend<T> {
         for msg in &self.connection.receiver {
             match msg {
                 Message::Request(req) => {
-                    // TODO(nmj): Also implement DocumentSymbols so that some logic can
-                    //            be handled client side.
                     if let Some(params) = as_request::<GotoDefinition>(&req) {
                         self.goto_definition(req.id, params, &initialize_params);
                     } else if let Some(params) = as_request::<StarlarkFileContentsRequest>(&req) {
@@ -1208,6 +1846,20 @@ impl<T: LspContext> Backend<T> {
                         self.completion(req.id, params, &initialize_params);
                     } else if let Some(params) = as_request::<HoverRequest>(&req) {
                         self.hover(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<References>(&req) {
+                        self.references(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<Rename>(&req) {
+                        self.rename(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<DocumentSymbolRequest>(&req) {
+                        self.document_symbols(req.id, params);
+                    } else if let Some(params) = as_request::<SignatureHelpRequest>(&req) {
+                        self.signature_help(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<WorkspaceSymbol>(&req) {
+                        self.workspace_symbols(req.id, params);
+                    } else if let Some(params) = as_request::<Formatting>(&req) {
+                        self.formatting(req.id, params);
+                    } else if let Some(params) = as_request::<CodeActionRequest>(&req) {
+                        self.code_action(req.id, params);
                     } else if self.connection.handle_shutdown(&req)? {
                         return Ok(());
                     }
@@ -1271,6 +1923,7 @@ pub fn server_with_connection<T: LspContext>(
         connection,
         context,
         last_valid_parse: RwLock::default(),
+        failed_parses: RwLock::default(),
     }
     .main_loop(initialization_params)?;
 
@@ -1350,21 +2003,51 @@ where
 //            some paths. Revisit later.
 #[cfg(all(test, not(windows)))]
 mod test {
+    use std::collections::HashMap;
     use std::path::Path;
     use std::path::PathBuf;
 
     use anyhow::Context;
     use lsp_server::Request;
     use lsp_server::RequestId;
+    use lsp_types::request::CodeActionRequest;
+    use lsp_types::request::DocumentSymbolRequest;
+    use lsp_types::request::Formatting;
     use lsp_types::request::GotoDefinition;
+    use lsp_types::request::References;
+    use lsp_types::request::Rename;
+    use lsp_types::request::SignatureHelpRequest;
+    use lsp_types::request::WorkspaceSymbol;
+    use lsp_types::CodeAction;
+    use lsp_types::CodeActionContext;
+    use lsp_types::CodeActionKind;
+    use lsp_types::CodeActionOrCommand;
+    use lsp_types::CodeActionParams;
+    use lsp_types::DocumentFormattingParams;
+    use lsp_types::DocumentSymbolParams;
+    use lsp_types::DocumentSymbolResponse;
+    use lsp_types::Documentation;
+    use lsp_types::FormattingOptions;
     use lsp_types::GotoDefinitionParams;
     use lsp_types::GotoDefinitionResponse;
+    use lsp_types::Location;
     use lsp_types::LocationLink;
+    use lsp_types::MarkupContent;
+    use lsp_types::MarkupKind;
     use lsp_types::Position;
     use lsp_types::Range;
+    use lsp_types::ReferenceContext;
+    use lsp_types::ReferenceParams;
+    use lsp_types::RenameParams;
+    use lsp_types::SignatureHelp;
+    use lsp_types::SignatureHelpParams;
+    use lsp_types::SymbolInformation;
     use lsp_types::TextDocumentIdentifier;
     use lsp_types::TextDocumentPositionParams;
+    use lsp_types::TextEdit;
     use lsp_types::Url;
+    use lsp_types::WorkspaceEdit;
+    use lsp_types::WorkspaceSymbolParams;
     use textwrap::dedent;
 
     use crate::codemap::ResolvedSpan;
@@ -1444,6 +2127,53 @@ mod test {
         }
     }
 
+    fn references_request(server: &mut TestServer, uri: Url, line: u32, character: u32) -> Request {
+        server.new_request::<References>(ReferenceParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext {
+                include_declaration: true,
+            },
+        })
+    }
+
+    fn rename_request(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+        new_name: &str,
+    ) -> Request {
+        server.new_request::<Rename>(RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            new_name: new_name.to_owned(),
+            work_done_progress_params: Default::default(),
+        })
+    }
+
+    fn signature_help_request(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+    ) -> Request {
+        server.new_request::<SignatureHelpRequest>(SignatureHelpParams {
+            context: None,
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+        })
+    }
+
     #[cfg(windows)]
     fn temp_file_uri(rel_path: &str) -> Url {
         Url::from_file_path(&PathBuf::from("C:/tmp").join(rel_path)).unwrap()
@@ -2464,4 +3194,503 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn finds_references_in_loading_files() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+        let qux_uri = temp_file_uri("qux.star");
+
+        let foo_contents = dedent(
+            r#"
+            load("{load}", <load>"baz"</load>)
+            <call>baz</call>()
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+        let bar_contents = dedent(
+            r#"
+            def <def>baz</def>():
+                pass
+            <call>baz</call>()
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let qux_contents = dedent(
+            r#"
+            load("{load}", other = <load>"baz"</load>)
+            other()
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+        let bar = FixtureWithRanges::from_fixture(bar_uri.path(), &bar_contents)?;
+        let qux = FixtureWithRanges::from_fixture(qux_uri.path(), &qux_contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar.program())?;
+        server.set_file_contents(PathBuf::from(qux_uri.path()), qux.program())?;
+
+        let request = references_request(
+            &mut server,
+            foo_uri.clone(),
+            foo.begin_line("call"),
+            foo.begin_column("call"),
+        );
+        let request_id = server.send_request(request)?;
+        let mut locations = server.get_response::<Vec<Location>>(request_id)?;
+        locations.sort_by_key(|location| {
+            (
+                location.uri.to_string(),
+                location.range.start.line,
+                location.range.start.character,
+            )
+        });
+
+        let expected = vec![
+            Location::new(bar_uri.clone(), bar.resolved_span("def").into()),
+            Location::new(bar_uri, bar.resolved_span("call").into()),
+            Location::new(foo_uri.clone(), foo.resolved_span("load").into()),
+            Location::new(foo_uri, foo.resolved_span("call").into()),
+            Location::new(qux_uri, qux.resolved_span("load").into()),
+        ];
+        assert_eq!(expected, locations);
+        Ok(())
+    }
+
+    #[test]
+    fn renames_symbols_in_loading_files() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+        let qux_uri = temp_file_uri("qux.star");
+
+        let foo_contents = dedent(
+            r#"
+            load("{load}", <load>"baz"</load>)
+            <call>baz</call>()
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+        let bar_contents = dedent(
+            r#"
+            def <def>baz</def>():
+                pass
+            <call>baz</call>()
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let qux_contents = dedent(
+            r#"
+            load("{load}", other = <load>"baz"</load>)
+            other()
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+        let bar = FixtureWithRanges::from_fixture(bar_uri.path(), &bar_contents)?;
+        let qux = FixtureWithRanges::from_fixture(qux_uri.path(), &qux_contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        server.open_file(bar_uri.clone(), bar.program())?;
+        server.set_file_contents(PathBuf::from(qux_uri.path()), qux.program())?;
+
+        let request = rename_request(
+            &mut server,
+            bar_uri.clone(),
+            bar.begin_line("def"),
+            bar.begin_column("def"),
+            "quux",
+        );
+        let request_id = server.send_request(request)?;
+        let edit = server.get_response::<WorkspaceEdit>(request_id)?;
+
+        let expected = maplit::hashmap! {
+            bar_uri => vec![
+                TextEdit::new(bar.resolved_span("def").into(), "quux".to_owned()),
+                TextEdit::new(bar.resolved_span("call").into(), "quux".to_owned()),
+            ],
+            foo_uri => vec![
+                TextEdit::new(foo.resolved_span("load").into(), "\"quux\"".to_owned()),
+                TextEdit::new(foo.resolved_span("call").into(), "quux".to_owned()),
+            ],
+            qux_uri => vec![
+                TextEdit::new(qux.resolved_span("load").into(), "\"quux\"".to_owned()),
+            ],
+        };
+        assert_eq!(Some(expected), edit.changes);
+        Ok(())
+    }
+
+    #[test]
+    fn renames_local_symbols() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let uri = temp_file_uri("file.star");
+        let contents = dedent(
+            r#"
+            load("missing.star", <load>"foo"</load>)
+            <top>x</top> = <foo>foo</foo>()
+            def f(<param>x</param>):
+                return <x>x</x> + x
+            <print>print</print>(x)
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let fixture = FixtureWithRanges::from_fixture(uri.path(), &contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(uri.clone(), fixture.program())?;
+
+        let request = rename_request(
+            &mut server,
+            uri.clone(),
+            fixture.begin_line("x"),
+            fixture.begin_column("x"),
+            "y",
+        );
+        let request_id = server.send_request(request)?;
+        let edits = server
+            .get_response::<WorkspaceEdit>(request_id)?
+            .changes
+            .and_then(|mut changes| changes.remove(&uri))
+            .unwrap_or_default();
+        assert_eq!(3, edits.len());
+        assert_eq!(
+            TextEdit::new(fixture.resolved_span("param").into(), "y".to_owned()),
+            edits[0]
+        );
+
+        // The symbol can't be found, so it gets an alias.
+        let request = rename_request(
+            &mut server,
+            uri.clone(),
+            fixture.begin_line("foo"),
+            fixture.begin_column("foo"),
+            "bar",
+        );
+        let request_id = server.send_request(request)?;
+        let edit = server.get_response::<WorkspaceEdit>(request_id)?;
+        let expected = maplit::hashmap! {
+            uri.clone() => vec![
+                TextEdit::new(fixture.resolved_span("load").into(), "bar = \"foo\"".to_owned()),
+                TextEdit::new(fixture.resolved_span("foo").into(), "bar".to_owned()),
+            ],
+        };
+        assert_eq!(Some(expected), edit.changes);
+
+        // `f` and `foo` are visible where `x` is bound, and `print` would refer to `x` rather
+        // than the builtin.
+        for (id, new_name) in [
+            ("x", "not"),
+            ("x", "a b"),
+            ("print", "p"),
+            ("x", "f"),
+            ("param", "foo"),
+            ("top", "print"),
+        ] {
+            let request = rename_request(
+                &mut server,
+                uri.clone(),
+                fixture.begin_line(id),
+                fixture.begin_column(id),
+                new_name,
+            );
+            let request_id = server.send_request(request)?;
+            assert!(
+                server.get_response::<WorkspaceEdit>(request_id).is_err(),
+                "Renaming `{}` to `{}` should fail",
+                id,
+                new_name
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn document_and_workspace_symbols() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let uri = temp_file_uri("file.star");
+        let other_uri = temp_file_uri("other.star");
+        let contents = dedent(
+            r#"
+            def <foo>foo</foo>(a):
+                return a
+            <bar>bar</bar> = 1
+            rule(name = <target>"foo_target"</target>)
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let fixture = FixtureWithRanges::from_fixture(uri.path(), &contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(uri.clone(), fixture.program())?;
+        server.open_file(other_uri.clone(), "def other_foo():\n    pass\n".to_owned())?;
+
+        let request = server.new_request::<DocumentSymbolRequest>(DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let request_id = server.send_request(request)?;
+        let symbols = match server.get_response::<DocumentSymbolResponse>(request_id)? {
+            DocumentSymbolResponse::Nested(symbols) => symbols,
+            DocumentSymbolResponse::Flat(_) => panic!("Expected nested document symbols"),
+        };
+        assert_eq!(
+            vec![
+                ("foo", fixture.resolved_span("foo").into()),
+                ("bar", fixture.resolved_span("bar").into()),
+                ("foo_target", fixture.resolved_span("target").into()),
+            ],
+            symbols
+                .iter()
+                .map(|symbol| (symbol.name.as_str(), symbol.selection_range))
+                .collect::<Vec<(&str, Range)>>()
+        );
+        assert_eq!(
+            vec!["a"],
+            symbols[0]
+                .children
+                .iter()
+                .flatten()
+                .map(|symbol| symbol.name.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        let request = server.new_request::<WorkspaceSymbol>(WorkspaceSymbolParams {
+            query: "FOO".to_owned(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let request_id = server.send_request(request)?;
+        let symbols = server.get_response::<Vec<SymbolInformation>>(request_id)?;
+        assert_eq!(
+            vec![
+                ("foo", &uri),
+                ("foo_target", &uri),
+                ("other_foo", &other_uri)
+            ],
+            symbols
+                .iter()
+                .map(|symbol| (symbol.name.as_str(), &symbol.location.uri))
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn signature_help_for_functions() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let uri = temp_file_uri("file.star");
+        let bar_uri = temp_file_uri("bar.star");
+        let contents = dedent(
+            r#"
+            load("{load}", "foo")
+            def local(x, y):
+                return x + y
+            foo(1, <b>b</b> = 2)
+            local(1, <y>2</y>)
+            native_function1(<native>1</native>)
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+        let fixture = FixtureWithRanges::from_fixture(uri.path(), &contents)?;
+        let bar_contents = dedent(
+            r#"
+            def foo(a, b = 1, *args):
+                """Does foo."""
+                pass
+            "#,
+        )
+        .trim()
+        .to_owned();
+
+        let mut server = TestServer::new()?;
+        server.open_file(uri.clone(), fixture.program())?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar_contents)?;
+
+        let mut signature_help = |id: &str| -> anyhow::Result<SignatureHelp> {
+            let request = signature_help_request(
+                &mut server,
+                uri.clone(),
+                fixture.begin_line(id),
+                fixture.begin_column(id),
+            );
+            let request_id = server.send_request(request)?;
+            server.get_response::<SignatureHelp>(request_id)
+        };
+
+        let help = signature_help("b")?;
+        assert_eq!("foo(a, b, *args)", help.signatures[0].label);
+        assert_eq!(
+            Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: "Does foo.".to_owned(),
+            })),
+            help.signatures[0].documentation
+        );
+        assert_eq!(Some(1), help.active_parameter);
+
+        let help = signature_help("y")?;
+        assert_eq!("local(x, y)", help.signatures[0].label);
+        assert_eq!(Some(1), help.active_parameter);
+
+        let help = signature_help("native")?;
+        assert_eq!("native_function1()", help.signatures[0].label);
+        assert_eq!(None, help.active_parameter);
+        Ok(())
+    }
+
+    fn formatting_request(server: &mut TestServer, uri: Url) -> Request {
+        server.new_request::<Formatting>(DocumentFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            options: FormattingOptions {
+                tab_size: 4,
+                insert_spaces: true,
+                ..Default::default()
+            },
+            work_done_progress_params: Default::default(),
+        })
+    }
+
+    #[test]
+    fn formatting() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let uri = temp_file_uri("file.star");
+        let mut server = TestServer::new()?;
+        server.open_file(uri.clone(), "x=[1,2]\n".to_owned())?;
+
+        let request = formatting_request(&mut server, uri.clone());
+        let request_id = server.send_request(request)?;
+        assert_eq!(
+            Some(vec![TextEdit {
+                range: Range::new(Position::new(0, 0), Position::new(1, 0)),
+                new_text: "x = [1, 2]\n".to_owned(),
+            }]),
+            server.get_response::<Option<Vec<TextEdit>>>(request_id)?
+        );
+
+        // Files that don't parse aren't formatted, even though they parsed before.
+        server.change_file(uri.clone(), "x = [1,\n".to_owned())?;
+        let request = formatting_request(&mut server, uri.clone());
+        let request_id = server.send_request(request)?;
+        assert_eq!(
+            None,
+            server.get_response::<Option<Vec<TextEdit>>>(request_id)?
+        );
+
+        server.change_file(uri.clone(), "x = [1, 2]\n".to_owned())?;
+        let request = formatting_request(&mut server, uri);
+        let request_id = server.send_request(request)?;
+        assert_eq!(
+            Some(Vec::new()),
+            server.get_response::<Option<Vec<TextEdit>>>(request_id)?
+        );
+        Ok(())
+    }
+
+    fn code_action_request(server: &mut TestServer, uri: Url, range: Range) -> Request {
+        server.new_request::<CodeActionRequest>(CodeActionParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            context: CodeActionContext::default(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })
+    }
+
+    #[test]
+    fn code_actions() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let uri = temp_file_uri("file.star");
+        let mut server = TestServer::new()?;
+        let diagnostics = server.open_file_with_diagnostics(
+            uri.clone(),
+            "load(\"a.bzl\", \"a\", \"b\")\nx = b\n".to_owned(),
+        )?;
+        assert_eq!(
+            vec!["Unused `load` of `a`"],
+            diagnostics
+                .iter()
+                .map(|x| x.message.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        let at_a = Range::new(Position::new(0, 15), Position::new(0, 15));
+        let request = code_action_request(&mut server, uri.clone(), at_a);
+        let request_id = server.send_request(request)?;
+        assert_eq!(
+            Some(vec![CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Remove unused load of `a`".to_owned(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit::new(HashMap::from([(
+                    uri.clone(),
+                    vec![TextEdit {
+                        range: Range::new(Position::new(0, 14), Position::new(0, 19)),
+                        new_text: String::new(),
+                    }]
+                )]))),
+                is_preferred: Some(true),
+                ..CodeAction::default()
+            })]),
+            server.get_response::<Option<Vec<CodeActionOrCommand>>>(request_id)?
+        );
+
+        // Nothing to fix on the second line.
+        let at_x = Range::new(Position::new(1, 0), Position::new(1, 5));
+        let request = code_action_request(&mut server, uri.clone(), at_x);
+        let request_id = server.send_request(request)?;
+        assert_eq!(
+            Some(Vec::new()),
+            server.get_response::<Option<Vec<CodeActionOrCommand>>>(request_id)?
+        );
+
+        // Files that don't parse have no fixes, even though they parsed before.
+        server.change_file(uri.clone(), "load(\"a.bzl\", \"a\"\n".to_owned())?;
+        let request = code_action_request(&mut server, uri, at_a);
+        let request_id = server.send_request(request)?;
+        assert_eq!(
+            None,
+            server.get_response::<Option<Vec<CodeActionOrCommand>>>(request_id)?
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,342 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Finding the function call around a location, and describing the signature of the
+//! function being called.
+
+use lsp_types::Documentation;
+use lsp_types::MarkupContent;
+use lsp_types::MarkupKind;
+use lsp_types::ParameterInformation;
+use lsp_types::ParameterLabel;
+use lsp_types::SignatureInformation;
+
+use crate::codemap::CodeMap;
+use crate::codemap::Pos;
+use crate::codemap::ResolvedSpan;
+use crate::codemap::Span;
+use crate::docs::DocFunction;
+use crate::docs::DocParam;
+use crate::docs::DocString;
+use crate::lsp::definition::LspModule;
+use crate::lsp::docs::get_signature_for_def;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AstArgumentP;
+use crate::syntax::ast::AstExprP;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::ExprP;
+use crate::syntax::ast::Stmt;
+use crate::syntax::uniplate::Visit;
+
+/// The argument of a function call that a location is in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ActiveArgument {
+    /// The nth positional argument (zero based).
+    Positional(usize),
+    /// A named argument.
+    Named(String),
+}
+
+/// A function call whose argument list contains a location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CallArguments {
+    /// The span of the expression that is called.
+    pub(crate) function_span: Span,
+    /// The argument that the location is in.
+    pub(crate) active_argument: ActiveArgument,
+}
+
+impl LspModule {
+    /// Find the innermost function call whose argument list (i.e. the part between the
+    /// parentheses) contains the given location.
+    ///
+    /// `line` and `col` are zero based.
+    pub(crate) fn find_call_arguments_at_location(
+        &self,
+        line: u32,
+        col: u32,
+    ) -> Option<CallArguments> {
+        fn visit(
+            codemap: &CodeMap,
+            pos: Pos,
+            node: Visit<AstNoPayload>,
+            ret: &mut Option<CallArguments>,
+        ) {
+            let span = match &node {
+                Visit::Stmt(stmt) => stmt.span,
+                Visit::Expr(expr) => expr.span,
+            };
+            if !span.contains(pos) {
+                return;
+            }
+            if let Visit::Expr(AstExprP {
+                node: ExprP::Call(function, args),
+                span,
+            }) = &node
+            {
+                if function.span.end() < pos && pos < span.end() {
+                    *ret = Some(CallArguments {
+                        function_span: function.span,
+                        active_argument: active_argument(codemap, args, pos),
+                    });
+                }
+            }
+            node.visit_children(|child| visit(codemap, pos, child, ret));
+        }
+
+        let line_span = self.ast.codemap.line_span_opt(line as usize)?;
+        let pos = std::cmp::min(line_span.begin() + col, line_span.end());
+        let mut ret = None;
+        visit(
+            &self.ast.codemap,
+            pos,
+            Visit::Stmt(&self.ast.statement),
+            &mut ret,
+        );
+        ret
+    }
+
+    /// Find the signature of the function defined with a `def` statement whose name is at
+    /// `destination`.
+    pub(crate) fn find_def_signature(&self, destination: ResolvedSpan) -> Option<DocFunction> {
+        fn visit(
+            codemap: &CodeMap,
+            destination: ResolvedSpan,
+            stmt: &AstStmt,
+            ret: &mut Option<DocFunction>,
+        ) {
+            if ret.is_some() {
+                return;
+            }
+            match &**stmt {
+                Stmt::Def(def) if codemap.resolve_span(def.name.span) == destination => {
+                    *ret = Some(get_signature_for_def(def));
+                }
+                _ => stmt.visit_stmt(|x| visit(codemap, destination, x, ret)),
+            }
+        }
+
+        let mut ret = None;
+        visit(
+            &self.ast.codemap,
+            destination,
+            &self.ast.statement,
+            &mut ret,
+        );
+        ret
+    }
+}
+
+/// Work out which argument `pos` is in, by counting the commas after the arguments that
+/// end before it.
+fn active_argument(
+    codemap: &CodeMap,
+    args: &[AstArgumentP<AstNoPayload>],
+    pos: Pos,
+) -> ActiveArgument {
+    let index = args
+        .iter()
+        .take_while(|arg| {
+            arg.span.end() < pos
+                && codemap
+                    .source_span(Span::new(arg.span.end(), pos))
+                    .contains(',')
+        })
+        .count();
+    match args.get(index).map(|arg| &arg.node) {
+        Some(ArgumentP::Named(name, _)) => ActiveArgument::Named(name.node.clone()),
+        _ => ActiveArgument::Positional(
+            args[..index]
+                .iter()
+                .filter(|arg| matches!(arg.node, ArgumentP::Positional(_)))
+                .count(),
+        ),
+    }
+}
+
+/// The index of the parameter of `params` that receives `argument`, if any.
+fn active_parameter(params: &[DocParam], argument: &ActiveArgument) -> Option<usize> {
+    match argument {
+        ActiveArgument::Named(arg_name) => params
+            .iter()
+            .position(|param| matches!(param, DocParam::Arg { name, .. } if name == arg_name))
+            .or_else(|| {
+                params
+                    .iter()
+                    .position(|param| matches!(param, DocParam::Kwargs { .. }))
+            }),
+        ActiveArgument::Positional(index) => {
+            let mut positional = 0;
+            for (i, param) in params.iter().enumerate() {
+                match param {
+                    DocParam::OnlyPosBefore => {}
+                    DocParam::Arg { .. } if positional == *index => return Some(i),
+                    DocParam::Arg { .. } => positional += 1,
+                    DocParam::Args { .. } => return Some(i),
+                    DocParam::NoArgs | DocParam::Kwargs { .. } => return None,
+                }
+            }
+            None
+        }
+    }
+}
+
+fn render_doc_string(docs: &DocString) -> String {
+    match &docs.details {
+        Some(details) => format!("{}\n\n{}", docs.summary, details),
+        None => docs.summary.clone(),
+    }
+}
+
+/// Describe calling the function `name` with the signature `function`, with the cursor in
+/// `argument`.
+pub(crate) fn signature_information(
+    name: &str,
+    function: &DocFunction,
+    argument: &ActiveArgument,
+) -> SignatureInformation {
+    let params: Vec<_> = function
+        .params
+        .iter()
+        .map(DocParam::render_as_code)
+        .collect();
+    let parameters = function
+        .params
+        .iter()
+        .zip(&params)
+        .map(|(param, label)| {
+            let docs = match param {
+                DocParam::Arg { docs, .. }
+                | DocParam::Args { docs, .. }
+                | DocParam::Kwargs { docs, .. } => docs.as_ref(),
+                DocParam::NoArgs | DocParam::OnlyPosBefore => None,
+            };
+            ParameterInformation {
+                label: ParameterLabel::Simple(label.clone()),
+                documentation: docs.map(|docs| {
+                    Documentation::MarkupContent(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: render_doc_string(docs),
+                    })
+                }),
+            }
+        })
+        .collect();
+    SignatureInformation {
+        label: format!("{}({})", name, params.join(", ")),
+        documentation: function.docs.as_ref().map(|docs| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: render_doc_string(docs),
+            })
+        }),
+        parameters: Some(parameters),
+        active_parameter: active_parameter(&function.params, argument).map(|i| i as u32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use textwrap::dedent;
+
+    use super::*;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn module(program: &str) -> LspModule {
+        LspModule::new(AstModule::parse("X", dedent(program), &Dialect::Extended).unwrap())
+    }
+
+    #[test]
+    fn test_find_call_arguments() {
+        let module = module(
+            r#"
+            foo(1, bar(2), x = 3)
+            "#,
+        );
+        let find = |col| {
+            module.find_call_arguments_at_location(1, col).map(|call| {
+                (
+                    module
+                        .ast
+                        .codemap
+                        .source_span(call.function_span)
+                        .to_owned(),
+                    call.active_argument,
+                )
+            })
+        };
+
+        assert_eq!(None, find(2));
+        assert_eq!(
+            Some(("foo".to_owned(), ActiveArgument::Positional(0))),
+            find(4)
+        );
+        assert_eq!(
+            Some(("foo".to_owned(), ActiveArgument::Positional(1))),
+            find(7)
+        );
+        assert_eq!(
+            Some(("bar".to_owned(), ActiveArgument::Positional(0))),
+            find(11)
+        );
+        assert_eq!(
+            Some(("foo".to_owned(), ActiveArgument::Positional(1))),
+            find(13)
+        );
+        assert_eq!(
+            Some(("foo".to_owned(), ActiveArgument::Named("x".to_owned()))),
+            find(16)
+        );
+        assert_eq!(None, find(21));
+    }
+
+    #[test]
+    fn test_signature_information() {
+        let module = module(
+            r#"
+            def foo(a, b, *args, c = 1, **kwargs):
+                """Does foo.
+
+                Args:
+                    b: The b.
+                """
+                pass
+            "#,
+        );
+        let function = module
+            .find_def_signature(module.find_exported_symbol_span("foo").unwrap())
+            .unwrap();
+
+        let info = signature_information("foo", &function, &ActiveArgument::Positional(1));
+        assert_eq!("foo(a, b, *args, c, **kwargs)", info.label);
+        assert_eq!(Some(1), info.active_parameter);
+        assert_eq!(
+            Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: "The b.".to_owned(),
+            })),
+            info.parameters.unwrap()[1].documentation
+        );
+
+        let active = |argument| active_parameter(&function.params, &argument);
+        assert_eq!(Some(2), active(ActiveArgument::Positional(5)));
+        assert_eq!(Some(3), active(ActiveArgument::Named("c".to_owned())));
+        assert_eq!(Some(4), active(ActiveArgument::Named("d".to_owned())));
+    }
+}
@@ -41,6 +41,7 @@ use lsp_types::request::Initialize;
 use lsp_types::request::Request;
 use lsp_types::request::Shutdown;
 use lsp_types::ClientCapabilities;
+use lsp_types::Diagnostic;
 use lsp_types::DidChangeTextDocumentParams;
 use lsp_types::DidOpenTextDocumentParams;
 use lsp_types::GotoCapability;
@@ -284,6 +285,21 @@ impl LspContext for TestServerContext {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
 
+    fn get_files_loading(
+        &self,
+        _uri: &LspUrl,
+        _workspace_root: Option<&Path>,
+    ) -> anyhow::Result<Vec<LspUrl>> {
+        // All of the files might load it, the server checks which actually do.
+        Ok(self
+            .file_contents
+            .read()
+            .unwrap()
+            .keys()
+            .map(|path| LspUrl::File(path.clone()))
+            .collect())
+    }
+
     fn get_environment(&self, _uri: &LspUrl) -> DocModule {
         DocModule {
             docs: None,
@@ -609,6 +625,25 @@ impl TestServer {
     ///
     /// This will return an error if there were any diagnostic messages.
     pub fn open_file(&mut self, uri: Url, contents: String) -> anyhow::Result<()> {
+        let diagnostics = self.open_file_with_diagnostics(uri.clone(), contents)?;
+        if !diagnostics.is_empty() {
+            Err(anyhow::anyhow!(
+                "Got unexpected diagnostic messages when opening {}, got {:?}",
+                uri,
+                diagnostics
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Send a notification saying that a file was opened with the given contents,
+    /// returning the diagnostics published for it.
+    pub fn open_file_with_diagnostics(
+        &mut self,
+        uri: Url,
+        contents: String,
+    ) -> anyhow::Result<Vec<Diagnostic>> {
         let open_params = DidOpenTextDocumentParams {
             text_document: TextDocumentItem {
                 uri: uri.clone(),
@@ -626,14 +661,8 @@ impl TestServer {
                 notification.uri,
                 uri
             ))
-        } else if !notification.diagnostics.is_empty() {
-            Err(anyhow::anyhow!(
-                "Got unexpected diagnostic messages when opening {}, got {:?}",
-                uri,
-                notification.diagnostics
-            ))
         } else {
-            Ok(())
+            Ok(notification.diagnostics)
         }
     }
 
@@ -53,6 +53,8 @@ use crate::values::never::StarlarkNever;
 use crate::values::none::NoneType;
 use crate::values::num::value::NumRef;
 use crate::values::range::Range;
+use crate::values::set::value::FrozenSet;
+use crate::values::set::Set;
 use crate::values::string::repr::string_repr;
 use crate::values::string::StarlarkStr;
 use crate::values::tuple::value::FrozenTuple;
@@ -961,6 +963,37 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
         Ok(v)
     }
 
+    /// Construct a set.
+    ///
+    /// `set(x)` returns a new set containing the elements of the iterable
+    /// sequence x, in the order they are first seen. The elements must be
+    /// hashable.
+    ///
+    /// With no argument, `set()` returns a new empty set.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set() == set([])
+    /// list(set([3, 1, 3, 2])) == [3, 1, 2]
+    /// set("abc".elems()) == set(["c", "b", "a"])
+    /// set({"a": 1, "b": 2}) == set(["a", "b"])
+    /// # "#);
+    /// ```
+    #[starlark(
+        as_type = FrozenSet,
+        speculative_exec_safe,
+        special_builtin_function = SpecialBuiltinFunction::Set,
+    )]
+    fn set<'v>(
+        #[starlark(require = pos)] a: Option<ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        match a {
+            Some(a) => Set::from_iterable(a.get(), heap),
+            None => Ok(Set::default()),
+        }
+    }
+
     /// Sort a sequence
     ///
     /// `sorted(x)` returns a new list containing the elements of the iterable
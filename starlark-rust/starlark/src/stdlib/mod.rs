@@ -31,6 +31,7 @@ pub(crate) mod json;
 pub(crate) mod partial;
 
 pub(crate) mod list;
+pub(crate) mod set;
 pub(crate) mod string;
 pub(crate) mod structs;
 pub(crate) mod util;
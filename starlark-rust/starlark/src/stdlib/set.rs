@@ -0,0 +1,466 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Methods for the `set` type.
+
+use starlark_derive::starlark_module;
+
+use crate as starlark;
+use crate::environment::MethodsBuilder;
+use crate::values::none::NoneType;
+use crate::values::set::Set;
+use crate::values::set::SetMut;
+use crate::values::set::SetRef;
+use crate::values::Heap;
+use crate::values::Value;
+use crate::values::ValueError;
+
+/// Collect the elements of all of `others` into one set, before `this` is borrowed mutably,
+/// so that `x.update(x)` and friends work.
+fn union_all<'v>(others: &[Value<'v>], heap: &'v Heap) -> anyhow::Result<Set<'v>> {
+    let mut res = Set::default();
+    for other in others {
+        res = res.union(&Set::from_iterable(*other, heap)?);
+    }
+    Ok(res)
+}
+
+#[starlark_module]
+pub(crate) fn set_methods(registry: &mut MethodsBuilder) {
+    /// Add an element to a set.
+    ///
+    /// `S.add(x)` adds `x` to the set S, if it is not already present, and returns `None`.
+    /// It fails if `x` is unhashable, or the set is frozen or has active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.add(3)
+    /// x.add(1)
+    /// x == set([1, 2, 3])
+    /// # "#);
+    /// ```
+    fn add<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let mut this = SetMut::from_value(this)?;
+        this.insert_hashed(value.get_hashed()?);
+        Ok(NoneType)
+    }
+
+    /// Clear a set.
+    ///
+    /// `S.clear()` removes all the elements of the set S and returns `None`.
+    /// It fails if the set is frozen or if there are active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.clear()
+    /// x == set()
+    /// # "#);
+    /// ```
+    fn clear(this: Value) -> anyhow::Result<NoneType> {
+        let mut this = SetMut::from_value(this)?;
+        this.clear();
+        Ok(NoneType)
+    }
+
+    /// The elements of a set that are not in any of the arguments.
+    ///
+    /// `S.difference(*others)` returns a new set, containing the elements of S
+    /// that are not in any of the iterables `others`. The set operator `-` is
+    /// equivalent, but requires both of its operands to be sets.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2, 3, 4])
+    /// # (
+    /// x.difference([1], (2,)) == set([3, 4])
+    /// # and
+    /// x - set([4]) == set([1, 2, 3])
+    /// # )"#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn difference<'v>(
+        this: SetRef<'v>,
+        #[starlark(args)] others: Vec<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.difference(&union_all(&others, heap)?))
+    }
+
+    /// Remove the elements of all of the arguments from a set.
+    ///
+    /// `S.difference_update(*others)` removes from the set S every element of
+    /// the iterables `others`, and returns `None`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2, 3, 4])
+    /// x.difference_update([1], [2])
+    /// x == set([3, 4])
+    /// # "#);
+    /// ```
+    fn difference_update<'v>(
+        this: Value<'v>,
+        #[starlark(args)] others: Vec<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<NoneType> {
+        let others = union_all(&others, heap)?;
+        let mut this = SetMut::from_value(this)?;
+        for x in others.iter_hashed() {
+            this.remove_hashed(x);
+        }
+        Ok(NoneType)
+    }
+
+    /// Remove an element from a set, if it is present.
+    ///
+    /// `S.discard(x)` removes `x` from the set S, and returns `None`. Unlike
+    /// `remove`, it does not fail if `x` is not in the set.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.discard(2)
+    /// x.discard(3)
+    /// x == set([1])
+    /// # "#);
+    /// ```
+    fn discard<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let mut this = SetMut::from_value(this)?;
+        this.remove_hashed(value.get_hashed()?);
+        Ok(NoneType)
+    }
+
+    /// The elements of a set that are in all of the arguments.
+    ///
+    /// `S.intersection(*others)` returns a new set, containing the elements of S
+    /// that are also in every one of the iterables `others`. The set operator `&`
+    /// is equivalent, but requires both of its operands to be sets.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2, 3])
+    /// # (
+    /// x.intersection([2, 3, 4], (3, 2)) == set([2, 3])
+    /// # and
+    /// x & set([1, 5]) == set([1])
+    /// # )"#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn intersection<'v>(
+        this: SetRef<'v>,
+        #[starlark(args)] others: Vec<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        let mut res = this.clone();
+        for other in others {
+            res = res.intersection(&Set::from_iterable(other, heap)?);
+        }
+        Ok(res)
+    }
+
+    /// Remove the elements of a set that are not in all of the arguments.
+    ///
+    /// `S.intersection_update(*others)` removes from the set S every element that
+    /// is not in all of the iterables `others`, and returns `None`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2, 3])
+    /// x.intersection_update([2, 3, 4], [3])
+    /// x == set([3])
+    /// # "#);
+    /// ```
+    fn intersection_update<'v>(
+        this: Value<'v>,
+        #[starlark(args)] others: Vec<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<NoneType> {
+        let others = others
+            .into_iter()
+            .map(|other| Set::from_iterable(other, heap))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut this = SetMut::from_value(this)?;
+        for other in others {
+            *this = this.intersection(&other);
+        }
+        Ok(NoneType)
+    }
+
+    /// Do a set and the argument have no elements in common?
+    ///
+    /// `S.isdisjoint(other)` returns `True` if none of the elements of the
+    /// iterable `other` are in the set S.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2]).isdisjoint([3, 4])
+    /// not set([1, 2]).isdisjoint(set([2]))
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn isdisjoint<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos)] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(this.is_disjoint(&Set::from_iterable(other, heap)?))
+    }
+
+    /// Is a set contained in the argument?
+    ///
+    /// `S.issubset(other)` returns `True` if every element of the set S is in the
+    /// iterable `other`.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2]).issubset([3, 2, 1])
+    /// set().issubset(set())
+    /// not set([1, 4]).issubset([1, 2])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn issubset<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos)] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(this.is_subset(&Set::from_iterable(other, heap)?))
+    }
+
+    /// Does a set contain the argument?
+    ///
+    /// `S.issuperset(other)` returns `True` if every element of the iterable `other`
+    /// is in the set S.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2, 3]).issuperset([3, 1])
+    /// not set([1]).issuperset(set([1, 2]))
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn issuperset<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos)] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(Set::from_iterable(other, heap)?.is_subset(&this))
+    }
+
+    /// Returns and removes the first element of a set.
+    ///
+    /// `S.pop()` returns the first element of the set S in iteration order,
+    /// removing it from the set.
+    ///
+    /// `pop` fails if the set is empty, frozen, or has active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([3, 1])
+    /// # (
+    /// x.pop() == 3
+    /// # and
+    /// x.pop() == 1
+    /// # and
+    /// x == set()
+    /// # )"#);
+    /// ```
+    ///
+    /// Failure:
+    ///
+    /// ```
+    /// # starlark::assert::fail(r#"
+    /// set().pop()   # error: empty set
+    /// # "#, "empty set");
+    /// ```
+    fn pop<'v>(this: Value<'v>) -> anyhow::Result<Value<'v>> {
+        let mut this = SetMut::from_value(this)?;
+        match this.pop_first() {
+            Some(x) => Ok(x),
+            None => Err(anyhow::anyhow!("Cannot .pop() on an empty set")),
+        }
+    }
+
+    /// Remove an element from a set.
+    ///
+    /// `S.remove(x)` removes `x` from the set S, and returns `None`.
+    ///
+    /// `remove` fails if `x` is not in the set, is unhashable, or the set is
+    /// frozen or has active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.remove(2)
+    /// x == set([1])
+    /// # "#);
+    /// ```
+    ///
+    /// Failure:
+    ///
+    /// ```
+    /// # starlark::assert::fail(r#"
+    /// set([1]).remove(2)   # error: not found
+    /// # "#, "not found");
+    /// ```
+    fn remove<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let mut this = SetMut::from_value(this)?;
+        if this.remove_hashed(value.get_hashed()?) {
+            Ok(NoneType)
+        } else {
+            Err(ValueError::KeyNotFound(value.to_repr()).into())
+        }
+    }
+
+    /// The elements that are in exactly one of a set and the argument.
+    ///
+    /// `S.symmetric_difference(other)` returns a new set, containing the elements
+    /// that are in either the set S or the iterable `other`, but not both. The set
+    /// operator `^` is equivalent, but requires both of its operands to be sets.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2, 3])
+    /// # (
+    /// x.symmetric_difference([3, 4]) == set([1, 2, 4])
+    /// # and
+    /// x ^ set([1]) == set([2, 3])
+    /// # )"#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn symmetric_difference<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos)] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.symmetric_difference(&Set::from_iterable(other, heap)?))
+    }
+
+    /// Update a set to the elements that are in exactly one of it and the argument.
+    ///
+    /// `S.symmetric_difference_update(other)` makes S the symmetric difference of
+    /// S and the iterable `other`, and returns `None`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2, 3])
+    /// x.symmetric_difference_update([3, 4])
+    /// x == set([1, 2, 4])
+    /// # "#);
+    /// ```
+    fn symmetric_difference_update<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<NoneType> {
+        let other = Set::from_iterable(other, heap)?;
+        let mut this = SetMut::from_value(this)?;
+        *this = this.symmetric_difference(&other);
+        Ok(NoneType)
+    }
+
+    /// The elements that are in a set or any of the arguments.
+    ///
+    /// `S.union(*others)` returns a new set, containing the elements of S followed
+    /// by the elements of the iterables `others`, in order. The set operator `|` is
+    /// equivalent, but requires both of its operands to be sets.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// # (
+    /// list(x.union([2, 3], (4,))) == [1, 2, 3, 4]
+    /// # and
+    /// x | set([0]) == set([1, 2, 0])
+    /// # )"#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn union<'v>(
+        this: SetRef<'v>,
+        #[starlark(args)] others: Vec<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.union(&union_all(&others, heap)?))
+    }
+
+    /// Add the elements of all of the arguments to a set.
+    ///
+    /// `S.update(*others)` adds every element of the iterables `others` to the set
+    /// S, and returns `None`.
+    ///
+    /// `update` fails if any element is unhashable, or the set is frozen or has
+    /// active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1])
+    /// x.update([2, 1], (3,))
+    /// x.update(x)
+    /// list(x) == [1, 2, 3]
+    /// # "#);
+    /// ```
+    fn update<'v>(
+        this: Value<'v>,
+        #[starlark(args)] others: Vec<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<NoneType> {
+        let others = union_all(&others, heap)?;
+        let mut this = SetMut::from_value(this)?;
+        for x in others.iter_hashed() {
+            this.insert_hashed(x);
+        }
+        Ok(NoneType)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+    use crate::assert::Assert;
+
+    #[test]
+    fn test_frozen_set_is_immutable() {
+        let mut a = Assert::new();
+        a.module("m.star", "x = set([1, 2])");
+        a.fail("load('m.star', 'x')\nx.add(3)", "Immutable");
+        a.fail("load('m.star', 'x')\nx.update([3])", "Immutable");
+        a.is_true("load('m.star', 'x')\nx.union([3]) == set([1, 2, 3])");
+    }
+
+    #[test]
+    fn test_mutation_during_iteration() {
+        assert::fail(
+            r#"
+x = set([1, 2])
+for y in x:
+    x.add(y + 10)
+"#,
+            "mutate an iterable",
+        );
+    }
+}
@@ -0,0 +1,1773 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Pretty printing of Starlark modules, following the conventions of
+//! [buildifier](https://github.com/bazelbuild/buildtools/tree/master/buildifier).
+//!
+//! The comments in the source aren't part of the AST, so they are lexed again and
+//! attached to the statements, container items and operands they are next to.
+
+use std::cmp;
+use std::iter;
+
+use dupe::Dupe;
+use itertools::Itertools;
+
+use crate::codemap::CodeMap;
+use crate::codemap::Pos;
+use crate::codemap::Span;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AssignP;
+use crate::syntax::ast::AstArgument;
+use crate::syntax::ast::AstAssign;
+use crate::syntax::ast::AstAssignIdent;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstParameter;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::AstString;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::Clause;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::ForClause;
+use crate::syntax::ast::Load;
+use crate::syntax::ast::ParameterP;
+use crate::syntax::ast::Stmt;
+use crate::syntax::lexer::Lexer;
+use crate::syntax::lexer::Token;
+use crate::syntax::AstModule;
+
+/// Lines are only wrapped when they would be longer than this.
+const MAX_WIDTH: usize = 100;
+
+/// The number of spaces blocks and wrapped items are indented by.
+const INDENT: usize = 4;
+
+// How tightly expressions bind, loosest first. An expression is parenthesized when it
+// appears where an expression binding more tightly is required.
+const PREC_LAMBDA: u8 = 0;
+const PREC_IF: u8 = 1;
+const PREC_OR: u8 = 2;
+const PREC_AND: u8 = 3;
+const PREC_NOT: u8 = 4;
+const PREC_COMPARE: u8 = 5;
+const PREC_UNARY: u8 = 12;
+const PREC_PRIMARY: u8 = 13;
+const PREC_ATOM: u8 = 14;
+
+fn binop_precedence(op: BinOp) -> u8 {
+    match op {
+        BinOp::Or => PREC_OR,
+        BinOp::And => PREC_AND,
+        BinOp::Equal
+        | BinOp::NotEqual
+        | BinOp::Less
+        | BinOp::Greater
+        | BinOp::LessOrEqual
+        | BinOp::GreaterOrEqual
+        | BinOp::In
+        | BinOp::NotIn => PREC_COMPARE,
+        BinOp::BitOr => 6,
+        BinOp::BitXor => 7,
+        BinOp::BitAnd => 8,
+        BinOp::LeftShift | BinOp::RightShift => 9,
+        BinOp::Add | BinOp::Subtract => 10,
+        BinOp::Multiply | BinOp::Percent | BinOp::Divide | BinOp::FloorDivide => 11,
+    }
+}
+
+/// The precedence required of the left and right operands of `op`. Comparisons don't
+/// chain, and the other operators are left associative.
+fn operand_precedence(op: BinOp) -> (u8, u8) {
+    let prec = binop_precedence(op);
+    if prec == PREC_COMPARE {
+        (prec + 1, prec + 1)
+    } else {
+        (prec, prec + 1)
+    }
+}
+
+fn precedence(x: &Expr) -> u8 {
+    match x {
+        Expr::Lambda(_) => PREC_LAMBDA,
+        Expr::If(_) => PREC_IF,
+        Expr::Op(_, op, _) => binop_precedence(*op),
+        Expr::Not(_) => PREC_NOT,
+        Expr::Minus(_) | Expr::Plus(_) | Expr::BitNot(_) => PREC_UNARY,
+        Expr::Dot(..) | Expr::Call(..) | Expr::Index(_) | Expr::Index2(_) | Expr::Slice(..) => {
+            PREC_PRIMARY
+        }
+        _ => PREC_ATOM,
+    }
+}
+
+fn width(s: &str) -> usize {
+    s.chars().count()
+}
+
+/// Whether `s` fits in the line width, if it starts at column `col`.
+fn fits(col: usize, s: &str) -> bool {
+    let mut lines = s.split('\n');
+    let first = lines.next().unwrap_or_default();
+    col + width(first) <= MAX_WIDTH && lines.all(|line| width(line) <= MAX_WIDTH)
+}
+
+/// The column after writing `s` starting at column `col`.
+fn end_col(col: usize, s: &str) -> usize {
+    match s.rfind('\n') {
+        Some(i) => width(&s[i + 1..]),
+        None => col + width(s),
+    }
+}
+
+fn spaces(n: usize) -> String {
+    " ".repeat(n)
+}
+
+/// Whether arguments named `name` hold a list of labels that should be kept sorted.
+fn is_sorted_attribute(name: &str) -> bool {
+    name == "deps" || name.ends_with("_deps")
+}
+
+/// The order labels are sorted in, like buildifier: relative names, then local targets,
+/// then targets in the same repository, then targets in other repositories, each compared
+/// component by component.
+fn label_sort_key(label: &str) -> (u8, Vec<&str>) {
+    let phase = if label.starts_with(':') {
+        1
+    } else if label.starts_with("//") {
+        2
+    } else if label.starts_with('@') || label.contains("//") {
+        3
+    } else {
+        0
+    };
+    (phase, label.split([':', '.']).collect())
+}
+
+/// Call statements with a `name` argument are rule invocations, which are always written
+/// one argument per line.
+fn is_rule_call(args: &[AstArgument]) -> bool {
+    args.len() > 1
+        && args
+            .iter()
+            .any(|arg| matches!(&arg.node, ArgumentP::Named(name, _) if name.node == "name"))
+}
+
+fn flatten_statements<'a>(stmt: &'a AstStmt, res: &mut Vec<&'a AstStmt>) {
+    match &stmt.node {
+        Stmt::Statements(xs) => xs.iter().for_each(|x| flatten_statements(x, res)),
+        _ => res.push(stmt),
+    }
+}
+
+/// The parts of an expression to write on separate lines when it is broken: the
+/// operands of a chain of binary operators of the same precedence, the branches and
+/// condition of a conditional expression, or the whole expression otherwise.
+fn parts(x: &AstExpr) -> Vec<Part<'_>> {
+    fn operands<'a>(x: &'a AstExpr, op: BinOp, res: &mut Vec<Part<'a>>) {
+        let (lhs_prec, rhs_prec) = operand_precedence(op);
+        match &x.node {
+            Expr::Op(lhs, lhs_op, rhs) if binop_precedence(*lhs_op) == binop_precedence(op) => {
+                if lhs_prec == binop_precedence(op) {
+                    operands(lhs, *lhs_op, res);
+                } else {
+                    res.push(Part::new("", lhs, lhs_prec));
+                }
+                res.last_mut().unwrap().suffix = lhs_op.to_string().trim_end().to_owned();
+                res.push(Part::new("", rhs, rhs_prec));
+            }
+            _ => res.push(Part::new("", x, lhs_prec)),
+        }
+    }
+
+    match &x.node {
+        Expr::Op(_, op, _) => {
+            let mut res = Vec::new();
+            operands(x, *op, &mut res);
+            res
+        }
+        Expr::If(cond_then_else) => {
+            let (cond, then, els) = &**cond_then_else;
+            vec![
+                Part::new("", then, PREC_OR),
+                Part::new("if ", cond, PREC_OR),
+                Part::new("else ", els, PREC_LAMBDA),
+            ]
+        }
+        _ => vec![Part::new("", x, PREC_LAMBDA)],
+    }
+}
+
+/// Something between brackets, separated by commas.
+#[derive(Clone, Copy)]
+enum Item<'a> {
+    Expr(&'a AstExpr),
+    Entry(&'a AstExpr, &'a AstExpr),
+    Arg(&'a AstArgument),
+    Param(&'a AstParameter),
+    String(&'a AstString),
+    Load(&'a AstAssignIdent, &'a AstString),
+}
+
+impl<'a> Item<'a> {
+    fn span(&self) -> Span {
+        match self {
+            Item::Expr(x) => x.span,
+            Item::Entry(k, v) => k.span.merge(v.span),
+            Item::Arg(x) => x.span,
+            Item::Param(x) => x.span,
+            Item::String(x) => x.span,
+            Item::Load(name, module) => name.span.merge(module.span),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    None,
+    /// Sort the loaded symbols, keeping the module first.
+    Load,
+    /// Sort string literals as labels.
+    Labels,
+}
+
+/// The items between a pair of brackets.
+struct Container<'a> {
+    open: &'static str,
+    close: &'static str,
+    /// Where the items are, including any comments before the closing bracket.
+    span: Span,
+    items: Vec<Item<'a>>,
+    sort: Sort,
+    /// Always put each item on its own line.
+    force_break: bool,
+    /// Whether a single item needs a trailing comma.
+    tuple: bool,
+}
+
+/// One line of an expression broken over several lines in parentheses, such as an
+/// operand of a binary operator, with the keyword before it and the operator after it.
+struct Part<'a> {
+    prefix: &'static str,
+    expr: &'a AstExpr,
+    prec: u8,
+    suffix: String,
+}
+
+impl<'a> Part<'a> {
+    fn new(prefix: &'static str, expr: &'a AstExpr, prec: u8) -> Self {
+        Self {
+            prefix,
+            expr,
+            prec,
+            suffix: String::new(),
+        }
+    }
+}
+
+struct Comment {
+    span: Span,
+    text: String,
+}
+
+struct Formatter<'a> {
+    codemap: &'a CodeMap,
+    /// All the comments in the module, in order.
+    comments: Vec<Comment>,
+    /// Which comments have been written out already.
+    taken: Vec<bool>,
+    /// All of the comments before this one have been taken.
+    first_untaken: usize,
+    /// Where the last comment taken ends.
+    last_taken: Pos,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(codemap: &'a CodeMap, comments: Vec<Comment>) -> Self {
+        let taken = vec![false; comments.len()];
+        Self {
+            codemap,
+            comments,
+            taken,
+            first_untaken: 0,
+            last_taken: Pos::new(0),
+        }
+    }
+
+    fn source(&self, begin: Pos, end: Pos) -> &'a str {
+        self.codemap.source_span(Span::new(begin, end))
+    }
+
+    fn line_begin(&self, pos: Pos) -> Pos {
+        self.codemap.line_span(self.codemap.find_line(pos)).begin()
+    }
+
+    fn column(&self, pos: Pos) -> usize {
+        width(self.source(self.line_begin(pos), pos))
+    }
+
+    /// The index of the first untaken comment that starts at or after `pos`.
+    fn next_comment(&self, pos: Pos) -> Option<usize> {
+        let start = self
+            .comments
+            .partition_point(|c| c.span.begin() < pos)
+            .max(self.first_untaken);
+        (start..self.comments.len()).find(|i| !self.taken[*i])
+    }
+
+    fn take(&mut self, i: usize) -> String {
+        self.taken[i] = true;
+        self.last_taken = cmp::max(self.last_taken, self.comments[i].span.end());
+        while self.first_untaken < self.taken.len() && self.taken[self.first_untaken] {
+            self.first_untaken += 1;
+        }
+        format!("#{}", self.comments[i].text.trim_end())
+    }
+
+    /// Take the comments that start between `begin` and `end`.
+    fn take_comments(&mut self, begin: Pos, end: Pos) -> Vec<(Span, String)> {
+        let mut res = Vec::new();
+        while let Some(i) = self.next_comment(begin) {
+            let span = self.comments[i].span;
+            if span.begin() >= end {
+                break;
+            }
+            res.push((span, self.take(i)));
+        }
+        res
+    }
+
+    /// Take the comment that starts between `begin` and `end` on the same line as `begin`.
+    fn take_trailing_comment(&mut self, begin: Pos, end: Pos) -> Option<String> {
+        let i = self.next_comment(begin)?;
+        let span = self.comments[i].span;
+        if span.begin() < end
+            && self.codemap.find_line(span.begin()) == self.codemap.find_line(begin)
+        {
+            Some(self.take(i))
+        } else {
+            None
+        }
+    }
+
+    fn has_comments(&self, span: Span) -> bool {
+        self.next_comment(span.begin())
+            .map_or(false, |i| self.comments[i].span.begin() < span.end())
+    }
+
+    /// Whether there is nothing but whitespace and comments between `begin` and `end`.
+    fn only_comments(&self, begin: Pos, end: Pos) -> bool {
+        self.source(begin, end).lines().all(|line| {
+            let line = line.trim();
+            line.is_empty() || line.starts_with('#')
+        })
+    }
+
+    /// Where the `else` keyword between the two branches of an `if` statement is.
+    fn else_keyword(&self, then_end: Pos, else_begin: Pos) -> Pos {
+        let mut offset = 0;
+        for line in self.source(then_end, else_begin).split_inclusive('\n') {
+            let keyword = line.trim_start();
+            if keyword.starts_with("else") {
+                return then_end + (offset + line.len() - keyword.len()) as u32;
+            }
+            offset += line.len();
+        }
+        else_begin
+    }
+
+    fn has_blank_line(&self, begin: Pos, end: Pos) -> bool {
+        let lines: Vec<_> = self.source(begin, end).split('\n').collect();
+        lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|l| l.trim().is_empty())
+    }
+
+    /// Whether the source has a line break between the opening bracket at `open` and the
+    /// first item, which is kept, like buildifier does.
+    fn has_line_break(&self, open: Pos, first: Option<Span>) -> bool {
+        first.map_or(false, |first| {
+            self.source(open, first.begin()).contains('\n')
+        })
+    }
+
+    fn is_parenthesized(&self, span: Span) -> bool {
+        self.source(Pos::new(0), span.begin())
+            .trim_end()
+            .ends_with('(')
+            && self
+                .source(span.end(), self.codemap.full_span().end())
+                .trim_start()
+                .starts_with(')')
+    }
+
+    /// The end of the last token before `pos`, skipping whitespace and comments.
+    fn prev_token_end(&self, pos: Pos) -> Pos {
+        let mut end = pos;
+        loop {
+            let p = Pos::new(self.source(Pos::new(0), end).trim_end().len() as u32);
+            let i = self.comments.partition_point(|c| c.span.begin() < p);
+            match i.checked_sub(1).map(|i| self.comments[i].span) {
+                Some(comment) if comment.end() >= p => end = comment.begin(),
+                _ => return p,
+            }
+        }
+    }
+
+    /// The start of the first token at or after `pos`, skipping whitespace and comments.
+    fn next_token_begin(&self, pos: Pos) -> Pos {
+        let mut begin = pos;
+        loop {
+            let rest = self.source(begin, self.codemap.full_span().end());
+            let p = begin + (rest.len() - rest.trim_start().len()) as u32;
+            let i = self.comments.partition_point(|c| c.span.begin() < p);
+            match self.comments.get(i) {
+                Some(comment) if comment.span.begin() == p => begin = comment.span.end(),
+                _ => return p,
+            }
+        }
+    }
+
+    /// The positions of the brackets around `span`, if it is in parentheses, even with
+    /// comments between them.
+    fn parens(&self, span: Span) -> Option<(Pos, Pos)> {
+        let open = self.prev_token_end(span.begin());
+        let close = self.next_token_begin(span.end());
+        let before = self.source(Pos::new(0), open);
+        let after = self.source(close, self.codemap.full_span().end());
+        if before.ends_with('(') && after.starts_with(')') {
+            Some((Pos::new(before.len() as u32 - 1), close))
+        } else {
+            None
+        }
+    }
+
+    fn is_elif(&self, stmt: &AstStmt) -> bool {
+        matches!(stmt.node, Stmt::If(..) | Stmt::IfElse(..))
+            && self
+                .source(Pos::new(0), stmt.span.begin())
+                .trim_end()
+                .ends_with("elif")
+    }
+
+    /// Write out a block of statements, returning where the last thing written ended.
+    fn stmts(&mut self, block: &AstStmt, indent: usize, out: &mut String) -> Option<Pos> {
+        let mut stmts = Vec::new();
+        flatten_statements(block, &mut stmts);
+
+        let mut last_end = None;
+        for stmt in &stmts {
+            for (span, comment) in self.take_comments(Pos::new(0), stmt.span.begin()) {
+                self.blank_line(last_end, span.begin(), out);
+                out.push_str(&format!("{}{}\n", spaces(indent), comment));
+                last_end = Some(span.end());
+            }
+            self.blank_line(last_end, stmt.span.begin(), out);
+            self.stmt(stmt, indent, out);
+            // Compound statements end at the next statement, so include the comments
+            // at the end of the block which are indented less than it.
+            for (span, comment) in self.take_comments(stmt.span.begin(), stmt.span.end()) {
+                let line = self.codemap.find_line(span.begin());
+                if line > 0 && self.codemap.source_line(line - 1).trim().is_empty() {
+                    out.push('\n');
+                }
+                out.push_str(&format!("{}{}\n", spaces(indent), comment));
+            }
+            // Compound statements end where the next statement starts, so measure blank
+            // lines from their last token.
+            last_end = Some(cmp::max(
+                self.prev_token_end(stmt.span.end()),
+                self.last_taken,
+            ));
+        }
+
+        // Comments after the last statement belong to the block, as long as they are
+        // indented as much as it is.
+        let column = stmts
+            .first()
+            .map_or(0, |stmt| self.column(stmt.span.begin()));
+        let mut pos = last_end.unwrap_or(Pos::new(0));
+        while let Some(i) = self.next_comment(pos) {
+            let span = self.comments[i].span;
+            if !self.only_comments(pos, span.begin()) || self.column(span.begin()) < column {
+                break;
+            }
+            self.blank_line(last_end, span.begin(), out);
+            let comment = self.take(i);
+            out.push_str(&format!("{}{}\n", spaces(indent), comment));
+            last_end = Some(span.end());
+            pos = span.end();
+        }
+        last_end
+    }
+
+    /// Keep a single blank line between things that had blank lines between them.
+    fn blank_line(&self, last_end: Option<Pos>, begin: Pos, out: &mut String) {
+        if let Some(last_end) = last_end {
+            if self.has_blank_line(last_end, begin) {
+                out.push('\n');
+            }
+        }
+    }
+
+    /// End the line that finished at `pos`, along with its trailing comment.
+    fn end_line(&mut self, pos: Pos, before: Pos, out: &mut String) {
+        if let Some(comment) = self.take_trailing_comment(pos, before) {
+            out.push_str("  ");
+            out.push_str(&comment);
+        }
+        out.push('\n');
+    }
+
+    fn stmt(&mut self, stmt: &AstStmt, indent: usize, out: &mut String) {
+        let text = match &stmt.node {
+            Stmt::Statements(_) => {
+                self.stmts(stmt, indent, out);
+                return;
+            }
+            Stmt::Break => "break".to_owned(),
+            Stmt::Continue => "continue".to_owned(),
+            Stmt::Pass => "pass".to_owned(),
+            Stmt::Return(None) => "return".to_owned(),
+            Stmt::Return(Some(x)) => format!("return {}", self.top_expr(x, indent, indent + 7)),
+            Stmt::Expression(x) => match &x.node {
+                Expr::Call(f, args) if is_rule_call(args) => {
+                    self.call(x, f, args, indent, indent, true)
+                }
+                _ => self.top_expr(x, indent, indent),
+            },
+            Stmt::Assign(lhs, ty_rhs) => {
+                let (ty, rhs) = &**ty_rhs;
+                let mut s = self.assign_target(lhs, true);
+                if let Some(ty) = ty {
+                    s.push_str(": ");
+                    s += &self.expr(&ty.node.expr, indent, end_col(indent, &s), PREC_LAMBDA);
+                }
+                s.push_str(" = ");
+                s += &self.top_expr(rhs, indent, end_col(indent, &s));
+                s
+            }
+            Stmt::AssignModify(lhs, op, rhs) => {
+                let mut s = format!("{}{}", self.assign_target(lhs, true), op);
+                s += &self.expr(rhs, indent, end_col(indent, &s), PREC_LAMBDA);
+                s
+            }
+            Stmt::Load(load) => self.load(load, stmt.span, indent),
+            Stmt::If(cond, then) => return self.if_stmt("if", cond, then, None, indent, out),
+            Stmt::IfElse(cond, then_else) => {
+                let (then, els) = &**then_else;
+                return self.if_stmt("if", cond, then, Some(els), indent, out);
+            }
+            Stmt::For(var, over_body) => {
+                let (over, body) = &**over_body;
+                let mut header = format!("for {} in ", self.assign_target(var, true));
+                header += &self.expr(over, indent, end_col(indent, &header), PREC_LAMBDA);
+                header.push(':');
+                return self.block(&header, over.span.end(), body, indent, out);
+            }
+            Stmt::Def(def) => {
+                let header = self.def_header(def, indent);
+                return self.block(&header, def.signature_span().end(), &def.body, indent, out);
+            }
+        };
+        out.push_str(&spaces(indent));
+        out.push_str(&text);
+        let line_end = self
+            .codemap
+            .line_span(self.codemap.find_line(stmt.span.end()))
+            .end();
+        self.end_line(stmt.span.end(), line_end, out);
+    }
+
+    /// Write the header of a compound statement, and its body.
+    fn block(
+        &mut self,
+        header: &str,
+        header_end: Pos,
+        body: &AstStmt,
+        indent: usize,
+        out: &mut String,
+    ) {
+        out.push_str(&spaces(indent));
+        out.push_str(header);
+        self.end_line(header_end, body.span.begin(), out);
+        self.stmts(body, indent + INDENT, out);
+    }
+
+    fn if_stmt(
+        &mut self,
+        keyword: &str,
+        cond: &AstExpr,
+        then: &AstStmt,
+        els: Option<&AstStmt>,
+        indent: usize,
+        out: &mut String,
+    ) {
+        let mut header = format!("{} ", keyword);
+        header += &self.expr(cond, indent, end_col(indent, &header), PREC_LAMBDA);
+        header.push(':');
+        self.block(&header, cond.span.end(), then, indent, out);
+        if let Some(els) = els {
+            if self.is_elif(els) {
+                // Comments before the `elif` go on their own lines, before it.
+                for (_, comment) in self.take_comments(then.span.end(), els.span.begin()) {
+                    out.push_str(&format!("{}{}\n", spaces(indent), comment));
+                }
+                match &els.node {
+                    Stmt::If(cond, then) => {
+                        return self.if_stmt("elif", cond, then, None, indent, out);
+                    }
+                    Stmt::IfElse(cond, then_else) => {
+                        let (then, els) = &**then_else;
+                        return self.if_stmt("elif", cond, then, Some(els), indent, out);
+                    }
+                    _ => {}
+                }
+            }
+            let keyword = self.else_keyword(then.span.end(), els.span.begin());
+            for (_, comment) in self.take_comments(then.span.end(), keyword) {
+                out.push_str(&format!("{}{}\n", spaces(indent), comment));
+            }
+            self.block("else:", keyword, els, indent, out);
+        }
+    }
+
+    fn def_header(&mut self, def: &DefP<AstNoPayload>, indent: usize) -> String {
+        let mut s = format!("def {}", def.name.node.0);
+        let params_end = def
+            .params
+            .last()
+            .map_or(def.name.span.end(), |param| param.span.end());
+        let params = Container {
+            open: "(",
+            close: ")",
+            span: Span::new(def.name.span.end(), params_end),
+            items: def.params.iter().map(Item::Param).collect(),
+            sort: Sort::None,
+            force_break: false,
+            tuple: false,
+        };
+        s += &self.container(params, indent, end_col(indent, &s));
+        if let Some(return_type) = &def.return_type {
+            s.push_str(" -> ");
+            s += &self.expr(
+                &return_type.node.expr,
+                indent,
+                end_col(indent, &s),
+                PREC_LAMBDA,
+            );
+        }
+        s.push(':');
+        s
+    }
+
+    fn load(&mut self, load: &Load, span: Span, indent: usize) -> String {
+        let args = Container {
+            open: "(",
+            close: ")",
+            span: Span::new(load.module.span.begin(), span.end()),
+            items: iter::once(Item::String(&load.module))
+                .chain(
+                    load.args
+                        .iter()
+                        .map(|(name, symbol)| Item::Load(name, symbol)),
+                )
+                .collect(),
+            sort: Sort::Load,
+            force_break: false,
+            tuple: false,
+        };
+        format!("load{}", self.container(args, indent, indent + 4))
+    }
+
+    fn assign_target(&self, x: &AstAssign, top_level: bool) -> String {
+        match &x.node {
+            AssignP::Tuple(xs) => {
+                let mut s = xs.iter().map(|x| self.assign_target(x, false)).join(", ");
+                if xs.len() == 1 {
+                    s.push(',');
+                }
+                if top_level { s } else { format!("({})", s) }
+            }
+            AssignP::Index(array_index) => {
+                let (array, index) = &**array_index;
+                format!(
+                    "{}[{}]",
+                    self.flat_expr(array, PREC_PRIMARY),
+                    self.flat_expr(index, PREC_LAMBDA)
+                )
+            }
+            AssignP::Dot(object, field) => {
+                format!("{}.{}", self.flat_expr(object, PREC_PRIMARY), field.node)
+            }
+            AssignP::Identifier(name) => name.node.0.clone(),
+        }
+    }
+
+    /// Write an expression at the top level of a statement, where tuples don't need to be
+    /// parenthesized.
+    fn top_expr(&mut self, x: &AstExpr, indent: usize, col: usize) -> String {
+        if let Expr::Tuple(xs) = &x.node {
+            if !xs.is_empty() && !self.is_parenthesized(x.span) && !self.must_break(x) {
+                let mut flat = xs.iter().map(|x| self.flat_expr(x, PREC_LAMBDA)).join(", ");
+                if xs.len() == 1 {
+                    flat.push(',');
+                }
+                if fits(col, &flat) {
+                    return flat;
+                }
+            }
+        }
+        self.expr(x, indent, col, PREC_LAMBDA)
+    }
+
+    /// Whether an expression can't be written on a single line, because it has comments
+    /// in it or brackets that had line breaks after them.
+    fn must_break(&self, x: &AstExpr) -> bool {
+        if self.has_comments(x.span) {
+            return true;
+        }
+        let broken = match &x.node {
+            Expr::List(xs) => self.has_line_break(x.span.begin(), xs.first().map(|x| x.span)),
+            Expr::Dict(xs) => self.has_line_break(x.span.begin(), xs.first().map(|x| x.0.span)),
+            Expr::Call(f, args) => self.has_line_break(f.span.end(), args.first().map(|x| x.span)),
+            Expr::ListComprehension(body, ..) => {
+                self.has_line_break(x.span.begin(), Some(body.span))
+            }
+            Expr::DictComprehension(body, ..) => {
+                self.has_line_break(x.span.begin(), Some(body.0.span))
+            }
+            Expr::Op(..) | Expr::If(..) => self.parts_have_line_break(&parts(x)),
+            _ => false,
+        };
+        let mut res = broken;
+        x.visit_expr(|x| res = res || self.must_break(x));
+        res
+    }
+
+    fn item_must_break(&self, item: Item) -> bool {
+        match item {
+            Item::Expr(x) => self.must_break(x),
+            Item::Entry(k, v) => self.must_break(k) || self.must_break(v),
+            Item::Arg(x) => self.must_break(x.node.expr()),
+            Item::Param(x) => {
+                let mut res = false;
+                x.node.visit_expr(|x| res = res || self.must_break(x));
+                res
+            }
+            Item::String(_) | Item::Load(..) => false,
+        }
+    }
+
+    fn parts_have_line_break(&self, parts: &[Part]) -> bool {
+        parts.iter().tuple_windows().any(|(a, b)| {
+            self.source(a.expr.span.end(), b.expr.span.begin())
+                .contains('\n')
+        })
+    }
+
+    /// Write an expression in parentheses with one part per line, if it has comments
+    /// between its parts or inside its parentheses, or it is an operator or conditional
+    /// expression that was written over several lines.
+    fn broken_expr(&mut self, x: &AstExpr, indent: usize) -> Option<String> {
+        let parts = parts(x);
+        let (begin, end) = self
+            .parens(x.span)
+            .unwrap_or((x.span.begin(), x.span.end()));
+        let first = parts.first()?.expr.span.begin();
+        let last = parts.last()?.expr.span.end();
+        let comments = self.has_comments(Span::new(begin, first))
+            || self.has_comments(Span::new(last, end))
+            || parts
+                .iter()
+                .tuple_windows()
+                .any(|(a, b)| self.has_comments(Span::new(a.expr.span.end(), b.expr.span.begin())));
+        if !comments && !self.parts_have_line_break(&parts) {
+            return None;
+        }
+        Some(self.broken_parts(parts, begin, end, indent))
+    }
+
+    /// Write `parts` on separate lines in parentheses, with the comments between `begin`
+    /// and `end`.
+    fn broken_parts(&mut self, parts: Vec<Part>, begin: Pos, end: Pos, indent: usize) -> String {
+        let inner = indent + INDENT;
+        let mut s = "(".to_owned();
+        let mut pos = begin;
+        for part in parts {
+            let span = part.expr.span;
+            if let Some(comment) = self.take_trailing_comment(pos, span.begin()) {
+                s.push_str("  ");
+                s.push_str(&comment);
+            }
+            for (_, comment) in self.take_comments(pos, span.begin()) {
+                s.push_str(&format!("\n{}{}", spaces(inner), comment));
+            }
+            let col = inner + part.prefix.len();
+            let text = self.expr(part.expr, inner, col, part.prec);
+            s.push_str(&format!(
+                "\n{}{}{}{}",
+                spaces(inner),
+                part.prefix,
+                text,
+                part.suffix
+            ));
+            pos = span.end();
+        }
+        if let Some(comment) = self.take_trailing_comment(pos, end) {
+            s.push_str("  ");
+            s.push_str(&comment);
+        }
+        for (_, comment) in self.take_comments(pos, end) {
+            s.push_str(&format!("\n{}{}", spaces(inner), comment));
+        }
+        s.push('\n');
+        s.push_str(&spaces(indent));
+        s.push(')');
+        s
+    }
+
+    /// Write an expression starting at column `col` on a line indented by `indent`,
+    /// breaking it over several lines if it doesn't fit.
+    fn expr(&mut self, x: &AstExpr, indent: usize, col: usize, prec: u8) -> String {
+        if let Some(s) = self.broken_expr(x, indent) {
+            return s;
+        }
+        if precedence(&x.node) < prec {
+            return format!("({})", self.expr(x, indent, col + 1, PREC_LAMBDA));
+        }
+        if !self.must_break(x) {
+            let flat = self.flat_expr(x, prec);
+            if fits(col, &flat) {
+                return flat;
+            }
+        }
+        if matches!(x.node, Expr::Op(..) | Expr::If(..)) {
+            if let Some(s) = self.broken_chain(x, indent) {
+                return s;
+            }
+        }
+        match &x.node {
+            Expr::Call(f, args) => self.call(x, f, args, indent, col, false),
+            Expr::List(xs) => self.container(
+                Container {
+                    open: "[",
+                    close: "]",
+                    span: x.span,
+                    items: xs.iter().map(Item::Expr).collect(),
+                    sort: Sort::None,
+                    force_break: false,
+                    tuple: false,
+                },
+                indent,
+                col,
+            ),
+            Expr::Tuple(xs) => self.container(
+                Container {
+                    open: "(",
+                    close: ")",
+                    span: x.span,
+                    items: xs.iter().map(Item::Expr).collect(),
+                    sort: Sort::None,
+                    force_break: false,
+                    tuple: true,
+                },
+                indent,
+                col,
+            ),
+            Expr::Dict(xs) => self.container(
+                Container {
+                    open: "{",
+                    close: "}",
+                    span: x.span,
+                    items: xs.iter().map(|(k, v)| Item::Entry(k, v)).collect(),
+                    sort: Sort::None,
+                    force_break: false,
+                    tuple: false,
+                },
+                indent,
+                col,
+            ),
+            Expr::ListComprehension(body, for_, clauses) => {
+                let inner = indent + INDENT;
+                let body = self.expr(body, inner, inner, PREC_LAMBDA);
+                self.comprehension(("[", "]"), body, for_, clauses, indent)
+            }
+            Expr::DictComprehension(k_v, for_, clauses) => {
+                let inner = indent + INDENT;
+                let (k, v) = &**k_v;
+                let mut body = self.expr(k, inner, inner, PREC_LAMBDA);
+                body.push_str(": ");
+                body += &self.expr(v, inner, end_col(inner, &body), PREC_LAMBDA);
+                self.comprehension(("{", "}"), body, for_, clauses, indent)
+            }
+            Expr::Dot(object, field) => {
+                let s = self.expr(object, indent, col, PREC_PRIMARY);
+                format!("{}.{}", s, field.node)
+            }
+            Expr::Index(array_index) => {
+                let (array, index) = &**array_index;
+                let mut s = self.expr(array, indent, col, PREC_PRIMARY);
+                s.push('[');
+                s += &self.expr(index, indent, end_col(col, &s), PREC_LAMBDA);
+                s.push(']');
+                s
+            }
+            Expr::Op(lhs, op, rhs) => {
+                let (lhs_prec, rhs_prec) = operand_precedence(*op);
+                let mut s = self.expr(lhs, indent, col, lhs_prec);
+                s += &op.to_string();
+                s += &self.expr(rhs, indent, end_col(col, &s), rhs_prec);
+                s
+            }
+            Expr::If(cond_then_else) => {
+                let (cond, then, els) = &**cond_then_else;
+                let mut s = self.expr(then, indent, col, PREC_OR);
+                s.push_str(" if ");
+                s += &self.expr(cond, indent, end_col(col, &s), PREC_OR);
+                s.push_str(" else ");
+                s += &self.expr(els, indent, end_col(col, &s), PREC_LAMBDA);
+                s
+            }
+            Expr::Not(x) => format!("not {}", self.expr(x, indent, col + 4, PREC_NOT)),
+            Expr::Minus(x) => format!("-{}", self.expr(x, indent, col + 1, PREC_UNARY)),
+            Expr::Plus(x) => format!("+{}", self.expr(x, indent, col + 1, PREC_UNARY)),
+            Expr::BitNot(x) => format!("~{}", self.expr(x, indent, col + 1, PREC_UNARY)),
+            _ => self.flat_expr(x, prec),
+        }
+    }
+
+    /// Write the operator or conditional expression `x`, which doesn't fit on a line, with
+    /// one part per line, unless some part has brackets it can be broken inside instead.
+    fn broken_chain(&mut self, x: &AstExpr, indent: usize) -> Option<String> {
+        let parts = parts(x);
+        let bracketed = parts.iter().any(|part| {
+            matches!(
+                part.expr.node,
+                Expr::Call(..)
+                    | Expr::List(_)
+                    | Expr::Dict(_)
+                    | Expr::Tuple(_)
+                    | Expr::ListComprehension(..)
+                    | Expr::DictComprehension(..)
+            )
+        });
+        if bracketed || self.has_comments(x.span) {
+            return None;
+        }
+        Some(self.broken_parts(parts, x.span.begin(), x.span.end(), indent))
+    }
+
+    fn call(
+        &mut self,
+        x: &AstExpr,
+        f: &AstExpr,
+        args: &[AstArgument],
+        indent: usize,
+        col: usize,
+        force_break: bool,
+    ) -> String {
+        let mut s = self.expr(f, indent, col, PREC_PRIMARY);
+        let args = Container {
+            open: "(",
+            close: ")",
+            span: Span::new(f.span.end(), x.span.end()),
+            items: args.iter().map(Item::Arg).collect(),
+            sort: Sort::None,
+            force_break,
+            tuple: false,
+        };
+        s += &self.container(args, indent, end_col(col, &s));
+        s
+    }
+
+    fn comprehension(
+        &mut self,
+        (open, close): (&str, &str),
+        body: String,
+        for_: &ForClause,
+        clauses: &[Clause],
+        indent: usize,
+    ) -> String {
+        let inner = indent + INDENT;
+        let mut s = format!("{}\n{}{}", open, spaces(inner), body);
+        for clause in iter::once(Clause::For(for_.clone())).chain(clauses.iter().cloned()) {
+            s.push('\n');
+            s.push_str(&spaces(inner));
+            s += &self.clause(&clause, inner, inner);
+        }
+        s.push('\n');
+        s.push_str(&spaces(indent));
+        s.push_str(close);
+        s
+    }
+
+    fn clause(&mut self, clause: &Clause, indent: usize, col: usize) -> String {
+        match clause {
+            Clause::For(for_) => {
+                let mut s = format!("for {} in ", self.assign_target(&for_.var, true));
+                s += &self.expr(&for_.over, indent, end_col(col, &s), PREC_OR);
+                s
+            }
+            Clause::If(cond) => format!("if {}", self.expr(cond, indent, col + 3, PREC_OR)),
+        }
+    }
+
+    fn sort_key<'b>(&self, sort: Sort, item: &Item<'b>) -> Option<(u8, Vec<&'b str>)> {
+        match (sort, *item) {
+            (Sort::Load, Item::Load(name, _)) => Some((0, vec![name.node.0.as_str()])),
+            (Sort::Labels, Item::Expr(x)) => match &x.node {
+                Expr::Literal(AstLiteral::String(s)) => Some(label_sort_key(&s.node)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// If the argument `name = value` holds a list of labels that should be sorted, return
+    /// it as a container. A comment saying "do not sort" in the list keeps it in order.
+    fn sorted_labels<'b>(&self, name: &str, value: &'b AstExpr) -> Option<Container<'b>> {
+        match &value.node {
+            Expr::List(xs)
+                if is_sorted_attribute(name)
+                    && xs
+                        .iter()
+                        .all(|x| matches!(x.node, Expr::Literal(AstLiteral::String(_))))
+                    && !self.comments.iter().any(|c| {
+                        value.span.contains(c.span.begin()) && c.text.contains("do not sort")
+                    }) =>
+            {
+                Some(Container {
+                    open: "[",
+                    close: "]",
+                    span: value.span,
+                    items: xs.iter().map(Item::Expr).collect(),
+                    sort: Sort::Labels,
+                    force_break: false,
+                    tuple: false,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Write the items of a container on one line if they fit, and one per line otherwise.
+    fn container(&mut self, container: Container, indent: usize, col: usize) -> String {
+        let must_break = container.force_break
+            || self.has_comments(container.span)
+            || self.has_line_break(
+                container.span.begin(),
+                container.items.first().map(|x| x.span()),
+            )
+            || container.items.iter().any(|x| self.item_must_break(*x));
+        if !must_break {
+            let flat = self.flat_container(&container);
+            if fits(col, &flat) {
+                return flat;
+            }
+        }
+
+        struct Entry {
+            leading: Vec<String>,
+            text: String,
+            trailing: Option<String>,
+        }
+
+        let inner = indent + INDENT;
+        let mut entries: Vec<(Option<(u8, Vec<&str>)>, Entry)> = Vec::new();
+        let mut pos = container.span.begin();
+        let mut dangling = Vec::new();
+        for (i, item) in container.items.iter().enumerate() {
+            let span = item.span();
+            if let Some((_, last)) = entries.last_mut() {
+                last.trailing = self.take_trailing_comment(pos, span.begin());
+            }
+            let leading = self
+                .take_comments(pos, span.begin())
+                .into_iter()
+                .map(|(_, c)| c)
+                .collect();
+            // The comments after the last item are taken first, so they aren't mistaken
+            // for comments inside parentheses around it.
+            let mut trailing = None;
+            if i + 1 == container.items.len() {
+                trailing = self.take_trailing_comment(span.end(), container.span.end());
+                dangling = self.take_comments(span.end(), container.span.end());
+            }
+            let text = self.item(*item, inner);
+            entries.push((
+                self.sort_key(container.sort, item),
+                Entry {
+                    leading,
+                    text,
+                    trailing,
+                },
+            ));
+            pos = span.end();
+        }
+        dangling.extend(self.take_comments(pos, container.span.end()));
+        if container.sort != Sort::None {
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+
+        let mut s = container.open.to_owned();
+        for (_, entry) in entries {
+            for comment in entry.leading {
+                s.push_str(&format!("\n{}{}", spaces(inner), comment));
+            }
+            s.push_str(&format!("\n{}{},", spaces(inner), entry.text));
+            if let Some(comment) = entry.trailing {
+                s.push_str("  ");
+                s.push_str(&comment);
+            }
+        }
+        for (_, comment) in dangling {
+            s.push_str(&format!("\n{}{}", spaces(inner), comment));
+        }
+        s.push('\n');
+        s.push_str(&spaces(indent));
+        s.push_str(container.close);
+        s
+    }
+
+    fn flat_container(&self, container: &Container) -> String {
+        let mut items: Vec<_> = container
+            .items
+            .iter()
+            .map(|x| (self.sort_key(container.sort, x), self.flat_item(*x)))
+            .collect();
+        if container.sort != Sort::None {
+            items.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        let trailing = if container.tuple && items.len() == 1 {
+            ","
+        } else {
+            ""
+        };
+        format!(
+            "{}{}{}{}",
+            container.open,
+            items.into_iter().map(|(_, x)| x).join(", "),
+            trailing,
+            container.close
+        )
+    }
+
+    fn item(&mut self, item: Item, indent: usize) -> String {
+        match item {
+            Item::Expr(x) => self.expr(x, indent, indent, PREC_LAMBDA),
+            Item::Entry(k, v) => {
+                let mut s = self.expr(k, indent, indent, PREC_LAMBDA);
+                s.push_str(": ");
+                s += &self.expr(v, indent, end_col(indent, &s), PREC_LAMBDA);
+                s
+            }
+            Item::Arg(arg) => match &arg.node {
+                ArgumentP::Positional(x) => self.expr(x, indent, indent, PREC_LAMBDA),
+                ArgumentP::Named(name, value) => {
+                    let col = indent + width(&name.node) + 3;
+                    let value = match self.sorted_labels(&name.node, value) {
+                        Some(labels) => self.container(labels, indent, col),
+                        None => self.expr(value, indent, col, PREC_LAMBDA),
+                    };
+                    format!("{} = {}", name.node, value)
+                }
+                ArgumentP::Args(x) => format!("*{}", self.expr(x, indent, indent + 1, PREC_LAMBDA)),
+                ArgumentP::KwArgs(x) => {
+                    format!("**{}", self.expr(x, indent, indent + 2, PREC_LAMBDA))
+                }
+            },
+            Item::Param(param) => {
+                let (prefix, name, ty, default) = match &param.node {
+                    ParameterP::Normal(name, ty) => ("", name, ty, None),
+                    ParameterP::WithDefaultValue(name, ty, default) => {
+                        ("", name, ty, Some(default))
+                    }
+                    ParameterP::NoArgs => return "*".to_owned(),
+                    ParameterP::Args(name, ty) => ("*", name, ty, None),
+                    ParameterP::KwArgs(name, ty) => ("**", name, ty, None),
+                };
+                let mut s = format!("{}{}", prefix, name.node.0);
+                if let Some(ty) = ty {
+                    s.push_str(": ");
+                    s += &self.expr(&ty.node.expr, indent, end_col(indent, &s), PREC_LAMBDA);
+                }
+                if let Some(default) = default {
+                    s.push_str(" = ");
+                    s += &self.expr(default, indent, end_col(indent, &s), PREC_LAMBDA);
+                }
+                s
+            }
+            Item::String(_) | Item::Load(..) => self.flat_item(item),
+        }
+    }
+
+    fn flat_item(&self, item: Item) -> String {
+        match item {
+            Item::Expr(x) => self.flat_expr(x, PREC_LAMBDA),
+            Item::Entry(k, v) => format!(
+                "{}: {}",
+                self.flat_expr(k, PREC_LAMBDA),
+                self.flat_expr(v, PREC_LAMBDA)
+            ),
+            Item::Arg(arg) => match &arg.node {
+                ArgumentP::Positional(x) => self.flat_expr(x, PREC_LAMBDA),
+                ArgumentP::Named(name, value) => {
+                    let value = match self.sorted_labels(&name.node, value) {
+                        Some(labels) => self.flat_container(&labels),
+                        None => self.flat_expr(value, PREC_LAMBDA),
+                    };
+                    format!("{} = {}", name.node, value)
+                }
+                ArgumentP::Args(x) => format!("*{}", self.flat_expr(x, PREC_LAMBDA)),
+                ArgumentP::KwArgs(x) => format!("**{}", self.flat_expr(x, PREC_LAMBDA)),
+            },
+            Item::Param(param) => {
+                let (prefix, name, ty, default) = match &param.node {
+                    ParameterP::Normal(name, ty) => ("", name, ty, None),
+                    ParameterP::WithDefaultValue(name, ty, default) => {
+                        ("", name, ty, Some(default))
+                    }
+                    ParameterP::NoArgs => return "*".to_owned(),
+                    ParameterP::Args(name, ty) => ("*", name, ty, None),
+                    ParameterP::KwArgs(name, ty) => ("**", name, ty, None),
+                };
+                let mut s = format!("{}{}", prefix, name.node.0);
+                if let Some(ty) = ty {
+                    s.push_str(": ");
+                    s += &self.flat_expr(&ty.node.expr, PREC_LAMBDA);
+                }
+                if let Some(default) = default {
+                    s.push_str(" = ");
+                    s += &self.flat_expr(default, PREC_LAMBDA);
+                }
+                s
+            }
+            Item::String(s) => self.string_literal(s),
+            Item::Load(name, symbol) => {
+                if name.node.0 == symbol.node {
+                    self.string_literal(symbol)
+                } else {
+                    format!("{} = {}", name.node.0, self.string_literal(symbol))
+                }
+            }
+        }
+    }
+
+    /// Write a string literal as it was written, except that simple single quoted strings
+    /// are double quoted.
+    fn string_literal(&self, s: &AstString) -> String {
+        let source = self.codemap.source_span(s.span);
+        if source.starts_with('\'')
+            && !source.starts_with("'''")
+            && !source.contains('\\')
+            && !s.node.contains('"')
+        {
+            format!("\"{}\"", s.node)
+        } else {
+            source.to_owned()
+        }
+    }
+
+    /// Write an expression on a single line.
+    fn flat_expr(&self, x: &AstExpr, prec: u8) -> String {
+        let s = match &x.node {
+            Expr::Tuple(xs) => {
+                let trailing = if xs.len() == 1 { "," } else { "" };
+                format!(
+                    "({}{})",
+                    xs.iter().map(|x| self.flat_expr(x, PREC_LAMBDA)).join(", "),
+                    trailing
+                )
+            }
+            Expr::Dot(object, field) => {
+                format!("{}.{}", self.flat_expr(object, PREC_PRIMARY), field.node)
+            }
+            Expr::Call(f, args) => format!(
+                "{}({})",
+                self.flat_expr(f, PREC_PRIMARY),
+                args.iter().map(|x| self.flat_item(Item::Arg(x))).join(", ")
+            ),
+            Expr::Index(array_index) => {
+                let (array, index) = &**array_index;
+                format!(
+                    "{}[{}]",
+                    self.flat_expr(array, PREC_PRIMARY),
+                    self.flat_expr(index, PREC_LAMBDA)
+                )
+            }
+            Expr::Index2(array_indices) => {
+                let (array, i0, i1) = &**array_indices;
+                format!(
+                    "{}[{}, {}]",
+                    self.flat_expr(array, PREC_PRIMARY),
+                    self.flat_expr(i0, PREC_LAMBDA),
+                    self.flat_expr(i1, PREC_LAMBDA)
+                )
+            }
+            Expr::Slice(array, start, stop, stride) => {
+                let part = |x: &Option<Box<AstExpr>>| {
+                    x.as_ref()
+                        .map(|x| self.flat_expr(x, PREC_LAMBDA))
+                        .unwrap_or_default()
+                };
+                let stride = match stride {
+                    Some(stride) => format!(":{}", self.flat_expr(stride, PREC_LAMBDA)),
+                    None => String::new(),
+                };
+                format!(
+                    "{}[{}:{}{}]",
+                    self.flat_expr(array, PREC_PRIMARY),
+                    part(start),
+                    part(stop),
+                    stride
+                )
+            }
+            Expr::Identifier(name) => name.node.0.clone(),
+            Expr::Lambda(lambda) => {
+                let params = lambda
+                    .params
+                    .iter()
+                    .map(|x| self.flat_item(Item::Param(x)))
+                    .join(", ");
+                let body = self.flat_expr(&lambda.body, PREC_LAMBDA);
+                if params.is_empty() {
+                    format!("lambda: {}", body)
+                } else {
+                    format!("lambda {}: {}", params, body)
+                }
+            }
+            Expr::Literal(AstLiteral::Int(x)) => self.codemap.source_span(x.span).to_owned(),
+            Expr::Literal(AstLiteral::Float(x)) => self.codemap.source_span(x.span).to_owned(),
+            Expr::Literal(AstLiteral::String(x)) => self.string_literal(x),
+            Expr::Not(x) => format!("not {}", self.flat_expr(x, PREC_NOT)),
+            Expr::Minus(x) => format!("-{}", self.flat_expr(x, PREC_UNARY)),
+            Expr::Plus(x) => format!("+{}", self.flat_expr(x, PREC_UNARY)),
+            Expr::BitNot(x) => format!("~{}", self.flat_expr(x, PREC_UNARY)),
+            Expr::Op(lhs, op, rhs) => {
+                let (lhs_prec, rhs_prec) = operand_precedence(*op);
+                format!(
+                    "{}{}{}",
+                    self.flat_expr(lhs, lhs_prec),
+                    op,
+                    self.flat_expr(rhs, rhs_prec)
+                )
+            }
+            Expr::If(cond_then_else) => {
+                let (cond, then, els) = &**cond_then_else;
+                format!(
+                    "{} if {} else {}",
+                    self.flat_expr(then, PREC_OR),
+                    self.flat_expr(cond, PREC_OR),
+                    self.flat_expr(els, PREC_LAMBDA)
+                )
+            }
+            Expr::List(xs) => format!(
+                "[{}]",
+                xs.iter().map(|x| self.flat_expr(x, PREC_LAMBDA)).join(", ")
+            ),
+            Expr::Dict(xs) => format!(
+                "{{{}}}",
+                xs.iter()
+                    .map(|(k, v)| self.flat_item(Item::Entry(k, v)))
+                    .join(", ")
+            ),
+            Expr::ListComprehension(body, for_, clauses) => format!(
+                "[{} {}]",
+                self.flat_expr(body, PREC_LAMBDA),
+                self.flat_clauses(for_, clauses)
+            ),
+            Expr::DictComprehension(k_v, for_, clauses) => {
+                let (k, v) = &**k_v;
+                format!(
+                    "{{{} {}}}",
+                    self.flat_item(Item::Entry(k, v)),
+                    self.flat_clauses(for_, clauses)
+                )
+            }
+            Expr::FString(_) => self.codemap.source_span(x.span).to_owned(),
+        };
+        if precedence(&x.node) < prec {
+            format!("({})", s)
+        } else {
+            s
+        }
+    }
+
+    fn flat_clauses(&self, for_: &ForClause, clauses: &[Clause]) -> String {
+        iter::once(Clause::For(for_.clone()))
+            .chain(clauses.iter().cloned())
+            .map(|clause| match clause {
+                Clause::For(for_) => format!(
+                    "for {} in {}",
+                    self.assign_target(&for_.var, true),
+                    self.flat_expr(&for_.over, PREC_OR)
+                ),
+                Clause::If(cond) => format!("if {}", self.flat_expr(&cond, PREC_OR)),
+            })
+            .join(" ")
+    }
+}
+
+impl AstModule {
+    /// Format the module following buildifier's conventions, keeping its comments.
+    ///
+    /// Blocks are indented by four spaces, calls and collections are written one item
+    /// per line when they don't fit on a line (or were written that way), the symbols
+    /// of `load` statements and the labels in `deps` are sorted, and calls to rules
+    /// (those with a `name` argument) have one argument per line.
+    pub fn format(&self) -> String {
+        let comments = Lexer::new(self.codemap.source(), &self.dialect, self.codemap.dupe())
+            .filter_map(|token| match token {
+                Ok((begin, Token::Comment(text), end)) => Some(Comment {
+                    span: Span::new(Pos::new(begin as u32), Pos::new(end as u32)),
+                    text,
+                }),
+                _ => None,
+            })
+            .collect();
+        let mut formatter = Formatter::new(&self.codemap, comments);
+        let mut out = String::new();
+        formatter.stmts(&self.statement, 0, &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use textwrap::dedent;
+
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn format(program: &str) -> String {
+        let dialect = Dialect {
+            enable_f_strings: true,
+            ..Dialect::Extended
+        };
+        let formatted = AstModule::parse("X", dedent(program).trim_start().to_owned(), &dialect)
+            .unwrap()
+            .format();
+        // Formatting is idempotent.
+        assert_eq!(
+            formatted,
+            AstModule::parse("X", formatted.clone(), &dialect)
+                .unwrap()
+                .format()
+        );
+        formatted
+    }
+
+    fn expected(program: &str) -> String {
+        dedent(program).trim_start().to_owned()
+    }
+
+    #[test]
+    fn test_format_statements() {
+        assert_eq!(
+            expected(
+                r#"
+                x = (1 + 2) * 3
+                y = 'it"s' if not x else {"a": [1, 2], "b": ()}
+                a, b = b, a
+
+                def f(x: int, *args, y = lambda: 1, **kwargs) -> str:
+                    if x:
+                        return "x"
+                    elif y:
+                        pass
+                    else:  # Otherwise.
+                        for a, b in kwargs.items():
+                            print(a)
+                    return "a" + "b"
+                "#
+            ),
+            format(
+                r#"
+                x = (1+2)*3
+                y = 'it"s' if not x else {"a": [1,2], 'b': ()}
+                a , b = b , a
+
+
+                def f(x : int, *args, y=lambda : 1, **kwargs)->str:
+                    if x: return "x"
+                    elif y:
+                        pass
+                    else: # Otherwise.
+                        for a,b in kwargs.items(): print(a)
+                    return 'a'+'b'
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        assert_eq!(
+            expected(
+                r#"
+                # Leading comment.
+                load("//a.bzl", "a", "b", c = "d")  # Trailing comment.
+
+                def f():  # Header comment.
+                    # In the body.
+                    return [
+                        1,  # One.
+                        # Before two.
+                        2,
+                        # Dangling.
+                    ]
+                    # At the end of the body.
+
+                # At the end of the file.
+                "#
+            ),
+            format(
+                r#"
+                # Leading comment.
+                load("//a.bzl", c = "d", "b", "a")  # Trailing comment.
+
+                def f():  # Header comment.
+                    # In the body.
+                    return [1,  # One.
+                        # Before two.
+                        2,
+                        # Dangling.
+                    ]
+                    # At the end of the body.
+
+                # At the end of the file.
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_comments_in_expressions() {
+        assert_eq!(
+            expected(
+                r#"
+                x = (
+                    1 +  # One.
+                    # Before two.
+                    2 -
+                    3  # Three.
+                )
+                y = (  # Why.
+                    foo
+                )
+                z = f(
+                    a,
+                    (
+                        b +  # Comment.
+                        c
+                    ),
+                )
+                if (
+                    a and
+                    # Note.
+                    not b
+                ):
+                    pass
+                "#
+            ),
+            format(
+                r#"
+                x = (
+                    1 +  # One.
+                    # Before two.
+                    2 - 3  # Three.
+                )
+                y = (  # Why.
+                  foo)
+                z = f(a, b +  # Comment.
+                      c)
+                if (a and
+                    # Note.
+                    not b):
+                    pass
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_strings() {
+        assert_eq!(
+            expected(
+                r#"
+                a = "single"
+                b = 'it"s'
+                c = r'\d+' + r"\s"
+                d = """Triple
+                'quoted'"""
+                e = '''Also
+                triple'''
+                f = f"{a} and {b}" + f'{c}'
+                "#
+            ),
+            format(
+                r#"
+                a = 'single'
+                b = 'it"s'
+                c = r'\d+'+r"\s"
+                d = """Triple
+                'quoted'"""
+                e = '''Also
+                triple'''
+                f = f"{a} and {b}"+f'{c}'
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_nested_defs() {
+        assert_eq!(
+            expected(
+                r#"
+                def outer(x):
+                    def inner(y):
+                        def innermost():
+                            return y  # Captured.
+
+                        return innermost
+
+                    # Before the return.
+                    return inner(x)
+                "#
+            ),
+            format(
+                r#"
+                def outer(x):
+                  def inner(y):
+                    def innermost(): return y  # Captured.
+
+                    return innermost
+
+                  # Before the return.
+                  return inner(x)
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_long_conditionals() {
+        assert_eq!(
+            expected(
+                r#"
+                if (
+                    aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa and
+                    bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb and
+                    not ccccccccccc
+                ):
+                    pass
+                v = (
+                    aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa
+                    if bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb
+                    else cccccccccccccccccc
+                )
+                w = (
+                    "a"
+                    if cond
+                    else "b"
+                )
+                srcs = glob(["aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"]) + [
+                    "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+                ]
+                kept = (
+                    a and
+                    b
+                )
+                "#
+            ),
+            format(
+                r#"
+                if aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa and bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb and not ccccccccccc:
+                    pass
+                v = aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa if bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb else cccccccccccccccccc
+                w = ("a" if cond
+                     else "b")
+                srcs = glob(["aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"]) + ["bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"]
+                kept = (a and
+                        b)
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_rules() {
+        assert_eq!(
+            expected(
+                r#"
+                cxx_library(
+                    name = "foo",
+                    srcs = ["b.cpp", "a.cpp"],
+                    deps = [
+                        "a",
+                        ":b",
+                        "//c:c",
+                        "//c/d:d",
+                        "cell//e:e",
+                    ],
+                    visibility = ["PUBLIC"],
+                )
+
+                cxx_library(name = "bar")
+
+                unsorted(
+                    deps = [
+                        # do not sort
+                        "b",
+                        "a",
+                    ],
+                )
+                some_function_with_a_long_name(
+                    "a long argument that needs to be wrapped",
+                    other = "and another long one",
+                )
+                "#
+            ),
+            format(
+                r#"
+                cxx_library(name = "foo", srcs = ["b.cpp", "a.cpp"], deps = [
+                    "//c/d:d",
+                    "cell//e:e",
+                    "//c:c",
+                    ":b",
+                    "a",
+                ], visibility = ["PUBLIC"])
+
+                cxx_library(name = "bar")
+
+                unsorted(deps = [
+                    # do not sort
+                    "b",
+                    "a",
+                ])
+                some_function_with_a_long_name("a long argument that needs to be wrapped", other = "and another long one")
+                "#
+            )
+        );
+    }
+}
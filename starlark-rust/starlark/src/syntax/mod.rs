@@ -25,6 +25,7 @@ pub use parser::AstLoad;
 pub(crate) mod ast;
 pub(crate) mod cursors;
 mod dialect;
+mod format;
 #[cfg(test)]
 mod grammar_tests;
 pub(crate) mod grammar_util;
@@ -50,6 +50,13 @@ pub(crate) enum TypeExprUnpackP<'a, P: AstPayload> {
         Box<Spanned<TypeExprUnpackP<'a, P>>>,
         Box<Spanned<TypeExprUnpackP<'a, P>>>,
     ),
+    /// `typing.Callable[[str, int], bool]`.
+    Callable(
+        &'a AstIdentP<P>,
+        Vec<Spanned<&'a str>>,
+        Vec<Spanned<TypeExprUnpackP<'a, P>>>,
+        Box<Spanned<TypeExprUnpackP<'a, P>>>,
+    ),
     Union(Vec<Spanned<TypeExprUnpackP<'a, P>>>),
     ListOf(Box<Spanned<TypeExprUnpackP<'a, P>>>),
     DictOf(
@@ -128,6 +135,19 @@ impl<'a, P: AstPayload> TypeExprUnpackP<'a, P> {
             }
             ExprP::Index2(a_i0_i1) => {
                 let (a, i0, i1) = &**a_i0_i1;
+                if let ExprP::List(params) = &i0.node {
+                    // Only callable types take a list of types as an index.
+                    let callable = TypeExprUnpackP::unpack(a, codemap)?;
+                    let TypeExprUnpackP::Path(first, rem) = callable.node else {
+                        return err("callable type where callable is not an identifier");
+                    };
+                    let params = params.try_map(|x| TypeExprUnpackP::unpack(x, codemap))?;
+                    let result = TypeExprUnpackP::unpack(i1, codemap)?;
+                    return Ok(Spanned {
+                        span,
+                        node: TypeExprUnpackP::Callable(first, rem, params, Box::new(result)),
+                    });
+                }
                 match &a.node {
                     ExprP::Identifier(ident) => {
                         let i0 = TypeExprUnpackP::unpack(i0, codemap)?;
@@ -122,6 +122,7 @@ fn test_go() {
             "frozen list",        // Our freeze does nothing
             "called recursively", // We allow recursion
             "hf",                 // We don't support hasfield
+            "closures",           // Bound methods compare by value, not identity
         ],
     ));
     // Skip int.star, a lot of bit mask stuff, floats and int's outside our range
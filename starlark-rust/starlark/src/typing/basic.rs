@@ -22,6 +22,7 @@ use allocative::Allocative;
 
 use crate::typing::custom::TyCustom;
 use crate::typing::starlark_value::TyStarlarkValue;
+use crate::typing::type_var::TyTypeVar;
 use crate::typing::Ty;
 use crate::typing::TyName;
 use crate::typing::TypingAttr;
@@ -53,8 +54,12 @@ pub enum TyBasic {
     Tuple(Vec<Ty>),
     /// A dictionary, with key and value types
     Dict(Box<(Ty, Ty)>),
+    /// A set, with the element type.
+    Set(Box<Ty>),
     /// Custom type.
     Custom(TyCustom),
+    /// A type variable of a generic function.
+    TypeVar(TyTypeVar),
 }
 
 impl TyBasic {
@@ -93,6 +98,11 @@ impl TyBasic {
         TyBasic::Dict(Box::new((key, value)))
     }
 
+    /// Create a set type.
+    pub(crate) fn set(element: Ty) -> Self {
+        TyBasic::Set(Box::new(element))
+    }
+
     /// Turn a type back into a name, potentially erasing some structure.
     /// E.g. the type `[bool]` would return `list`.
     /// Types like [`Ty::any`] will return `None`.
@@ -103,8 +113,9 @@ impl TyBasic {
             TyBasic::List(_) => Some("list"),
             TyBasic::Tuple(_) => Some("tuple"),
             TyBasic::Dict(_) => Some("dict"),
+            TyBasic::Set(_) => Some("set"),
             TyBasic::Custom(c) => c.as_name(),
-            TyBasic::Any | TyBasic::Iter(_) => None,
+            TyBasic::Any | TyBasic::Iter(_) | TyBasic::TypeVar(_) => None,
         }
     }
 
@@ -132,7 +143,14 @@ impl TyBasic {
     pub(crate) fn attribute(&self, attr: TypingAttr, ctx: TypingOracleCtx) -> Result<Ty, ()> {
         // There are some structural types which have to be handled in a specific way
         match self {
-            TyBasic::Any => Ok(Ty::any()),
+            TyBasic::Any | TyBasic::TypeVar(_) => Ok(Ty::any()),
+            // Custom types know their own attributes, e.g. the fields of `struct(..)`,
+            // which the oracle only knows by the type name.
+            TyBasic::Custom(c) => c.0.attribute_dyn(attr).or_else(|()| {
+                ctx.oracle
+                    .attribute(self, attr)
+                    .unwrap_or_else(|| Ok(Ty::any()))
+            }),
             _ => match ctx.oracle.attribute(self, attr) {
                 Some(r) => r,
                 None => Ok(Ty::any()),
@@ -157,7 +175,9 @@ impl Display for TyBasic {
                 }
             }
             TyBasic::Dict(k_v) => write!(f, "dict[{}, {}]", k_v.0, k_v.1),
+            TyBasic::Set(x) => write!(f, "set[{}]", x),
             TyBasic::Custom(c) => Display::fmt(c, f),
+            TyBasic::TypeVar(v) => Display::fmt(v, f),
         }
     }
 }
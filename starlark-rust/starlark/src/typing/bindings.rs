@@ -30,8 +30,10 @@ use crate::eval::compiler::scope::payload::CstPayload;
 use crate::eval::compiler::scope::payload::CstStmt;
 use crate::eval::compiler::scope::BindingId;
 use crate::eval::compiler::scope::ResolvedIdent;
+use crate::syntax::ast::ArgumentP;
 use crate::syntax::ast::AssignOp;
 use crate::syntax::ast::AssignP;
+use crate::syntax::ast::AstLiteral;
 use crate::syntax::ast::ClauseP;
 use crate::syntax::ast::DefP;
 use crate::syntax::ast::ExprP;
@@ -45,6 +47,7 @@ use crate::typing::function::Param;
 use crate::typing::mode::TypecheckMode;
 use crate::typing::ty::Approximation;
 use crate::typing::ty::Ty;
+use crate::typing::type_var::TyTypeVar;
 
 #[derive(Clone)]
 pub(crate) enum BindExpr<'a> {
@@ -113,6 +116,35 @@ impl Interface {
 pub(crate) struct BindingsCollect<'a> {
     pub(crate) bindings: Bindings<'a>,
     pub(crate) approximations: Vec<Approximation>,
+    /// Variables assigned `typing.TypeVar("T")`, to resolve type variables in lint mode.
+    pub(crate) type_vars: HashMap<BindingId, Ty>,
+}
+
+/// `typing.TypeVar("T")`, the type variable declared by the expression.
+fn type_var_decl(x: &CstExpr, codemap: &CodeMap) -> Option<Ty> {
+    let ExprP::Call(f, args) = &x.node else {
+        return None;
+    };
+    let (ExprP::Dot(typing, attr), [arg]) = (&f.node, args.as_slice()) else {
+        return None;
+    };
+    match (&typing.node, &arg.node) {
+        (ExprP::Identifier(typing), ArgumentP::Positional(name))
+            if typing.node.0 == "typing"
+                && matches!(typing.node.1, Some(ResolvedIdent::Global(_)))
+                && attr.as_str() == "TypeVar" =>
+        {
+            match &name.node {
+                ExprP::Literal(AstLiteral::String(name)) => Some(Ty::type_var(TyTypeVar::new(
+                    name.as_str(),
+                    codemap.filename(),
+                    x.span.begin(),
+                ))),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
 }
 
 impl<'a> BindingsCollect<'a> {
@@ -192,6 +224,7 @@ impl<'a> BindingsCollect<'a> {
                             let ty2 = Ty::from_type_expr(
                                 ty,
                                 typecheck_mode,
+                                &bindings.type_vars,
                                 &mut bindings.approximations,
                                 codemap,
                             )?;
@@ -209,6 +242,13 @@ impl<'a> BindingsCollect<'a> {
                                     .insert(id.resolved_binding_id(codemap)?, ty2);
                             }
                         }
+                        if let AssignP::Identifier(id) = &**lhs {
+                            if let Some(ty) = type_var_decl(&ty_rhs.1, codemap) {
+                                bindings
+                                    .type_vars
+                                    .insert(id.resolved_binding_id(codemap)?, ty);
+                            }
+                        }
                         assign(lhs, BindExpr::Expr(&ty_rhs.1), bindings, codemap)?
                     }
                     StmtP::AssignModify(lhs, op, rhs) => assign(
@@ -238,6 +278,7 @@ impl<'a> BindingsCollect<'a> {
                                     let ty = Ty::from_type_expr_opt(
                                         ty,
                                         typecheck_mode,
+                                        &bindings.type_vars,
                                         &mut bindings.approximations,
                                         codemap,
                                     )?;
@@ -262,6 +303,7 @@ impl<'a> BindingsCollect<'a> {
                                     params2.push(Param::args(Ty::from_type_expr_opt(
                                         ty,
                                         typecheck_mode,
+                                        &bindings.type_vars,
                                         &mut bindings.approximations,
                                         codemap,
                                     )?));
@@ -271,6 +313,7 @@ impl<'a> BindingsCollect<'a> {
                                     let ty = Ty::from_type_expr_opt(
                                         ty,
                                         typecheck_mode,
+                                        &bindings.type_vars,
                                         &mut bindings.approximations,
                                         codemap,
                                     )?;
@@ -293,6 +336,7 @@ impl<'a> BindingsCollect<'a> {
                         let ret_ty = Ty::from_type_expr_opt(
                             return_type,
                             typecheck_mode,
+                            &bindings.type_vars,
                             &mut bindings.approximations,
                             codemap,
                         )?;
@@ -154,12 +154,12 @@ impl TypingContext<'_> {
             node: self.expression_type(x),
         });
 
-        // Hack for `list[str]`: list of `list` is just "function", and we don't want
-        // to make it custom type and have overly complex machinery for handling it.
+        // Hack for `list[str]` and `set[str]`: type of `list` is just "function", and we don't
+        // want to make it custom type and have overly complex machinery for handling it.
         // So we just special case it here.
         if t0.is_function() && name == TypingAttr::Index {
             if let ExprP::Identifier(v0) = &args[0].node {
-                if v0.0 == "list" {
+                if v0.0 == "list" || v0.0 == "set" {
                     // TODO: make this "eval_type" or something.
                     return Ty::any();
                 }
@@ -181,6 +181,7 @@ impl TypingContext<'_> {
                     Ok(x) => results.push(Ty::basic(TyBasic::StarlarkValue(x))),
                     Err(()) => {}
                 },
+                TyBasic::TypeVar(_) => results.push(Ty::any()),
                 _ => {
                     // The rest do not support unary operators.
                 }
@@ -318,6 +319,11 @@ impl TypingContext<'_> {
         bin_op: TypingBinOp,
         rhs: Spanned<&TyBasic>,
     ) -> Result<Ty, ()> {
+        // Inside a generic function, a type variable is like `Any`.
+        let rhs = rhs.into_map(|t| match t {
+            TyBasic::TypeVar(_) => &TyBasic::Any,
+            t => t,
+        });
         if let TyBasic::StarlarkValue(lhs) = &lhs.node {
             return lhs.bin_op(bin_op, rhs.node);
         }
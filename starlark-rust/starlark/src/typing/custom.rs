@@ -32,6 +32,9 @@ use starlark_map::StarlarkHasher;
 use crate::codemap::Span;
 use crate::codemap::Spanned;
 use crate::typing::error::TypingError;
+use crate::typing::function::TyCallable;
+use crate::typing::function::TyCustomFunction;
+use crate::typing::function::TyFunction;
 use crate::typing::Arg;
 use crate::typing::Ty;
 use crate::typing::TypingAttr;
@@ -61,6 +64,7 @@ pub(crate) trait TyCustomDyn: Debug + Display + Allocative + Send + Sync + 'stat
     fn hash_code(&self) -> u64;
     fn cmp_token(&self) -> (OrdAny, &'static str);
     fn into_any(self: Box<Self>) -> Box<dyn Any>;
+    fn as_any(&self) -> &dyn Any;
 
     fn clone_box_dyn(&self) -> Box<dyn TyCustomDyn>;
     fn as_name_dyn(&self) -> Option<&str>;
@@ -96,6 +100,10 @@ impl<T: TyCustomImpl> TyCustomDyn for T {
         self
     }
 
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn clone_box_dyn(&self) -> Box<dyn TyCustomDyn> {
         Box::new(self.clone())
     }
@@ -140,6 +148,20 @@ impl TyCustom {
         self.0.as_name_dyn()
     }
 
+    pub(crate) fn downcast_ref<T: TyCustomImpl>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref::<T>()
+    }
+
+    /// The signature of the function, if this is a function type with a known signature.
+    pub(crate) fn as_function(&self) -> Option<TyFunction> {
+        if let Some(f) = self.downcast_ref::<TyCustomFunction<TyFunction>>() {
+            Some(f.0.clone())
+        } else {
+            self.downcast_ref::<TyCallable>()
+                .map(TyCallable::to_function)
+        }
+    }
+
     pub(crate) fn union2(x: TyCustom, y: TyCustom) -> Result<TyCustom, (TyCustom, TyCustom)> {
         x.0.union2_dyn(y.0)
             .map(TyCustom)
@@ -159,6 +159,11 @@ pub trait TyCustomFunctionImpl:
         false
     }
 
+    /// How the function type is shown in error messages.
+    fn fmt_ty(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "\"function\"")
+    }
+
     fn validate_call(
         &self,
         span: Span,
@@ -167,20 +172,15 @@ pub trait TyCustomFunctionImpl:
     ) -> Result<Ty, TypingError>;
 }
 
-#[derive(
-    Allocative,
-    Eq,
-    PartialEq,
-    Hash,
-    Ord,
-    PartialOrd,
-    Debug,
-    Clone,
-    derive_more::Display
-)]
-#[display(fmt = "\"function\"")]
+#[derive(Allocative, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Clone)]
 pub struct TyCustomFunction<F: TyCustomFunctionImpl>(pub F);
 
+impl<F: TyCustomFunctionImpl> Display for TyCustomFunction<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.fmt_ty(f)
+    }
+}
+
 impl<F: TyCustomFunctionImpl> TyCustomImpl for TyCustomFunction<F> {
     fn as_name(&self) -> Option<&str> {
         Some("function")
@@ -238,17 +238,28 @@ impl Display for TyFunction {
         let TyFunction { params, result, .. } = self;
         write!(f, "def(")?;
         let mut first = true;
+        // Whether named-only parameters are already marked by `*` or `*args`.
+        let mut star = false;
         for param in params {
             if !first {
                 write!(f, ", ")?;
-                first = false;
             }
-            let opt = if param.optional { "=.." } else { "" };
+            first = false;
+            let opt = if param.optional { " = ..." } else { "" };
             match &param.mode {
-                ParamMode::PosOnly => write!(f, "#: {}{}", param.ty, opt)?,
-                ParamMode::PosOrName(name) => write!(f, "#{}: {}{}", name, param.ty, opt)?,
-                ParamMode::NameOnly(name) => write!(f, "{}: {}{}", name, param.ty, opt)?,
-                ParamMode::Args => write!(f, "*args: {}", param.ty)?,
+                ParamMode::PosOnly => write!(f, "{}{}", param.ty, opt)?,
+                ParamMode::PosOrName(name) => write!(f, "{}: {}{}", name, param.ty, opt)?,
+                ParamMode::NameOnly(name) => {
+                    if !star {
+                        write!(f, "*, ")?;
+                        star = true;
+                    }
+                    write!(f, "{}: {}{}", name, param.ty, opt)?
+                }
+                ParamMode::Args => {
+                    star = true;
+                    write!(f, "*args: {}", param.ty)?
+                }
                 ParamMode::Kwargs => write!(f, "**kwargs: {}", param.ty)?,
             }
         }
@@ -256,11 +267,63 @@ impl Display for TyFunction {
     }
 }
 
+/// A function type written as `typing.Callable[[str, int], bool]`:
+/// only positional parameter types and the result type are known.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Allocative)]
+pub(crate) struct TyCallable {
+    /// The types of the positional parameters.
+    pub(crate) params: Vec<Ty>,
+    /// The result type.
+    pub(crate) result: Box<Ty>,
+}
+
+impl TyCallable {
+    /// The callable as a function with positional-only parameters.
+    pub(crate) fn to_function(&self) -> TyFunction {
+        TyFunction {
+            type_attr: None,
+            params: self.params.iter().cloned().map(Param::pos_only).collect(),
+            result: self.result.clone(),
+        }
+    }
+}
+
+impl Display for TyCallable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "typing.Callable[")?;
+        display_container::fmt_container(f, "[", "]", &self.params)?;
+        write!(f, ", {}]", self.result)
+    }
+}
+
+impl TyCustomImpl for TyCallable {
+    fn as_name(&self) -> Option<&str> {
+        Some("function")
+    }
+
+    fn validate_call(
+        &self,
+        span: Span,
+        args: &[Spanned<Arg>],
+        oracle: TypingOracleCtx,
+    ) -> Result<Ty, TypingError> {
+        oracle.validate_fn_call(span, &self.to_function(), args)
+    }
+
+    fn attribute(&self, _attr: TypingAttr) -> Result<Ty, ()> {
+        Err(())
+    }
+}
+
 impl TyCustomFunctionImpl for TyFunction {
     fn has_type_attr(&self) -> bool {
         self.type_attr.is_some()
     }
 
+    fn fmt_ty(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+
     fn validate_call(
         &self,
         span: Span,
@@ -34,6 +34,7 @@ pub(crate) mod oracle;
 pub(crate) mod starlark_value;
 pub(crate) mod structs;
 pub(crate) mod ty;
+pub(crate) mod type_var;
 pub(crate) mod typecheck;
 pub(crate) mod unordered_map;
 
@@ -58,4 +59,5 @@ pub use structs::TyStruct;
 pub use ty::Approximation;
 pub use ty::Ty;
 pub use ty::TyName;
+pub use type_var::TyTypeVar;
 pub use typecheck::TypeMap;
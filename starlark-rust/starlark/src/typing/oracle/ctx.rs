@@ -15,6 +15,7 @@
  * limitations under the License.
  */
 
+use std::borrow::Cow;
 use std::fmt::Display;
 
 use dupe::Dupe;
@@ -22,13 +23,17 @@ use dupe::Dupe;
 use crate::codemap::CodeMap;
 use crate::codemap::Span;
 use crate::codemap::Spanned;
+use crate::slice_vec_ext::SliceExt;
 use crate::typing::basic::TyBasic;
 use crate::typing::custom::TyCustom;
 use crate::typing::error::TypingError;
 use crate::typing::function::Arg;
 use crate::typing::function::Param;
 use crate::typing::function::ParamMode;
+use crate::typing::function::TyCallable;
 use crate::typing::function::TyFunction;
+use crate::typing::type_var::has_type_vars;
+use crate::typing::type_var::TypeVarBindings;
 use crate::typing::Ty;
 use crate::typing::TyName;
 use crate::typing::TypingAttr;
@@ -47,6 +52,12 @@ enum TypingOracleCtxError {
     UnexpectedNamedArgument { name: String },
     #[error("Too many positional arguments")]
     TooManyPositionalArguments,
+    #[error("Type variable `{var}` is bound to `{bound}` but got `{got}`")]
+    TypeVarMismatch {
+        var: String,
+        bound: String,
+        got: String,
+    },
     #[error("Call arguments incompatible")]
     CallArgumentsIncompatible,
 }
@@ -88,6 +99,7 @@ impl<'a> TypingOracle for TypingOracleCtx<'a> {
                 _ => return Some(Err(())),
             },
             TyBasic::Custom(c) => return Some(c.0.attribute_dyn(attr)),
+            TyBasic::TypeVar(_) => Ty::any(),
             ty => return self.oracle.attribute(ty, attr),
         }))
     }
@@ -147,12 +159,14 @@ impl<'a> TypingOracleCtx<'a> {
         }
     }
 
+    /// Check the arguments against the parameters. Returns the types bound to the type variables
+    /// of the parameters, if the function is generic.
     fn validate_args(
         &self,
         params: &[Param],
         args: &[Spanned<Arg>],
         span: Span,
-    ) -> Result<(), TypingError> {
+    ) -> Result<TypeVarBindings, TypingError> {
         // Want to figure out which arguments go in which positions
         let mut param_args: Vec<Vec<Spanned<&Ty>>> = vec![vec![]; params.len()];
         // The next index a positional parameter might fill
@@ -213,7 +227,39 @@ impl<'a> TypingOracleCtx<'a> {
             }
         }
 
-        for (param, args) in std::iter::zip(params, param_args) {
+        // For a generic function, bind the type variables to the argument types first,
+        // then check the arguments against the parameter types with the bound types substituted.
+        let mut type_vars = TypeVarBindings::default();
+        let params: Cow<[Param]> = if params.iter().any(|p| has_type_vars(&p.ty)) {
+            for (param, args) in std::iter::zip(params, &param_args) {
+                if param.mode != ParamMode::Kwargs {
+                    for arg in args {
+                        let mut arg_type_vars = TypeVarBindings::default();
+                        arg_type_vars.unify(&param.ty, arg.node);
+                        type_vars
+                            .merge(arg_type_vars, |x, y| self.intersects(x, y))
+                            .map_err(|e| {
+                                self.mk_error(
+                                    arg.span,
+                                    TypingOracleCtxError::TypeVarMismatch {
+                                        var: e.var.to_string(),
+                                        bound: e.bound.to_string(),
+                                        got: e.got.to_string(),
+                                    },
+                                )
+                            })?;
+                    }
+                }
+            }
+            Cow::Owned(params.map(|p| Param {
+                ty: type_vars.apply(&p.ty),
+                ..p.clone()
+            }))
+        } else {
+            Cow::Borrowed(params)
+        };
+
+        for (param, args) in std::iter::zip(params.iter(), param_args) {
             if !param.allows_many() && args.len() > 1 {
                 panic!("bad")
             }
@@ -259,7 +305,7 @@ impl<'a> TypingOracleCtx<'a> {
                 }
             }
         }
-        Ok(())
+        Ok(type_vars)
     }
 
     pub(crate) fn validate_fn_call(
@@ -268,8 +314,12 @@ impl<'a> TypingOracleCtx<'a> {
         fun: &TyFunction,
         args: &[Spanned<Arg>],
     ) -> Result<Ty, TypingError> {
-        self.validate_args(&fun.params, args, span)?;
-        Ok((*fun.result).clone())
+        let type_vars = self.validate_args(&fun.params, args, span)?;
+        if has_type_vars(&fun.result) {
+            Ok(type_vars.apply(&fun.result))
+        } else {
+            Ok((*fun.result).clone())
+        }
     }
 
     fn validate_call_for_type_name(
@@ -299,18 +349,19 @@ impl<'a> TypingOracleCtx<'a> {
         args: &[Spanned<Arg>],
     ) -> Result<Ty, TypingError> {
         match fun {
-            TyBasic::Any => Ok(Ty::any()),
+            TyBasic::Any | TyBasic::TypeVar(_) => Ok(Ty::any()),
             TyBasic::Name(n) => self.validate_call_for_type_name(span, n, args),
             TyBasic::StarlarkValue(t) => Err(self.mk_error(
                 span,
                 TypingOracleCtxError::CallToNonCallable { ty: t.to_string() },
             )),
-            TyBasic::List(_) | TyBasic::Dict(_) | TyBasic::Tuple(_) => Err(self.mk_error(
-                span,
-                TypingOracleCtxError::CallToNonCallable {
-                    ty: fun.to_string(),
-                },
-            )),
+            TyBasic::List(_) | TyBasic::Dict(_) | TyBasic::Set(_) | TyBasic::Tuple(_) => Err(self
+                .mk_error(
+                    span,
+                    TypingOracleCtxError::CallToNonCallable {
+                        ty: fun.to_string(),
+                    },
+                )),
             TyBasic::Iter(_) => {
                 // Unknown type, may be callable.
                 Ok(Ty::any())
@@ -369,6 +420,23 @@ impl<'a> TypingOracleCtx<'a> {
         false
     }
 
+    /// Can `fun` be called with the required positional parameters of `sig`,
+    /// returning a result compatible with the result of `sig`.
+    fn accepts_signature(&self, fun: &TyFunction, sig: &TyFunction) -> bool {
+        let args: Vec<Spanned<Arg>> = sig
+            .params
+            .iter()
+            .filter(|p| !p.optional && p.allows_pos() && !p.allows_many())
+            .map(|p| Spanned {
+                span: Span::default(),
+                node: Arg::Pos(p.ty.clone()),
+            })
+            .collect();
+        self.validate_args(&fun.params, &args, Span::default())
+            .is_ok()
+            && self.intersects(&fun.result, &sig.result)
+    }
+
     fn intersects_name(&self, x: &TyName, y: &TyName) -> bool {
         x == y || self.subtype(x, y) || self.subtype(y, x)
     }
@@ -380,8 +448,11 @@ impl<'a> TypingOracleCtx<'a> {
         };
 
         match (x, y) {
+            // Type variables are only known at call sites, inside the function they are like `Any`.
+            (TyBasic::TypeVar(_), _) | (_, TyBasic::TypeVar(_)) => true,
             (TyBasic::Name(x), TyBasic::Name(y)) => self.intersects_name(x, y),
             (TyBasic::List(x), TyBasic::List(y)) => self.intersects(x, y),
+            (TyBasic::Set(x), TyBasic::Set(y)) => self.intersects(x, y),
             (TyBasic::Dict(x), TyBasic::Dict(y)) => {
                 self.intersects(&x.0, &y.0) && self.intersects(&x.1, &y.1)
             }
@@ -394,7 +465,18 @@ impl<'a> TypingOracleCtx<'a> {
                 Some(yy) => self.intersects(x, &yy),
                 None => false,
             },
-            (TyBasic::Custom(x), TyBasic::Custom(y)) => TyCustom::intersects(x, y),
+            (TyBasic::Custom(x), TyBasic::Custom(y)) => {
+                // Signatures are only compared against explicit `typing.Callable` types,
+                // other function types are compatible with each other.
+                let callable = x.downcast_ref::<TyCallable>().is_some()
+                    || y.downcast_ref::<TyCallable>().is_some();
+                match (x.as_function(), y.as_function()) {
+                    (Some(x), Some(y)) if callable => {
+                        self.accepts_signature(&x, &y) || self.accepts_signature(&y, &x)
+                    }
+                    _ => TyCustom::intersects(x, y),
+                }
+            }
             (x, y) if x.is_function() && y.is_function() => true,
             // There are lots of other cases that overlap, but add them as we need them
             (x, y) => x == y,
@@ -65,6 +65,9 @@ impl OracleStandard {
         add::<crate::values::list::value::ListGen<crate::values::list::value::FrozenListData>>(
             &mut fallback,
         );
+        add::<crate::values::set::value::SetGen<crate::values::set::value::FrozenSetData>>(
+            &mut fallback,
+        );
         add::<crate::values::structs::value::FrozenStruct>(&mut fallback);
         add::<crate::values::tuple::value::FrozenTuple>(&mut fallback);
 
@@ -154,6 +157,23 @@ impl TypingOracle for OracleStandard {
                     _ => return fallback(),
                 }
             }
+            TyBasic::Set(elem) => match attr {
+                TypingAttr::Iter => (**elem).clone(),
+                TypingAttr::BinOp(TypingBinOp::In) => {
+                    Ty::function(vec![Param::pos_only((**elem).clone())], Ty::bool())
+                }
+                TypingAttr::BinOp(
+                    TypingBinOp::BitOr
+                    | TypingBinOp::BitAnd
+                    | TypingBinOp::BitXor
+                    | TypingBinOp::Sub,
+                ) => Ty::function(
+                    vec![Param::pos_only(Ty::basic(ty.clone()))],
+                    Ty::basic(ty.clone()),
+                ),
+                TypingAttr::Regular("pop") => Ty::function(vec![], (**elem).clone()),
+                _ => return fallback(),
+            },
             TyBasic::StarlarkValue(x) if x.as_name() == "int" => match attr {
                 TypingAttr::BinOp(TypingBinOp::Less) => {
                     Ty::function(vec![Param::pos_only(Ty::int())], Ty::bool())
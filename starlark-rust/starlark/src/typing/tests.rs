@@ -96,6 +96,7 @@ fn test_oracle() {
     assert!(has_type(&b.builtin("int")));
     assert!(has_type(&b.builtin("str")));
     assert!(has_type(&b.builtin("list")));
+    assert!(has_type(&b.builtin("set")));
     assert!(!has_type(&b.builtin("hash")));
 }
 
@@ -454,6 +455,19 @@ x = new_list_dict_syntax({"a": 1, "b": 2})
     );
 }
 
+#[test]
+fn test_set_syntax() {
+    TypeCheck::new().ty("x").check(
+        "set_syntax",
+        r#"
+def set_syntax(x: set[str]) -> set[str]:
+    return x | set(["a"])
+
+x = set_syntax(set(["b"]))
+"#,
+    );
+}
+
 #[test]
 fn test_new_list_dict_syntax_as_value() {
     // TODO(nga): fix.
@@ -506,3 +520,56 @@ def func_which_returns_union(p) -> str | int:
 "#,
     );
 }
+
+#[test]
+fn test_type_var() {
+    TypeCheck::new().ty("x").check(
+        "type_var",
+        r#"
+T = typing.TypeVar("T")
+
+def first(xs: list[T]) -> T:
+    return xs[0]
+
+x = first([1, 2])
+
+hash(first([1]))
+"#,
+    );
+}
+
+#[test]
+fn test_type_var_mismatch() {
+    TypeCheck::new().check(
+        "type_var_mismatch",
+        r#"
+T = typing.TypeVar("T")
+
+def same(x: T, y: T) -> T:
+    return x
+
+same(1, 2)
+same(1, "a")
+"#,
+    );
+}
+
+#[test]
+fn test_callable() {
+    TypeCheck::new().ty("x").check(
+        "callable",
+        r#"
+def apply(f: typing.Callable[[str], int], x: str) -> int:
+    return f(x)
+
+def good(s: str) -> int:
+    return len(s)
+
+def bad(s: int) -> int:
+    return s
+
+x = apply(good, "a")
+apply(bad, "a")
+"#,
+    );
+}
@@ -15,6 +15,7 @@
  * limitations under the License.
  */
 
+use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
@@ -34,6 +35,7 @@ use crate::eval::compiler::constants::Constants;
 use crate::eval::compiler::scope::payload::CstIdent;
 use crate::eval::compiler::scope::payload::CstPayload;
 use crate::eval::compiler::scope::payload::CstTypeExpr;
+use crate::eval::compiler::scope::BindingId;
 use crate::eval::compiler::scope::ResolvedIdent;
 use crate::eval::compiler::small_vec_1::SmallVec1;
 use crate::slice_vec_ext::SliceExt;
@@ -44,18 +46,22 @@ use crate::typing::custom::TyCustomImpl;
 use crate::typing::error::InternalError;
 use crate::typing::function::Param;
 use crate::typing::function::ParamMode;
+use crate::typing::function::TyCallable;
 use crate::typing::function::TyCustomFunction;
 use crate::typing::function::TyCustomFunctionImpl;
 use crate::typing::function::TyFunction;
 use crate::typing::mode::TypecheckMode;
 use crate::typing::structs::TyStruct;
+use crate::typing::type_var::TyTypeVar;
 use crate::values::bool::StarlarkBool;
 use crate::values::tuple::value::FrozenTuple;
+use crate::values::typing::callable::TypingCallable;
 use crate::values::typing::never::TypingNever;
 use crate::values::typing::type_compiled::TypeCompiled;
 use crate::values::FrozenValue;
 use crate::values::Heap;
 use crate::values::StarlarkValue;
+use crate::values::ValueLike;
 
 /// A typing operation wasn't able to produce a precise result,
 /// so made some kind of approximation.
@@ -170,6 +176,7 @@ impl Ty {
         match name {
             "list" => Self::list(Ty::any()),
             "dict" => Self::dict(Ty::any(), Ty::any()),
+            "set" => Self::set(Ty::any()),
             "function" => Self::any_function(),
             "struct" => Self::custom(TyStruct::any()),
             "never" => Self::never(),
@@ -260,6 +267,15 @@ impl Ty {
         Self::dict(Ty::any(), Ty::any())
     }
 
+    /// Create a set type.
+    pub fn set(element: Ty) -> Self {
+        Ty::basic(TyBasic::set(element))
+    }
+
+    pub(crate) fn any_set() -> Self {
+        Self::set(Ty::any())
+    }
+
     /// Create a tuple of two elements
     pub fn tuple2(a: Ty, b: Ty) -> Self {
         Ty::tuple(vec![a, b])
@@ -298,6 +314,19 @@ impl Ty {
         Self::custom(TyCustomFunction(TyFunction::any()))
     }
 
+    /// Function type with positional parameters, `typing.Callable[[str, int], bool]`.
+    pub(crate) fn callable(params: Vec<Ty>, result: Ty) -> Self {
+        Self::custom(TyCallable {
+            params,
+            result: Box::new(result),
+        })
+    }
+
+    /// A type variable, `typing.TypeVar("T")`.
+    pub(crate) fn type_var(var: TyTypeVar) -> Self {
+        Ty::basic(TyBasic::TypeVar(var))
+    }
+
     pub(crate) const fn starlark_value<'v, T: StarlarkValue<'v>>() -> Self {
         Ty::basic(TyBasic::starlark_value::<T>())
     }
@@ -332,6 +361,7 @@ impl Ty {
             (TyBasic::Dict(x), TyBasic::Dict(y)) => {
                 Either::Left(TyBasic::dict(Ty::union2(x.0, y.0), Ty::union2(x.1, y.1)))
             }
+            (TyBasic::Set(x), TyBasic::Set(y)) => Either::Left(TyBasic::set(Ty::union2(*x, *y))),
             (TyBasic::Custom(x), TyBasic::Custom(y)) => match TyCustom::union2(x, y) {
                 Ok(u) => Either::Left(TyBasic::Custom(u)),
                 Err((x, y)) => Either::Right((TyBasic::Custom(x), TyBasic::Custom(y))),
@@ -381,18 +411,22 @@ impl Ty {
     pub(crate) fn from_type_expr_opt(
         x: &Option<Box<CstTypeExpr>>,
         typecheck_mode: TypecheckMode,
+        type_vars: &HashMap<BindingId, Ty>,
         approximations: &mut Vec<Approximation>,
         codemap: &CodeMap,
     ) -> Result<Self, InternalError> {
         match x {
             None => Ok(Ty::any()),
-            Some(x) => Self::from_type_expr(x, typecheck_mode, approximations, codemap),
+            Some(x) => Self::from_type_expr(x, typecheck_mode, type_vars, approximations, codemap),
         }
     }
 
+    /// `type_vars` are the module variables assigned `typing.TypeVar(...)`,
+    /// only used in lint mode, where module variables are not evaluated.
     pub(crate) fn from_type_expr(
         x: &CstTypeExpr,
         typecheck_mode: TypecheckMode,
+        type_vars: &HashMap<BindingId, Ty>,
         approximations: &mut Vec<Approximation>,
         codemap: &CodeMap,
     ) -> Result<Self, InternalError> {
@@ -400,7 +434,7 @@ impl Ty {
             TypecheckMode::Lint => {
                 // TODO(nga): remove this branch: in lint, populate types in CstPayload
                 //   before running typechecking, and always fetch the type from the payload.
-                Self::from_type_expr_for_lint(x, codemap, approximations)
+                Self::from_type_expr_for_lint(x, codemap, type_vars, approximations)
             }
             TypecheckMode::Compiler => match x.payload {
                 Some(ty) => Ok(ty.as_ty().clone()),
@@ -416,16 +450,18 @@ impl Ty {
     fn from_type_expr_for_lint(
         x: &CstTypeExpr,
         codemap: &CodeMap,
+        type_vars: &HashMap<BindingId, Ty>,
         approximations: &mut Vec<Approximation>,
     ) -> Result<Self, InternalError> {
         let x = TypeExprUnpackP::unpack(&x.expr, codemap)
             .map_err(InternalError::from_eval_exception)?;
-        Ok(Self::from_expr_impl(&x, approximations))
+        Ok(Self::from_expr_impl(&x, type_vars, approximations))
     }
 
     // This should go away when `ExprType` is disconnected from `Expr`.
     fn from_expr_impl(
         x: &Spanned<TypeExprUnpackP<CstPayload>>,
+        type_vars: &HashMap<BindingId, Ty>,
         approximations: &mut Vec<Approximation>,
     ) -> Self {
         let mut unknown = || {
@@ -440,17 +476,26 @@ impl Ty {
             }
         }
 
+        fn ident_type_var(ident: &CstIdent, type_vars: &HashMap<BindingId, Ty>) -> Option<Ty> {
+            match &ident.node.1 {
+                Some(ResolvedIdent::Slot(_, binding_id)) => type_vars.get(binding_id).cloned(),
+                _ => None,
+            }
+        }
+
         match &x.node {
             TypeExprUnpackP::Tuple(xs) => {
-                Ty::tuple(xs.map(|x| Self::from_expr_impl(x, approximations)))
+                Ty::tuple(xs.map(|x| Self::from_expr_impl(x, type_vars, approximations)))
             }
             TypeExprUnpackP::Union(xs) => {
-                Ty::unions(xs.map(|x| Self::from_expr_impl(x, approximations)))
+                Ty::unions(xs.map(|x| Self::from_expr_impl(x, type_vars, approximations)))
+            }
+            TypeExprUnpackP::ListOf(x) => {
+                Ty::list(Self::from_expr_impl(x, type_vars, approximations))
             }
-            TypeExprUnpackP::ListOf(x) => Ty::list(Self::from_expr_impl(x, approximations)),
             TypeExprUnpackP::DictOf(k, v) => Ty::dict(
-                Self::from_expr_impl(k, approximations),
-                Self::from_expr_impl(v, approximations),
+                Self::from_expr_impl(k, type_vars, approximations),
+                Self::from_expr_impl(v, type_vars, approximations),
             ),
             TypeExprUnpackP::Literal(x) => {
                 if x.is_empty() || x.starts_with('_') {
@@ -467,6 +512,8 @@ impl Ty {
                             Ok(ty) => ty.as_ty().clone(),
                             Err(_) => unknown(),
                         }
+                    } else if let Some(ty) = ident_type_var(first, type_vars) {
+                        ty
                     } else {
                         unknown()
                     }
@@ -486,11 +533,13 @@ impl Ty {
             }
             TypeExprUnpackP::Index(a, i) => {
                 if let Some(a) = ident_global(a) {
-                    if !a.to_value().ptr_eq(Constants::get().fn_list.0.to_value()) {
-                        approximations.push(Approximation::new("Not list", x));
+                    if !a.to_value().ptr_eq(Constants::get().fn_list.0.to_value())
+                        && !a.to_value().ptr_eq(Constants::get().fn_set.0.to_value())
+                    {
+                        approximations.push(Approximation::new("Not list or set", x));
                         return Ty::any();
                     }
-                    let i = Self::from_expr_impl(i, approximations);
+                    let i = Self::from_expr_impl(i, type_vars, approximations);
                     let heap = Heap::new();
                     let i = TypeCompiled::from_ty(&i, &heap);
                     match a.to_value().get_ref().at(i.to_inner(), &heap) {
@@ -512,14 +561,32 @@ impl Ty {
                     Ty::any()
                 }
             }
+            TypeExprUnpackP::Callable(first, rem, params, result) => {
+                let heap = Heap::new();
+                let mut callable = ident_global(first).map(|v| v.to_value());
+                for step in rem {
+                    callable = callable.and_then(|v| v.get_attr(step.node, &heap).ok().flatten());
+                }
+                if callable
+                    .and_then(|v| v.downcast_ref::<TypingCallable>())
+                    .is_none()
+                {
+                    approximations.push(Approximation::new("Not typing.Callable", x));
+                    return Ty::any();
+                }
+                Ty::callable(
+                    params.map(|x| Self::from_expr_impl(x, type_vars, approximations)),
+                    Self::from_expr_impl(result, type_vars, approximations),
+                )
+            }
             TypeExprUnpackP::Index2(a, i0, i1) => {
                 if let Some(a) = ident_global(a) {
                     if !a.to_value().ptr_eq(Constants::get().fn_dict.0.to_value()) {
                         approximations.push(Approximation::new("Not dict", x));
                         return Ty::any();
                     }
-                    let i0 = Self::from_expr_impl(i0, approximations);
-                    let i1 = Self::from_expr_impl(i1, approximations);
+                    let i0 = Self::from_expr_impl(i0, type_vars, approximations);
+                    let i1 = Self::from_expr_impl(i1, type_vars, approximations);
                     let heap = Heap::new();
                     let i0 = TypeCompiled::from_ty(&i0, &heap);
                     let i1 = TypeCompiled::from_ty(&i1, &heap);
@@ -0,0 +1,210 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Type variables, used to write generic functions like `def first(xs: list[T]) -> T`.
+//!
+//! When a generic function is called, the type variables in its parameter types
+//! are bound to the types of the arguments, and the result type is computed
+//! by substituting the bound types. Inside the function body a type variable
+//! is treated like `typing.Any`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Display;
+
+use allocative::Allocative;
+
+use crate::codemap::Pos;
+use crate::slice_vec_ext::SliceExt;
+use crate::typing::basic::TyBasic;
+use crate::typing::function::ParamMode;
+use crate::typing::function::TyCallable;
+use crate::typing::Ty;
+
+/// A type variable, declared with `T = typing.TypeVar("T")`.
+///
+/// Type variables are identified by where they are declared, so type variables of the same name
+/// declared in different modules are different.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Allocative)]
+pub struct TyTypeVar {
+    name: String,
+    /// The file the type variable is declared in.
+    file: String,
+    /// The position of the declaration in the file.
+    pos: Pos,
+}
+
+impl TyTypeVar {
+    pub(crate) fn new(name: &str, file: &str, pos: Pos) -> TyTypeVar {
+        TyTypeVar {
+            name: name.to_owned(),
+            file: file.to_owned(),
+            pos,
+        }
+    }
+
+    /// The name of the type variable.
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Display for TyTypeVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Does the type mention any type variables.
+pub(crate) fn has_type_vars(ty: &Ty) -> bool {
+    ty.iter_union().iter().any(|t| match t {
+        TyBasic::TypeVar(_) => true,
+        TyBasic::List(x) | TyBasic::Set(x) | TyBasic::Iter(x) => has_type_vars(x),
+        TyBasic::Dict(k_v) => has_type_vars(&k_v.0) || has_type_vars(&k_v.1),
+        TyBasic::Tuple(xs) => xs.iter().any(has_type_vars),
+        TyBasic::Custom(c) => match c.downcast_ref::<TyCallable>() {
+            Some(c) => c.params.iter().any(has_type_vars) || has_type_vars(&c.result),
+            None => false,
+        },
+        TyBasic::Any | TyBasic::Name(_) | TyBasic::StarlarkValue(_) => false,
+    })
+}
+
+/// A type variable bound by several arguments to types which don't intersect.
+#[derive(Debug)]
+pub(crate) struct TypeVarMismatch {
+    pub(crate) var: TyTypeVar,
+    pub(crate) bound: Ty,
+    pub(crate) got: Ty,
+}
+
+/// Types bound to type variables by the arguments of a call to a generic function.
+#[derive(Default, Debug)]
+pub(crate) struct TypeVarBindings(BTreeMap<TyTypeVar, Vec<Ty>>);
+
+impl TypeVarBindings {
+    /// Bind the type variables in `require` (a parameter type) to the matching parts of `got`
+    /// (an argument type). A variable bound several times within the argument type is bound to
+    /// the union of the types, e.g. `T` to `int | str` for `list[T]` and `list[int | str]`.
+    pub(crate) fn unify(&mut self, require: &Ty, got: &Ty) {
+        let (vars, others): (Vec<&TyBasic>, Vec<&TyBasic>) = require
+            .iter_union()
+            .iter()
+            .partition(|t| matches!(t, TyBasic::TypeVar(_)));
+        for got in got.iter_union() {
+            // E.g. `None` passed to `T | None` does not bind `T`.
+            if others.contains(&got) {
+                continue;
+            }
+            for require in &others {
+                self.unify_basic(require, got);
+            }
+            for var in &vars {
+                if let TyBasic::TypeVar(var) = var {
+                    self.0
+                        .entry(var.clone())
+                        .or_default()
+                        .push(Ty::basic(got.clone()));
+                }
+            }
+        }
+    }
+
+    /// Add the bindings of another argument. A variable bound by both must be bound to types
+    /// which intersect, e.g. `same(1, "a")` is an error for `def same(x: T, y: T)`.
+    pub(crate) fn merge(
+        &mut self,
+        other: TypeVarBindings,
+        intersects: impl Fn(&Ty, &Ty) -> bool,
+    ) -> Result<(), Box<TypeVarMismatch>> {
+        for (var, tys) in other.0 {
+            match self.0.get_mut(&var) {
+                Some(bound) => {
+                    let bound_ty = Ty::unions(bound.clone());
+                    let got = Ty::unions(tys.clone());
+                    if !intersects(&bound_ty, &got) {
+                        return Err(Box::new(TypeVarMismatch {
+                            var,
+                            bound: bound_ty,
+                            got,
+                        }));
+                    }
+                    bound.extend(tys);
+                }
+                None => {
+                    self.0.insert(var, tys);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn unify_basic(&mut self, require: &TyBasic, got: &TyBasic) {
+        match (require, got) {
+            (TyBasic::List(r), TyBasic::List(g))
+            | (TyBasic::Set(r), TyBasic::Set(g))
+            | (TyBasic::Iter(r), TyBasic::Iter(g)) => self.unify(r, g),
+            (TyBasic::Dict(r), TyBasic::Dict(g)) => {
+                self.unify(&r.0, &g.0);
+                self.unify(&r.1, &g.1);
+            }
+            (TyBasic::Tuple(rs), TyBasic::Tuple(gs)) if rs.len() == gs.len() => {
+                for (r, g) in std::iter::zip(rs, gs) {
+                    self.unify(r, g);
+                }
+            }
+            (TyBasic::Custom(r), TyBasic::Custom(g)) => {
+                if let (Some(r), Some(g)) = (r.downcast_ref::<TyCallable>(), g.as_function()) {
+                    let positional = g
+                        .params
+                        .iter()
+                        .filter(|p| matches!(p.mode, ParamMode::PosOnly | ParamMode::PosOrName(_)));
+                    for (r, g) in std::iter::zip(&r.params, positional) {
+                        self.unify(r, &g.ty);
+                    }
+                    self.unify(&r.result, &g.result);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Substitute the bound types for the type variables in `ty`.
+    /// Variables which are not bound are replaced with `typing.Any`.
+    pub(crate) fn apply(&self, ty: &Ty) -> Ty {
+        Ty::unions(ty.iter_union().map(|t| self.apply_basic(t)))
+    }
+
+    fn apply_basic(&self, ty: &TyBasic) -> Ty {
+        match ty {
+            TyBasic::TypeVar(var) => match self.0.get(var) {
+                Some(tys) => Ty::unions(tys.clone()),
+                None => Ty::any(),
+            },
+            TyBasic::List(x) => Ty::list(self.apply(x)),
+            TyBasic::Set(x) => Ty::set(self.apply(x)),
+            TyBasic::Iter(x) => Ty::iter(self.apply(x)),
+            TyBasic::Dict(k_v) => Ty::dict(self.apply(&k_v.0), self.apply(&k_v.1)),
+            TyBasic::Tuple(xs) => Ty::tuple(xs.map(|x| self.apply(x))),
+            TyBasic::Custom(c) => match c.downcast_ref::<TyCallable>() {
+                Some(c) => Ty::callable(c.params.map(|x| self.apply(x)), self.apply(&c.result)),
+                None => Ty::basic(ty.clone()),
+            },
+            TyBasic::Any | TyBasic::Name(_) | TyBasic::StarlarkValue(_) => Ty::basic(ty.clone()),
+        }
+    }
+}
@@ -74,6 +74,7 @@ impl StarlarkTypeRepr for StarlarkFunction {
 pub enum SpecialBuiltinFunction {
     List,
     Dict,
+    Set,
 }
 
 /// A native function that can be evaluated.
@@ -304,6 +305,10 @@ impl<'v> StarlarkValue<'v> for NativeFunction {
                 let index = TypeCompiled::new(index, heap)?;
                 Ok(TypeCompiled::type_list_of(index, heap).to_inner())
             }
+            Some(SpecialBuiltinFunction::Set) => {
+                let index = TypeCompiled::new(index, heap)?;
+                Ok(TypeCompiled::type_set_of(index, heap).to_inner())
+            }
             _ => ValueError::unsupported(self, "[]"),
         }
     }
@@ -34,6 +34,7 @@ pub mod none;
 pub mod range;
 pub mod record;
 pub mod regex;
+pub mod set;
 pub mod starlark_value_as_type;
 pub mod string;
 pub mod structs;
@@ -0,0 +1,515 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::mem;
+use std::ops::Deref;
+
+use allocative::Allocative;
+use display_container::fmt_container;
+use serde::Serialize;
+use starlark_derive::starlark_value;
+use starlark_derive::StarlarkDocs;
+
+use crate as starlark;
+use crate::any::ProvidesStaticType;
+use crate::coerce::coerce;
+use crate::coerce::Coerce;
+use crate::collections::Hashed;
+use crate::collections::SmallMap;
+use crate::environment::Methods;
+use crate::environment::MethodsStatic;
+use crate::typing::Ty;
+use crate::values::comparison::equals_small_map;
+use crate::values::dict::refcell::unleak_borrow;
+use crate::values::error::ValueError;
+use crate::values::set::SetRef;
+use crate::values::type_repr::StarlarkTypeRepr;
+use crate::values::AllocFrozenValue;
+use crate::values::AllocValue;
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::FrozenHeap;
+use crate::values::FrozenStringValue;
+use crate::values::FrozenValue;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::Trace;
+use crate::values::Value;
+use crate::values::ValueLike;
+
+#[derive(
+    Clone,
+    Default,
+    Trace,
+    Debug,
+    ProvidesStaticType,
+    StarlarkDocs,
+    Allocative
+)]
+#[starlark_docs(builtin = "standard")]
+pub(crate) struct SetGen<T>(pub(crate) T);
+
+fn fmt_set<T: Display>(f: &mut fmt::Formatter<'_>, content: &SmallMap<T, ()>) -> fmt::Result {
+    if content.is_empty() {
+        f.write_str("set()")
+    } else {
+        fmt_container(f, "set([", "])", content.keys())
+    }
+}
+
+impl<'v, T: SetLike<'v>> Display for SetGen<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_set(f, &self.0.content().content)
+    }
+}
+
+impl<'v> Display for Set<'v> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_set(f, &self.content)
+    }
+}
+
+/// Define the set type.
+#[derive(Clone, Default, Trace, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub struct Set<'v> {
+    /// The elements of the set. They must all be hashable values.
+    content: SmallMap<Value<'v>, ()>,
+}
+
+impl<'v> StarlarkTypeRepr for Set<'v> {
+    fn starlark_type_repr() -> Ty {
+        Ty::set(Value::<'v>::starlark_type_repr())
+    }
+}
+
+#[derive(Clone, Default, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub(crate) struct FrozenSetData {
+    /// The elements of the set. They must all be hashable values.
+    pub(crate) content: SmallMap<FrozenValue, ()>,
+}
+
+/// Alias is used in `StarlarkDocs` derive.
+pub(crate) type FrozenSet = SetGen<FrozenSetData>;
+
+unsafe impl<'v> Coerce<Set<'v>> for FrozenSetData {}
+
+impl<'v> AllocValue<'v> for Set<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex(SetGen(RefCell::new(self)))
+    }
+}
+
+impl StarlarkTypeRepr for FrozenSetData {
+    fn starlark_type_repr() -> Ty {
+        Ty::set(Ty::any())
+    }
+}
+
+impl AllocFrozenValue for FrozenSetData {
+    fn alloc_frozen_value(self, heap: &FrozenHeap) -> FrozenValue {
+        heap.alloc_simple(SetGen(self))
+    }
+}
+
+impl<'v> Set<'v> {
+    /// The result of calling `type()` on sets.
+    pub const TYPE: &'static str = "set";
+
+    /// Set type string as Starlark frozen string value.
+    pub fn get_type_value_static() -> FrozenStringValue {
+        SetGen::<FrozenSetData>::get_type_value_static()
+    }
+
+    /// Create a new [`Set`].
+    pub fn new(content: SmallMap<Value<'v>, ()>) -> Self {
+        Self { content }
+    }
+
+    /// Collect the elements of `x`, which may be a set or any other iterable of hashable values.
+    pub(crate) fn from_iterable(x: Value<'v>, heap: &'v Heap) -> anyhow::Result<Set<'v>> {
+        if let Some(x) = SetRef::from_value(x) {
+            return Ok(x.clone());
+        }
+        let it = x.iterate(heap)?;
+        let mut res = SmallMap::with_capacity(it.size_hint().0);
+        for x in it {
+            res.insert_hashed(x.get_hashed()?, ());
+        }
+        Ok(Set::new(res))
+    }
+
+    /// Number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.content.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    /// Iterate through the elements of the set, in insertion order.
+    pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = Value<'v>> + 'a {
+        self.content.keys().copied()
+    }
+
+    /// Iterate through the elements of the set, retaining their hashes.
+    pub fn iter_hashed<'a>(&'a self) -> impl ExactSizeIterator<Item = Hashed<Value<'v>>> + 'a
+    where
+        'v: 'a,
+    {
+        self.content.iter_hashed().map(|(x, _)| x.copied())
+    }
+
+    /// Is the value in the set? Will be [`Err`] if the value is not hashable.
+    pub fn contains(&self, value: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self.contains_hashed(value.get_hashed()?))
+    }
+
+    /// Is the prehashed value in the set?
+    pub fn contains_hashed(&self, value: Hashed<Value<'v>>) -> bool {
+        self.content.contains_key_hashed_by_value(value)
+    }
+
+    /// Reserve capacity to insert `additional` elements without reallocating.
+    pub fn reserve(&mut self, additional: usize) {
+        self.content.reserve(additional);
+    }
+
+    /// Add an element to the set, if it is not already present.
+    pub fn insert_hashed(&mut self, value: Hashed<Value<'v>>) {
+        self.content.insert_hashed(value, ());
+    }
+
+    /// Remove an element from the set, returning whether it was present.
+    pub fn remove_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.remove_hashed(value.as_ref()).is_some()
+    }
+
+    /// Remove and return the first element of the set, if there is one.
+    pub fn pop_first(&mut self) -> Option<Value<'v>> {
+        let first = self.iter_hashed().next()?;
+        self.remove_hashed(first);
+        Some(first.into_key())
+    }
+
+    /// Remove all elements from the set.
+    pub fn clear(&mut self) {
+        self.content.clear();
+    }
+
+    /// The elements that are in either set, in insertion order of `self` then `other`.
+    pub fn union(&self, other: &Set<'v>) -> Set<'v> {
+        let mut res = self.clone();
+        for x in other.iter_hashed() {
+            res.insert_hashed(x);
+        }
+        res
+    }
+
+    /// The elements of `self` that are also in `other`.
+    pub fn intersection(&self, other: &Set<'v>) -> Set<'v> {
+        self.filter(|x| other.contains_hashed(x))
+    }
+
+    /// The elements of `self` that are not in `other`.
+    pub fn difference(&self, other: &Set<'v>) -> Set<'v> {
+        self.filter(|x| !other.contains_hashed(x))
+    }
+
+    /// The elements that are in exactly one of the sets.
+    pub fn symmetric_difference(&self, other: &Set<'v>) -> Set<'v> {
+        let mut res = self.difference(other);
+        for x in other.iter_hashed() {
+            if !self.contains_hashed(x) {
+                res.insert_hashed(x);
+            }
+        }
+        res
+    }
+
+    /// Is every element of `self` also in `other`?
+    pub fn is_subset(&self, other: &Set<'v>) -> bool {
+        self.len() <= other.len() && self.iter_hashed().all(|x| other.contains_hashed(x))
+    }
+
+    /// Do the sets have no elements in common?
+    pub fn is_disjoint(&self, other: &Set<'v>) -> bool {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        !small.iter_hashed().any(|x| large.contains_hashed(x))
+    }
+
+    fn filter(&self, mut f: impl FnMut(Hashed<Value<'v>>) -> bool) -> Set<'v> {
+        let mut res = SmallMap::new();
+        for x in self.iter_hashed() {
+            if f(x) {
+                res.insert_hashed(x, ());
+            }
+        }
+        Set::new(res)
+    }
+}
+
+impl<'v> Freeze for SetGen<RefCell<Set<'v>>> {
+    type Frozen = SetGen<FrozenSetData>;
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        let content = self.0.into_inner().content.freeze(freezer)?;
+        Ok(SetGen(FrozenSetData { content }))
+    }
+}
+
+trait SetLike<'v>: Debug + Allocative {
+    type ContentRef<'a>: Deref<Target = Set<'v>>
+    where
+        Self: 'a,
+        'v: 'a;
+    fn content<'a>(&'a self) -> Self::ContentRef<'a>;
+    // These functions are unsafe for the same reason
+    // `StarlarkValue` iterator functions are unsafe.
+    unsafe fn iter_start(&self);
+    unsafe fn content_unchecked(&self) -> &Set<'v>;
+    unsafe fn iter_stop(&self);
+}
+
+impl<'v> SetLike<'v> for RefCell<Set<'v>> {
+    type ContentRef<'a> = Ref<'a, Set<'v>> where Self: 'a, 'v: 'a;
+
+    fn content<'a>(&'a self) -> Ref<'a, Set<'v>> {
+        self.borrow()
+    }
+
+    #[inline]
+    unsafe fn iter_start(&self) {
+        mem::forget(self.borrow());
+    }
+
+    #[inline]
+    unsafe fn iter_stop(&self) {
+        unleak_borrow(self);
+    }
+
+    #[inline]
+    unsafe fn content_unchecked(&self) -> &Set<'v> {
+        // SAFETY: this function contract is, caller must ensure that the value is borrowed.
+        self.try_borrow_unguarded().ok().unwrap_unchecked()
+    }
+}
+
+impl<'v> SetLike<'v> for FrozenSetData {
+    type ContentRef<'a> = &'a Set<'v> where Self: 'a, 'v: 'a;
+
+    fn content<'a>(&'a self) -> &'a Set<'v> {
+        coerce(self)
+    }
+
+    unsafe fn iter_start(&self) {}
+
+    unsafe fn iter_stop(&self) {}
+
+    unsafe fn content_unchecked(&self) -> &Set<'v> {
+        coerce(self)
+    }
+}
+
+pub(crate) fn set_methods() -> Option<&'static Methods> {
+    static RES: MethodsStatic = MethodsStatic::new();
+    RES.methods(crate::stdlib::set::set_methods)
+}
+
+#[starlark_value(type = Set::TYPE)]
+impl<'v, T: SetLike<'v> + 'v> StarlarkValue<'v> for SetGen<T>
+where
+    Self: ProvidesStaticType<'v>,
+{
+    fn get_methods() -> Option<&'static Methods> {
+        set_methods()
+    }
+
+    fn collect_repr(&self, r: &mut String) {
+        let content = self.0.content();
+        if content.is_empty() {
+            r.push_str("set()");
+            return;
+        }
+        r.push_str("set([");
+        for (i, x) in content.iter().enumerate() {
+            if i != 0 {
+                r.push_str(", ");
+            }
+            x.collect_repr(r);
+        }
+        r.push_str("])");
+    }
+
+    fn collect_repr_cycle(&self, collector: &mut String) {
+        collector.push_str("set([...])");
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.content().is_empty()
+    }
+
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        match SetRef::from_value(other) {
+            None => Ok(false),
+            Some(other) => {
+                equals_small_map(&self.0.content().content, &other.content, |_, _| Ok(true))
+            }
+        }
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        Ok(self.0.content().len() as i32)
+    }
+
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        self.0.content().contains(other)
+    }
+
+    unsafe fn iterate(&self, me: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.0.iter_start();
+        Ok(me)
+    }
+
+    unsafe fn iter_size_hint(&self, index: usize) -> (usize, Option<usize>) {
+        debug_assert!(index <= self.0.content_unchecked().len());
+        let rem = self.0.content_unchecked().len() - index;
+        (rem, Some(rem))
+    }
+
+    unsafe fn iter_next(&self, index: usize, _heap: &'v Heap) -> Option<Value<'v>> {
+        self.0.content_unchecked().iter().nth(index)
+    }
+
+    unsafe fn iter_stop(&self) {
+        self.0.iter_stop();
+    }
+
+    fn bit_or(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        let rhs = SetRef::from_value(rhs)
+            .map_or_else(|| ValueError::unsupported_with(self, "|", rhs), Ok)?;
+        Ok(heap.alloc(self.0.content().union(&rhs)))
+    }
+
+    fn bit_and(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        let rhs = SetRef::from_value(rhs)
+            .map_or_else(|| ValueError::unsupported_with(self, "&", rhs), Ok)?;
+        Ok(heap.alloc(self.0.content().intersection(&rhs)))
+    }
+
+    fn bit_xor(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        let rhs = SetRef::from_value(rhs)
+            .map_or_else(|| ValueError::unsupported_with(self, "^", rhs), Ok)?;
+        Ok(heap.alloc(self.0.content().symmetric_difference(&rhs)))
+    }
+
+    fn sub(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        let rhs = SetRef::from_value(rhs)
+            .map_or_else(|| ValueError::unsupported_with(self, "-", rhs), Ok)?;
+        Ok(heap.alloc(self.0.content().difference(&rhs)))
+    }
+}
+
+impl<'v, T: SetLike<'v>> Serialize for SetGen<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.0.content().iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    #[test]
+    fn test_set() {
+        assert::all_true(
+            r#"
+set() == set([])
+set([1, 2, 1]) == set([2, 1])
+set([1, 2]) != set([1, 2, 3])
+set([1]) != [1]
+len(set("abc".elems())) == 3
+2 in set([1, 2])
+3 not in set([1, 2])
+not set()
+list(set([3, 1, 3, 2])) == [3, 1, 2]
+type(set()) == "set"
+"#,
+        );
+        assert::fail("set([[]])", "not hashable");
+        assert::fail("{set(): 1}", "not hashable");
+        assert::fail_skip_typecheck("set([1]) | [2]", "not supported");
+    }
+
+    #[test]
+    fn test_set_operators() {
+        assert::all_true(
+            r#"
+list(set([1, 2]) | set([3, 1])) == [1, 2, 3]
+list(set([1, 2, 3]) & set([3, 1])) == [1, 3]
+list(set([1, 2, 3]) - set([2])) == [1, 3]
+list(set([1, 2, 3]) ^ set([4, 2])) == [1, 3, 4]
+"#,
+        );
+    }
+
+    #[test]
+    fn test_repr() {
+        assert::eq("repr(set())", "'set()'");
+        assert::eq("repr(set([1, 'a']))", "'set([1, \"a\"])'");
+        assert::eq("str(set([(1, 2)]))", "'set([(1, 2)])'");
+    }
+
+    #[test]
+    fn test_set_type() {
+        assert::pass(
+            r#"
+def f(x: set[int]) -> set[int]:
+    return x
+f(set([1, 2]))
+f(set())
+"#,
+        );
+        assert::fail(
+            r#"
+def f(x: set[int]):
+    pass
+noop(f)(set(["a"]))
+"#,
+            r#"Value `set(["a"])` of type `set` does not match"#,
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        assert::eq("json.encode(set([1, 'a']))", "'[1,\"a\"]'");
+    }
+}
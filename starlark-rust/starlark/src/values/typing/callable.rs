@@ -0,0 +1,152 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use allocative::Allocative;
+use starlark_derive::NoSerialize;
+use starlark_derive::ProvidesStaticType;
+use thiserror::Error;
+
+use crate as starlark;
+use crate::private::Private;
+use crate::slice_vec_ext::SliceExt;
+use crate::typing::Ty;
+use crate::values::layout::avalue::alloc_static;
+use crate::values::layout::avalue::AValueImpl;
+use crate::values::layout::avalue::Basic;
+use crate::values::layout::heap::repr::AValueRepr;
+use crate::values::list::ListRef;
+use crate::values::starlark_value;
+use crate::values::typing::type_compiled::TypeCompiled;
+use crate::values::AllocFrozenValue;
+use crate::values::FrozenHeap;
+use crate::values::FrozenValue;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::Value;
+
+#[derive(Debug, Error)]
+enum TypingCallableError {
+    #[error("Parameter types of `typing.Callable` must be a list, got `{0}`")]
+    ParamsNotList(String),
+}
+
+/// `typing.Callable`, or with a signature, `typing.Callable[[str, int], bool]`.
+#[derive(
+    Debug,
+    derive_more::Display,
+    Allocative,
+    ProvidesStaticType,
+    NoSerialize
+)]
+#[display(fmt = "{}", Self::TYPE)]
+pub(crate) struct TypingCallable;
+
+#[starlark_value(type = "typing.Callable")]
+impl<'v> StarlarkValue<'v> for TypingCallable {
+    fn eval_type(&self, _private: Private) -> Option<Ty> {
+        Some(Ty::any_function())
+    }
+
+    fn at2(
+        &self,
+        params: Value<'v>,
+        result: Value<'v>,
+        heap: &'v Heap,
+        _private: Private,
+    ) -> anyhow::Result<Value<'v>> {
+        let Some(params) = ListRef::from_value(params) else {
+            return Err(TypingCallableError::ParamsNotList(params.to_repr()).into());
+        };
+        let params = params.content().try_map(|p| TypeCompiled::new(*p, heap))?;
+        let result = TypeCompiled::new(result, heap)?;
+        Ok(TypeCompiled::type_callable(params, result, heap).to_inner())
+    }
+}
+
+impl AllocFrozenValue for TypingCallable {
+    fn alloc_frozen_value(self, _heap: &FrozenHeap) -> FrozenValue {
+        static CALLABLE: AValueRepr<AValueImpl<Basic, TypingCallable>> =
+            alloc_static(Basic, TypingCallable);
+
+        FrozenValue::new_repr(&CALLABLE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    #[test]
+    fn test_callable_runtime() {
+        assert::is_true("isinstance(len, typing.Callable)");
+        assert::is_true("isinstance(len, typing.Callable[[str], int])");
+        assert::is_true("not isinstance(1, typing.Callable[[str], int])");
+        assert::eq(
+            "\"typing.Callable[[str, int], bool]\"",
+            "repr(eval_type(typing.Callable[[str, int], bool]))",
+        );
+        assert::pass(
+            r#"
+Handler = typing.Callable[[str], bool]
+def call(f: Handler) -> bool:
+    return f("x")
+def handler(x: str) -> bool:
+    return x == "x"
+assert_eq(True, call(noop(handler)))
+"#,
+        );
+        assert::fail(
+            "def f(x: typing.Callable[[str], bool]):\n  pass\nf(noop(1))",
+            "does not match the type annotation",
+        );
+    }
+
+    #[test]
+    fn test_callable_compile_time() {
+        assert::pass(
+            r#"
+def apply(f: typing.Callable[[str, int], bool], x: str) -> bool:
+    return f(x, 1)
+
+def good(s: str, i: int, extra: int = 0) -> bool:
+    return len(s) == i
+
+apply(good, "x")
+apply(lambda s, i: True, "x")
+"#,
+        );
+        assert::fail(
+            r#"
+def apply(f: typing.Callable[[str, int], bool], x: str) -> bool:
+    return f(x, 1)
+
+def bad(s: str) -> bool:
+    return True
+
+apply(bad, "x")
+"#,
+            "Expected type `typing.Callable[[str, int], bool]` but got `def(s: str) -> bool`",
+        );
+        assert::fail(
+            r#"
+def apply(f: typing.Callable[[str], bool]):
+    f(1)
+"#,
+            "Expected type `str` but got `int`",
+        );
+    }
+}
@@ -16,7 +16,9 @@
  */
 
 pub(crate) mod any;
+pub(crate) mod callable;
 pub(crate) mod globals;
 pub(crate) mod macro_refs;
 pub(crate) mod never;
 pub(crate) mod type_compiled;
+pub(crate) mod type_var;
@@ -51,6 +51,7 @@ use crate::values::layout::avalue::Basic;
 use crate::values::layout::heap::repr::AValueRepr;
 use crate::values::list::ListRef;
 use crate::values::none::NoneType;
+use crate::values::set::SetRef;
 use crate::values::type_repr::StarlarkTypeRepr;
 use crate::values::types::tuple::value::Tuple;
 use crate::values::types::tuple::value::TupleGen;
@@ -660,6 +661,29 @@ impl<'v> TypeCompiled<Value<'v>> {
         Self::alloc(IsDict, Ty::any_dict(), heap)
     }
 
+    fn type_set(heap: &'v Heap) -> TypeCompiled<Value<'v>> {
+        #[derive(
+            Clone,
+            Copy,
+            Dupe,
+            Eq,
+            PartialEq,
+            Hash,
+            Allocative,
+            Debug,
+            ProvidesStaticType
+        )]
+        struct IsSet;
+
+        impl TypeCompiledImpl for IsSet {
+            fn matches(&self, value: Value) -> bool {
+                SetRef::from_value(value).is_some()
+            }
+        }
+
+        Self::alloc(IsSet, Ty::any_set(), heap)
+    }
+
     pub(crate) fn type_list_of(
         t: TypeCompiled<Value<'v>>,
         heap: &'v Heap,
@@ -684,6 +708,30 @@ impl<'v> TypeCompiled<Value<'v>> {
         Self::alloc(IsListOf(t.to_box_dyn()), ty, heap)
     }
 
+    pub(crate) fn type_set_of(
+        t: TypeCompiled<Value<'v>>,
+        heap: &'v Heap,
+    ) -> TypeCompiled<Value<'v>> {
+        let ty = Ty::set(t.as_ty().clone());
+        if t.is_runtime_wildcard() {
+            return TypeCompiled::<Value>::type_set(heap).patch_ty(ty, heap);
+        }
+
+        #[derive(Clone, Allocative, Eq, PartialEq, Hash, Debug, ProvidesStaticType)]
+        struct IsSetOf(TypeCompiledBox);
+
+        impl TypeCompiledImpl for IsSetOf {
+            fn matches(&self, value: Value) -> bool {
+                match SetRef::from_value(value) {
+                    None => false,
+                    Some(set) => set.iter().all(|v| self.0.0.matches_dyn(v)),
+                }
+            }
+        }
+
+        Self::alloc(IsSetOf(t.to_box_dyn()), ty, heap)
+    }
+
     pub(crate) fn type_any_of_two(
         t0: TypeCompiled<Value<'v>>,
         t1: TypeCompiled<Value<'v>>,
@@ -789,6 +837,16 @@ impl<'v> TypeCompiled<Value<'v>> {
         Self::alloc(IsTupleOf(ts.into_map(|t| t.to_box_dyn())), ty, heap)
     }
 
+    /// `typing.Callable[[str, int], bool]`. At runtime, only checks the value is a function.
+    pub(crate) fn type_callable(
+        params: Vec<TypeCompiled<Value<'v>>>,
+        result: TypeCompiled<Value<'v>>,
+        heap: &'v Heap,
+    ) -> TypeCompiled<Value<'v>> {
+        let ty = Ty::callable(params.map(|t| t.as_ty().clone()), result.as_ty().clone());
+        TypeCompiled::from_ty(&ty, heap)
+    }
+
     /// Types that are `""` or start with `"_"` are wildcard - they match everything.
     pub(crate) fn is_wildcard(x: &str) -> bool {
         x == "" || x.starts_with('_')
@@ -876,7 +934,11 @@ impl<'v> TypeCompiled<Value<'v>> {
                 let v = TypeCompiled::from_ty(v, heap);
                 TypeCompiled::type_dict_of(k, v, heap)
             }
-            TyBasic::Iter(_) | TyBasic::Custom(_) => {
+            TyBasic::Set(item) => {
+                let item = TypeCompiled::from_ty(item, heap);
+                TypeCompiled::type_set_of(item, heap)
+            }
+            TyBasic::Iter(_) | TyBasic::Custom(_) | TyBasic::TypeVar(_) => {
                 // There are no runtime matchers for these types.
                 TypeCompiled::ty_other(ty.clone(), heap)
             }
@@ -0,0 +1,131 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use allocative::Allocative;
+use starlark_derive::starlark_module;
+use starlark_derive::NoSerialize;
+use starlark_derive::ProvidesStaticType;
+
+use crate as starlark;
+use crate::environment::GlobalsBuilder;
+use crate::eval::Evaluator;
+use crate::private::Private;
+use crate::typing::Ty;
+use crate::typing::TyTypeVar;
+use crate::values::starlark_value;
+use crate::values::StarlarkValue;
+use crate::values::Value;
+
+/// Type variable created by `typing.TypeVar("T")`.
+#[derive(
+    Debug,
+    derive_more::Display,
+    Allocative,
+    ProvidesStaticType,
+    NoSerialize
+)]
+#[display(fmt = "typing.TypeVar(\"{}\")", var)]
+pub(crate) struct TypingTypeVar {
+    var: TyTypeVar,
+}
+
+#[starlark_value(type = "typing.TypeVar")]
+impl<'v> StarlarkValue<'v> for TypingTypeVar {
+    fn eval_type(&self, _private: Private) -> Option<Ty> {
+        Some(Ty::type_var(self.var.clone()))
+    }
+}
+
+#[starlark_module]
+pub(crate) fn register_type_var(globals: &mut GlobalsBuilder) {
+    /// Declare a type variable, to write generic functions.
+    ///
+    /// ```python
+    /// T = typing.TypeVar("T")
+    ///
+    /// def first(xs: list[T]) -> T:
+    ///     return xs[0]
+    /// ```
+    ///
+    /// The typechecker binds `T` to the types of the arguments at each call,
+    /// so the type of `first([1, 2])` is `int`. At runtime a type variable matches any value.
+    fn TypeVar<'v>(
+        #[starlark(require = pos)] name: &str,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        // Identify the type variable by where it is declared, like the typechecker does.
+        let var = match eval.call_stack_top_location() {
+            Some(location) => TyTypeVar::new(name, location.filename(), location.span.begin()),
+            None => TyTypeVar::new(name, "", Default::default()),
+        };
+        Ok(eval.heap().alloc_simple(TypingTypeVar { var }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    #[test]
+    fn test_type_var_runtime() {
+        assert::is_true("isinstance(1, typing.TypeVar(\"T\"))");
+        assert::eq("\"T\"", "repr(eval_type(typing.TypeVar(\"T\")))");
+        assert::pass(
+            r#"
+T = typing.TypeVar("T")
+def first(xs: list[T]) -> T:
+    return xs[0]
+assert_eq(1, first(noop([1, 2])))
+assert_eq("a", first(noop(["a"])))
+"#,
+        );
+    }
+
+    #[test]
+    fn test_type_var_compile_time() {
+        assert::pass(
+            r#"
+T = typing.TypeVar("T")
+def first(xs: list[T]) -> T:
+    return xs[0]
+def takes_int(x: int):
+    pass
+takes_int(first([1, 2]))
+"#,
+        );
+        assert::fail(
+            r#"
+T = typing.TypeVar("T")
+def first(xs: list[T]) -> T:
+    return xs[0]
+def takes_int(x: int):
+    pass
+takes_int(first(["a"]))
+"#,
+            "Expected type `int` but got `str`",
+        );
+        assert::fail(
+            r#"
+T = typing.TypeVar("T")
+def same(x: T, y: T) -> T:
+    return x
+same(1, "a")
+"#,
+            "Type variable `T` is bound to `int` but got `str`",
+        );
+    }
+}
@@ -106,6 +106,10 @@ const manualSidebar = [
         items: [
           'users/advanced/deferred_materialization',
           'users/advanced/restarter',
+          'users/advanced/local_action_cache',
+          'users/advanced/sandboxing',
+          'users/advanced/resource_limits',
+          'users/advanced/persistent_workers',
           'users/advanced/in_memory_cache',
           isInternal() ? 'users/advanced/offline_build_archives' : [],
         ],